@@ -5,6 +5,16 @@ use ariel_os::{log::info, power, time::Timer};
 
 #[ariel_os::task(autostart)]
 async fn main() {
+    info!("Reset reason: {:?}", power::reset_reason());
+
+    // Count the boots using retained data, which survives resets but not power losses.
+    let boot_count = power::retained::previous()
+        .and_then(|data| data.as_bytes().try_into().ok())
+        .map_or(0, u32::from_le_bytes)
+        + 1;
+    info!("Boot count since power-on: {}", boot_count);
+    power::retained::store(&boot_count.to_le_bytes()).unwrap();
+
     info!("Rebooting in 3 s");
 
     Timer::after_secs(3).await;
@@ -0,0 +1,19 @@
+#![no_main]
+#![no_std]
+
+use core::fmt::Write;
+
+use ariel_os::shell::Error;
+
+#[ariel_os::shell_command(name = "add", usage = "<a> <b>", help = "Adds two integers")]
+fn add_command(out: &mut dyn Write, args: &[&str]) -> Result<(), Error> {
+    let [a, b] = args else {
+        return Err(Error::InvalidArguments);
+    };
+    let (Ok(a), Ok(b)) = (a.parse::<i32>(), b.parse::<i32>()) else {
+        return Err(Error::InvalidArguments);
+    };
+
+    writeln!(out, "{}", a.wrapping_add(b))?;
+    Ok(())
+}
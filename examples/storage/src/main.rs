@@ -20,6 +20,44 @@ struct MyConfig {
     val_two: u64,
 }
 
+/// Example object, stored along with the identifier and version of its schema.
+#[derive(Serialize, Deserialize, Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+struct Settings {
+    brightness: u8,
+    auto_off: bool,
+}
+
+impl storage::Schema for Settings {
+    const ID: u32 = 0x5345_5454;
+    const VERSION: u16 = 2;
+}
+
+/// Previous version of [`Settings`], which may still be in storage.
+#[derive(Serialize, Deserialize)]
+struct SettingsV1 {
+    brightness: u8,
+}
+
+impl storage::Schema for SettingsV1 {
+    const ID: u32 = 0x5345_5454;
+    const VERSION: u16 = 1;
+}
+
+impl From<SettingsV1> for Settings {
+    fn from(old: SettingsV1) -> Self {
+        Self {
+            brightness: old.brightness,
+            auto_off: false,
+        }
+    }
+}
+
+static SETTINGS: storage::Key<Settings> = storage::Key::new(
+    "settings",
+    &[storage::Migration::new::<SettingsV1, Settings>()],
+);
+
 #[ariel_os::task(autostart)]
 async fn main() {
     info!("Start storage example");
@@ -128,6 +166,32 @@ async fn main() {
     }
     info!("");
 
+    // Storing a versioned object
+    let settings = Settings {
+        brightness: 80,
+        auto_off: true,
+    };
+    info!("Storing versioned settings object {:?}", settings);
+    storage::insert_versioned(&SETTINGS, &settings)
+        .await
+        .unwrap();
+
+    // Values stored with `SettingsV1` would be upgraded when read.
+    if let Some(settings) = storage::get_versioned(&SETTINGS).await.unwrap() {
+        info!("got versioned settings object: {:?}", settings);
+    }
+    info!("");
+
+    // Listing the stored keys
+    {
+        let mut s = storage::lock().await;
+        let mut keys = s.keys("");
+        while let Some(key) = keys.next().await.unwrap() {
+            info!("found key \"{}\"", key.as_str());
+        }
+    }
+    info!("");
+
     info!("Exit storage example");
 
     exit(ExitCode::SUCCESS);
@@ -0,0 +1,41 @@
+#![no_main]
+#![no_std]
+
+use ariel_os::{
+    gpio::{Input, Pull},
+    log::{error, info},
+    power::{self, IdleMode, WakeEdge, WakeSource},
+    time::{Duration, Timer},
+};
+use ariel_os_boards::pins;
+
+ariel_os::hal::group_peripherals!(Peripherals {
+    buttons: pins::ButtonPeripherals,
+});
+
+#[ariel_os::task(autostart, peripherals)]
+async fn main(peripherals: Peripherals) {
+    info!("Reset reason: {:?}", power::reset_reason());
+
+    let btn0 = Input::builder(peripherals.buttons.button0, Pull::Up)
+        .build_with_interrupt()
+        .unwrap();
+
+    {
+        // Keep the MCU responsive while doing latency-sensitive work.
+        let _constraint = power::constrain_idle_mode(IdleMode::Sleep);
+        info!("Deepest idle mode: {:?}", power::idle_mode());
+        Timer::after_secs(3).await;
+    }
+    info!("Deepest idle mode: {:?}", power::idle_mode());
+
+    info!("Entering system off, press the button or wait 10 s to wake up");
+
+    // Not all wake sources are supported on all MCUs, so fall back to the button only.
+    let timer = WakeSource::Timer(Duration::from_secs(10));
+    let button = WakeSource::Pin(&btn0, WakeEdge::Falling);
+    let Err(err) = power::system_off(&[button, timer]);
+    info!("Could not enter system off with the timer: {}", err);
+    let Err(err) = power::system_off(&[button]);
+    error!("Could not enter system off: {}", err);
+}
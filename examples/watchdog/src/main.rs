@@ -0,0 +1,32 @@
+#![no_main]
+#![no_std]
+
+use ariel_os::{
+    log::{info, warn},
+    power,
+    time::{Duration, Timer},
+    watchdog,
+};
+
+#[ariel_os::task(autostart)]
+async fn main() {
+    if let Some(starvation) = power::last_watchdog_starvation() {
+        warn!(
+            "Reset by the watchdog, starved by client \"{}\"",
+            starvation.client_name()
+        );
+    }
+
+    let client = watchdog::register("main", Duration::from_secs(2)).unwrap();
+
+    for i in 1..=5 {
+        info!("Checking in with the watchdog ({}/5)", i);
+        client.check_in();
+        Timer::after_secs(1).await;
+    }
+
+    info!("Simulating a hang, the watchdog should reset the device");
+    loop {
+        Timer::after_secs(1).await;
+    }
+}
@@ -15,3 +15,12 @@ pub fn context(context: &'static str) -> bool {
     // Contexts cannot include commas.
     context_var.split(',').any(|c| c == context)
 }
+
+/// Layout of the flash emulated on native, shared by the emulation and the storage layout.
+pub mod native_flash {
+    /// Erase page size, in bytes.
+    pub const PAGE_SIZE: u32 = 4096;
+    /// Size of the emulated flash, in bytes, shared by the storage partitions, queues and raw
+    /// partitions.
+    pub const CAPACITY: u32 = 80 * PAGE_SIZE;
+}
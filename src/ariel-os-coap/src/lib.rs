@@ -18,6 +18,12 @@ mod stored;
 #[cfg(feature = "coap-transport-udp")]
 mod transport_udp;
 
+#[cfg(feature = "wall-clock")]
+mod wall_clock;
+
+#[cfg(feature = "update")]
+pub mod update;
+
 use ariel_os_embassy::cell::SameExecutorCell;
 #[cfg(feature = "coap-server")]
 use coap_handler_implementations::ReportingHandlerBuilder as _;
@@ -57,7 +63,8 @@ mod demo_setup {
     const ADMIN_SCOPE: cboritem::CborItem<'_> = cbor!([
             ["/stdout", 17 / GET and FETCH /],
             ["/.well-known/core", 1],
-            ["/poem", 1]
+            ["/poem", 1],
+            ["/fwup", 5 / GET and PUT /]
     ]);
     /// Credential by which the administrator of any demo device is recognized.
     ///
@@ -153,7 +160,7 @@ async fn coap_run_impl(handler: impl coap_handler::Handler + coap_handler::Repor
         security_config,
         || lakers_crypto_rustcrypto::Crypto::new(ariel_os_random::crypto_rng()),
         ariel_os_random::crypto_rng(),
-        coapcore::time::TimeUnknown,
+        time_provider(),
     );
 
     cfg_select! {
@@ -169,6 +176,18 @@ async fn coap_run_impl(handler: impl coap_handler::Handler + coap_handler::Repor
     }
 }
 
+/// Returns the clock against which the expiry of tokens is checked.
+#[cfg(any(
+    feature = "coap-server-config-storage",
+    feature = "coap-server-config-demokeys"
+))]
+fn time_provider() -> impl coapcore::time::TimeProvider {
+    cfg_select! {
+        feature = "wall-clock" => { wall_clock::WallClock }
+        _ => { coapcore::time::TimeUnknown }
+    }
+}
+
 /// Returns a CoAP client requester.
 ///
 /// This asynchronously blocks until [`coap_run()`] has been called (which happens at startup
@@ -8,6 +8,9 @@ mod flash_peers {
     include!(concat!(env!("OUT_DIR"), "/peers.rs"));
 }
 
+/// Name of the storage partition holding the credentials.
+const CREDENTIALS_PARTITION: &str = "credentials";
+
 pub async fn server_security_config() -> impl ServerSecurityConfig {
     StoredPolicy::load().await
 }
@@ -99,16 +102,34 @@ impl StoredPolicy {
         // becomes a thing.
         const OWN_CREDENTIAL_KEY: &str = "ariel-os-coap.own-edhoc-credential";
 
-        let (credential, key) = match ariel_os_storage::get(OWN_CREDENTIAL_KEY)
+        // Credentials are kept in their own partition, so that they survive erasing the settings
+        // of the application.
+        let storage = ariel_os_storage::partition(CREDENTIALS_PARTITION)
+            .expect("credentials partition is declared by the laze module");
+
+        let (credential, key) = match storage
+            .get(OWN_CREDENTIAL_KEY)
             .await
             .expect("flash error prevents startup")
         {
             Some(credpair) => credpair,
             None => {
-                let credpair = generate_credpair();
-                ariel_os_storage::insert(OWN_CREDENTIAL_KEY, credpair.clone())
+                // Credentials used to be kept in the default partition: move them over, so that
+                // the device keeps its identity. A copy that cannot be read is not worth failing
+                // startup for.
+                let legacy = ariel_os_storage::get(OWN_CREDENTIAL_KEY)
+                    .await
+                    .ok()
+                    .flatten();
+                let credpair = legacy.clone().unwrap_or_else(generate_credpair);
+                storage
+                    .insert(OWN_CREDENTIAL_KEY, credpair.clone())
                     .await
                     .expect("flash error prevents startup");
+                if legacy.is_some() {
+                    info!("Moved the CoAP server identity to the credentials partition.");
+                    remove_legacy_credpair(OWN_CREDENTIAL_KEY).await;
+                }
                 credpair
             }
         };
@@ -125,6 +146,20 @@ impl StoredPolicy {
     }
 }
 
+/// Deletes the copy of the credentials left in the default partition after moving them.
+async fn remove_legacy_credpair(key: &str) {
+    #[cfg(not(context = "stm32"))]
+    ariel_os_storage::remove(key)
+        .await
+        .expect("flash error prevents startup");
+    // STM32 flash drivers cannot remove items: overwrite the copy instead, its item being erased
+    // when its page is reclaimed.
+    #[cfg(context = "stm32")]
+    ariel_os_storage::insert(key, ())
+        .await
+        .expect("flash error prevents startup");
+}
+
 #[derive(Debug)]
 struct StoredClaims {
     scope: coapcore::scope::UnionScope,
@@ -0,0 +1,506 @@
+//! Provides a CoAP resource for uploading firmware updates.
+//!
+//! The [resource](handler()) accepts a signed image through a block-wise PUT ([RFC 7959]), and
+//! writes each block to the secondary slot as it arrives, so the image is never held in RAM as a
+//! whole.
+//! Once the last block has been written, the image is verified and marked to be booted for testing
+//! after the next reset, see [`ariel_os_update`].
+//!
+//! A GET returns the status of the update as a CBOR map, with the following keys:
+//!
+//! | Key          | Value                                                              |
+//! | --           | --                                                                 |
+//! | `"state"`    | One of `"idle"`, `"receiving"`, `"verifying"`, `"ready"`, `"failed"` |
+//! | `"received"` | Number of bytes of the image received so far                       |
+//! | `"written"`  | Number of bytes of the image written to flash so far               |
+//! | `"version"`  | When ready, version of the image as `[major, minor, revision, build]` |
+//! | `"error"`    | When failed, a short description of the error                      |
+//!
+//! The status cannot be observed ([RFC 7641]): the CoAP server only answers requests, and has no
+//! way of sending (and protecting with OSCORE) notifications later on.
+//! Registrations are answered like plain GET requests, without an Observe option, as allowed by
+//! [RFC 7641]; this tells clients to poll the status instead.
+//!
+//! # Access control
+//!
+//! The resource does not perform any access control on its own: as for any other resource, whether
+//! a request is allowed is decided by the [access policy] of the CoAP server.
+//! Uploading requires PUT permissions on the path the resource is mounted at, which should only be
+//! granted to administrators; [`admin_scope()`] returns the scope to grant them.
+//!
+//! # Flow control
+//!
+//! Blocks are written by a separate task while the next ones are received.
+//! When writing to flash does not keep up with the transfer, blocks are rejected with 5.03
+//! (Service Unavailable), and need to be sent again by the client after the indicated Max-Age.
+//!
+//! [RFC 7959]: https://www.rfc-editor.org/rfc/rfc7959
+//! [RFC 7641]: https://www.rfc-editor.org/rfc/rfc7641#section-4.1
+//! [access policy]: https://ariel-os.github.io/ariel-os/dev/docs/book/tooling/coap.html#server-access-policy
+
+use core::cell::Cell;
+
+use ariel_os_log::{info, warn};
+use coap_handler::{Attribute, Handler, Reporting};
+use coap_handler_implementations::wkc::ConstantSingleRecordReport;
+use coap_message::{
+    Code as _, MessageOption as _, MinimalWritableMessage, MutableWritableMessage, ReadableMessage,
+};
+use coap_message_utils::{Error as CoAPError, OptionsExt as _};
+use coapcore::scope::{AifValue, InvalidScope};
+use embassy_sync::{
+    blocking_mutex::{CriticalSectionMutex, raw::CriticalSectionRawMutex},
+    channel::Channel,
+};
+
+/// Largest block size accepted, as the exponent of the Block1 option (i.e., 512 bytes).
+const MAX_SZX: u8 = 5;
+const MAX_BLOCK_LEN: usize = block_len(MAX_SZX) as usize;
+
+/// Number of blocks that can be queued while the previous ones are being written.
+const QUEUE_LEN: usize = 2;
+
+/// Seconds after which a client may retry a block rejected for lack of queue space.
+const RETRY_MAX_AGE: u8 = 1;
+
+/// Upper bound of the length of the encoded status.
+const STATUS_MAX_LEN: usize = 80;
+
+/// Content-Format of `application/cbor`.
+const CONTENT_FORMAT_CBOR: u8 = 60;
+
+/// Attributes of the resource, advertising its Content-Format.
+const ATTRIBUTES: &[Attribute] = &[Attribute::Ct(60)];
+
+/// Permissions needed for updates, GET and PUT, as bits of the REST-specific model of AIF.
+const ADMIN_PERMISSIONS: u32 =
+    (1 << (coap_numbers::code::GET - 1)) | (1 << (coap_numbers::code::PUT - 1));
+
+static BLOCKS: Channel<CriticalSectionRawMutex, Block, QUEUE_LEN> = Channel::new();
+
+static STATUS: CriticalSectionMutex<Cell<Status>> = CriticalSectionMutex::new(Cell::new(Status {
+    state: State::Idle,
+    received: 0,
+    written: 0,
+}));
+
+/// Block of the image, queued for being written.
+struct Block {
+    offset: u32,
+    data: heapless::Vec<u8, MAX_BLOCK_LEN>,
+    last: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Idle,
+    Receiving,
+    Verifying,
+    Ready(ariel_os_update::ImageVersion),
+    Failed(&'static str),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Status {
+    state: State,
+    received: u32,
+    written: u32,
+}
+
+fn update_status(f: impl FnOnce(&mut Status)) {
+    STATUS.lock(|status| {
+        let mut new_status = status.get();
+        f(&mut new_status);
+        status.set(new_status);
+    });
+}
+
+/// Returns the CoAP resource for uploading firmware updates.
+///
+/// The resource needs to be mounted by the application, e.g.:
+///
+/// ```ignore
+/// use coap_handler_implementations::{HandlerBuilder, new_dispatcher};
+///
+/// let handler = new_dispatcher().at(&["fwup"], ariel_os::coap::update::handler());
+/// ariel_os::coap::coap_run(handler).await;
+/// ```
+///
+/// Only a single upload can be in progress at any time, so this should be mounted only once.
+#[must_use]
+pub fn handler() -> impl Handler + Reporting {
+    ConstantSingleRecordReport::new(UpdateHandler { next_offset: 0 }, ATTRIBUTES)
+}
+
+/// Returns the scope granting the access needed for updates through the resource mounted at
+/// `path`, i.e., GET and PUT requests.
+///
+/// This should only be granted to administrators, e.g., when setting up the security
+/// configuration of the server:
+///
+/// ```ignore
+/// let config = coapcore::seccfg::ConfigBuilder::new()
+///     .with_own_edhoc_credential(own_credential, own_key)
+///     .with_known_edhoc_credential(admin_credential, admin_scope("/fwup")?.into());
+/// ```
+///
+/// # Errors
+///
+/// Returns [`InvalidScope`] if `path` does not start with `/`, or is too long for an
+/// [`AifValue`].
+pub fn admin_scope(path: &str) -> Result<AifValue, InvalidScope> {
+    let mut buffer = [0; 64];
+    let mut cursor = minicbor::encode::write::Cursor::new(&mut buffer[..]);
+    minicbor::Encoder::new(&mut cursor)
+        .array(1)
+        .and_then(|encoder| encoder.array(2))
+        .and_then(|encoder| encoder.str(path))
+        .and_then(|encoder| encoder.u32(ADMIN_PERMISSIONS))
+        .map_err(|_| InvalidScope)?;
+    let len = cursor.position();
+
+    AifValue::parse(buffer.get(..len).ok_or(InvalidScope)?)
+}
+
+/// Handler behind [`handler()`].
+struct UpdateHandler {
+    /// Offset in the image of the block expected next.
+    next_offset: u32,
+}
+
+/// Response to a request, as decided when extracting its data.
+#[derive(Debug)]
+enum Response {
+    Status,
+    /// The block has been queued; `block1` is the option of the request, if any, and `more` its M
+    /// flag.
+    Queued {
+        block1: Option<u32>,
+        more: bool,
+    },
+    /// The block is not the one expected next.
+    Incomplete,
+    /// The block is too large; the block size to use instead is indicated.
+    TooLarge,
+    /// The queue of blocks is full.
+    Busy,
+    /// Writing the image has failed, see the status.
+    Failed,
+}
+
+impl Handler for UpdateHandler {
+    type RequestData = Response;
+    type ExtractRequestError = CoAPError;
+    type BuildResponseError<M: MinimalWritableMessage> = M::UnionError;
+
+    fn extract_request_data<M: ReadableMessage>(
+        &mut self,
+        request: &M,
+    ) -> Result<Self::RequestData, Self::ExtractRequestError> {
+        let mut block1 = None;
+        request
+            .options()
+            .filter(|option| {
+                if option.number() == coap_numbers::option::BLOCK1 {
+                    block1 = Some(uint_value(option.value()));
+                    false
+                } else {
+                    true
+                }
+            })
+            .ignore_elective_others()?;
+
+        let code: u8 = request.code().into();
+        match code {
+            coap_numbers::code::GET => Ok(Response::Status),
+            coap_numbers::code::PUT => {
+                let block1 = block1
+                    .transpose()
+                    .map_err(|()| CoAPError::bad_option(coap_numbers::option::BLOCK1))?;
+                self.extract_block(block1, request.payload())
+            }
+            _ => Err(CoAPError::method_not_allowed()),
+        }
+    }
+
+    fn estimate_length(&mut self, _request: &Self::RequestData) -> usize {
+        STATUS_MAX_LEN
+    }
+
+    fn build_response<M: MutableWritableMessage>(
+        &mut self,
+        response: &mut M,
+        request: Self::RequestData,
+    ) -> Result<(), Self::BuildResponseError<M>> {
+        match request {
+            Response::Status => {
+                // The Observe option of registrations is ignored, as any elective option.
+                response.set_code(M::Code::new(coap_numbers::code::CONTENT)?);
+                response
+                    .add_option(coap_numbers::option::CONTENT_FORMAT, &[CONTENT_FORMAT_CBOR])?;
+                render_status(response, STATUS.lock(Cell::get))?;
+            }
+            Response::Queued { block1, more } => {
+                let code = if more {
+                    coap_numbers::code::CONTINUE
+                } else {
+                    coap_numbers::code::CHANGED
+                };
+                response.set_code(M::Code::new(code)?);
+                if let Some(block1) = block1 {
+                    let value = block1.to_be_bytes();
+                    let len = (u32::BITS - block1.leading_zeros()).div_ceil(8) as usize;
+                    response.add_option(
+                        coap_numbers::option::BLOCK1,
+                        value.get(value.len() - len..).unwrap_or_default(),
+                    )?;
+                }
+            }
+            Response::Incomplete => {
+                response.set_code(M::Code::new(coap_numbers::code::REQUEST_ENTITY_INCOMPLETE)?);
+            }
+            Response::TooLarge => {
+                response.set_code(M::Code::new(coap_numbers::code::REQUEST_ENTITY_TOO_LARGE)?);
+                // Block number 0 of the largest accepted size (RFC 7959, Section 2.9.3).
+                response.add_option(coap_numbers::option::BLOCK1, &[MAX_SZX])?;
+            }
+            Response::Busy => {
+                response.set_code(M::Code::new(coap_numbers::code::SERVICE_UNAVAILABLE)?);
+                response.add_option(coap_numbers::option::MAX_AGE, &[RETRY_MAX_AGE])?;
+            }
+            Response::Failed => {
+                response.set_code(M::Code::new(coap_numbers::code::INTERNAL_SERVER_ERROR)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl UpdateHandler {
+    fn extract_block(
+        &mut self,
+        block1: Option<u32>,
+        payload: &[u8],
+    ) -> Result<Response, CoAPError> {
+        // A request without a Block1 option is handled as a single, last block.
+        let option = block1.unwrap_or(u32::from(MAX_SZX));
+        let number = option >> 4;
+        let more = option & 0x8 != 0;
+        let [.., last_byte] = option.to_be_bytes();
+        let szx = last_byte & 0x7;
+
+        if szx == 7 {
+            return Err(CoAPError::bad_option(coap_numbers::option::BLOCK1));
+        }
+        let len = u32::try_from(payload.len()).unwrap_or(u32::MAX);
+        if szx > MAX_SZX || len > block_len(MAX_SZX) {
+            return Ok(Response::TooLarge);
+        }
+        let block_len = block_len(szx);
+        if len > block_len || (more && len != block_len) {
+            return Err(CoAPError::bad_request());
+        }
+
+        // Cannot overflow: the block number has at most 20 bits, and blocks are at most 512 bytes.
+        let offset = number * block_len;
+        if offset == 0 {
+            // A new upload, discarding any previous one.
+            self.next_offset = 0;
+        } else if offset != self.next_offset {
+            return Ok(Response::Incomplete);
+        } else if matches!(STATUS.lock(Cell::get).state, State::Failed(_)) {
+            return Ok(Response::Failed);
+        }
+
+        let block = Block {
+            offset,
+            data: heapless::Vec::from_slice(payload).map_err(|_| CoAPError::bad_request())?,
+            last: !more,
+        };
+        if BLOCKS.try_send(block).is_err() {
+            return Ok(Response::Busy);
+        }
+
+        self.next_offset = offset + len;
+        update_status(|status| {
+            if offset == 0 {
+                *status = Status {
+                    state: State::Receiving,
+                    received: 0,
+                    written: 0,
+                };
+            }
+            status.received = self.next_offset;
+        });
+
+        Ok(Response::Queued { block1, more })
+    }
+}
+
+/// Returns the length of blocks with the size exponent `szx`.
+const fn block_len(szx: u8) -> u32 {
+    1 << (szx + 4)
+}
+
+/// Decodes the value of a uint option.
+fn uint_value(value: &[u8]) -> Result<u32, ()> {
+    if value.len() > 3 {
+        return Err(());
+    }
+    Ok(value
+        .iter()
+        .fold(0, |uint, byte| (uint << 8) | u32::from(*byte)))
+}
+
+#[allow(clippy::missing_panics_doc, reason = "will never panic for any status")]
+fn render_status<M: MutableWritableMessage>(
+    response: &mut M,
+    status: Status,
+) -> Result<(), M::UnionError> {
+    let payload = response.payload_mut_with_len(STATUS_MAX_LEN)?;
+    let mut cursor = minicbor::encode::write::Cursor::new(payload);
+    encode_status(&mut minicbor::Encoder::new(&mut cursor), status)
+        .expect("Sufficient size was requested");
+    let written = cursor.position();
+    response.truncate(written)?;
+
+    Ok(())
+}
+
+fn encode_status<W: minicbor::encode::Write>(
+    encoder: &mut minicbor::Encoder<W>,
+    status: Status,
+) -> Result<(), minicbor::encode::Error<W::Error>> {
+    let (state, len) = match status.state {
+        State::Idle => ("idle", 3),
+        State::Receiving => ("receiving", 3),
+        State::Verifying => ("verifying", 3),
+        State::Ready(_) => ("ready", 4),
+        State::Failed(_) => ("failed", 4),
+    };
+
+    encoder.map(len)?;
+    encoder.str("state")?.str(state)?;
+    encoder.str("received")?.u32(status.received)?;
+    encoder.str("written")?.u32(status.written)?;
+    match status.state {
+        State::Ready(version) => {
+            encoder
+                .str("version")?
+                .array(4)?
+                .u8(version.major)?
+                .u8(version.minor)?
+                .u16(version.revision)?
+                .u32(version.build)?;
+        }
+        State::Failed(error) => {
+            encoder.str("error")?.str(error)?;
+        }
+        State::Idle | State::Receiving | State::Verifying => {}
+    }
+
+    Ok(())
+}
+
+/// Writes the queued blocks of the image.
+#[ariel_os_macros::task(autostart)]
+async fn update_task() {
+    let mut update = None;
+
+    loop {
+        let block = BLOCKS.receive().await;
+
+        if block.offset == 0 {
+            update = match ariel_os_update::begin().await {
+                Ok(update) => Some(update),
+                Err(err) => {
+                    fail(&err);
+                    None
+                }
+            };
+        }
+        // Blocks of an upload which has failed are discarded.
+        let Some(current) = update.as_mut() else {
+            continue;
+        };
+
+        if let Err(err) = current.write(&block.data).await {
+            fail(&err);
+            update = None;
+            continue;
+        }
+        let written = current.offset();
+        update_status(|status| status.written = written);
+
+        if block.last
+            && let Some(complete) = update.take()
+        {
+            update_status(|status| status.state = State::Verifying);
+
+            match complete.finish().await {
+                Ok(image) => {
+                    info!("coap: update of {} bytes ready", image.size);
+                    update_status(|status| status.state = State::Ready(image.version));
+                }
+                Err(err) => fail(&err),
+            }
+        }
+    }
+}
+
+fn fail<E>(err: &ariel_os_update::Error<E>) {
+    let error = match err {
+        ariel_os_update::Error::Flash(_) => "flash error",
+        ariel_os_update::Error::ImageTooLarge => "image too large",
+        ariel_os_update::Error::InvalidImage => "invalid image",
+        ariel_os_update::Error::HashMismatch => "hash mismatch",
+        ariel_os_update::Error::InvalidSignature => "invalid signature",
+        ariel_os_update::Error::InvalidKey => "invalid public key",
+        _ => "update error",
+    };
+    warn!("coap: update failed: {}", error);
+
+    update_status(|status| status.state = State::Failed(error));
+}
+
+#[cfg(test)]
+mod tests {
+    use coap_message::MinimalWritableMessage as _;
+    use coapcore::scope::Scope as _;
+
+    use super::*;
+
+    fn is_allowed(scope: &AifValue, code: u8, path: &[&str]) -> bool {
+        let mut code_buffer = 0;
+        let mut buffer = [0; 64];
+        let mut request = coap_message_implementations::inmemory_write::Message::new(
+            &mut code_buffer,
+            &mut buffer[..],
+        );
+        request.set_code(code);
+        for segment in path {
+            request
+                .add_option(coap_numbers::option::URI_PATH, segment.as_bytes())
+                .unwrap();
+        }
+        scope.request_is_allowed(&request)
+    }
+
+    #[test]
+    fn admin_scope_allows_updates() {
+        let scope = admin_scope("/fwup").unwrap();
+
+        assert!(is_allowed(&scope, coap_numbers::code::GET, &["fwup"]));
+        assert!(is_allowed(&scope, coap_numbers::code::PUT, &["fwup"]));
+        assert!(!is_allowed(&scope, coap_numbers::code::POST, &["fwup"]));
+        assert!(!is_allowed(&scope, coap_numbers::code::DELETE, &["fwup"]));
+        assert!(!is_allowed(&scope, coap_numbers::code::PUT, &["other"]));
+        assert!(!is_allowed(&scope, coap_numbers::code::PUT, &[]));
+    }
+
+    #[test]
+    fn admin_scope_rejects_invalid_paths() {
+        assert!(admin_scope("fwup").is_err());
+        assert!(admin_scope(&"/fwup".repeat(16)).is_err());
+    }
+}
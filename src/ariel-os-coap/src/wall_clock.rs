@@ -0,0 +1,42 @@
+//! Provides the system wall clock to [`coapcore`] for checking the expiry of tokens.
+
+use ariel_os_wall_clock::{TimeSource, UtcDateTime};
+
+/// [`TimeProvider`](coapcore::time::TimeProvider) backed by the system wall clock.
+///
+/// Until the wall clock is set, this knows as little as [`coapcore::time::TimeUnknown`].
+/// When the wall clock was only set from CoAP peers, the current time is only known to be later
+/// than it.
+pub(crate) struct WallClock;
+
+impl coapcore::time::TimeProvider for WallClock {
+    fn now(&mut self) -> (u64, Option<u64>) {
+        let Some(now) =
+            ariel_os_wall_clock::now().and_then(|now| u64::try_from(now.unix_timestamp()).ok())
+        else {
+            return (0, None);
+        };
+
+        // Times vouched for by peers are only lower bounds, see `past_trusted()`.
+        if ariel_os_wall_clock::source() == Some(TimeSource::Coap) {
+            (now, None)
+        } else {
+            (now, Some(now))
+        }
+    }
+
+    fn past_trusted(&mut self, timestamp: u64) {
+        let Some(time) = i64::try_from(timestamp)
+            .ok()
+            .and_then(|timestamp| UtcDateTime::from_unix_timestamp(timestamp).ok())
+        else {
+            return;
+        };
+
+        // Only move the clock forward: a trusted peer vouching for a time to have passed gives a
+        // lower bound of the current time.
+        if ariel_os_wall_clock::now().is_none_or(|now| now < time) {
+            ariel_os_wall_clock::set(time, TimeSource::Coap);
+        }
+    }
+}
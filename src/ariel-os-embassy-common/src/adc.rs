@@ -0,0 +1,219 @@
+//! Provides HAL-agnostic ADC-related types.
+
+/// ADC conversion error.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The conversion could not be carried out.
+    Conversion,
+}
+
+/// Common ADC resolutions.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Resolution<A> {
+    /// HAL-specific resolution.
+    Hal(A),
+    /// 8-bit resolution.
+    Bits8,
+    /// 10-bit resolution.
+    Bits10,
+    /// 12-bit resolution.
+    Bits12,
+}
+
+impl<A> Resolution<A>
+where
+    u8: From<A>,
+{
+    /// Returns the number of bits of the resolution.
+    #[must_use]
+    pub fn bits(self) -> u8 {
+        match self {
+            Self::Hal(hal) => hal.into(),
+            Self::Bits8 => 8,
+            Self::Bits10 => 10,
+            Self::Bits12 => 12,
+        }
+    }
+}
+
+/// Common oversampling ratios.
+///
+/// When the hardware does not support oversampling, HALs average consecutive conversions in
+/// software instead.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Oversampling<A> {
+    /// HAL-specific oversampling ratio.
+    Hal(A),
+    /// No oversampling.
+    None,
+    /// 2× oversampling.
+    X2,
+    /// 4× oversampling.
+    X4,
+    /// 8× oversampling.
+    X8,
+    /// 16× oversampling.
+    X16,
+}
+
+impl<A> From<Oversampling<A>> for u16
+where
+    u16: From<A>,
+{
+    fn from(oversampling: Oversampling<A>) -> u16 {
+        match oversampling {
+            Oversampling::Hal(hal) => hal.into(),
+            Oversampling::None => 1,
+            Oversampling::X2 => 2,
+            Oversampling::X4 => 4,
+            Oversampling::X8 => 8,
+            Oversampling::X16 => 16,
+        }
+    }
+}
+
+/// Common voltage references.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Reference<A> {
+    /// HAL-specific voltage reference.
+    Hal(A),
+    /// The analog supply voltage, the full scale of the ADC then covers the whole supply range.
+    Vdd,
+}
+
+/// Result of an ADC conversion.
+///
+/// Carries the information required to convert the raw conversion result into a voltage.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Sample {
+    raw: i32,
+    full_scale_raw: u32,
+    full_scale_mv: u32,
+}
+
+impl Sample {
+    /// Creates a new sample.
+    ///
+    /// `full_scale_raw` is the raw value corresponding to `full_scale_mv`.
+    ///
+    /// # Note
+    ///
+    /// For HAL implementors only.
+    #[must_use]
+    pub const fn new(raw: i32, full_scale_raw: u32, full_scale_mv: u32) -> Self {
+        Self {
+            raw,
+            full_scale_raw,
+            full_scale_mv,
+        }
+    }
+
+    /// Returns the raw conversion result.
+    ///
+    /// Some ADCs can return slightly negative values around zero.
+    #[must_use]
+    pub const fn raw(&self) -> i32 {
+        self.raw
+    }
+
+    /// Returns the raw value corresponding to the full scale of the ADC.
+    #[must_use]
+    pub const fn full_scale_raw(&self) -> u32 {
+        self.full_scale_raw
+    }
+
+    /// Returns the input voltage in millivolts.
+    ///
+    /// # Note
+    ///
+    /// When [`Reference::Vdd`] is used, the supply voltage is assumed to be nominal, which
+    /// directly impacts the accuracy of the returned value.
+    #[must_use]
+    pub fn millivolts(&self) -> i32 {
+        if self.full_scale_raw == 0 {
+            return 0;
+        }
+        let mv = i64::from(self.raw) * i64::from(self.full_scale_mv)
+            / i64::from(self.full_scale_raw);
+        // The result is always smaller in magnitude than `full_scale_mv`, except for raw values
+        // outside of the conversion range, which we saturate.
+        i32::try_from(mv).unwrap_or(if mv < 0 { i32::MIN } else { i32::MAX })
+    }
+}
+
+/// An analog input that can be sampled.
+///
+/// This is implemented by the portable ADC channel types, and allows drivers (e.g., sensor
+/// drivers) to be generic over the actual ADC.
+pub trait AnalogInput {
+    /// Performs a single-shot conversion and returns its result.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Conversion`] if the conversion could not be carried out.
+    fn sample(&mut self) -> impl Future<Output = Result<Sample, Error>>;
+}
+
+/// Averages `ratio` conversions obtained from `convert`, for HALs that do not support hardware
+/// oversampling.
+#[doc(hidden)]
+pub async fn software_oversample<F, Fut, E>(ratio: u16, mut convert: F) -> Result<i32, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<i32, E>>,
+{
+    let ratio = ratio.max(1);
+    let mut sum = 0i64;
+    for _ in 0..ratio {
+        sum += i64::from(convert().await?);
+    }
+    // The average of `i32`s always fits into an `i32`.
+    #[expect(clippy::cast_possible_truncation)]
+    Ok((sum / i64::from(ratio)) as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_to_millivolts() {
+        let sample = Sample::new(2048, 4095, 3300);
+        assert_eq!(sample.millivolts(), 1650);
+
+        let sample = Sample::new(-3, 4095, 3600);
+        assert_eq!(sample.millivolts(), -2);
+
+        let sample = Sample::new(4095, 4095, 3300);
+        assert_eq!(sample.millivolts(), 3300);
+    }
+
+    #[test]
+    fn oversampling_averages() {
+        let mut values = [10, 20, 30, 40].into_iter();
+        let avg = block_on(software_oversample(4, || {
+            let value = values.next().unwrap();
+            async move { Ok::<_, ()>(value) }
+        }));
+        assert_eq!(avg, Ok(25));
+    }
+
+    // Minimal executor, the futures used here never return `Pending`.
+    fn block_on<F: Future>(fut: F) -> F::Output {
+        use core::task::{Context, Poll, Waker};
+
+        let mut fut = core::pin::pin!(fut);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+}
@@ -4,6 +4,9 @@
 #![cfg_attr(nightly, feature(doc_cfg))]
 #![deny(missing_docs)]
 
+#[cfg(feature = "adc")]
+pub mod adc;
+
 pub mod cell;
 pub mod gpio;
 
@@ -24,9 +27,15 @@ pub mod cellular_networking;
 
 pub mod identity;
 
+#[cfg(feature = "pwm")]
+pub mod pwm;
+
 #[cfg(feature = "spi")]
 pub mod spi;
 
+#[cfg(feature = "system-off")]
+pub mod system_off;
+
 #[cfg(feature = "uart")]
 pub mod uart;
 
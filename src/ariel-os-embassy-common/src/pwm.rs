@@ -0,0 +1,307 @@
+//! Provides HAL-agnostic PWM-related types.
+
+pub use fugit::HertzU32 as Hertz;
+
+/// PWM configuration or operation error.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// The requested frequency cannot be obtained with this PWM instance.
+    FrequencyNotSupported,
+    /// The channel does not exist or has no pin attached.
+    InvalidChannel,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::FrequencyNotSupported => write!(f, "frequency not supported"),
+            Self::InvalidChannel => write!(f, "invalid channel"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl embedded_hal::pwm::Error for Error {
+    fn kind(&self) -> embedded_hal::pwm::ErrorKind {
+        embedded_hal::pwm::ErrorKind::Other
+    }
+}
+
+/// Polarity of a PWM output.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Polarity {
+    /// The output is high during the active part of the period.
+    #[default]
+    ActiveHigh,
+    /// The output is low during the active part of the period.
+    ActiveLow,
+}
+
+/// Duty cycle of a PWM output, i.e., the active fraction of the period.
+///
+/// The duty cycle has a resolution of 0.01 %; the actual resolution depends on the
+/// hardware and on the frequency.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DutyCycle {
+    basis_points: u16,
+}
+
+impl DutyCycle {
+    /// The raw value corresponding to a 100 % duty cycle.
+    pub const MAX: u16 = 10_000;
+
+    /// The output is never active.
+    pub const FULLY_OFF: Self = Self { basis_points: 0 };
+
+    /// The output is always active.
+    pub const FULLY_ON: Self = Self {
+        basis_points: Self::MAX,
+    };
+
+    /// Creates a duty cycle from a percentage.
+    ///
+    /// Values above 100 are saturated.
+    #[must_use]
+    pub const fn from_percent(percent: u8) -> Self {
+        let percent = if percent > 100 { 100 } else { percent };
+        Self {
+            basis_points: percent as u16 * 100,
+        }
+    }
+
+    /// Creates a duty cycle from a per-mille value.
+    ///
+    /// Values above 1000 are saturated.
+    #[must_use]
+    pub const fn from_permille(permille: u16) -> Self {
+        let permille = if permille > 1000 { 1000 } else { permille };
+        Self {
+            basis_points: permille * 10,
+        }
+    }
+
+    /// Creates a duty cycle from the fraction `numerator / denominator`.
+    ///
+    /// Fractions larger than one are saturated.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `denominator` is zero.
+    #[must_use]
+    pub const fn from_fraction(numerator: u32, denominator: u32) -> Self {
+        assert!(denominator != 0, "denominator is zero");
+        let numerator = if numerator > denominator {
+            denominator
+        } else {
+            numerator
+        };
+        let basis_points = numerator as u64 * Self::MAX as u64 / denominator as u64;
+        // Cannot truncate as the fraction has been saturated to one.
+        #[expect(clippy::cast_possible_truncation)]
+        Self {
+            basis_points: basis_points as u16,
+        }
+    }
+
+    /// Returns the duty cycle in hundredths of a percent.
+    #[must_use]
+    pub const fn basis_points(self) -> u16 {
+        self.basis_points
+    }
+
+    /// Returns the number of ticks, out of `period_ticks`, during which the output must be high
+    /// to obtain this duty cycle with the given polarity.
+    ///
+    /// # Note
+    ///
+    /// For HAL implementors only.
+    #[must_use]
+    pub const fn high_ticks(self, period_ticks: u32, polarity: Polarity) -> u32 {
+        let active = period_ticks as u64 * self.basis_points as u64 / Self::MAX as u64;
+        // Cannot truncate as the result is not larger than `period_ticks`.
+        #[expect(clippy::cast_possible_truncation)]
+        let active = active as u32;
+        match polarity {
+            Polarity::ActiveHigh => active,
+            Polarity::ActiveLow => period_ticks - active,
+        }
+    }
+}
+
+/// Settings common to the PWM builders of all HALs.
+#[doc(hidden)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Settings {
+    /// Frequency of the PWM signal.
+    pub frequency: Hertz,
+    /// Polarity of all the channels.
+    pub polarity: Polarity,
+    /// Duty cycle of all the channels once the driver is built.
+    pub initial_duty_cycle: DutyCycle,
+}
+
+impl Settings {
+    /// Returns the default settings for the given frequency.
+    #[must_use]
+    pub const fn new(frequency: Hertz) -> Self {
+        Self {
+            frequency,
+            polarity: Polarity::ActiveHigh,
+            initial_duty_cycle: DutyCycle::FULLY_OFF,
+        }
+    }
+}
+
+/// Implements the builder methods common to all HALs, on a `PwmBuilder` type having a `settings`
+/// field of type [`Settings`].
+#[doc(hidden)]
+#[macro_export]
+macro_rules! impl_pwm_builder_settings {
+    ($( $generics:ident $(: $bound:path)? ),*) => {
+        impl<$( $generics $(: $bound)? ),*> PwmBuilder<$( $generics ),*> {
+            /// Sets the polarity of all the channels.
+            ///
+            /// Defaults to [`Polarity::ActiveHigh`](ariel_os_embassy_common::pwm::Polarity::ActiveHigh).
+            #[must_use]
+            pub fn polarity(mut self, polarity: ariel_os_embassy_common::pwm::Polarity) -> Self {
+                self.settings.polarity = polarity;
+                self
+            }
+
+            /// Sets the duty cycle of all the channels once the driver is built.
+            ///
+            /// Defaults to [`DutyCycle::FULLY_OFF`](ariel_os_embassy_common::pwm::DutyCycle::FULLY_OFF).
+            #[must_use]
+            pub fn initial_duty_cycle(
+                mut self,
+                duty_cycle: ariel_os_embassy_common::pwm::DutyCycle,
+            ) -> Self {
+                self.settings.initial_duty_cycle = duty_cycle;
+                self
+            }
+        }
+    };
+}
+
+/// Implements the PWM driver methods on the driver enum, by dispatching to the
+/// peripheral-specific drivers.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! impl_pwm_for_driver_enum {
+    ($driver_enum:ident, $( $peripheral:ident ),*) => {
+        impl $driver_enum {
+            /// Returns the number of channels of this PWM instance.
+            #[must_use]
+            pub fn channel_count(&self) -> usize {
+                match self {
+                    $( Self::$peripheral(pwm) => pwm.channel_count(), )*
+                }
+            }
+
+            /// Sets the duty cycle of `channel`.
+            ///
+            /// See the `PwmBuilder` of the HAL for how channels are numbered.
+            ///
+            /// # Errors
+            ///
+            /// Returns [`Error::InvalidChannel`](ariel_os_embassy_common::pwm::Error::InvalidChannel)
+            /// if `channel` does not exist.
+            pub fn set_duty_cycle(
+                &mut self,
+                channel: usize,
+                duty_cycle: ariel_os_embassy_common::pwm::DutyCycle,
+            ) -> Result<(), ariel_os_embassy_common::pwm::Error> {
+                match self {
+                    $( Self::$peripheral(pwm) => pwm.set_duty_cycle(channel, duty_cycle), )*
+                }
+            }
+
+            /// Changes the frequency of all the channels, keeping their duty cycles.
+            ///
+            /// # Errors
+            ///
+            /// Returns [`Error::FrequencyNotSupported`](ariel_os_embassy_common::pwm::Error::FrequencyNotSupported)
+            /// if the frequency cannot be obtained.
+            pub fn set_frequency(
+                &mut self,
+                frequency: ariel_os_embassy_common::pwm::Hertz,
+            ) -> Result<(), ariel_os_embassy_common::pwm::Error> {
+                match self {
+                    $( Self::$peripheral(pwm) => pwm.set_frequency(frequency), )*
+                }
+            }
+        }
+    };
+}
+
+/// Returns the smallest power-of-two clock divider, up to `2^max_divider_log2`, such that a
+/// period at `frequency` fits into `max_ticks` ticks of the divided `clock_hz` clock.
+///
+/// Returns the base-2 logarithm of the divider and the number of ticks per period.
+///
+/// # Note
+///
+/// For HAL implementors only.
+#[doc(hidden)]
+#[must_use]
+pub fn find_divider(
+    clock_hz: u32,
+    frequency: Hertz,
+    max_divider_log2: u8,
+    max_ticks: u32,
+) -> Option<(u8, u32)> {
+    let frequency = frequency.to_Hz();
+    if frequency == 0 {
+        return None;
+    }
+
+    (0..=max_divider_log2).find_map(|divider_log2| {
+        let ticks = (clock_hz >> divider_log2) / frequency;
+        (ticks > 0 && ticks <= max_ticks).then_some((divider_log2, ticks))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duty_cycle_constructors() {
+        assert_eq!(DutyCycle::from_percent(50).basis_points(), 5000);
+        assert_eq!(DutyCycle::from_percent(150), DutyCycle::FULLY_ON);
+        assert_eq!(DutyCycle::from_permille(125).basis_points(), 1250);
+        assert_eq!(DutyCycle::from_fraction(1, 3).basis_points(), 3333);
+        assert_eq!(DutyCycle::from_fraction(4, 3), DutyCycle::FULLY_ON);
+    }
+
+    #[test]
+    fn duty_cycle_to_ticks() {
+        let duty_cycle = DutyCycle::from_percent(25);
+        assert_eq!(duty_cycle.high_ticks(1000, Polarity::ActiveHigh), 250);
+        assert_eq!(duty_cycle.high_ticks(1000, Polarity::ActiveLow), 750);
+        assert_eq!(DutyCycle::FULLY_OFF.high_ticks(1000, Polarity::ActiveLow), 1000);
+    }
+
+    #[test]
+    fn divider_search() {
+        // 16 MHz clock, 15-bit counter: 1 kHz fits without dividing.
+        assert_eq!(
+            find_divider(16_000_000, Hertz::Hz(1000), 7, 32767),
+            Some((0, 16_000))
+        );
+        // 50 Hz requires dividing by 16.
+        assert_eq!(
+            find_divider(16_000_000, Hertz::Hz(50), 7, 32767),
+            Some((4, 20_000))
+        );
+        // Too slow.
+        assert_eq!(find_divider(16_000_000, Hertz::Hz(1), 7, 32767), None);
+        // Too fast.
+        assert_eq!(find_divider(16_000_000, Hertz::MHz(32), 7, 32767), None);
+    }
+}
@@ -0,0 +1,39 @@
+//! Provides HAL-agnostic types related to system off.
+
+/// Edge of a GPIO input that wakes the MCU up from system off.
+///
+/// On MCUs that can only detect levels in system off, the MCU wakes up when the input reaches
+/// the level the edge leads to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum WakeEdge {
+    /// The input transitions from low to high.
+    Rising,
+    /// The input transitions from high to low.
+    Falling,
+}
+
+/// Error returned when system off cannot be entered.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Error {
+    /// System off is not supported by this MCU.
+    Unsupported,
+    /// A wake source is not supported by this MCU, or not from system off.
+    UnsupportedWakeSource,
+    /// The idle mode is currently constrained, see `ariel_os::power::constrain_idle_mode()`.
+    Constrained,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Unsupported => write!(f, "system off not supported"),
+            Self::UnsupportedWakeSource => write!(f, "unsupported wake source"),
+            Self::Constrained => write!(f, "idle mode constrained"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
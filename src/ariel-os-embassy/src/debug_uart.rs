@@ -1,29 +1,93 @@
 #![deny(clippy::pedantic)]
 
+//! Provides the debug UART, which carries the logging output and can be read from.
+
 #[cfg(context = "nrf")]
 type UartDriver = embassy_nrf::uarte::Uarte<'static>;
+#[cfg(context = "nrf")]
+type UartTx = embassy_nrf::uarte::UarteTx<'static>;
+#[cfg(context = "nrf")]
+type UartRx = embassy_nrf::uarte::UarteRx<'static>;
+#[cfg(context = "rp2040")]
+type UartDriver = embassy_rp::uart::BufferedUart;
 #[cfg(context = "rp2040")]
-type UartDriver = embassy_rp::uart::Uart<'static, embassy_rp::uart::Blocking>;
+type UartTx = embassy_rp::uart::BufferedUartTx;
+#[cfg(context = "rp2040")]
+type UartRx = embassy_rp::uart::BufferedUartRx;
+#[cfg(context = "stm32")]
+type UartDriver = embassy_stm32::usart::BufferedUart<'static>;
 #[cfg(context = "stm32")]
-type UartDriver = embassy_stm32::usart::Uart<'static, embassy_stm32::mode::Blocking>;
+type UartTx = embassy_stm32::usart::BufferedUartTx<'static>;
+#[cfg(context = "stm32")]
+type UartRx = embassy_stm32::usart::BufferedUartRx<'static>;
+
+type Mutex<T> =
+    embassy_sync::mutex::Mutex<embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex, T>;
 
-static DEBUG_UART: embassy_sync::once_lock::OnceLock<
-    embassy_sync::mutex::Mutex<
-        embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
-        UartDriver,
-    >,
-> = embassy_sync::once_lock::OnceLock::new();
+static DEBUG_UART_TX: embassy_sync::once_lock::OnceLock<Mutex<UartTx>> =
+    embassy_sync::once_lock::OnceLock::new();
+static DEBUG_UART_RX: embassy_sync::once_lock::OnceLock<Mutex<UartRx>> =
+    embassy_sync::once_lock::OnceLock::new();
+
+/// Size of the buffers of interrupt-driven UART drivers.
+///
+/// The receive buffer holds the input received while it is not being read, e.g., when pasting.
+#[cfg(any(context = "rp", context = "stm32"))]
+const BUFFER_SIZE: usize = 256;
 
 #[expect(clippy::missing_panics_doc)]
 pub fn init(peripherals: &mut crate::hal::OptionalPeripherals) {
     // TODO: this could later be replaced with our UART abstraction and app configuration.
-    let uart = iot_lab::get_uart_driver(peripherals);
+    let uart: UartDriver = iot_lab::get_uart_driver(peripherals);
+    // Splitting the driver allows to wait for input without blocking the logging output.
+    let (tx, rx) = uart.split();
 
-    let _ = DEBUG_UART.init(embassy_sync::mutex::Mutex::new(uart));
+    let _ = DEBUG_UART_TX.init(Mutex::new(tx));
+    let _ = DEBUG_UART_RX.init(Mutex::new(rx));
 
     let _ = ariel_os_log::backend::DEBUG_UART_WRITE_FN.init(write_debug_uart);
 }
 
+/// Writes `buffer` to the debug UART.
+///
+/// This blocks until the whole buffer has been sent.
+/// Data written before the debug UART is initialized is dropped.
+///
+/// # Errors
+///
+/// Returns an error when the UART driver fails to send the data.
+pub fn write(buffer: &[u8]) -> Result<(), ariel_os_log::backend::Error> {
+    write_debug_uart(buffer)
+}
+
+/// Waits for data to be received on the debug UART, and reads it into `buffer`.
+///
+/// Returns the number of bytes read, which is at least one unless `buffer` is empty.
+/// Receive errors (e.g., framing errors) are skipped over.
+pub async fn read(buffer: &mut [u8]) -> usize {
+    if buffer.is_empty() {
+        return 0;
+    }
+
+    let mut rx = DEBUG_UART_RX.get().await.lock().await;
+
+    loop {
+        // Reading a single byte at a time keeps the latency low for interactive usage.
+        #[cfg(context = "nrf")]
+        if let Some(first) = buffer.first_mut()
+            && rx.read(core::slice::from_mut(first)).await.is_ok()
+        {
+            return 1;
+        }
+
+        // Input is received into a buffer by the interrupt handler, and returned as it arrives.
+        #[cfg(any(context = "rp", context = "stm32"))]
+        if let Ok(len) = embedded_io_async::Read::read(&mut *rx, buffer).await {
+            return len;
+        }
+    }
+}
+
 fn write_debug_uart(buffer: &[u8]) -> Result<(), ariel_os_log::backend::Error> {
     use ariel_os_log::backend::Error;
 
@@ -36,29 +100,22 @@ fn write_debug_uart(buffer: &[u8]) -> Result<(), ariel_os_log::backend::Error> {
         // This effectively drops logs until the UART driver is populated.
         // If we instead waited on it to be set, this would deadlock when trying to print
         // on the logging output before the driver is populated.
-        if let Some(uart) = DEBUG_UART.try_get() {
+        if let Some(uart) = DEBUG_UART_TX.try_get() {
             let mut uart = uart.lock().await;
 
             #[cfg(context = "nrf")]
             {
-                uart.write(buffer).await.map_err(|_| Error::Writing)?;
+                uart.write_all(buffer).await.map_err(|_| Error::Writing)?;
                 // TODO: is flushing needed here?
                 uart.flush().await.map_err(|_| Error::Writing)?;
             }
 
-            #[cfg(context = "rp")]
+            #[cfg(any(context = "rp", context = "stm32"))]
             {
                 uart.write_all(buffer).map_err(|_| Error::Writing)?;
                 // TODO: is flushing needed here?
                 uart.flush().map_err(|_| Error::Writing)?;
             }
-
-            #[cfg(context = "stm32")]
-            {
-                uart.write(buffer).map_err(|_| Error::Writing)?;
-                // TODO: is flushing needed here?
-                uart.flush().map_err(|_| Error::Writing)?;
-            }
         }
 
         Ok(())
@@ -104,13 +161,34 @@ mod iot_lab {
             )
         };
 
-        embassy_rp::uart::Uart::new_blocking(p, uart_tx, uart_rx, config)
+        embassy_rp::bind_interrupts!(struct Irqs {
+            UART0_IRQ => embassy_rp::uart::BufferedInterruptHandler<embassy_rp::peripherals::UART0>;
+        });
+
+        static TX_BUFFER: static_cell::ConstStaticCell<[u8; super::BUFFER_SIZE]> =
+            static_cell::ConstStaticCell::new([0; super::BUFFER_SIZE]);
+        static RX_BUFFER: static_cell::ConstStaticCell<[u8; super::BUFFER_SIZE]> =
+            static_cell::ConstStaticCell::new([0; super::BUFFER_SIZE]);
+
+        embassy_rp::uart::BufferedUart::new(
+            p,
+            uart_tx,
+            uart_rx,
+            Irqs,
+            TX_BUFFER.take(),
+            RX_BUFFER.take(),
+            config,
+        )
     }
 
     #[cfg(context = "stm32")]
     pub fn get_uart_driver(peripherals: &mut crate::hal::OptionalPeripherals) -> super::UartDriver {
         let mut config = embassy_stm32::usart::Config::default();
 
+        #[cfg(any(context = "st-b-l475e-iot01a", context = "st-nucleo-wb55"))]
+        embassy_stm32::bind_interrupts!(struct Irqs {
+            USART1 => embassy_stm32::usart::BufferedInterruptHandler<embassy_stm32::peripherals::USART1>;
+        });
         #[cfg(any(context = "st-b-l475e-iot01a", context = "st-nucleo-wb55"))]
         let (p, uart_rx, uart_tx) = {
             // https://www.iot-lab.info/docs/boards/st-b-l475e-iot01a/
@@ -122,6 +200,10 @@ mod iot_lab {
             )
         };
 
+        #[cfg(context = "stm32u083c-dk")]
+        embassy_stm32::bind_interrupts!(struct Irqs {
+            USART2_LPUART2 => embassy_stm32::usart::BufferedInterruptHandler<embassy_stm32::peripherals::USART2>;
+        });
         #[cfg(context = "stm32u083c-dk")]
         let (p, uart_rx, uart_tx) = {
             config.baudrate = 115_200;
@@ -132,6 +214,20 @@ mod iot_lab {
             )
         };
 
-        embassy_stm32::usart::Uart::new_blocking(p, uart_rx, uart_tx, config).unwrap()
+        static TX_BUFFER: static_cell::ConstStaticCell<[u8; super::BUFFER_SIZE]> =
+            static_cell::ConstStaticCell::new([0; super::BUFFER_SIZE]);
+        static RX_BUFFER: static_cell::ConstStaticCell<[u8; super::BUFFER_SIZE]> =
+            static_cell::ConstStaticCell::new([0; super::BUFFER_SIZE]);
+
+        embassy_stm32::usart::BufferedUart::new(
+            p,
+            uart_rx,
+            uart_tx,
+            TX_BUFFER.take(),
+            RX_BUFFER.take(),
+            Irqs,
+            config,
+        )
+        .unwrap()
     }
 }
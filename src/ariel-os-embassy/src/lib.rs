@@ -34,6 +34,12 @@ mod ethernet;
 #[cfg(feature = "cellular-networking")]
 mod cellular_networking;
 
+#[cfg(all(feature = "wall-clock", feature = "ltem-nrf-modem"))]
+mod wall_clock;
+
+#[cfg(feature = "watchdog")]
+mod watchdog;
+
 use ariel_os_log::debug;
 
 use linkme::distributed_slice;
@@ -208,16 +214,31 @@ async fn init_task(mut peripherals: hal::OptionalPeripherals) {
 
     debug!("ariel-os-embassy::init_task()");
 
+    ariel_os_power::init();
+
     // gated so doc builds pass
     #[cfg(all(not(feature = "no-boards"), context = "ariel-os"))]
     ariel_os_boards::init(&mut peripherals);
 
+    // Start the watchdog early, so that it also supervises the rest of the system startup.
+    #[cfg(feature = "watchdog")]
+    {
+        let watchdog = ariel_os_watchdog::init(&mut peripherals);
+        spawner.spawn(watchdog::watchdog_task(watchdog)).unwrap();
+    }
+
     #[cfg(all(context = "stm32", feature = "external-interrupts"))]
     hal::extint_registry::EXTINT_REGISTRY.init(&mut peripherals);
 
+    #[cfg(feature = "adc")]
+    hal::adc::init(&mut peripherals);
+
     #[cfg(feature = "i2c")]
     hal::i2c::init(&mut peripherals);
 
+    #[cfg(feature = "pwm")]
+    hal::pwm::init(&mut peripherals);
+
     #[cfg(feature = "spi")]
     hal::spi::init(&mut peripherals);
 
@@ -425,6 +446,11 @@ async fn init_task(mut peripherals: hal::OptionalPeripherals) {
                         stack,
                     ))
                     .unwrap();
+
+                #[cfg(feature = "wall-clock")]
+                spawner
+                    .spawn(wall_clock::cellular_network_time_task())
+                    .unwrap();
             }
         }
     }
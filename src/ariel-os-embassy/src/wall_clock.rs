@@ -0,0 +1,29 @@
+use ariel_os_wall_clock::TimeSource;
+use embassy_time::{Duration, Timer};
+
+use crate::hal;
+
+/// Sets the wall clock from the time provided by the cellular network, unless it has been set
+/// from a more accurate source.
+#[embassy_executor::task]
+pub(crate) async fn cellular_network_time_task() -> ! {
+    const RETRY_INTERVAL: Duration = Duration::from_secs(10);
+    const SYNC_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+    loop {
+        let interval = match hal::ltem::network_time().await {
+            Some(time) => {
+                if matches!(
+                    ariel_os_wall_clock::source(),
+                    None | Some(TimeSource::Rtc | TimeSource::CellularNetwork)
+                ) {
+                    ariel_os_wall_clock::set(time, TimeSource::CellularNetwork);
+                }
+                SYNC_INTERVAL
+            }
+            None => RETRY_INTERVAL,
+        };
+
+        Timer::after(interval).await;
+    }
+}
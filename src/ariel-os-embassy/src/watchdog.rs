@@ -0,0 +1,6 @@
+use crate::hal;
+
+#[embassy_executor::task]
+pub(crate) async fn watchdog_task(watchdog: hal::watchdog::Watchdog) -> ! {
+    ariel_os_watchdog::supervise(watchdog).await
+}
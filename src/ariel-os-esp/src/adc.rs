@@ -0,0 +1,352 @@
+//! Provides support for the ADC.
+//!
+//! Only ADC1 is supported, as ADC2 is shared with the radio on these MCUs.
+
+#![expect(unsafe_code)]
+
+use core::marker::PhantomData;
+
+use portable_atomic::{AtomicBool, Ordering};
+
+use ariel_os_embassy_common::adc::{Error, Sample, software_oversample};
+use esp_hal::{
+    analog::adc::{self, AdcConfig, Attenuation},
+    peripherals,
+};
+
+// NOTE(hal): lower resolutions are obtained by discarding the least significant bits.
+#[cfg(context = "esp32s2")]
+const HW_RESOLUTION_BITS: u8 = 13;
+#[cfg(not(context = "esp32s2"))]
+const HW_RESOLUTION_BITS: u8 = 12;
+
+/// ADC configuration.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// Resolution of the conversions.
+    pub resolution: ariel_os_embassy_common::adc::Resolution<Resolution>,
+    /// Oversampling ratio, carried out in software.
+    pub oversampling: ariel_os_embassy_common::adc::Oversampling<Oversampling>,
+    /// Voltage reference, selected through the input attenuation.
+    pub reference: ariel_os_embassy_common::adc::Reference<Reference>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            resolution: ariel_os_embassy_common::adc::Resolution::Bits12,
+            oversampling: ariel_os_embassy_common::adc::Oversampling::None,
+            reference: ariel_os_embassy_common::adc::Reference::Vdd,
+        }
+    }
+}
+
+/// ADC resolution.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Resolution {
+    /// 8-bit resolution.
+    Bits8,
+    /// 10-bit resolution.
+    Bits10,
+    /// 12-bit resolution.
+    Bits12,
+    /// 13-bit resolution.
+    #[cfg(context = "esp32s2")]
+    Bits13,
+}
+
+impl From<Resolution> for u8 {
+    fn from(resolution: Resolution) -> u8 {
+        match resolution {
+            Resolution::Bits8 => 8,
+            Resolution::Bits10 => 10,
+            Resolution::Bits12 => 12,
+            #[cfg(context = "esp32s2")]
+            Resolution::Bits13 => 13,
+        }
+    }
+}
+
+impl From<ariel_os_embassy_common::adc::Resolution<Self>> for Resolution {
+    fn from(resolution: ariel_os_embassy_common::adc::Resolution<Self>) -> Self {
+        match resolution {
+            ariel_os_embassy_common::adc::Resolution::Hal(resolution) => resolution,
+            ariel_os_embassy_common::adc::Resolution::Bits8 => Self::Bits8,
+            ariel_os_embassy_common::adc::Resolution::Bits10 => Self::Bits10,
+            ariel_os_embassy_common::adc::Resolution::Bits12 => Self::Bits12,
+        }
+    }
+}
+
+/// ADC oversampling ratio, carried out in software.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Oversampling {
+    /// No oversampling.
+    None,
+    /// 2× oversampling.
+    X2,
+    /// 4× oversampling.
+    X4,
+    /// 8× oversampling.
+    X8,
+    /// 16× oversampling.
+    X16,
+}
+
+impl From<Oversampling> for u16 {
+    fn from(oversampling: Oversampling) -> u16 {
+        match oversampling {
+            Oversampling::None => 1,
+            Oversampling::X2 => 2,
+            Oversampling::X4 => 4,
+            Oversampling::X8 => 8,
+            Oversampling::X16 => 16,
+        }
+    }
+}
+
+impl From<ariel_os_embassy_common::adc::Oversampling<Self>> for Oversampling {
+    fn from(oversampling: ariel_os_embassy_common::adc::Oversampling<Self>) -> Self {
+        match oversampling {
+            ariel_os_embassy_common::adc::Oversampling::Hal(oversampling) => oversampling,
+            ariel_os_embassy_common::adc::Oversampling::None => Self::None,
+            ariel_os_embassy_common::adc::Oversampling::X2 => Self::X2,
+            ariel_os_embassy_common::adc::Oversampling::X4 => Self::X4,
+            ariel_os_embassy_common::adc::Oversampling::X8 => Self::X8,
+            ariel_os_embassy_common::adc::Oversampling::X16 => Self::X16,
+        }
+    }
+}
+
+/// ADC voltage reference, selected through the input attenuation.
+///
+/// The full scale values are approximate, as conversions are not calibrated.
+/// Refer to the "ADC Characteristics" section of the datasheet for the effective measurement
+/// ranges.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Reference {
+    /// No attenuation, full scale is about 950 mV.
+    Attenuation0dB,
+    /// 2.5 dB attenuation, full scale is about 1250 mV.
+    Attenuation2p5dB,
+    /// 6 dB attenuation, full scale is about 1750 mV.
+    Attenuation6dB,
+    /// 11 dB attenuation, full scale is about 2500 mV to 3100 mV depending on the chip.
+    Attenuation11dB,
+}
+
+impl From<ariel_os_embassy_common::adc::Reference<Self>> for Reference {
+    fn from(reference: ariel_os_embassy_common::adc::Reference<Self>) -> Self {
+        match reference {
+            ariel_os_embassy_common::adc::Reference::Hal(reference) => reference,
+            // NOTE(hal): the supply cannot be used as reference, use the largest range instead.
+            ariel_os_embassy_common::adc::Reference::Vdd => Self::Attenuation11dB,
+        }
+    }
+}
+
+impl Reference {
+    fn attenuation(self) -> Attenuation {
+        match self {
+            Self::Attenuation0dB => Attenuation::_0dB,
+            Self::Attenuation2p5dB => Attenuation::_2p5dB,
+            Self::Attenuation6dB => Attenuation::_6dB,
+            Self::Attenuation11dB => Attenuation::_11dB,
+        }
+    }
+
+    fn full_scale_mv(self) -> u32 {
+        match self {
+            Self::Attenuation0dB => 950,
+            Self::Attenuation2p5dB => 1250,
+            Self::Attenuation6dB => 1750,
+            #[cfg(any(context = "esp32c3", context = "esp32c6"))]
+            Self::Attenuation11dB => 2500,
+            #[cfg(not(any(context = "esp32c3", context = "esp32c6")))]
+            Self::Attenuation11dB => 3100,
+        }
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Pins connected to ADC1.
+#[doc(hidden)]
+pub trait AdcPin: sealed::Sealed {
+    /// GPIO number of the pin.
+    const GPIO: u8;
+}
+
+/// An analog input of ADC1.
+pub struct Channel<'d> {
+    gpio: u8,
+    _lt: PhantomData<&'d ()>,
+}
+
+impl<'d> Channel<'d> {
+    /// Returns a channel sampling the given analog-capable pin.
+    pub fn new<P: AdcPin>(pin: impl crate::IntoPeripheral<'d, P>) -> Self {
+        // The pin is only used to enforce exclusive access, it is stolen again when needed.
+        let _ = pin.into_hal_peripheral();
+        Self {
+            gpio: P::GPIO,
+            _lt: PhantomData,
+        }
+    }
+}
+
+/// Performs a single conversion on the given GPIO.
+///
+/// # Safety
+///
+/// The GPIO must be owned by the caller.
+unsafe fn read_gpio(gpio: u8, attenuation: Attenuation) -> u16 {
+    macro_rules! read_gpio {
+        ($($gpio:literal),*) => {
+            paste::paste! {
+                match gpio {
+                    $(
+                        $gpio => {
+                            // SAFETY: ownership of the GPIO is guaranteed by the caller.
+                            let pin = unsafe { peripherals::[<GPIO $gpio>]::steal() };
+                            let mut adc_config = AdcConfig::new();
+                            let mut pin = adc_config.enable_pin(pin, attenuation);
+                            // SAFETY: `ADC1` is only used by the ADC driver, which guarantees
+                            // exclusive access.
+                            let adc1 = unsafe { peripherals::ADC1::steal() };
+                            let mut adc = adc::Adc::new(adc1, adc_config);
+                            loop {
+                                // The only error is `WouldBlock`; a conversion only takes a few
+                                // microseconds.
+                                if let Ok(raw) = adc.read_oneshot(&mut pin) {
+                                    break raw;
+                                }
+                                core::hint::spin_loop();
+                            }
+                        }
+                    )*
+                    _ => unreachable!(),
+                }
+            }
+        };
+    }
+
+    cfg_select! {
+        context = "esp32" => { read_gpio!(32, 33, 34, 35, 36, 37, 38, 39) }
+        context = "esp32c3" => { read_gpio!(0, 1, 2, 3, 4) }
+        context = "esp32c6" => { read_gpio!(0, 1, 2, 3, 4, 5, 6) }
+        any(context = "esp32s2", context = "esp32s3") => {
+            read_gpio!(1, 2, 3, 4, 5, 6, 7, 8, 9, 10)
+        }
+    }
+}
+
+macro_rules! impl_adc_pin {
+    ($($gpio:literal),*) => {
+        paste::paste! {
+            $(
+                impl sealed::Sealed for peripherals::[<GPIO $gpio>]<'_> {}
+                impl AdcPin for peripherals::[<GPIO $gpio>]<'_> {
+                    const GPIO: u8 = $gpio;
+                }
+            )*
+        }
+    };
+}
+
+cfg_select! {
+    context = "esp32" => { impl_adc_pin!(32, 33, 34, 35, 36, 37, 38, 39); }
+    context = "esp32c3" => { impl_adc_pin!(0, 1, 2, 3, 4); }
+    context = "esp32c6" => { impl_adc_pin!(0, 1, 2, 3, 4, 5, 6); }
+    any(context = "esp32s2", context = "esp32s3") => {
+        impl_adc_pin!(1, 2, 3, 4, 5, 6, 7, 8, 9, 10);
+    }
+}
+
+/// Peripheral-specific ADC driver.
+pub struct ADC1 {
+    config: Config,
+}
+
+// Ensure this peripheral has only one active Instance.
+static ACTIVE_ADC1: AtomicBool = AtomicBool::new(false);
+
+impl ADC1 {
+    /// Returns a driver for the ADC1 peripheral.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the peripheral is already in use.
+    #[expect(clippy::new_ret_no_self)]
+    #[expect(clippy::manual_assert, reason = "consistent with the other drivers")]
+    #[must_use]
+    pub fn new(config: Config) -> Adc {
+        // Check if we can initialize this peripheral (check if the value was previously false, set it to true).
+        if ACTIVE_ADC1.swap(true, Ordering::AcqRel) {
+            panic!("ADC1 peripheral already initialized")
+        }
+
+        Adc::ADC1(Self { config })
+    }
+
+    async fn read(&mut self, channel: &mut Channel<'_>) -> Result<Sample, Error> {
+        let resolution = u8::from(Resolution::from(self.config.resolution));
+        let ratio = u16::from(Oversampling::from(self.config.oversampling));
+        let reference = Reference::from(self.config.reference);
+
+        // NOTE(hal): the upstream driver is generic over the pin, so it is instantiated for each
+        // conversion.
+        let raw = software_oversample(ratio, || {
+            // SAFETY: the `Channel` owns the GPIO.
+            let raw = unsafe { read_gpio(channel.gpio, reference.attenuation()) };
+            core::future::ready(Ok::<_, Error>(i32::from(raw)))
+        })
+        .await?;
+
+        let raw = raw >> (HW_RESOLUTION_BITS - resolution);
+        let full_scale_raw = (1u32 << resolution) - 1;
+
+        Ok(Sample::new(raw, full_scale_raw, reference.full_scale_mv()))
+    }
+}
+
+impl Drop for ADC1 {
+    fn drop(&mut self) {
+        ACTIVE_ADC1.store(false, Ordering::Release);
+    }
+}
+
+/// Peripheral-agnostic ADC driver.
+pub enum Adc {
+    /// ADC1 peripheral.
+    ADC1(ADC1),
+}
+
+impl Adc {
+    /// Performs a single-shot conversion on `channel`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Conversion`] if the conversion could not be carried out.
+    pub async fn read(&mut self, channel: &mut Channel<'_>) -> Result<Sample, Error> {
+        match self {
+            Self::ADC1(adc) => adc.read(channel).await,
+        }
+    }
+}
+
+#[doc(hidden)]
+pub fn init(peripherals: &mut crate::OptionalPeripherals) {
+    // Take the ADC peripheral and do nothing with it.
+    let _ = peripherals.ADC1.take().unwrap();
+}
@@ -20,6 +20,9 @@ mod semaphore;
 #[cfg(feature = "_radio-esp")]
 mod wait_queue;
 
+#[cfg(feature = "adc")]
+pub mod adc;
+
 pub mod gpio;
 
 #[cfg(feature = "ble-esp")]
@@ -44,9 +47,15 @@ pub mod identity {
     pub type DeviceId = identity::NoDeviceId<identity::NotImplemented>;
 }
 
+#[cfg(feature = "pwm")]
+pub mod pwm;
+
 #[cfg(feature = "spi")]
 pub mod spi;
 
+#[cfg(feature = "system-off")]
+pub mod system_off;
+
 #[cfg(feature = "uart")]
 pub mod uart;
 
@@ -54,6 +63,10 @@ pub mod uart;
 #[doc(hidden)]
 pub mod usb;
 
+#[cfg(feature = "watchdog")]
+#[doc(hidden)]
+pub mod watchdog;
+
 #[cfg(feature = "wifi")]
 #[doc(hidden)]
 pub mod wifi;
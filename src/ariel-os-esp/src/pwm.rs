@@ -0,0 +1,274 @@
+//! Provides support for PWM outputs, using the LED PWM controller (LEDC).
+//!
+//! All the channels share the same low-speed LEDC timer, and therefore the same frequency.
+
+#![expect(unsafe_code)]
+
+use portable_atomic::{AtomicBool, Ordering};
+
+use ariel_os_embassy_common::{
+    impl_pwm_builder_settings, impl_pwm_for_driver_enum,
+    pwm::{DutyCycle, Error, Hertz, Polarity, Settings},
+};
+use esp_hal::{
+    gpio::{
+        DriveMode,
+        interconnect::{OutputSignal, PeripheralOutput},
+    },
+    ledc::{
+        LSGlobalClkSource, Ledc, LowSpeed,
+        channel::{self, Channel, ChannelHW, ChannelIFace},
+        timer::{self, LSClockSource, Timer, TimerIFace},
+    },
+    peripherals,
+    time::Rate,
+};
+
+/// Number of channels of the LEDC peripheral.
+#[cfg(any(context = "esp32c3", context = "esp32c6"))]
+pub const CHANNELS: usize = 6;
+/// Number of channels of the LEDC peripheral.
+#[cfg(any(context = "esp32", context = "esp32s2", context = "esp32s3"))]
+pub const CHANNELS: usize = 8;
+
+#[cfg(any(context = "esp32c3", context = "esp32c6"))]
+const CHANNEL_NUMBERS: [channel::Number; CHANNELS] = [
+    channel::Number::Channel0,
+    channel::Number::Channel1,
+    channel::Number::Channel2,
+    channel::Number::Channel3,
+    channel::Number::Channel4,
+    channel::Number::Channel5,
+];
+#[cfg(any(context = "esp32", context = "esp32s2", context = "esp32s3"))]
+const CHANNEL_NUMBERS: [channel::Number; CHANNELS] = [
+    channel::Number::Channel0,
+    channel::Number::Channel1,
+    channel::Number::Channel2,
+    channel::Number::Channel3,
+    channel::Number::Channel4,
+    channel::Number::Channel5,
+    channel::Number::Channel6,
+    channel::Number::Channel7,
+];
+
+// NOTE(hal): the low-speed timers are clocked from the 80 MHz APB clock, and their duty
+// resolution is at most 14 bits on all the supported MCUs.
+const CLOCK_HZ: u32 = 80_000_000;
+const MAX_DUTY_RESOLUTION_BITS: u32 = 14;
+
+/// Builder for a PWM driver, obtained by calling [`LEDC::builder()`].
+///
+/// Any GPIO can be used by any channel; channels are numbered in the order they are added with
+/// [`PwmBuilder::channel()`].
+pub struct PwmBuilder {
+    settings: Settings,
+    pins: [Option<OutputSignal<'static>>; CHANNELS],
+}
+
+impl_pwm_builder_settings!();
+
+impl PwmBuilder {
+    /// Adds a channel outputting to `pin`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if all the [`CHANNELS`] channels are already used.
+    #[must_use]
+    pub fn channel<P: PeripheralOutput<'static>>(
+        mut self,
+        pin: impl crate::IntoPeripheral<'static, P>,
+    ) -> Self {
+        let Some(slot) = self.pins.iter_mut().find(|slot| slot.is_none()) else {
+            panic!("all PWM channels are already used");
+        };
+        *slot = Some(pin.into_hal_peripheral().into());
+        self
+    }
+
+    /// Returns a PWM driver by finalizing the builder.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::FrequencyNotSupported`] if the frequency cannot be obtained.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the peripheral is already in use.
+    pub fn build(self) -> Result<Pwm, Error> {
+        LEDC::build(self)
+    }
+}
+
+/// Returns the timer configuration for `frequency`, using the highest possible duty resolution.
+fn timer_config(frequency: Hertz) -> Result<timer::config::Config<LSClockSource>, Error> {
+    let hz = frequency.to_Hz();
+    if hz == 0 {
+        return Err(Error::FrequencyNotSupported);
+    }
+
+    let resolution_bits = (CLOCK_HZ / hz)
+        .checked_ilog2()
+        .ok_or(Error::FrequencyNotSupported)?
+        .min(MAX_DUTY_RESOLUTION_BITS);
+    let duty = timer::config::Duty::try_from(resolution_bits)
+        .map_err(|()| Error::FrequencyNotSupported)?;
+
+    Ok(timer::config::Config {
+        duty,
+        clock_source: LSClockSource::APBClk,
+        frequency: Rate::from_hz(hz),
+    })
+}
+
+// The channels keep a reference to the timer they are configured with, which therefore needs to
+// live in a static.
+static mut TIMER: Option<Timer<'static, LowSpeed>> = None;
+
+/// LEDC driver.
+pub struct LEDC {
+    ledc: Ledc<'static>,
+    channels: [Option<Channel<'static, LowSpeed>>; CHANNELS],
+    polarity: Polarity,
+    period_ticks: u32,
+    duty_cycles: [DutyCycle; CHANNELS],
+}
+
+static ACTIVE_LEDC: AtomicBool = AtomicBool::new(false);
+
+impl LEDC {
+    /// Returns a [`PwmBuilder`] for this peripheral, producing a PWM signal at `frequency`.
+    #[must_use]
+    pub fn builder(frequency: Hertz) -> PwmBuilder {
+        PwmBuilder {
+            settings: Settings::new(frequency),
+            pins: [const { None }; CHANNELS],
+        }
+    }
+
+    fn build(builder: PwmBuilder) -> Result<Pwm, Error> {
+        let config = timer_config(builder.settings.frequency)?;
+
+        // Check if we can initialize this peripheral (check if the value was previously false, set it to true).
+        assert!(
+            !ACTIVE_LEDC.swap(true, Ordering::AcqRel),
+            "LEDC peripheral already initialized"
+        );
+
+        // FIXME(safety): enforce that the init code indeed has run
+        // SAFETY: We check with an AtomicBool that only one instance of this peripheral
+        // is active at once.
+        let ledc_peripheral = unsafe { peripherals::LEDC::steal() };
+
+        let mut ledc = Ledc::new(ledc_peripheral);
+        ledc.set_global_slow_clock(LSGlobalClkSource::APBClk);
+
+        let mut timer = ledc.timer::<LowSpeed>(timer::Number::Timer0);
+        if timer.configure(config).is_err() {
+            ACTIVE_LEDC.store(false, Ordering::Release);
+            return Err(Error::FrequencyNotSupported);
+        }
+
+        let timer_slot = &raw mut TIMER;
+        // SAFETY: the timer is only accessed by the active driver, and the previous one has been
+        // dropped, along with its channels.
+        let timer: &'static Timer<'static, LowSpeed> = unsafe { (*timer_slot).insert(timer) };
+
+        let mut channels = [const { None }; CHANNELS];
+        for ((slot, pin), number) in channels
+            .iter_mut()
+            .zip(builder.pins)
+            .zip(CHANNEL_NUMBERS)
+        {
+            let Some(pin) = pin else {
+                continue;
+            };
+
+            let mut channel = ledc.channel(number, pin);
+            // Cannot fail as the timer has been configured.
+            channel
+                .configure(channel::config::Config {
+                    timer,
+                    duty_pct: 0,
+                    drive_mode: DriveMode::PushPull,
+                })
+                .unwrap();
+            *slot = Some(channel);
+        }
+
+        let ledc = Self {
+            ledc,
+            channels,
+            polarity: builder.settings.polarity,
+            period_ticks: 1 << config.duty as u32,
+            duty_cycles: [builder.settings.initial_duty_cycle; CHANNELS],
+        };
+        ledc.apply_duty_cycles();
+
+        Ok(Pwm::LEDC(ledc))
+    }
+
+    fn apply_duty_cycles(&self) {
+        for (channel, duty_cycle) in self.channels.iter().zip(self.duty_cycles) {
+            if let Some(channel) = channel {
+                channel.set_duty_hw(duty_cycle.high_ticks(self.period_ticks, self.polarity));
+            }
+        }
+    }
+
+    #[expect(clippy::unused_self)]
+    fn channel_count(&self) -> usize {
+        CHANNELS
+    }
+
+    fn set_duty_cycle(&mut self, channel: usize, duty_cycle: DutyCycle) -> Result<(), Error> {
+        let Some(Some(hw_channel)) = self.channels.get(channel) else {
+            return Err(Error::InvalidChannel);
+        };
+        hw_channel.set_duty_hw(duty_cycle.high_ticks(self.period_ticks, self.polarity));
+
+        let slot = self
+            .duty_cycles
+            .get_mut(channel)
+            .ok_or(Error::InvalidChannel)?;
+        *slot = duty_cycle;
+
+        Ok(())
+    }
+
+    fn set_frequency(&mut self, frequency: Hertz) -> Result<(), Error> {
+        let config = timer_config(frequency)?;
+
+        // Reconfigure the hardware timer through a new handle, as the channels hold a shared
+        // reference to the first one; the channels only use it during their configuration.
+        let mut timer = self.ledc.timer::<LowSpeed>(timer::Number::Timer0);
+        timer
+            .configure(config)
+            .map_err(|_| Error::FrequencyNotSupported)?;
+
+        self.period_ticks = 1 << config.duty as u32;
+        self.apply_duty_cycles();
+
+        Ok(())
+    }
+}
+
+impl Drop for LEDC {
+    fn drop(&mut self) {
+        ACTIVE_LEDC.store(false, Ordering::Release);
+    }
+}
+
+/// Peripheral-agnostic PWM driver.
+pub enum Pwm {
+    /// LEDC peripheral.
+    LEDC(LEDC),
+}
+
+impl_pwm_for_driver_enum!(Pwm, LEDC);
+
+#[doc(hidden)]
+pub fn init(peripherals: &mut crate::OptionalPeripherals) {
+    // Take the LEDC peripheral and do nothing with it.
+    let _ = peripherals.LEDC.take().unwrap();
+}
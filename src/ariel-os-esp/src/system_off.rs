@@ -0,0 +1,65 @@
+//! Provides system off, from which the MCU wakes up with a reset.
+//!
+//! The MCU is put in deep sleep.
+
+#![expect(unsafe_code)]
+
+use core::{convert::Infallible, time::Duration};
+
+use esp_hal::{
+    gpio::InputPin,
+    rtc_cntl::{Rtc, sleep::TimerWakeupSource},
+};
+
+pub use ariel_os_embassy_common::system_off::{Error, WakeEdge};
+
+/// GPIO pin that can wake the MCU up from system off.
+#[derive(Debug, Clone, Copy)]
+pub struct WakePin {
+    #[expect(dead_code, reason = "RTC IO pins are not supported yet")]
+    number: u8,
+}
+
+#[doc(hidden)]
+pub fn wake_pin<P: InputPin>(pin: &P) -> WakePin {
+    WakePin {
+        number: pin.number(),
+    }
+}
+
+/// Source waking the MCU up from system off.
+#[derive(Debug, Clone, Copy)]
+pub enum WakeSource {
+    #[doc(hidden)]
+    Pin(WakePin, WakeEdge),
+    #[doc(hidden)]
+    Timer(Duration),
+}
+
+#[doc(hidden)]
+pub fn system_off(wake_sources: impl IntoIterator<Item = WakeSource>) -> Result<Infallible, Error> {
+    let mut timer = None;
+
+    for wake_source in wake_sources {
+        match wake_source {
+            // FIXME: support RTC IO pins, which are the only ones usable in deep sleep.
+            WakeSource::Pin(..) => return Err(Error::UnsupportedWakeSource),
+            WakeSource::Timer(duration) => {
+                // Keep the earliest timer.
+                let duration = timer.map_or(duration, |t: Duration| t.min(duration));
+                timer = Some(duration);
+            }
+        }
+    }
+
+    // SAFETY: the RTC peripheral is only used to enter deep sleep, from which execution never
+    // resumes.
+    let mut rtc = Rtc::new(unsafe { esp_hal::peripherals::LPWR::steal() });
+
+    if let Some(duration) = timer {
+        let timer = TimerWakeupSource::new(duration);
+        rtc.sleep_deep(&[&timer])
+    } else {
+        rtc.sleep_deep(&[])
+    }
+}
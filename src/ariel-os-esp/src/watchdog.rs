@@ -0,0 +1,36 @@
+use esp_hal::{
+    rtc_cntl::{Rtc, RwdtStage},
+    time::Duration,
+};
+
+/// Longest supported timeout, in milliseconds.
+// The timeout is converted to slow clock cycles in a 32-bit register.
+pub const MAX_TIMEOUT_MS: u32 = 3_600_000;
+
+/// Hardware watchdog, started by [`init()`].
+pub struct Watchdog {
+    rtc: Rtc<'static>,
+}
+
+impl Watchdog {
+    /// Feeds the watchdog, restarting its timeout.
+    pub fn feed(&mut self) {
+        self.rtc.rwdt.feed();
+    }
+}
+
+/// Starts the RTC watchdog (RWDT) with a timeout of `timeout_ms` milliseconds.
+///
+/// The timeout is capped to [`MAX_TIMEOUT_MS`].
+///
+/// # Panics
+///
+/// Panics if the low-power management peripheral has been previously taken.
+pub fn init(peripherals: &mut crate::OptionalPeripherals, timeout_ms: u32) -> Watchdog {
+    let mut rtc = Rtc::new(peripherals.LPWR.take().unwrap());
+    let timeout = Duration::from_millis(u64::from(timeout_ms.min(MAX_TIMEOUT_MS)));
+    rtc.rwdt.set_timeout(RwdtStage::Stage0, timeout);
+    rtc.rwdt.enable();
+
+    Watchdog { rtc }
+}
@@ -0,0 +1,156 @@
+//! Provides FAT filesystems on block devices, e.g., SD cards, using [`embedded_fatfs`].
+
+use block_device_adapters::BufStream;
+use block_device_driver::BlockDevice;
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex};
+use embedded_fatfs::{FileSystem, FsOptions};
+use embedded_io_async::{Read as _, Seek as _, SeekFrom, Write as _};
+
+use crate::{Error, Metadata};
+
+/// Size of the blocks of the block devices FAT filesystems are mounted on, in bytes.
+const BLOCK_SIZE: usize = 512;
+
+/// A FAT filesystem mounted on a block device, e.g., an SD card using the `ariel-os-sdcard` crate.
+///
+/// Paths use `/` as separator, and are relative to the root directory.
+/// File names are not limited to 8.3 names, but only 8.3 names are returned by
+/// [`read_dir()`](Self::read_dir).
+pub struct Fat<D: BlockDevice<BLOCK_SIZE>> {
+    fs: Mutex<CriticalSectionRawMutex, FileSystem<BufStream<D, BLOCK_SIZE>>>,
+}
+
+impl<D: BlockDevice<BLOCK_SIZE>> Fat<D> {
+    /// Mounts the FAT filesystem of `device`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `device` does not hold a FAT filesystem.
+    pub async fn mount(device: D) -> Result<Self, Error> {
+        let fs = FileSystem::new(BufStream::new(device), FsOptions::new()).await?;
+        Ok(Self { fs: Mutex::new(fs) })
+    }
+
+    /// Unmounts the filesystem, writing its pending changes to the block device.
+    pub async fn unmount(self) -> Result<(), Error> {
+        Ok(self.fs.into_inner().unmount().await?)
+    }
+
+    /// Reads the beginning of the file at `path` into `buf`.
+    ///
+    /// Returns the number of bytes read, which is smaller than the length of `buf` if the file is
+    /// shorter.
+    pub async fn read(&self, path: &str, buf: &mut [u8]) -> Result<usize, Error> {
+        self.read_at(path, 0, buf).await
+    }
+
+    /// Reads the file at `path` into `buf`, starting at `offset`.
+    ///
+    /// Returns the number of bytes read, which is smaller than the length of `buf` when reaching
+    /// the end of the file.
+    pub async fn read_at(&self, path: &str, offset: u32, buf: &mut [u8]) -> Result<usize, Error> {
+        let fs = self.fs.lock().await;
+        let mut file = fs.root_dir().open_file(relative(path)).await?;
+        file.seek(SeekFrom::Start(u64::from(offset))).await?;
+
+        let mut len = 0;
+        while let Some(rest) = buf.get_mut(len..).filter(|rest| !rest.is_empty()) {
+            match file.read(rest).await? {
+                0 => break,
+                read => len += read,
+            }
+        }
+        Ok(len)
+    }
+
+    /// Writes `data` to the file at `path`, replacing its content; the file is created if it does
+    /// not exist.
+    pub async fn write(&self, path: &str, data: &[u8]) -> Result<(), Error> {
+        let fs = self.fs.lock().await;
+        let mut file = fs.root_dir().create_file(relative(path)).await?;
+        file.truncate().await?;
+        file.write_all(data).await?;
+        Ok(file.flush().await?)
+    }
+
+    /// Appends `data` to the file at `path`, which is created if it does not exist.
+    pub async fn append(&self, path: &str, data: &[u8]) -> Result<(), Error> {
+        let fs = self.fs.lock().await;
+        let mut file = fs.root_dir().create_file(relative(path)).await?;
+        file.seek(SeekFrom::End(0)).await?;
+        file.write_all(data).await?;
+        Ok(file.flush().await?)
+    }
+
+    /// Removes the file or empty directory at `path`.
+    pub async fn remove(&self, path: &str) -> Result<(), Error> {
+        let fs = self.fs.lock().await;
+        Ok(fs.root_dir().remove(relative(path)).await?)
+    }
+
+    /// Renames the file or directory at `from` to `to`.
+    pub async fn rename(&self, from: &str, to: &str) -> Result<(), Error> {
+        let fs = self.fs.lock().await;
+        let root = fs.root_dir();
+        Ok(root.rename(relative(from), &root, relative(to)).await?)
+    }
+
+    /// Creates the directory at `path`, whose parent must exist.
+    pub async fn create_dir(&self, path: &str) -> Result<(), Error> {
+        let fs = self.fs.lock().await;
+        fs.root_dir().create_dir(relative(path)).await?;
+        Ok(())
+    }
+
+    /// Calls `f` with the 8.3 name and [`Metadata`] of each entry of the directory at `path`.
+    ///
+    /// The filesystem is locked while `f` is called, which must thus not access it.
+    pub async fn read_dir(
+        &self,
+        path: &str,
+        mut f: impl FnMut(&str, Metadata),
+    ) -> Result<(), Error> {
+        let fs = self.fs.lock().await;
+        let root = fs.root_dir();
+        let dir = match relative(path) {
+            "" => root,
+            path => root.open_dir(path).await?,
+        };
+
+        let mut entries = dir.iter();
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            let Ok(name) = core::str::from_utf8(entry.short_file_name_as_bytes()) else {
+                continue;
+            };
+            if name != "." && name != ".." {
+                let metadata = Metadata {
+                    is_dir: entry.is_dir(),
+                    len: usize::try_from(entry.len()).unwrap_or(usize::MAX),
+                };
+                f(name, metadata);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Strips the leading separators of `path`, as paths are relative to the root directory.
+fn relative(path: &str) -> &str {
+    path.trim_start_matches('/')
+}
+
+impl<E> From<embedded_fatfs::Error<E>> for Error {
+    fn from(err: embedded_fatfs::Error<E>) -> Self {
+        match err {
+            embedded_fatfs::Error::NotFound => Self::NotFound,
+            embedded_fatfs::Error::AlreadyExists => Self::AlreadyExists,
+            embedded_fatfs::Error::DirectoryIsNotEmpty => Self::DirectoryNotEmpty,
+            embedded_fatfs::Error::NotEnoughSpace => Self::NoSpace,
+            embedded_fatfs::Error::InvalidFileNameLength
+            | embedded_fatfs::Error::UnsupportedFileNameCharacter => Self::InvalidPath,
+            embedded_fatfs::Error::CorruptedFileSystem => Self::Corrupted,
+            _ => Self::Io,
+        }
+    }
+}
@@ -0,0 +1,225 @@
+//! Provides a filesystem, for applications needing files, e.g., web assets, recordings or
+//! exports.
+//!
+//! The functions of this crate access a [littlefs] filesystem in the `fs` raw partition of the
+//! [storage](ariel_os_storage), a range of the internal flash reserved at build time.
+//! The filesystem is mounted once by a dedicated thread, and formatted when it cannot be mounted,
+//! e.g., on first boot; the flash is only accessed by that thread, which leaves the executor free
+//! to run other tasks meanwhile.
+//! On native, the filesystem lives in the file emulating the flash, which allows testing
+//! applications on the host.
+//!
+//! [`Littlefs`] can also be used on other NOR flash, e.g., external flash chips; with the `fat`
+//! feature, [`Fat`] mounts FAT filesystems on block devices such as SD cards.
+//!
+//! All functions are async; threads can use them by blocking on them, e.g., using
+//! `ariel_os::thread::block_on()`.
+//!
+//! # Configuration
+//!
+//! The following environment variables are read at build time:
+//!
+//! | Variable              | Description                                                        |
+//! | --                    | --                                                                 |
+//! | `CONFIG_FS_SIZE`      | Size of the filesystem in bytes, a multiple of the flash page size |
+//! | `CONFIG_FS_STACKSIZE` | Stack size of the filesystem thread in bytes                       |
+//!
+//! # Examples
+//!
+//! ```ignore
+//! use ariel_os::fs;
+//!
+//! fs::create_dir_all("/logs").await?;
+//! fs::append("/logs/readings.csv", b"1700000000,21.5\n").await?;
+//!
+//! let mut buf = [0; 64];
+//! let len = fs::read("/logs/readings.csv", &mut buf).await?;
+//! ```
+//!
+//! [littlefs]: https://github.com/littlefs-project/littlefs
+
+#![cfg_attr(not(test), no_std)]
+#![deny(missing_docs)]
+// TODO: overhaul errors
+#![expect(clippy::missing_errors_doc)]
+
+#[cfg(feature = "fat")]
+mod fat;
+mod littlefs;
+
+use ariel_os_storage::SharedFlash;
+use embedded_storage_async::nor_flash::NorFlash;
+
+#[cfg(feature = "fat")]
+pub use fat::Fat;
+pub use littlefs::Littlefs;
+
+/// Size of the filesystem in the internal flash, in bytes.
+const FS_SIZE: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_FS_SIZE",
+    64 * 1024,
+    "size of the filesystem in the internal flash"
+);
+
+/// Number of blocks of the filesystem in the internal flash, each being an erase page.
+const BLOCK_COUNT: usize = FS_SIZE / <SharedFlash as NorFlash>::ERASE_SIZE;
+
+/// Stack size of the thread serving the filesystem in the internal flash, in bytes.
+const STACKSIZE: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_FS_STACKSIZE",
+    8192,
+    "filesystem thread stack size (in bytes)"
+);
+
+/// Name of the raw storage partition holding the filesystem, declared by the laze module.
+const PARTITION: &str = "fs";
+
+static FILESYSTEM: Littlefs = Littlefs::new();
+
+/// Errors returned by filesystem operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// No file or directory exists at the path.
+    NotFound,
+    /// A file or directory already exists at the path.
+    AlreadyExists,
+    /// The path is a directory, where a file was expected.
+    IsADirectory,
+    /// A component of the path is not a directory.
+    NotADirectory,
+    /// The directory is not empty.
+    DirectoryNotEmpty,
+    /// There is not enough space left.
+    NoSpace,
+    /// The path is too long, or contains invalid characters.
+    InvalidPath,
+    /// The filesystem is corrupted.
+    Corrupted,
+    /// Accessing the underlying flash or block device failed.
+    Io,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::NotFound => f.write_str("no such file or directory"),
+            Self::AlreadyExists => f.write_str("file or directory already exists"),
+            Self::IsADirectory => f.write_str("is a directory"),
+            Self::NotADirectory => f.write_str("not a directory"),
+            Self::DirectoryNotEmpty => f.write_str("directory not empty"),
+            Self::NoSpace => f.write_str("no space left"),
+            Self::InvalidPath => f.write_str("invalid path"),
+            Self::Corrupted => f.write_str("filesystem corrupted"),
+            Self::Io => f.write_str("I/O error"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// Metadata of a file or directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Metadata {
+    is_dir: bool,
+    len: usize,
+}
+
+impl Metadata {
+    /// Returns whether this is a directory.
+    #[must_use]
+    pub fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    /// Returns whether this is a file.
+    #[must_use]
+    pub fn is_file(&self) -> bool {
+        !self.is_dir
+    }
+
+    /// Returns the length of the file, in bytes.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the file is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// Serves the filesystem in the internal flash, once the storage has been initialized.
+#[ariel_os_macros::thread(autostart, stacksize = STACKSIZE)]
+fn filesystem_thread() {
+    let partition = ariel_os_storage::raw_partition(PARTITION)
+        .expect("the `fs` raw partition is declared by the laze module");
+    let flash = ariel_os_threads::block_on(partition.flash());
+    FILESYSTEM.serve::<_, BLOCK_COUNT>(flash, partition.range());
+}
+
+/// Reads the beginning of the file at `path` into `buf`.
+///
+/// Returns the number of bytes read, which is smaller than the length of `buf` if the file is
+/// shorter.
+pub async fn read(path: &str, buf: &mut [u8]) -> Result<usize, Error> {
+    FILESYSTEM.read(path, buf).await
+}
+
+/// Reads the file at `path` into `buf`, starting at `offset`.
+///
+/// Returns the number of bytes read, which is smaller than the length of `buf` when reaching the
+/// end of the file.
+pub async fn read_at(path: &str, offset: u32, buf: &mut [u8]) -> Result<usize, Error> {
+    FILESYSTEM.read_at(path, offset, buf).await
+}
+
+/// Writes `data` to the file at `path`, replacing its content; the file is created if it does not
+/// exist.
+pub async fn write(path: &str, data: &[u8]) -> Result<(), Error> {
+    FILESYSTEM.write(path, data).await
+}
+
+/// Appends `data` to the file at `path`, which is created if it does not exist.
+pub async fn append(path: &str, data: &[u8]) -> Result<(), Error> {
+    FILESYSTEM.append(path, data).await
+}
+
+/// Returns the [`Metadata`] of the file or directory at `path`.
+pub async fn metadata(path: &str) -> Result<Metadata, Error> {
+    FILESYSTEM.metadata(path).await
+}
+
+/// Returns whether a file or directory exists at `path`.
+pub async fn exists(path: &str) -> Result<bool, Error> {
+    FILESYSTEM.exists(path).await
+}
+
+/// Removes the file or empty directory at `path`.
+pub async fn remove(path: &str) -> Result<(), Error> {
+    FILESYSTEM.remove(path).await
+}
+
+/// Renames the file or directory at `from` to `to`, replacing `to` if it exists.
+pub async fn rename(from: &str, to: &str) -> Result<(), Error> {
+    FILESYSTEM.rename(from, to).await
+}
+
+/// Creates the directory at `path`, along with its missing parents.
+pub async fn create_dir_all(path: &str) -> Result<(), Error> {
+    FILESYSTEM.create_dir_all(path).await
+}
+
+/// Calls `f` with the name and [`Metadata`] of each entry of the directory at `path`.
+///
+/// See [`Littlefs::read_dir()`].
+pub async fn read_dir(path: &str, f: impl FnMut(&str, Metadata)) -> Result<(), Error> {
+    FILESYSTEM.read_dir(path, f).await
+}
+
+/// Returns the space left in the filesystem, in bytes.
+pub async fn available_space() -> Result<usize, Error> {
+    FILESYSTEM.available_space().await
+}
@@ -0,0 +1,472 @@
+//! Provides [littlefs] filesystems on NOR flash.
+//!
+//! littlefs is synchronous: each filesystem is mounted once by a dedicated thread, see
+//! [`Littlefs::serve()`], which blocks on the flash operations littlefs issues.
+//! The async functions of [`Littlefs`] send requests to that thread and wait for its responses,
+//! so that the executor keeps running other tasks while the flash is being accessed.
+//!
+//! [littlefs]: https://github.com/littlefs-project/littlefs
+
+use core::ops::Range;
+
+use ariel_os_threads::block_on;
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex, signal::Signal};
+use embedded_storage_async::nor_flash::NorFlash;
+use littlefs2::{
+    consts,
+    driver::Storage,
+    fs::Filesystem,
+    io::{self, Read as _, Seek as _, SeekFrom, Write as _},
+    path::PathBuf,
+};
+
+use crate::{Error, Metadata};
+
+/// Maximum number of bytes read or written per request.
+const TRANSFER_SIZE: usize = 256;
+
+/// Data read from or written to a file by a single request.
+type Transfer = heapless::Vec<u8, TRANSFER_SIZE>;
+
+/// A littlefs filesystem on a range of NOR flash, served by a dedicated thread.
+///
+/// The thread calls [`serve()`](Self::serve), which mounts the filesystem and then handles the
+/// operations requested through the other methods, e.g.:
+///
+/// ```ignore
+/// static FILESYSTEM: Littlefs = Littlefs::new();
+///
+/// #[ariel_os::thread(autostart, stacksize = 8192)]
+/// fn filesystem() {
+///     FILESYSTEM.serve::<_, BLOCKS>(flash, range);
+/// }
+/// ```
+pub struct Littlefs {
+    /// Serializes the operations, holding the identifier of the last request.
+    clients: Mutex<CriticalSectionRawMutex, u32>,
+    requests: Signal<CriticalSectionRawMutex, (u32, Request)>,
+    responses: Signal<CriticalSectionRawMutex, (u32, Response)>,
+}
+
+impl Default for Littlefs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Littlefs {
+    /// Creates a filesystem, to be [served](Self::serve) by a thread.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            clients: Mutex::new(0),
+            requests: Signal::new(),
+            responses: Signal::new(),
+        }
+    }
+
+    /// Mounts the filesystem on `range` of `flash`, formatting it if it cannot be mounted, e.g.,
+    /// on first boot, and then handles the requested operations forever.
+    ///
+    /// This must be called from a dedicated thread, which blocks on the flash operations.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is not made of exactly `BLOCKS` erase pages of `flash`, or if the
+    /// filesystem can neither be mounted nor formatted.
+    pub fn serve<F: NorFlash, const BLOCKS: usize>(&self, flash: F, range: Range<u32>) -> ! {
+        assert!(
+            range.len() == BLOCKS * F::ERASE_SIZE
+                && (range.start as usize).is_multiple_of(F::ERASE_SIZE),
+            "the filesystem range must be made of whole erase pages"
+        );
+        let mut storage = FlashStorage::<F, BLOCKS>::new(flash, range.clone());
+
+        if !Filesystem::is_mountable(&mut storage) {
+            ariel_os_log::info!("fs: formatting flash range {:?}", &range);
+            Filesystem::format(&mut storage).expect("formatting the filesystem failed");
+        }
+        let mut allocation = Filesystem::allocate();
+        let fs = Filesystem::mount(&mut allocation, &mut storage)
+            .expect("mounting the filesystem failed");
+
+        loop {
+            let (id, request) = block_on(self.requests.wait());
+            self.responses.signal((id, request.handle(&fs)));
+        }
+    }
+
+    /// Sends `request` to the thread serving the filesystem, and waits for its response.
+    ///
+    /// Operations made of several requests hold `clients` for their whole duration.
+    async fn request(&self, id: &mut u32, request: Request) -> Response {
+        *id = id.wrapping_add(1);
+        self.requests.signal((*id, request));
+        loop {
+            let (response_id, response) = self.responses.wait().await;
+            // Responses to requests of cancelled operations are dropped.
+            if response_id == *id {
+                return response;
+            }
+        }
+    }
+
+    /// Reads the beginning of the file at `path` into `buf`.
+    ///
+    /// Returns the number of bytes read, which is smaller than the length of `buf` if the file is
+    /// shorter.
+    pub async fn read(&self, path: &str, buf: &mut [u8]) -> Result<usize, Error> {
+        self.read_at(path, 0, buf).await
+    }
+
+    /// Reads the file at `path` into `buf`, starting at `offset`.
+    ///
+    /// Returns the number of bytes read, which is smaller than the length of `buf` when reaching
+    /// the end of the file.
+    pub async fn read_at(&self, path: &str, offset: u32, buf: &mut [u8]) -> Result<usize, Error> {
+        let path = path_buf(path)?;
+        let mut id = self.clients.lock().await;
+
+        let mut len = 0;
+        for chunk in buf.chunks_mut(TRANSFER_SIZE) {
+            let offset = u32::try_from(len)
+                .ok()
+                .and_then(|len| offset.checked_add(len))
+                .ok_or(Error::Io)?;
+            let request = Request::Read {
+                path: path.clone(),
+                offset,
+                len: chunk.len(),
+            };
+            let Response::Read(data) = self.request(&mut id, request).await else {
+                unreachable!("response to another request");
+            };
+            let data = data?;
+            chunk
+                .get_mut(..data.len())
+                .ok_or(Error::Io)?
+                .copy_from_slice(&data);
+            len += data.len();
+            if data.len() < chunk.len() {
+                break;
+            }
+        }
+        Ok(len)
+    }
+
+    /// Writes `data` to the file at `path`, replacing its content; the file is created if it does
+    /// not exist.
+    ///
+    /// Data is written in chunks of 256 bytes, a power loss can thus leave the file with only the
+    /// first chunks of `data`.
+    pub async fn write(&self, path: &str, data: &[u8]) -> Result<(), Error> {
+        self.write_chunks(path, data, false).await
+    }
+
+    /// Appends `data` to the file at `path`, which is created if it does not exist.
+    pub async fn append(&self, path: &str, data: &[u8]) -> Result<(), Error> {
+        self.write_chunks(path, data, true).await
+    }
+
+    /// Writes `data` to the file at `path`, appending all chunks but the first one, which is
+    /// appended only if `append` is set.
+    async fn write_chunks(&self, path: &str, data: &[u8], append: bool) -> Result<(), Error> {
+        let path = path_buf(path)?;
+        let mut id = self.clients.lock().await;
+
+        // Empty data still creates or truncates the file.
+        let mut chunks = data.chunks(TRANSFER_SIZE);
+        let mut chunk = Some(chunks.next().unwrap_or_default());
+        let mut append = append;
+        while let Some(data) = chunk {
+            let request = Request::Write {
+                path: path.clone(),
+                data: Transfer::from_slice(data).map_err(|_| Error::Io)?,
+                append,
+            };
+            let Response::Done(result) = self.request(&mut id, request).await else {
+                unreachable!("response to another request");
+            };
+            result?;
+            chunk = chunks.next();
+            append = true;
+        }
+        Ok(())
+    }
+
+    /// Returns the [`Metadata`] of the file or directory at `path`.
+    pub async fn metadata(&self, path: &str) -> Result<Metadata, Error> {
+        let path = path_buf(path)?;
+        let mut id = self.clients.lock().await;
+        let Response::Metadata(metadata) = self.request(&mut id, Request::Metadata(path)).await
+        else {
+            unreachable!("response to another request");
+        };
+        metadata
+    }
+
+    /// Returns whether a file or directory exists at `path`.
+    pub async fn exists(&self, path: &str) -> Result<bool, Error> {
+        match self.metadata(path).await {
+            Ok(_) => Ok(true),
+            Err(Error::NotFound) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Removes the file or empty directory at `path`.
+    pub async fn remove(&self, path: &str) -> Result<(), Error> {
+        let path = path_buf(path)?;
+        self.request_done(Request::Remove(path)).await
+    }
+
+    /// Renames the file or directory at `from` to `to`, replacing `to` if it exists.
+    pub async fn rename(&self, from: &str, to: &str) -> Result<(), Error> {
+        let from = path_buf(from)?;
+        let to = path_buf(to)?;
+        self.request_done(Request::Rename(from, to)).await
+    }
+
+    /// Creates the directory at `path`, along with its missing parents.
+    pub async fn create_dir_all(&self, path: &str) -> Result<(), Error> {
+        let path = path_buf(path)?;
+        self.request_done(Request::CreateDirAll(path)).await
+    }
+
+    /// Sends a request that only returns whether it succeeded.
+    async fn request_done(&self, request: Request) -> Result<(), Error> {
+        let mut id = self.clients.lock().await;
+        let Response::Done(result) = self.request(&mut id, request).await else {
+            unreachable!("response to another request");
+        };
+        result
+    }
+
+    /// Calls `f` with the name and [`Metadata`] of each entry of the directory at `path`.
+    ///
+    /// Entries are fetched one at a time, and `f` may access the filesystem; entries may however
+    /// be skipped or repeated if the directory is modified meanwhile.
+    pub async fn read_dir(
+        &self,
+        path: &str,
+        mut f: impl FnMut(&str, Metadata),
+    ) -> Result<(), Error> {
+        let path = path_buf(path)?;
+
+        let mut index = 0;
+        loop {
+            let request = Request::ReadDir {
+                path: path.clone(),
+                index,
+            };
+            let response = {
+                let mut id = self.clients.lock().await;
+                self.request(&mut id, request).await
+            };
+            let Response::Entry(entry) = response else {
+                unreachable!("response to another request");
+            };
+            let Some((name, metadata)) = entry? else {
+                return Ok(());
+            };
+            f(name.as_str(), metadata);
+            index += 1;
+        }
+    }
+
+    /// Returns the space left in the filesystem, in bytes.
+    pub async fn available_space(&self) -> Result<usize, Error> {
+        let mut id = self.clients.lock().await;
+        let Response::Space(space) = self.request(&mut id, Request::AvailableSpace).await else {
+            unreachable!("response to another request");
+        };
+        space
+    }
+}
+
+/// Operation requested to the thread serving a filesystem.
+enum Request {
+    Read {
+        path: PathBuf,
+        offset: u32,
+        len: usize,
+    },
+    /// Writes `data` to the file at `path`, replacing its content unless `append` is set.
+    Write {
+        path: PathBuf,
+        data: Transfer,
+        append: bool,
+    },
+    Metadata(PathBuf),
+    Remove(PathBuf),
+    Rename(PathBuf, PathBuf),
+    CreateDirAll(PathBuf),
+    /// Reads the entry at `index` of the directory at `path`, skipping `.` and `..`.
+    ReadDir {
+        path: PathBuf,
+        index: usize,
+    },
+    AvailableSpace,
+}
+
+/// Response of the thread serving a filesystem, with one variant per kind of result.
+enum Response {
+    Read(Result<Transfer, Error>),
+    Done(Result<(), Error>),
+    Metadata(Result<Metadata, Error>),
+    Entry(Result<Option<(PathBuf, Metadata)>, Error>),
+    Space(Result<usize, Error>),
+}
+
+impl Request {
+    /// Carries out this request on the mounted filesystem.
+    fn handle<S: Storage>(self, fs: &Filesystem<'_, S>) -> Response {
+        match self {
+            Self::Read { path, offset, len } => Response::Read(
+                fs.open_file_and_then(&path, |file| {
+                    file.seek(SeekFrom::Start(offset))?;
+                    let mut data = Transfer::new();
+                    data.resize_default(len).map_err(|_| io::Error::INVALID)?;
+                    let read = file.read(&mut data)?;
+                    data.truncate(read);
+                    Ok(data)
+                })
+                .map_err(Error::from),
+            ),
+            Self::Write { path, data, append } => Response::Done(
+                fs.open_file_with_options_and_then(
+                    |options| {
+                        options
+                            .write(true)
+                            .create(true)
+                            .truncate(!append)
+                            .append(append)
+                    },
+                    &path,
+                    |file| file.write_all(&data),
+                )
+                .map_err(Error::from),
+            ),
+            Self::Metadata(path) => {
+                Response::Metadata(fs.metadata(&path).map(Metadata::from).map_err(Error::from))
+            }
+            Self::Remove(path) => Response::Done(fs.remove(&path).map_err(Error::from)),
+            Self::Rename(from, to) => Response::Done(fs.rename(&from, &to).map_err(Error::from)),
+            Self::CreateDirAll(path) => {
+                Response::Done(fs.create_dir_all(&path).map_err(Error::from))
+            }
+            Self::ReadDir { path, index } => Response::Entry(
+                fs.read_dir_and_then(&path, |entries| {
+                    let mut entries = entries.filter(|entry| match entry {
+                        Ok(entry) => !matches!(entry.file_name().as_str(), "." | ".."),
+                        Err(_) => true,
+                    });
+                    match entries.nth(index) {
+                        Some(entry) => {
+                            let entry = entry?;
+                            Ok(Some((
+                                PathBuf::from(entry.file_name()),
+                                entry.metadata().into(),
+                            )))
+                        }
+                        None => Ok(None),
+                    }
+                })
+                .map_err(Error::from),
+            ),
+            Self::AvailableSpace => Response::Space(fs.available_space().map_err(Error::from)),
+        }
+    }
+}
+
+/// Converts `path` to a littlefs path.
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidPath`] if `path` is too long or contains a nul byte.
+fn path_buf(path: &str) -> Result<PathBuf, Error> {
+    PathBuf::try_from(path).map_err(|_| Error::InvalidPath)
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        match err {
+            io::Error::NO_SUCH_ENTRY => Self::NotFound,
+            io::Error::ENTRY_ALREADY_EXISTED => Self::AlreadyExists,
+            io::Error::PATH_IS_DIR => Self::IsADirectory,
+            io::Error::PATH_NOT_DIR => Self::NotADirectory,
+            io::Error::DIR_NOT_EMPTY => Self::DirectoryNotEmpty,
+            io::Error::NO_SPACE => Self::NoSpace,
+            io::Error::FILENAME_TOO_LONG => Self::InvalidPath,
+            io::Error::CORRUPTION => Self::Corrupted,
+            _ => Self::Io,
+        }
+    }
+}
+
+impl From<littlefs2::fs::Metadata> for Metadata {
+    fn from(metadata: littlefs2::fs::Metadata) -> Self {
+        Self {
+            is_dir: metadata.is_dir(),
+            len: metadata.len(),
+        }
+    }
+}
+
+/// littlefs storage on a range of NOR flash.
+///
+/// Flash operations are blocked on by the thread serving the filesystem; when the flash is
+/// shared, e.g., with the storage, it is only locked for each of them.
+struct FlashStorage<F, const BLOCKS: usize> {
+    flash: F,
+    range: Range<u32>,
+}
+
+impl<F: NorFlash, const BLOCKS: usize> FlashStorage<F, BLOCKS> {
+    fn new(flash: F, range: Range<u32>) -> Self {
+        Self { flash, range }
+    }
+
+    /// Converts an offset in the filesystem to an offset in the flash.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`io::Error::INVALID`] if the offset is outside of the filesystem.
+    fn flash_offset(&self, offset: usize) -> io::Result<u32> {
+        u32::try_from(offset)
+            .ok()
+            .and_then(|offset| self.range.start.checked_add(offset))
+            .filter(|offset| *offset <= self.range.end)
+            .ok_or(io::Error::INVALID)
+    }
+}
+
+impl<F: NorFlash, const BLOCKS: usize> Storage for FlashStorage<F, BLOCKS> {
+    const READ_SIZE: usize = F::READ_SIZE;
+    const WRITE_SIZE: usize = F::WRITE_SIZE;
+    const BLOCK_SIZE: usize = F::ERASE_SIZE;
+    const BLOCK_COUNT: usize = BLOCKS;
+
+    // Multiple of the read and write sizes, and divides the erase size, of supported flash.
+    type CACHE_SIZE = consts::U256;
+    type LOOKAHEAD_SIZE = consts::U2;
+
+    fn read(&mut self, off: usize, buf: &mut [u8]) -> io::Result<usize> {
+        let offset = self.flash_offset(off)?;
+        block_on(self.flash.read(offset, buf)).map_err(|_| io::Error::IO)?;
+        Ok(buf.len())
+    }
+
+    fn write(&mut self, off: usize, data: &[u8]) -> io::Result<usize> {
+        let offset = self.flash_offset(off)?;
+        block_on(self.flash.write(offset, data)).map_err(|_| io::Error::IO)?;
+        Ok(data.len())
+    }
+
+    fn erase(&mut self, off: usize, len: usize) -> io::Result<usize> {
+        let from = self.flash_offset(off)?;
+        let to = self.flash_offset(off.checked_add(len).ok_or(io::Error::INVALID)?)?;
+        block_on(self.flash.erase(from, to)).map_err(|_| io::Error::IO)?;
+        Ok(len)
+    }
+}
@@ -0,0 +1,70 @@
+//! Provides support for analog-to-digital converters (ADCs).
+//!
+//! ADC drivers are MCU-specific and are provided as [`hal::adc::Adc`], tied to a specific ADC
+//! peripheral (e.g., `hal::adc::SAADC::new(config)` on nRF); the analog inputs to sample are
+//! obtained as [`hal::adc::Channel`]s.
+#![deny(missing_docs)]
+
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex};
+use embassy_time::{Duration, Ticker};
+
+use crate::hal;
+
+pub use ariel_os_embassy_common::adc::*;
+
+/// An ADC channel implementing [`AnalogInput`], sharing its ADC driver with other channels.
+///
+/// This is useful to hand out ADC channels to drivers that should not have exclusive access to
+/// the ADC, e.g., sensor drivers.
+pub struct AdcInput<'a> {
+    adc: &'a Mutex<CriticalSectionRawMutex, hal::adc::Adc>,
+    channel: hal::adc::Channel<'a>,
+}
+
+impl<'a> AdcInput<'a> {
+    /// Returns a new [`AdcInput`] for `channel`, using the shared `adc` driver.
+    #[must_use]
+    pub fn new(
+        adc: &'a Mutex<CriticalSectionRawMutex, hal::adc::Adc>,
+        channel: hal::adc::Channel<'a>,
+    ) -> Self {
+        Self { adc, channel }
+    }
+}
+
+impl AnalogInput for AdcInput<'_> {
+    async fn sample(&mut self) -> Result<Sample, Error> {
+        let mut adc = self.adc.lock().await;
+        adc.read(&mut self.channel).await
+    }
+}
+
+/// Samples `channel` continuously, every `interval`, until `samples` has been filled.
+///
+/// The first conversion is carried out immediately.
+///
+/// # Note
+///
+/// The sampling is software-timed, so the sampling rate is limited by the latency of the
+/// executor; it is intended for low sampling rates (e.g., monitoring), not for signal acquisition.
+///
+/// # Errors
+///
+/// Returns an [`Error`] as soon as a conversion fails; `samples` is then only partially filled.
+pub async fn read_continuous(
+    adc: &mut hal::adc::Adc,
+    channel: &mut hal::adc::Channel<'_>,
+    interval: Duration,
+    samples: &mut [Sample],
+) -> Result<(), Error> {
+    let mut ticker = Ticker::every(interval);
+
+    for (i, sample) in samples.iter_mut().enumerate() {
+        if i > 0 {
+            ticker.next().await;
+        }
+        *sample = adc.read(channel).await?;
+    }
+
+    Ok(())
+}
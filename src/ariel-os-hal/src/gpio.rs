@@ -102,6 +102,8 @@ impl<'a> embedded_hal::digital::ErrorType for Input<'a> {
 #[cfg(feature = "external-interrupts")]
 pub struct IntEnabledInput<'a> {
     input: HalIntEnabledInput<'a>,
+    #[cfg(feature = "system-off")]
+    wake_pin: hal::system_off::WakePin,
 }
 
 #[cfg(feature = "external-interrupts")]
@@ -134,6 +136,10 @@ impl IntEnabledInput<'_> {
     pub async fn wait_for_any_edge(&mut self) {
         self.input.wait_for_any_edge().await;
     }
+    #[cfg(feature = "system-off")]
+    pub(crate) fn wake_pin(&self) -> hal::system_off::WakePin {
+        self.wake_pin
+    }
 }
 
 #[cfg(feature = "external-interrupts")]
@@ -307,9 +313,15 @@ pub mod input {
         #[cfg(feature = "external-interrupts")]
         pub fn build_with_interrupt(self) -> Result<IntEnabledInput<'a>, Error> {
             let pin = self.pin.into_hal_peripheral();
+            #[cfg(feature = "system-off")]
+            let wake_pin = hal::system_off::wake_pin(&pin);
             let input = hal::gpio::input::new_int_enabled(pin, self.pull, self.schmitt_trigger)?;
 
-            Ok(IntEnabledInput { input })
+            Ok(IntEnabledInput {
+                input,
+                #[cfg(feature = "system-off")]
+                wake_pin,
+            })
         }
     }
 }
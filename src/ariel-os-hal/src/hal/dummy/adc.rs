@@ -0,0 +1,34 @@
+//! HAL- and MCU-specific types for the ADC.
+//!
+//! This module provides a driver for each ADC peripheral, the driver name being the same as the
+//! peripheral; see the tests and examples to learn how to instantiate them.
+
+use ariel_os_embassy_common::adc::{Error, Sample};
+
+/// Peripheral-agnostic ADC driver.
+///
+/// This type is not meant to be instantiated directly; instead instantiate a peripheral-specific
+/// driver provided by this module.
+// NOTE: we keep this type public because it may still required in user-written type signatures.
+pub enum Adc {
+    // Make the docs show that this enum has variants, but do not show any because they are
+    // MCU-specific.
+    #[doc(hidden)]
+    Hidden,
+}
+
+impl Adc {
+    /// Performs a single-shot conversion on `channel`.
+    pub async fn read(&mut self, _channel: &mut Channel<'_>) -> Result<Sample, Error> {
+        unimplemented!();
+    }
+}
+
+/// An analog input of the ADC.
+pub struct Channel<'d> {
+    _lt: core::marker::PhantomData<&'d ()>,
+}
+
+pub fn init(_peripherals: &mut crate::hal::OptionalPeripherals) {
+    unimplemented!();
+}
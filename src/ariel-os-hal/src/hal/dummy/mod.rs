@@ -16,6 +16,10 @@
 
 mod executor;
 
+#[doc(hidden)]
+#[cfg(feature = "adc")]
+pub mod adc;
+
 #[doc(hidden)]
 pub mod gpio;
 
@@ -37,6 +41,10 @@ pub mod i2c;
 #[doc(hidden)]
 pub mod identity;
 
+#[doc(hidden)]
+#[cfg(feature = "pwm")]
+pub mod pwm;
+
 #[doc(hidden)]
 #[cfg(feature = "spi")]
 pub mod spi;
@@ -45,6 +53,10 @@ pub mod spi;
 #[cfg(feature = "storage")]
 pub mod storage;
 
+#[doc(hidden)]
+#[cfg(feature = "system-off")]
+pub mod system_off;
+
 #[doc(hidden)]
 #[cfg(feature = "uart")]
 pub mod uart;
@@ -53,6 +65,10 @@ pub mod uart;
 #[cfg(feature = "usb")]
 pub mod usb;
 
+#[doc(hidden)]
+#[cfg(feature = "watchdog")]
+pub mod watchdog;
+
 pub use executor::{Executor, Spawner};
 pub use peripheral::{IntoPeripheral, OptionalPeripherals};
 
@@ -0,0 +1,40 @@
+//! HAL- and MCU-specific types for PWM.
+//!
+//! This module provides a driver for each PWM peripheral, the driver name being the same as the
+//! peripheral; see the tests and examples to learn how to instantiate them.
+
+use ariel_os_embassy_common::pwm::{DutyCycle, Error, Hertz};
+
+/// Peripheral-agnostic PWM driver.
+///
+/// This type is not meant to be instantiated directly; instead instantiate a peripheral-specific
+/// driver provided by this module.
+// NOTE: we keep this type public because it may still required in user-written type signatures.
+pub enum Pwm {
+    // Make the docs show that this enum has variants, but do not show any because they are
+    // MCU-specific.
+    #[doc(hidden)]
+    Hidden,
+}
+
+impl Pwm {
+    /// Returns the number of channels of this PWM instance.
+    #[must_use]
+    pub fn channel_count(&self) -> usize {
+        unimplemented!();
+    }
+
+    /// Sets the duty cycle of `channel`.
+    pub fn set_duty_cycle(&mut self, _channel: usize, _duty_cycle: DutyCycle) -> Result<(), Error> {
+        unimplemented!();
+    }
+
+    /// Changes the frequency of all the channels, keeping their duty cycles.
+    pub fn set_frequency(&mut self, _frequency: Hertz) -> Result<(), Error> {
+        unimplemented!();
+    }
+}
+
+pub fn init(_peripherals: &mut crate::hal::OptionalPeripherals) {
+    unimplemented!();
+}
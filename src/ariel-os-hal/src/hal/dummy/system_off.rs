@@ -0,0 +1,22 @@
+use core::{convert::Infallible, time::Duration};
+
+pub use ariel_os_embassy_common::system_off::{Error, WakeEdge};
+
+#[derive(Debug, Clone, Copy)]
+pub struct WakePin;
+
+pub fn wake_pin<P>(_pin: &P) -> WakePin {
+    unimplemented!();
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum WakeSource {
+    Pin(WakePin, WakeEdge),
+    Timer(Duration),
+}
+
+pub fn system_off(
+    _wake_sources: impl IntoIterator<Item = WakeSource>,
+) -> Result<Infallible, Error> {
+    unimplemented!();
+}
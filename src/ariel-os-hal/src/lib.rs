@@ -1,4 +1,8 @@
 #![no_std]
+
+#[cfg(feature = "adc")]
+pub mod adc;
+
 pub mod gpio;
 
 #[cfg(feature = "i2c")]
@@ -6,12 +10,20 @@ pub mod i2c;
 
 pub mod hal;
 
+#[cfg(feature = "pwm")]
+pub mod pwm;
+
+#[cfg(feature = "system-off")]
+pub mod system_off;
+
 #[cfg(feature = "uart")]
 pub mod uart;
 
 // All items of this module are re-exported at the root of `ariel_os`.
 #[doc(hidden)]
 pub mod api {
+    #[cfg(feature = "adc")]
+    pub use crate::adc;
     pub use crate::gpio;
     pub use crate::hal;
 
@@ -21,6 +33,8 @@ pub mod api {
     pub use crate::i2c;
     // #[cfg(feature = "net")]
     // pub use crate::net;
+    #[cfg(feature = "pwm")]
+    pub use crate::pwm;
     // #[cfg(feature = "spi")]
     // pub use crate::spi;
     #[cfg(feature = "uart")]
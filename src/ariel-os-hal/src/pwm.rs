@@ -0,0 +1,51 @@
+//! Provides support for pulse-width modulation (PWM) outputs.
+//!
+//! PWM drivers are MCU-specific and are provided as [`hal::pwm::Pwm`], tied to a specific PWM
+//! peripheral; they are obtained from a builder, e.g.,
+//! `hal::pwm::PWM0::builder(Hertz::kHz(1)).channel(pin).build()` on nRF, which sets the frequency,
+//! the polarity, the initial duty cycle, and the pins of the channels.
+//! As the pins that can be used by each channel are MCU-specific, the peripherals and the pins
+//! are best grouped per board with [`define_peripherals!`](crate::hal::define_peripherals).
+#![deny(missing_docs)]
+
+use crate::hal;
+
+pub use ariel_os_embassy_common::pwm::{DutyCycle, Error, Hertz, Polarity};
+
+/// A single channel of a PWM driver, implementing [`embedded_hal::pwm::SetDutyCycle`].
+///
+/// This is useful to hand out a PWM channel to drivers expecting an `embedded-hal` type,
+/// e.g., LED or servo drivers.
+pub struct PwmChannel<'a> {
+    pwm: &'a mut hal::pwm::Pwm,
+    channel: usize,
+}
+
+impl<'a> PwmChannel<'a> {
+    /// Returns channel `channel` of `pwm`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidChannel`] if `channel` does not exist.
+    pub fn new(pwm: &'a mut hal::pwm::Pwm, channel: usize) -> Result<Self, Error> {
+        if channel >= pwm.channel_count() {
+            return Err(Error::InvalidChannel);
+        }
+        Ok(Self { pwm, channel })
+    }
+}
+
+impl embedded_hal::pwm::ErrorType for PwmChannel<'_> {
+    type Error = Error;
+}
+
+impl embedded_hal::pwm::SetDutyCycle for PwmChannel<'_> {
+    fn max_duty_cycle(&self) -> u16 {
+        DutyCycle::MAX
+    }
+
+    fn set_duty_cycle(&mut self, duty: u16) -> Result<(), Self::Error> {
+        let duty_cycle = DutyCycle::from_fraction(u32::from(duty), u32::from(DutyCycle::MAX));
+        self.pwm.set_duty_cycle(self.channel, duty_cycle)
+    }
+}
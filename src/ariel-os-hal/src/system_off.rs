@@ -0,0 +1,73 @@
+//! Provides system off, the lowest-power mode of the MCU, from which it wakes up with a reset.
+//!
+//! Depending on the MCU family, this corresponds to system off (nRF), standby (STM32), dormant
+//! (RP), or deep sleep (ESP).
+//! Most of the system is powered down and RAM contents are lost, except for retained data (see
+//! [`retained`](crate::power::retained)).
+//! Once woken up by one of the configured wake sources, the MCU resets and
+//! [`reset_reason()`](crate::power::reset_reason) returns
+//! [`ResetReason::WakeUp`](crate::power::ResetReason::WakeUp) where the hardware reports it.
+#![deny(missing_docs)]
+
+use core::convert::Infallible;
+
+use embassy_time::Duration;
+
+use crate::{gpio::IntEnabledInput, hal};
+
+pub use ariel_os_embassy_common::system_off::{Error, WakeEdge};
+
+/// Source waking the MCU up from system off.
+///
+/// The sources supported from system off depend on the MCU family:
+///
+/// | MCU family | GPIO pins       | Timer          | Other                    |
+/// | ---------- | --------------- | -------------- | ------------------------ |
+/// | nRF        | All             | No             | LPCOMP (nRF51 and nRF52) |
+/// | STM32      | `PA0` (`WKUP1`) | Yes, except C0 |                          |
+/// | RP         | All             | No             |                          |
+/// | ESP        | No              | Yes            |                          |
+///
+/// On STM32, the timer uses the RTC wake-up timer, and its duration is rounded up to the second;
+/// it cannot exceed 2<sup>17</sup> seconds (about 36 hours).
+/// On nRF, the RTCs are not clocked in system off.
+/// On RP, all clocks are stopped in the dormant state, including the one of the RTC.
+#[derive(Clone, Copy)]
+pub enum WakeSource<'a> {
+    /// Wakes the MCU up on an edge of a GPIO input.
+    ///
+    /// The input keeps its pull configuration while in system off.
+    Pin(&'a IntEnabledInput<'a>, WakeEdge),
+    /// Wakes the MCU up after a duration.
+    Timer(Duration),
+    /// MCU-specific wake source.
+    Hal(hal::system_off::WakeSource),
+}
+
+/// Puts the MCU in system off, configuring it to wake up from any of the `wake_sources`.
+///
+/// This function only returns in case of an error, as the MCU resets when woken up.
+/// Drivers should be shut down beforehand when they need to leave the peripherals in a specific
+/// state.
+///
+/// # Errors
+///
+/// Returns [`Error::Unsupported`] if system off is not supported on this MCU, and
+/// [`Error::UnsupportedWakeSource`] if one of the `wake_sources` is not supported; in the latter
+/// case, some wake sources may have already been configured.
+/// Returns [`Error::Constrained`] if a driver currently prevents the MCU from entering deep idle
+/// modes (see [`constrain_idle_mode()`](ariel_os_power::constrain_idle_mode)), as system off
+/// would then interrupt its operation.
+pub fn system_off(wake_sources: &[WakeSource<'_>]) -> Result<Infallible, Error> {
+    if ariel_os_power::idle_mode() != ariel_os_power::IdleMode::DeepSleep {
+        return Err(Error::Constrained);
+    }
+
+    hal::system_off::system_off(wake_sources.iter().map(|wake_source| match *wake_source {
+        WakeSource::Pin(input, edge) => hal::system_off::WakeSource::Pin(input.wake_pin(), edge),
+        WakeSource::Timer(duration) => hal::system_off::WakeSource::Timer(
+            core::time::Duration::from_micros(duration.as_micros()),
+        ),
+        WakeSource::Hal(wake_source) => wake_source,
+    }))
+}
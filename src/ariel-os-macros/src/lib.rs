@@ -3,6 +3,7 @@ mod utils;
 use proc_macro::TokenStream;
 
 include!("config.rs");
+include!("shell_command.rs");
 include!("spawner.rs");
 include!("task.rs");
 include!("thread.rs");
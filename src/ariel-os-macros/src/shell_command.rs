@@ -0,0 +1,125 @@
+/// Registers the function decorated with this attribute macro as a command of the interactive
+/// shell.
+///
+/// The function is passed the output of the shell and the arguments following the command name,
+/// and must have the following signature:
+///
+/// ```ignore
+/// fn(&mut dyn core::fmt::Write, &[&str]) -> Result<(), ariel_os::shell::Error>
+/// ```
+///
+/// # Parameters
+///
+/// - `name`: (*optional*) the name of the command, defaults to the name of the function.
+/// - `usage`: (*optional*) a synopsis of the arguments of the command, shown by `help` and when
+///   `Error::InvalidArguments` is returned.
+/// - `help`: (*optional*) a short description of the command, shown by `help`.
+///
+/// # Examples
+///
+/// ```ignore
+/// use core::fmt::Write;
+///
+/// use ariel_os::shell::Error;
+///
+/// #[ariel_os::shell_command(name = "add", usage = "<a> <b>", help = "Adds two integers")]
+/// fn add_command(out: &mut dyn Write, args: &[&str]) -> Result<(), Error> {
+///     let [a, b] = args else {
+///         return Err(Error::InvalidArguments);
+///     };
+///     let (Ok(a), Ok(b)) = (a.parse::<i32>(), b.parse::<i32>()) else {
+///         return Err(Error::InvalidArguments);
+///     };
+///     writeln!(out, "{}", a.wrapping_add(b))?;
+///     Ok(())
+/// }
+/// ```
+///
+/// # Panics
+///
+/// This macro panics when the `ariel-os` crate cannot be found as a dependency of the crate where
+/// this macro is used.
+#[proc_macro_attribute]
+pub fn shell_command(args: TokenStream, item: TokenStream) -> TokenStream {
+    #[allow(clippy::wildcard_imports)]
+    use shell_command::*;
+
+    use quote::{format_ident, quote};
+
+    use crate::utils::find_crate;
+
+    let mut attrs = Attributes::default();
+    let shell_command_parser = syn::meta::parser(|meta| attrs.parse(&meta));
+    syn::parse_macro_input!(args with shell_command_parser);
+
+    let command_function = syn::parse_macro_input!(item as syn::ItemFn);
+
+    assert!(
+        command_function.sig.asyncness.is_none(),
+        "shell commands cannot be async",
+    );
+
+    let shell_crate = {
+        match (find_crate("ariel-os"), find_crate("ariel-os-shell")) {
+            (Some(ariel_os), _) => quote! { #ariel_os::shell },
+            (None, Some(ariel_os_shell)) => quote! { #ariel_os_shell },
+            _ => panic!(r#"neither "ariel-os" nor "ariel-os-shell" found in dependencies!"#),
+        }
+    };
+
+    let fn_name = &command_function.sig.ident;
+    let static_name = format_ident!("__SHELL_COMMAND_{}", fn_name.to_string().to_uppercase());
+
+    let name = attrs.name.unwrap_or_else(|| {
+        let name = fn_name.to_string();
+        syn::LitStr::new(name.strip_prefix("r#").unwrap_or(&name), fn_name.span())
+    });
+    let usage = attrs.usage.unwrap_or_else(|| syn::parse_quote! { "" });
+    let help = attrs.help.unwrap_or_else(|| syn::parse_quote! { "" });
+
+    let expanded = quote! {
+        #command_function
+
+        #[#shell_crate::macro_reexports::linkme::distributed_slice(#shell_crate::COMMANDS)]
+        #[linkme(crate = #shell_crate::macro_reexports::linkme)]
+        static #static_name: #shell_crate::Command =
+            #shell_crate::Command::new(#name, #usage, #help, #fn_name);
+    };
+
+    TokenStream::from(expanded)
+}
+
+mod shell_command {
+    #[derive(Default)]
+    pub struct Attributes {
+        pub name: Option<syn::LitStr>,
+        pub usage: Option<syn::LitStr>,
+        pub help: Option<syn::LitStr>,
+    }
+
+    impl Attributes {
+        /// Parse macro attributes.
+        ///
+        /// # Errors
+        ///
+        /// Returns an error when an unsupported parameter is found.
+        pub fn parse(&mut self, meta: &syn::meta::ParseNestedMeta<'_>) -> syn::Result<()> {
+            if meta.path.is_ident("name") {
+                self.name = Some(meta.value()?.parse()?);
+                return Ok(());
+            }
+
+            if meta.path.is_ident("usage") {
+                self.usage = Some(meta.value()?.parse()?);
+                return Ok(());
+            }
+
+            if meta.path.is_ident("help") {
+                self.help = Some(meta.value()?.parse()?);
+                return Ok(());
+            }
+
+            Err(meta.error("unsupported parameter"))
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! Provides a simulated ADC.
+//!
+//! The voltages applied to the simulated inputs are set with [`set_simulated_millivolts()`].
+
+use std::{
+    marker::PhantomData,
+    sync::atomic::{AtomicI32, Ordering},
+};
+
+use ariel_os_embassy_common::adc::{Error, Sample};
+
+/// Number of simulated analog inputs.
+pub const SIMULATED_CHANNELS: usize = 8;
+
+/// Full scale of the simulated ADC, in millivolts.
+const FULL_SCALE_MV: u32 = 3300;
+
+static SIMULATED_MILLIVOLTS: [AtomicI32; SIMULATED_CHANNELS] =
+    [const { AtomicI32::new(0) }; SIMULATED_CHANNELS];
+
+/// Sets the voltage applied to the simulated analog input `index`.
+///
+/// # Panics
+///
+/// Panics if `index` is not smaller than [`SIMULATED_CHANNELS`].
+pub fn set_simulated_millivolts(index: usize, millivolts: i32) {
+    let Some(input) = SIMULATED_MILLIVOLTS.get(index) else {
+        panic!("simulated ADC input {index} does not exist");
+    };
+    input.store(millivolts, Ordering::Relaxed);
+}
+
+/// ADC configuration.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// Resolution of the conversions.
+    pub resolution: ariel_os_embassy_common::adc::Resolution<Resolution>,
+    /// Oversampling ratio, ignored as the simulated inputs are noiseless.
+    pub oversampling: ariel_os_embassy_common::adc::Oversampling<Oversampling>,
+    /// Voltage reference.
+    pub reference: ariel_os_embassy_common::adc::Reference<Reference>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            resolution: ariel_os_embassy_common::adc::Resolution::Bits12,
+            oversampling: ariel_os_embassy_common::adc::Oversampling::None,
+            reference: ariel_os_embassy_common::adc::Reference::Vdd,
+        }
+    }
+}
+
+/// ADC resolution.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Resolution {
+    /// 8-bit resolution.
+    Bits8,
+    /// 10-bit resolution.
+    Bits10,
+    /// 12-bit resolution.
+    Bits12,
+    /// 16-bit resolution.
+    Bits16,
+}
+
+impl From<Resolution> for u8 {
+    fn from(resolution: Resolution) -> u8 {
+        match resolution {
+            Resolution::Bits8 => 8,
+            Resolution::Bits10 => 10,
+            Resolution::Bits12 => 12,
+            Resolution::Bits16 => 16,
+        }
+    }
+}
+
+impl From<ariel_os_embassy_common::adc::Resolution<Self>> for Resolution {
+    fn from(resolution: ariel_os_embassy_common::adc::Resolution<Self>) -> Self {
+        match resolution {
+            ariel_os_embassy_common::adc::Resolution::Hal(resolution) => resolution,
+            ariel_os_embassy_common::adc::Resolution::Bits8 => Self::Bits8,
+            ariel_os_embassy_common::adc::Resolution::Bits10 => Self::Bits10,
+            ariel_os_embassy_common::adc::Resolution::Bits12 => Self::Bits12,
+        }
+    }
+}
+
+/// ADC oversampling ratio.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Oversampling {
+    /// No oversampling.
+    None,
+}
+
+impl From<Oversampling> for u16 {
+    fn from(oversampling: Oversampling) -> u16 {
+        match oversampling {
+            Oversampling::None => 1,
+        }
+    }
+}
+
+/// ADC voltage reference.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Reference {
+    /// Simulated 3.3 V supply.
+    Vdd,
+}
+
+/// A simulated analog input.
+pub struct Channel<'d> {
+    index: usize,
+    _lt: PhantomData<&'d ()>,
+}
+
+impl Channel<'_> {
+    /// Returns the simulated analog input `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is not smaller than [`SIMULATED_CHANNELS`].
+    #[must_use]
+    pub fn simulated(index: usize) -> Self {
+        assert!(
+            index < SIMULATED_CHANNELS,
+            "simulated ADC input {index} does not exist"
+        );
+        Self {
+            index,
+            _lt: PhantomData,
+        }
+    }
+}
+
+/// Simulated ADC driver.
+pub struct ADC {
+    config: Config,
+}
+
+impl ADC {
+    /// Returns a simulated ADC driver.
+    #[expect(clippy::new_ret_no_self)]
+    #[must_use]
+    pub fn new(config: Config) -> Adc {
+        Adc::ADC(Self { config })
+    }
+
+    #[expect(clippy::unused_async, reason = "mimics the hardware drivers")]
+    async fn read(&mut self, channel: &mut Channel<'_>) -> Result<Sample, Error> {
+        let bits = self.config.resolution.bits();
+        let full_scale_raw = (1u32 << bits) - 1;
+
+        let millivolts = SIMULATED_MILLIVOLTS
+            .get(channel.index)
+            .ok_or(Error::Conversion)?
+            .load(Ordering::Relaxed);
+        let millivolts = millivolts.clamp(0, FULL_SCALE_MV.cast_signed());
+
+        let raw = i64::from(millivolts) * i64::from(full_scale_raw) / i64::from(FULL_SCALE_MV);
+        let raw = i32::try_from(raw).map_err(|_| Error::Conversion)?;
+
+        Ok(Sample::new(raw, full_scale_raw, FULL_SCALE_MV))
+    }
+}
+
+/// Peripheral-agnostic ADC driver.
+pub enum Adc {
+    /// Simulated ADC.
+    ADC(ADC),
+}
+
+impl Adc {
+    /// Performs a single-shot conversion on `channel`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Conversion`] if the conversion could not be carried out.
+    pub async fn read(&mut self, channel: &mut Channel<'_>) -> Result<Sample, Error> {
+        match self {
+            Self::ADC(adc) => adc.read(channel).await,
+        }
+    }
+}
+
+#[doc(hidden)]
+pub fn init(_peripherals: &mut crate::OptionalPeripherals) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_simulated_voltage() {
+        set_simulated_millivolts(0, 1650);
+
+        let mut adc = ADC::new(Config::default());
+        let mut channel = Channel::simulated(0);
+        let sample = block_on(adc.read(&mut channel)).unwrap();
+
+        assert_eq!(sample.full_scale_raw(), 4095);
+        assert_eq!(sample.raw(), 2047);
+        assert_eq!(sample.millivolts(), 1649);
+    }
+
+    #[test]
+    fn applies_resolution() {
+        set_simulated_millivolts(1, 3300);
+
+        let config = Config {
+            resolution: ariel_os_embassy_common::adc::Resolution::Bits8,
+            ..Config::default()
+        };
+        let mut adc = ADC::new(config);
+        let sample = block_on(adc.read(&mut Channel::simulated(1))).unwrap();
+        assert_eq!(sample.raw(), 255);
+        assert_eq!(sample.millivolts(), 3300);
+
+        let config = Config {
+            resolution: ariel_os_embassy_common::adc::Resolution::Hal(Resolution::Bits16),
+            ..Config::default()
+        };
+        let mut adc = ADC::new(config);
+        let sample = block_on(adc.read(&mut Channel::simulated(1))).unwrap();
+        assert_eq!(sample.raw(), 65535);
+    }
+
+    #[test]
+    fn clamps_to_conversion_range() {
+        set_simulated_millivolts(2, -500);
+        set_simulated_millivolts(3, 5000);
+
+        let mut adc = ADC::new(Config::default());
+        let low = block_on(adc.read(&mut Channel::simulated(2))).unwrap();
+        let high = block_on(adc.read(&mut Channel::simulated(3))).unwrap();
+
+        assert_eq!(low.raw(), 0);
+        assert_eq!(high.raw(), 4095);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not exist")]
+    fn rejects_missing_input() {
+        let _ = Channel::simulated(SIMULATED_CHANNELS);
+    }
+
+    // Minimal executor, the simulated conversions never return `Pending`.
+    fn block_on<F: Future>(fut: F) -> F::Output {
+        use core::task::{Context, Poll, Waker};
+
+        let mut fut = core::pin::pin!(fut);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+}
@@ -2,12 +2,27 @@
 
 #![cfg_attr(nightly, feature(doc_cfg))]
 
+#[cfg(feature = "adc")]
+pub mod adc;
+
 #[cfg(feature = "hwrng")]
 pub mod hwrng;
 
 pub mod identity;
 pub mod peripherals {}
 
+#[cfg(feature = "pwm")]
+pub mod pwm;
+
+#[cfg(feature = "storage")]
+pub mod storage;
+
+#[cfg(feature = "system-off")]
+pub mod system_off;
+
+#[cfg(feature = "watchdog")]
+pub mod watchdog;
+
 pub struct OptionalPeripherals {}
 
 #[must_use]
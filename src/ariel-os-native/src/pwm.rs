@@ -0,0 +1,165 @@
+//! Provides a simulated PWM peripheral.
+//!
+//! The signals produced on the simulated outputs are read back with [`simulated_duty_cycle()`]
+//! and [`simulated_frequency()`].
+
+use std::sync::atomic::{AtomicBool, AtomicU16, AtomicU32, Ordering};
+
+use ariel_os_embassy_common::{
+    impl_pwm_builder_settings, impl_pwm_for_driver_enum,
+    pwm::{DutyCycle, Error, Hertz, Polarity, Settings},
+};
+
+/// Number of simulated PWM channels.
+pub const CHANNELS: usize = 4;
+
+static SIMULATED_FREQUENCY_HZ: AtomicU32 = AtomicU32::new(0);
+// Fraction of the period during which the output is high, in basis points.
+static SIMULATED_HIGH_BASIS_POINTS: [AtomicU16; CHANNELS] =
+    [const { AtomicU16::new(0) }; CHANNELS];
+
+/// Returns the fraction of the period during which the simulated output `channel` is high.
+///
+/// # Panics
+///
+/// Panics if `channel` is not smaller than [`CHANNELS`].
+#[must_use]
+pub fn simulated_duty_cycle(channel: usize) -> DutyCycle {
+    let Some(output) = SIMULATED_HIGH_BASIS_POINTS.get(channel) else {
+        panic!("simulated PWM channel {channel} does not exist");
+    };
+    DutyCycle::from_fraction(
+        u32::from(output.load(Ordering::Relaxed)),
+        u32::from(DutyCycle::MAX),
+    )
+}
+
+/// Returns the frequency of the simulated PWM signal.
+#[must_use]
+pub fn simulated_frequency() -> Hertz {
+    Hertz::Hz(SIMULATED_FREQUENCY_HZ.load(Ordering::Relaxed))
+}
+
+/// Builder for the simulated PWM driver, obtained by calling [`PWM::builder()`].
+pub struct PwmBuilder {
+    settings: Settings,
+    channel_count: usize,
+}
+
+impl_pwm_builder_settings!();
+
+impl PwmBuilder {
+    /// Adds a simulated channel; channels are numbered in the order they are added.
+    ///
+    /// # Panics
+    ///
+    /// Panics if all the [`CHANNELS`] channels are already used.
+    #[must_use]
+    pub fn simulated_channel(mut self) -> Self {
+        assert!(
+            self.channel_count < CHANNELS,
+            "all PWM channels are already used"
+        );
+        self.channel_count += 1;
+        self
+    }
+
+    /// Returns the simulated PWM driver by finalizing the builder.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::FrequencyNotSupported`] if the frequency is zero.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the simulated peripheral is already in use.
+    pub fn build(self) -> Result<Pwm, Error> {
+        if self.settings.frequency.to_Hz() == 0 {
+            return Err(Error::FrequencyNotSupported);
+        }
+
+        assert!(
+            !ACTIVE_PWM.swap(true, Ordering::AcqRel),
+            "PWM peripheral already initialized"
+        );
+
+        SIMULATED_FREQUENCY_HZ.store(self.settings.frequency.to_Hz(), Ordering::Relaxed);
+
+        let mut pwm = PWM {
+            channel_count: self.channel_count,
+            polarity: self.settings.polarity,
+        };
+        for channel in 0..self.channel_count {
+            pwm.set_duty_cycle(channel, self.settings.initial_duty_cycle)?;
+        }
+
+        Ok(Pwm::PWM(pwm))
+    }
+}
+
+static ACTIVE_PWM: AtomicBool = AtomicBool::new(false);
+
+/// Simulated PWM driver.
+pub struct PWM {
+    channel_count: usize,
+    polarity: Polarity,
+}
+
+impl PWM {
+    /// Returns a [`PwmBuilder`] for the simulated peripheral, producing a PWM signal at
+    /// `frequency`.
+    #[must_use]
+    pub fn builder(frequency: Hertz) -> PwmBuilder {
+        PwmBuilder {
+            settings: Settings::new(frequency),
+            channel_count: 0,
+        }
+    }
+
+    fn channel_count(&self) -> usize {
+        self.channel_count
+    }
+
+    fn set_duty_cycle(&mut self, channel: usize, duty_cycle: DutyCycle) -> Result<(), Error> {
+        if channel >= self.channel_count {
+            return Err(Error::InvalidChannel);
+        }
+        let output = SIMULATED_HIGH_BASIS_POINTS
+            .get(channel)
+            .ok_or(Error::InvalidChannel)?;
+
+        let high = duty_cycle.high_ticks(u32::from(DutyCycle::MAX), self.polarity);
+        // Cannot truncate as `high` is not larger than `DutyCycle::MAX`.
+        #[expect(clippy::cast_possible_truncation)]
+        output.store(high as u16, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    #[expect(clippy::unused_self)]
+    fn set_frequency(&mut self, frequency: Hertz) -> Result<(), Error> {
+        if frequency.to_Hz() == 0 {
+            return Err(Error::FrequencyNotSupported);
+        }
+        SIMULATED_FREQUENCY_HZ.store(frequency.to_Hz(), Ordering::Relaxed);
+
+        Ok(())
+    }
+}
+
+impl Drop for PWM {
+    fn drop(&mut self) {
+        ACTIVE_PWM.store(false, Ordering::Release);
+    }
+}
+
+/// Peripheral-agnostic PWM driver.
+pub enum Pwm {
+    /// Simulated PWM peripheral.
+    PWM(PWM),
+}
+
+impl_pwm_for_driver_enum!(Pwm, PWM);
+
+#[doc(hidden)]
+pub fn init(_peripherals: &mut crate::OptionalPeripherals) {}
@@ -0,0 +1,299 @@
+//! Provides flash emulation backed by a file, for use by the storage.
+//!
+//! The file is memory-mapped, and its path is taken from the `ARIEL_NATIVE_FLASH` environment
+//! variable (defaulting to `flash.bin` in the current directory).
+//! It is created and erased when it does not exist yet, and extended when it is smaller than the
+//! emulated flash.
+//!
+//! The emulation enforces the constraints of NOR flash: writes and erases must be aligned, and
+//! bits can only be flipped from 1 to 0 by writes, so memory must be erased before being written
+//! again.
+
+use std::{fs::OpenOptions, path::Path};
+
+use ariel_os_buildutils::native_flash;
+use embassy_embedded_hal::adapter::BlockingAsync;
+use embedded_storage::nor_flash::{
+    ErrorType, MultiwriteNorFlash, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash,
+};
+use memmap2::MmapMut;
+
+pub type Flash = BlockingAsync<FileFlash>;
+
+/// Value of erased bytes.
+const ERASED: u8 = 0xff;
+
+/// Erase page size, in bytes.
+const PAGE_SIZE: usize = native_flash::PAGE_SIZE as usize;
+
+/// Size of the emulated flash, in bytes.
+///
+/// This holds the storage partitions, queues and raw partitions, as laid out by the storage from
+/// the same [`native_flash`] constants.
+const CAPACITY: usize = native_flash::CAPACITY as usize;
+
+/// Flash emulated using a memory-mapped file.
+pub struct FileFlash {
+    mmap: MmapMut,
+}
+
+/// Errors returned by the emulated flash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum FlashError {
+    /// The offset or length is not aligned to the read, write or erase size.
+    NotAligned,
+    /// The operation targets memory outside of the flash.
+    OutOfBounds,
+    /// The write would flip bits from 0 to 1, which requires erasing the memory first.
+    NotErased,
+    /// The backing file could not be synced.
+    Io,
+}
+
+impl NorFlashError for FlashError {
+    fn kind(&self) -> NorFlashErrorKind {
+        match self {
+            Self::NotAligned => NorFlashErrorKind::NotAligned,
+            Self::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+            Self::NotErased | Self::Io => NorFlashErrorKind::Other,
+        }
+    }
+}
+
+impl FileFlash {
+    /// Opens the emulated flash backed by the file at `path`, creating and erasing it if it does
+    /// not exist.
+    ///
+    /// Existing files smaller than the emulated flash, e.g., created by previous versions, are
+    /// extended with erased memory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file cannot be opened or mapped, or if an existing file is larger
+    /// than the emulated flash.
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let len = file.metadata()?.len();
+        if len > CAPACITY as u64 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("flash file has size {len}, expected at most {CAPACITY}"),
+            ));
+        }
+        // Bounded by the capacity.
+        #[expect(clippy::cast_possible_truncation)]
+        let len = len as usize;
+        if len < CAPACITY {
+            file.set_len(CAPACITY as u64)?;
+        }
+
+        // SAFETY: the file is only expected to be modified through this mapping; modifications by
+        // other processes would only corrupt the stored data.
+        #[expect(unsafe_code)]
+        let mut mmap = unsafe { MmapMut::map_mut(&file)? };
+
+        if let Some(added) = mmap.get_mut(len..).filter(|added| !added.is_empty()) {
+            added.fill(ERASED);
+            mmap.flush()?;
+        }
+
+        Ok(Self { mmap })
+    }
+
+    /// Checks that `offset..offset + len` is aligned to `align` and inside the flash, and returns
+    /// it as a range.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the range is not aligned or not inside the flash.
+    fn range(offset: u32, len: usize, align: usize) -> Result<core::ops::Range<usize>, FlashError> {
+        let start = usize::try_from(offset).map_err(|_| FlashError::OutOfBounds)?;
+        if !start.is_multiple_of(align) || !len.is_multiple_of(align) {
+            return Err(FlashError::NotAligned);
+        }
+        let end = start.checked_add(len).ok_or(FlashError::OutOfBounds)?;
+        if end > CAPACITY {
+            return Err(FlashError::OutOfBounds);
+        }
+        Ok(start..end)
+    }
+}
+
+impl ErrorType for FileFlash {
+    type Error = FlashError;
+}
+
+impl ReadNorFlash for FileFlash {
+    const READ_SIZE: usize = 1;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        let range = Self::range(offset, bytes.len(), Self::READ_SIZE)?;
+        let data = self.mmap.get(range).ok_or(FlashError::OutOfBounds)?;
+        bytes.copy_from_slice(data);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        CAPACITY
+    }
+}
+
+impl NorFlash for FileFlash {
+    const WRITE_SIZE: usize = 4;
+    const ERASE_SIZE: usize = PAGE_SIZE;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        let len = to.checked_sub(from).ok_or(FlashError::OutOfBounds)?;
+        let len = usize::try_from(len).map_err(|_| FlashError::OutOfBounds)?;
+        let range = Self::range(from, len, Self::ERASE_SIZE)?;
+
+        let pages = self.mmap.get_mut(range).ok_or(FlashError::OutOfBounds)?;
+        pages.fill(ERASED);
+
+        self.mmap.flush().map_err(|_| FlashError::Io)
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        let range = Self::range(offset, bytes.len(), Self::WRITE_SIZE)?;
+
+        let words = self.mmap.get_mut(range).ok_or(FlashError::OutOfBounds)?;
+        // Writing can only clear bits, check this before modifying anything.
+        if words
+            .iter()
+            .zip(bytes)
+            .any(|(word, byte)| !word & byte != 0)
+        {
+            return Err(FlashError::NotErased);
+        }
+        words.copy_from_slice(bytes);
+
+        self.mmap.flush().map_err(|_| FlashError::Io)
+    }
+}
+
+// Words can be written multiple times, as long as bits are only cleared.
+impl MultiwriteNorFlash for FileFlash {}
+
+/// Opens the emulated flash.
+///
+/// # Panics
+///
+/// Panics if the flash file cannot be opened.
+pub fn init(_peripherals: &mut crate::OptionalPeripherals) -> Flash {
+    let path = std::env::var("ARIEL_NATIVE_FLASH").unwrap_or_else(|_| "flash.bin".to_owned());
+    match FileFlash::open(&path) {
+        Ok(flash) => BlockingAsync::new(flash),
+        Err(e) => panic!("Error opening flash file {path}: {e}"),
+    }
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::cast_possible_truncation,
+    reason = "the size of the emulated flash fits in a u32"
+)]
+mod tests {
+    use super::*;
+
+    fn open_empty(name: &str) -> FileFlash {
+        let path = std::env::temp_dir().join(format!(
+            "ariel-os-native-flash-{name}-{}.bin",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        FileFlash::open(&path).unwrap()
+    }
+
+    #[test]
+    fn starts_erased() {
+        let mut flash = open_empty("erased");
+        let mut buffer = [0; 8];
+        flash.read(100, &mut buffer).unwrap();
+        assert_eq!(buffer, [ERASED; 8]);
+    }
+
+    #[test]
+    fn write_requires_erase() {
+        let mut flash = open_empty("write");
+        flash.write(8, &[0x0f, 0xff, 0x00, 0xaa]).unwrap();
+        // Clearing more bits is allowed.
+        flash.write(8, &[0x0e, 0x7f, 0x00, 0x00]).unwrap();
+        // Setting bits is not.
+        assert_eq!(
+            flash.write(8, &[0x1e, 0x7f, 0x00, 0x00]),
+            Err(FlashError::NotErased)
+        );
+
+        let mut buffer = [0; 4];
+        flash.read(8, &mut buffer).unwrap();
+        assert_eq!(buffer, [0x0e, 0x7f, 0x00, 0x00]);
+
+        flash.erase(0, PAGE_SIZE as u32).unwrap();
+        flash.write(8, &[0x1e, 0x7f, 0x00, 0x00]).unwrap();
+    }
+
+    #[test]
+    fn enforces_alignment_and_bounds() {
+        let mut flash = open_empty("alignment");
+        assert_eq!(flash.write(2, &[0; 4]), Err(FlashError::NotAligned));
+        assert_eq!(flash.write(4, &[0; 3]), Err(FlashError::NotAligned));
+        assert_eq!(flash.erase(0, 100), Err(FlashError::NotAligned));
+        assert_eq!(
+            flash.erase(0, 2 * CAPACITY as u32),
+            Err(FlashError::OutOfBounds)
+        );
+        assert_eq!(
+            flash.write(CAPACITY as u32, &[0; 4]),
+            Err(FlashError::OutOfBounds)
+        );
+        assert_eq!(
+            flash.erase(PAGE_SIZE as u32, 0),
+            Err(FlashError::OutOfBounds)
+        );
+    }
+
+    #[test]
+    fn persists_data() {
+        let path = std::env::temp_dir().join(format!(
+            "ariel-os-native-flash-persist-{}.bin",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+
+        FileFlash::open(&path)
+            .unwrap()
+            .write(PAGE_SIZE as u32, &[1, 2, 3, 4])
+            .unwrap();
+
+        let mut buffer = [0; 4];
+        FileFlash::open(&path)
+            .unwrap()
+            .read(PAGE_SIZE as u32, &mut buffer)
+            .unwrap();
+        assert_eq!(buffer, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn extends_smaller_files() {
+        let path = std::env::temp_dir().join(format!(
+            "ariel-os-native-flash-extend-{}.bin",
+            std::process::id()
+        ));
+        std::fs::write(&path, [0; PAGE_SIZE]).unwrap();
+
+        let mut flash = FileFlash::open(&path).unwrap();
+        let mut buffer = [ERASED; 4];
+        flash.read(0, &mut buffer).unwrap();
+        assert_eq!(buffer, [0; 4]);
+        flash.read(PAGE_SIZE as u32, &mut buffer).unwrap();
+        assert_eq!(buffer, [ERASED; 4]);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), CAPACITY as u64);
+    }
+}
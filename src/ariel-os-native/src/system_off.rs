@@ -0,0 +1,30 @@
+//! Provides system off, which is not supported on native.
+
+use core::{convert::Infallible, time::Duration};
+
+pub use ariel_os_embassy_common::system_off::{Error, WakeEdge};
+
+/// GPIO pin that can wake the MCU up from system off.
+#[derive(Debug, Clone, Copy)]
+pub struct WakePin {}
+
+#[doc(hidden)]
+pub fn wake_pin<P>(_pin: &P) -> WakePin {
+    WakePin {}
+}
+
+/// Source waking the MCU up from system off.
+#[derive(Debug, Clone, Copy)]
+pub enum WakeSource {
+    #[doc(hidden)]
+    Pin(WakePin, WakeEdge),
+    #[doc(hidden)]
+    Timer(Duration),
+}
+
+#[doc(hidden)]
+pub fn system_off(
+    _wake_sources: impl IntoIterator<Item = WakeSource>,
+) -> Result<Infallible, Error> {
+    Err(Error::Unsupported)
+}
@@ -0,0 +1,302 @@
+//! Provides support for the SAADC (successive approximation ADC).
+
+#![expect(unsafe_code)]
+
+#[cfg(context = "nrf51")]
+compile_error!("the ADC of this nRF chip is not supported");
+
+use portable_atomic::{AtomicBool, Ordering};
+
+use ariel_os_embassy_common::adc::{Error, Sample};
+use embassy_nrf::{
+    bind_interrupts, peripherals,
+    saadc::{self, AnyInput, ChannelConfig, Gain, InterruptHandler, Saadc},
+};
+
+#[doc(hidden)]
+pub use embassy_nrf::saadc::Input as AdcPin;
+
+/// ADC configuration.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// Resolution of the conversions.
+    pub resolution: ariel_os_embassy_common::adc::Resolution<Resolution>,
+    /// Hardware oversampling ratio.
+    pub oversampling: ariel_os_embassy_common::adc::Oversampling<Oversampling>,
+    /// Voltage reference.
+    pub reference: ariel_os_embassy_common::adc::Reference<Reference>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            resolution: ariel_os_embassy_common::adc::Resolution::Bits12,
+            oversampling: ariel_os_embassy_common::adc::Oversampling::None,
+            reference: ariel_os_embassy_common::adc::Reference::Hal(Reference::Internal),
+        }
+    }
+}
+
+/// ADC resolution.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Resolution {
+    /// 8-bit resolution.
+    Bits8,
+    /// 10-bit resolution.
+    Bits10,
+    /// 12-bit resolution.
+    Bits12,
+    /// 14-bit resolution, requires oversampling to be effective.
+    Bits14,
+}
+
+impl From<Resolution> for u8 {
+    fn from(resolution: Resolution) -> u8 {
+        match resolution {
+            Resolution::Bits8 => 8,
+            Resolution::Bits10 => 10,
+            Resolution::Bits12 => 12,
+            Resolution::Bits14 => 14,
+        }
+    }
+}
+
+impl From<ariel_os_embassy_common::adc::Resolution<Self>> for Resolution {
+    fn from(resolution: ariel_os_embassy_common::adc::Resolution<Self>) -> Self {
+        match resolution {
+            ariel_os_embassy_common::adc::Resolution::Hal(resolution) => resolution,
+            ariel_os_embassy_common::adc::Resolution::Bits8 => Self::Bits8,
+            ariel_os_embassy_common::adc::Resolution::Bits10 => Self::Bits10,
+            ariel_os_embassy_common::adc::Resolution::Bits12 => Self::Bits12,
+        }
+    }
+}
+
+fn from_resolution(resolution: Resolution) -> saadc::Resolution {
+    match resolution {
+        Resolution::Bits8 => saadc::Resolution::_8BIT,
+        Resolution::Bits10 => saadc::Resolution::_10BIT,
+        Resolution::Bits12 => saadc::Resolution::_12BIT,
+        Resolution::Bits14 => saadc::Resolution::_14BIT,
+    }
+}
+
+/// ADC oversampling ratio, carried out in hardware.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Oversampling {
+    /// No oversampling.
+    None,
+    /// 2× oversampling.
+    X2,
+    /// 4× oversampling.
+    X4,
+    /// 8× oversampling.
+    X8,
+    /// 16× oversampling.
+    X16,
+    /// 32× oversampling.
+    X32,
+    /// 64× oversampling.
+    X64,
+    /// 128× oversampling.
+    X128,
+    /// 256× oversampling.
+    X256,
+}
+
+impl From<Oversampling> for u16 {
+    fn from(oversampling: Oversampling) -> u16 {
+        match oversampling {
+            Oversampling::None => 1,
+            Oversampling::X2 => 2,
+            Oversampling::X4 => 4,
+            Oversampling::X8 => 8,
+            Oversampling::X16 => 16,
+            Oversampling::X32 => 32,
+            Oversampling::X64 => 64,
+            Oversampling::X128 => 128,
+            Oversampling::X256 => 256,
+        }
+    }
+}
+
+impl From<ariel_os_embassy_common::adc::Oversampling<Self>> for Oversampling {
+    fn from(oversampling: ariel_os_embassy_common::adc::Oversampling<Self>) -> Self {
+        match oversampling {
+            ariel_os_embassy_common::adc::Oversampling::Hal(oversampling) => oversampling,
+            ariel_os_embassy_common::adc::Oversampling::None => Self::None,
+            ariel_os_embassy_common::adc::Oversampling::X2 => Self::X2,
+            ariel_os_embassy_common::adc::Oversampling::X4 => Self::X4,
+            ariel_os_embassy_common::adc::Oversampling::X8 => Self::X8,
+            ariel_os_embassy_common::adc::Oversampling::X16 => Self::X16,
+        }
+    }
+}
+
+fn from_oversampling(oversampling: Oversampling) -> saadc::Oversample {
+    match oversampling {
+        Oversampling::None => saadc::Oversample::BYPASS,
+        Oversampling::X2 => saadc::Oversample::OVER2X,
+        Oversampling::X4 => saadc::Oversample::OVER4X,
+        Oversampling::X8 => saadc::Oversample::OVER8X,
+        Oversampling::X16 => saadc::Oversample::OVER16X,
+        Oversampling::X32 => saadc::Oversample::OVER32X,
+        Oversampling::X64 => saadc::Oversample::OVER64X,
+        Oversampling::X128 => saadc::Oversample::OVER128X,
+        Oversampling::X256 => saadc::Oversample::OVER256X,
+    }
+}
+
+/// ADC voltage reference.
+///
+/// The gain is selected so that the full scale is as close to the supply voltage as possible.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Reference {
+    /// Internal 0.6 V reference, with a 1/6 gain: full scale is 3.6 V.
+    Internal,
+    /// VDD/4 reference, with a 1/4 gain: full scale is VDD, assumed to be 3.3 V.
+    Vdd,
+}
+
+impl From<ariel_os_embassy_common::adc::Reference<Self>> for Reference {
+    fn from(reference: ariel_os_embassy_common::adc::Reference<Self>) -> Self {
+        match reference {
+            ariel_os_embassy_common::adc::Reference::Hal(reference) => reference,
+            ariel_os_embassy_common::adc::Reference::Vdd => Self::Vdd,
+        }
+    }
+}
+
+impl Reference {
+    fn full_scale_mv(self) -> u32 {
+        match self {
+            Self::Internal => 3600,
+            Self::Vdd => 3300,
+        }
+    }
+}
+
+/// An analog input of the SAADC.
+pub struct Channel<'d> {
+    input: AnyInput<'d>,
+}
+
+impl<'d> Channel<'d> {
+    /// Returns a channel sampling the given analog-capable pin, in single-ended mode.
+    pub fn new<P: AdcPin>(pin: impl crate::IntoPeripheral<'d, P>) -> Self {
+        Self {
+            input: pin.into_hal_peripheral().degrade_saadc(),
+        }
+    }
+}
+
+/// Peripheral-specific ADC driver.
+pub struct SAADC {
+    config: Config,
+}
+
+// Ensure this peripheral has only one active Instance.
+static ACTIVE_SAADC: AtomicBool = AtomicBool::new(false);
+
+impl SAADC {
+    /// Returns a driver for the SAADC peripheral.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the peripheral is already in use.
+    #[expect(clippy::new_ret_no_self)]
+    #[expect(clippy::manual_assert, reason = "consistent with the other drivers")]
+    #[must_use]
+    pub fn new(config: Config) -> Adc {
+        // Check if we can initialize this peripheral (check if the value was previously false, set it to true).
+        if ACTIVE_SAADC.swap(true, Ordering::AcqRel) {
+            panic!("ADC peripheral already initialized")
+        }
+
+        Adc::SAADC(Self { config })
+    }
+
+    async fn read(&mut self, channel: &mut Channel<'_>) -> Result<Sample, Error> {
+        let resolution = Resolution::from(self.config.resolution);
+        let reference = Reference::from(self.config.reference);
+
+        let mut saadc_config = saadc::Config::default();
+        saadc_config.resolution = from_resolution(resolution);
+        saadc_config.oversample = from_oversampling(Oversampling::from(self.config.oversampling));
+
+        let mut channel_config = ChannelConfig::single_ended(channel.input.reborrow());
+        match reference {
+            Reference::Internal => {
+                channel_config.reference = saadc::Reference::INTERNAL;
+                channel_config.gain = Gain::GAIN1_6;
+            }
+            Reference::Vdd => {
+                channel_config.reference = saadc::Reference::VDD1_4;
+                channel_config.gain = Gain::GAIN1_4;
+            }
+        }
+
+        bind_interrupts!(struct Irqs {
+            SAADC => InterruptHandler;
+        });
+
+        // NOTE(hal): the channel configuration is fixed when instantiating the upstream driver,
+        // so we instantiate it for each conversion, which also allows to leave the peripheral
+        // disabled in between.
+        // SAFETY: We check with an AtomicBool that only one instance of this peripheral
+        // is active at once, and the upstream driver is dropped before returning.
+        let saadc_peripheral = unsafe { peripherals::SAADC::steal() };
+        let mut saadc = Saadc::new(saadc_peripheral, Irqs, saadc_config, [channel_config]);
+
+        let mut buf = [0i16; 1];
+        saadc.sample(&mut buf).await;
+        let [raw] = buf;
+
+        let full_scale_raw = (1u32 << u8::from(resolution)) - 1;
+
+        Ok(Sample::new(
+            i32::from(raw),
+            full_scale_raw,
+            reference.full_scale_mv(),
+        ))
+    }
+}
+
+impl Drop for SAADC {
+    fn drop(&mut self) {
+        ACTIVE_SAADC.store(false, Ordering::Release);
+    }
+}
+
+/// Peripheral-agnostic ADC driver.
+pub enum Adc {
+    /// SAADC peripheral.
+    SAADC(SAADC),
+}
+
+impl Adc {
+    /// Performs a single-shot conversion on `channel`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Conversion`] if the conversion could not be carried out.
+    pub async fn read(&mut self, channel: &mut Channel<'_>) -> Result<Sample, Error> {
+        match self {
+            Self::SAADC(adc) => adc.read(channel).await,
+        }
+    }
+}
+
+#[doc(hidden)]
+pub fn init(peripherals: &mut crate::OptionalPeripherals) {
+    // Take the ADC peripheral and do nothing with it.
+    let _ = peripherals.SAADC.take().unwrap();
+}
@@ -8,6 +8,9 @@ bind_interrupts!(pub(crate) struct Irqs {
     #[cfg(feature = "hwrng")]
     RNG => embassy_nrf::rng::InterruptHandler<embassy_nrf::peripherals::RNG>;
 
+    #[cfg(all(feature = "qspi", any(context = "nrf52840", context = "nrf5340-app")))]
+    QSPI => embassy_nrf::qspi::InterruptHandler<embassy_nrf::peripherals::QSPI>;
+
     #[cfg(feature = "usb")]
     USBD => embassy_nrf::usb::InterruptHandler<embassy_nrf::peripherals::USBD>;
 
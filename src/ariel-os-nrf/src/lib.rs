@@ -4,6 +4,9 @@
 #![cfg_attr(nightly, feature(doc_cfg))]
 #![deny(missing_docs)]
 
+#[cfg(feature = "adc")]
+pub mod adc;
+
 pub mod gpio;
 
 mod irqs;
@@ -39,9 +42,19 @@ pub mod modem;
 #[doc(hidden)]
 pub mod ltem;
 
+#[cfg(feature = "pwm")]
+pub mod pwm;
+
+#[cfg(feature = "qspi")]
+#[cfg(any(context = "nrf52840", context = "nrf5340-app"))]
+pub mod qspi;
+
 #[cfg(feature = "spi")]
 pub mod spi;
 
+#[cfg(feature = "system-off")]
+pub mod system_off;
+
 #[cfg(feature = "uart")]
 pub mod uart;
 
@@ -53,6 +66,10 @@ pub mod storage;
 #[doc(hidden)]
 pub mod usb;
 
+#[cfg(feature = "watchdog")]
+#[doc(hidden)]
+pub mod watchdog;
+
 #[cfg(feature = "executor-interrupt")]
 #[doc(hidden)]
 pub use embassy_executor::InterruptExecutor as Executor;
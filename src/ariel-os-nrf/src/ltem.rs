@@ -179,6 +179,53 @@ fn status_to_config(status: &Status) -> embassy_net::Config {
     config
 }
 
+/// Returns the current time as provided by the cellular network, or `None` if the modem has not
+/// received it (yet).
+#[doc(hidden)]
+pub async fn network_time() -> Option<time::UtcDateTime> {
+    // NOTE(hal): the modem returns an error until it has received the time from the network.
+    let response = nrf_modem::send_at::<64>("AT+CCLK?").await.ok()?;
+    parse_cclk(&response)
+}
+
+/// Parses a `+CCLK: "yy/MM/dd,hh:mm:ss±zz"` response, where `zz` is the offset of the local time
+/// from UTC, in quarters of an hour.
+fn parse_cclk(response: &str) -> Option<time::UtcDateTime> {
+    let (_, timestamp) = response.split_once('"')?;
+    let (timestamp, _) = timestamp.split_once('"')?;
+
+    let (date, time_and_offset) = timestamp.split_once(',')?;
+    let (time_of_day, offset) = time_and_offset.split_at(time_and_offset.find(['+', '-'])?);
+
+    let [year, month, day] = parse_fields(date, '/')?;
+    let [hour, minute, second] = parse_fields(time_of_day, ':')?;
+    let offset_quarters = offset.parse::<i8>().ok()?;
+
+    let date = time::Date::from_calendar_date(
+        2000 + i32::from(year),
+        time::Month::try_from(month).ok()?,
+        day,
+    )
+    .ok()?;
+    let time_of_day = time::Time::from_hms(hour, minute, second).ok()?;
+    let offset = time::UtcOffset::from_whole_seconds(i32::from(offset_quarters) * 15 * 60).ok()?;
+
+    Some(
+        time::PrimitiveDateTime::new(date, time_of_day)
+            .assume_offset(offset)
+            .to_utc(),
+    )
+}
+
+/// Parses three numeric fields separated by `separator`.
+fn parse_fields(fields: &str, separator: char) -> Option<[u8; 3]> {
+    let mut fields = fields.split(separator).map(str::parse);
+    match (fields.next(), fields.next(), fields.next(), fields.next()) {
+        (Some(Ok(a)), Some(Ok(b)), Some(Ok(c)), None) => Some([a, b, c]),
+        _ => None,
+    }
+}
+
 /// Initializes the modem for LTE-M networking.
 /// The control task needs to be spawned using [`control_task`].
 ///
@@ -196,3 +243,41 @@ pub async fn init<'a>(spawner: Spawner) -> (NetworkDevice, &'a context::Control<
 
     (driver, control)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_cclk_positive_offset() {
+        let time = parse_cclk("+CCLK: \"25/06/18,09:41:17+08\"\r\nOK\r\n").unwrap();
+        // 2025-06-18 07:41:17 UTC.
+        assert_eq!(time.unix_timestamp(), 1_750_232_477);
+    }
+
+    #[test]
+    fn parse_cclk_negative_offset() {
+        let time = parse_cclk("+CCLK: \"25/06/18,09:41:17-16\"\r\nOK\r\n").unwrap();
+        // 2025-06-18 13:41:17 UTC.
+        assert_eq!(time.unix_timestamp(), 1_750_254_077);
+    }
+
+    #[test]
+    fn parse_cclk_offset_crossing_midnight() {
+        let time = parse_cclk("+CCLK: \"25/01/01,01:00:00+12\"\r\nOK\r\n").unwrap();
+        // 2024-12-31 22:00:00 UTC.
+        assert_eq!(time.unix_timestamp(), 1_735_682_400);
+    }
+
+    #[test]
+    fn parse_cclk_malformed() {
+        assert_eq!(parse_cclk("ERROR"), None);
+        assert_eq!(parse_cclk("+CCLK: \"25/06/18,09:41:17+08"), None);
+        assert_eq!(parse_cclk("+CCLK: \"25/06/18,09:41:17\""), None);
+        assert_eq!(parse_cclk("+CCLK: \"25/06/18 09:41:17+08\""), None);
+        assert_eq!(parse_cclk("+CCLK: \"25/06/18/01,09:41:17+08\""), None);
+        assert_eq!(parse_cclk("+CCLK: \"25/13/18,09:41:17+08\""), None);
+        assert_eq!(parse_cclk("+CCLK: \"25/06/18,24:41:17+08\""), None);
+        assert_eq!(parse_cclk("+CCLK: \"25/06/18,09:41:17+xx\""), None);
+    }
+}
@@ -0,0 +1,337 @@
+//! Provides support for PWM outputs.
+
+#![expect(unsafe_code)]
+
+use core::marker::PhantomData;
+
+use portable_atomic::{AtomicBool, Ordering};
+
+use ariel_os_embassy_common::{
+    impl_pwm_builder_settings, impl_pwm_for_driver_enum,
+    pwm::{DutyCycle, Error, Hertz, Polarity, Settings, find_divider},
+};
+use embassy_nrf::{
+    Peri,
+    gpio::{AnyPin, Pin as GpioPin},
+    peripherals,
+    pwm::{DutyCycle as NrfDutyCycle, Prescaler, SimpleConfig, SimplePwm},
+};
+
+/// Number of channels of each PWM peripheral.
+pub const CHANNELS: usize = 4;
+
+// The PWM clock runs at 16 MHz, can be divided by up to 128, and the counter is 15-bit wide.
+const CLOCK_HZ: u32 = 16_000_000;
+const MAX_PRESCALER_LOG2: u8 = 7;
+const MAX_COUNTER_TOP: u32 = 0x7fff;
+
+/// Builder for a PWM driver, obtained by calling `builder()` on a peripheral-specific driver.
+///
+/// Any GPIO can be used by any channel; channels are numbered in the order they are added with
+/// [`PwmBuilder::channel()`].
+pub struct PwmBuilder<T> {
+    settings: Settings,
+    pins: [Option<Peri<'static, AnyPin>>; CHANNELS],
+    _instance: PhantomData<T>,
+}
+
+impl_pwm_builder_settings!(T);
+
+impl<T: Instance> PwmBuilder<T> {
+    fn new(frequency: Hertz) -> Self {
+        Self {
+            settings: Settings::new(frequency),
+            pins: [const { None }; CHANNELS],
+            _instance: PhantomData,
+        }
+    }
+
+    /// Adds a channel outputting to `pin`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if all the [`CHANNELS`] channels are already used.
+    #[must_use]
+    pub fn channel<P: GpioPin>(mut self, pin: impl crate::IntoPeripheral<'static, P>) -> Self {
+        let Some(slot) = self.pins.iter_mut().find(|slot| slot.is_none()) else {
+            panic!("all PWM channels are already used");
+        };
+        *slot = Some(pin.into_hal_peripheral().into());
+        self
+    }
+
+    /// Returns a PWM driver by finalizing the builder.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::FrequencyNotSupported`] if the frequency cannot be obtained.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the peripheral is already in use, or if no channel has been added.
+    pub fn build(self) -> Result<Pwm, Error> {
+        T::build(self)
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+#[doc(hidden)]
+pub trait Instance: sealed::Sealed + Sized {
+    fn build(builder: PwmBuilder<Self>) -> Result<Pwm, Error>;
+}
+
+fn prescaler(prescaler_log2: u8) -> Prescaler {
+    match prescaler_log2 {
+        0 => Prescaler::Div1,
+        1 => Prescaler::Div2,
+        2 => Prescaler::Div4,
+        3 => Prescaler::Div8,
+        4 => Prescaler::Div16,
+        5 => Prescaler::Div32,
+        6 => Prescaler::Div64,
+        _ => Prescaler::Div128,
+    }
+}
+
+/// Returns the prescaler and the counter top for `frequency`.
+fn timing(frequency: Hertz) -> Result<(Prescaler, u16), Error> {
+    let (prescaler_log2, ticks) =
+        find_divider(CLOCK_HZ, frequency, MAX_PRESCALER_LOG2, MAX_COUNTER_TOP)
+            .ok_or(Error::FrequencyNotSupported)?;
+    // `ticks` is not larger than `MAX_COUNTER_TOP`.
+    let top = u16::try_from(ticks).map_err(|_| Error::FrequencyNotSupported)?;
+    Ok((prescaler(prescaler_log2), top))
+}
+
+// State shared by all the peripheral-specific drivers.
+struct Inner {
+    pwm: SimplePwm<'static>,
+    channel_count: usize,
+    polarity: Polarity,
+    top: u16,
+    duty_cycles: [DutyCycle; CHANNELS],
+}
+
+impl Inner {
+    fn new<T>(pwm_peripheral: Peri<'static, T>, builder: PwmBuilder<T>) -> Result<Self, Error>
+    where
+        T: embassy_nrf::pwm::Instance,
+    {
+        let (prescaler, top) = timing(builder.settings.frequency)?;
+
+        let mut pwm_config = SimpleConfig::default();
+        pwm_config.prescaler = prescaler;
+        pwm_config.max_duty = top;
+
+        let [ch0, ch1, ch2, ch3] = builder.pins;
+        let (pwm, channel_count) = match (ch0, ch1, ch2, ch3) {
+            (Some(ch0), None, None, None) => {
+                (SimplePwm::new_1ch(pwm_peripheral, ch0, &pwm_config), 1)
+            }
+            (Some(ch0), Some(ch1), None, None) => {
+                (SimplePwm::new_2ch(pwm_peripheral, ch0, ch1, &pwm_config), 2)
+            }
+            (Some(ch0), Some(ch1), Some(ch2), None) => (
+                SimplePwm::new_3ch(pwm_peripheral, ch0, ch1, ch2, &pwm_config),
+                3,
+            ),
+            (Some(ch0), Some(ch1), Some(ch2), Some(ch3)) => (
+                SimplePwm::new_4ch(pwm_peripheral, ch0, ch1, ch2, ch3, &pwm_config),
+                4,
+            ),
+            _ => panic!("no PWM channel has been added"),
+        };
+
+        let mut inner = Self {
+            pwm,
+            channel_count,
+            polarity: builder.settings.polarity,
+            top,
+            duty_cycles: [builder.settings.initial_duty_cycle; CHANNELS],
+        };
+        inner.apply_duty_cycles();
+
+        Ok(inner)
+    }
+
+    fn apply_duty_cycles(&mut self) {
+        for (channel, duty_cycle) in self
+            .duty_cycles
+            .iter()
+            .enumerate()
+            .take(self.channel_count)
+        {
+            let high_ticks = duty_cycle.high_ticks(u32::from(self.top), self.polarity);
+            // Cannot truncate as `high_ticks` is not larger than `top`.
+            #[expect(clippy::cast_possible_truncation)]
+            self.pwm
+                .set_duty(channel, NrfDutyCycle::normal(high_ticks as u16));
+        }
+    }
+
+    fn set_duty_cycle(&mut self, channel: usize, duty_cycle: DutyCycle) -> Result<(), Error> {
+        if channel >= self.channel_count {
+            return Err(Error::InvalidChannel);
+        }
+        let slot = self
+            .duty_cycles
+            .get_mut(channel)
+            .ok_or(Error::InvalidChannel)?;
+        *slot = duty_cycle;
+
+        let high_ticks = duty_cycle.high_ticks(u32::from(self.top), self.polarity);
+        // Cannot truncate as `high_ticks` is not larger than `top`.
+        #[expect(clippy::cast_possible_truncation)]
+        self.pwm
+            .set_duty(channel, NrfDutyCycle::normal(high_ticks as u16));
+
+        Ok(())
+    }
+
+    fn set_frequency(&mut self, frequency: Hertz) -> Result<(), Error> {
+        let (prescaler, top) = timing(frequency)?;
+
+        self.pwm.set_prescaler(prescaler);
+        self.pwm.set_max_duty(top);
+        self.top = top;
+        self.apply_duty_cycles();
+
+        Ok(())
+    }
+}
+
+macro_rules! define_pwm_drivers {
+    ($( $peripheral:ident ),* $(,)?) => {
+        $(
+            /// Peripheral-specific PWM driver.
+            pub struct $peripheral {
+                inner: Inner,
+            }
+
+            // Ensure this peripheral has only one active Instance.
+            paste::paste! {
+                static [< ACTIVE_ $peripheral >]: AtomicBool = AtomicBool::new(false);
+            }
+
+            impl $peripheral {
+                /// Returns a [`PwmBuilder`] for this peripheral, producing a PWM signal at
+                /// `frequency`.
+                #[must_use]
+                pub fn builder(frequency: Hertz) -> PwmBuilder<Self> {
+                    PwmBuilder::new(frequency)
+                }
+
+                fn channel_count(&self) -> usize {
+                    self.inner.channel_count
+                }
+
+                fn set_duty_cycle(
+                    &mut self,
+                    channel: usize,
+                    duty_cycle: DutyCycle,
+                ) -> Result<(), Error> {
+                    self.inner.set_duty_cycle(channel, duty_cycle)
+                }
+
+                fn set_frequency(&mut self, frequency: Hertz) -> Result<(), Error> {
+                    self.inner.set_frequency(frequency)
+                }
+            }
+
+            impl sealed::Sealed for $peripheral {}
+
+            impl Instance for $peripheral {
+                fn build(builder: PwmBuilder<Self>) -> Result<Pwm, Error> {
+                    // Make this struct a compile-time-enforced singleton: having multiple statics
+                    // defined with the same name would result in a compile-time error.
+                    paste::paste! {
+                        #[allow(dead_code)]
+                        static [<PREVENT_MULTIPLE_ $peripheral>]: () = ();
+                    }
+
+                    // Check if we can initialize this peripheral (check if the value was previously false, set it to true).
+                    paste::paste! {
+                        if [< ACTIVE_ $peripheral >].swap(true, Ordering::AcqRel) {
+                            panic!("PWM peripheral already initialized")
+                        }
+                    }
+
+                    // FIXME(safety): enforce that the init code indeed has run
+                    // SAFETY: We check with an AtomicBool that only one instance of this peripheral
+                    // is active at once.
+                    let pwm_peripheral = unsafe { peripherals::$peripheral::steal() };
+
+                    let inner = match Inner::new(pwm_peripheral, builder) {
+                        Ok(inner) => inner,
+                        Err(err) => {
+                            paste::paste! {
+                                [< ACTIVE_ $peripheral >].store(false, Ordering::Release);
+                            }
+                            return Err(err);
+                        }
+                    };
+
+                    Ok(Pwm::$peripheral(Self { inner }))
+                }
+            }
+
+            impl Drop for $peripheral {
+                fn drop(&mut self) {
+                    paste::paste! {
+                        [< ACTIVE_ $peripheral >].store(false, Ordering::Release);
+                    }
+                }
+            }
+        )*
+
+        /// Peripheral-agnostic PWM driver.
+        pub enum Pwm {
+            $(
+                #[doc = concat!(stringify!($peripheral), " peripheral.")]
+                $peripheral($peripheral),
+            )*
+        }
+
+        impl_pwm_for_driver_enum!(Pwm, $( $peripheral ),*);
+    }
+}
+
+// Define a driver per peripheral
+#[cfg(context = "nrf52832")]
+define_pwm_drivers!(PWM0, PWM1, PWM2);
+#[cfg(any(
+    context = "nrf52833",
+    context = "nrf52840",
+    context = "nrf5340-app",
+    context = "nrf91"
+))]
+define_pwm_drivers!(PWM0, PWM1, PWM2, PWM3);
+
+#[doc(hidden)]
+pub fn init(peripherals: &mut crate::OptionalPeripherals) {
+    // Take all PWM peripherals and do nothing with them.
+    cfg_select! {
+        context = "nrf52832" => {
+            let _ = peripherals.PWM0.take().unwrap();
+            let _ = peripherals.PWM1.take().unwrap();
+            let _ = peripherals.PWM2.take().unwrap();
+        }
+        any(
+            context = "nrf52833",
+            context = "nrf52840",
+            context = "nrf5340-app",
+            context = "nrf91"
+        ) => {
+            let _ = peripherals.PWM0.take().unwrap();
+            let _ = peripherals.PWM1.take().unwrap();
+            let _ = peripherals.PWM2.take().unwrap();
+            let _ = peripherals.PWM3.take().unwrap();
+        }
+        _ => {
+            compile_error!("this nRF chip is not supported");
+        }
+    }
+}
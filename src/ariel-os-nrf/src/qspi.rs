@@ -0,0 +1,41 @@
+//! Provides the QSPI peripheral, to access external NOR flash chips.
+//!
+//! [`Qspi`] implements `embedded_storage_async::nor_flash::NorFlash`, with 4-byte aligned reads
+//! and writes, and can thus be used as a storage backend.
+//! The flash is additionally mapped for execute-in-place (XIP) reads at [`XIP_BASE`], which
+//! requires the QSPI peripheral to stay enabled.
+
+use embassy_nrf::{Peri, gpio::Pin, peripherals};
+
+use crate::irqs::Irqs;
+
+pub use embassy_nrf::qspi::{
+    AddressMode, Config, Error, Frequency, ReadOpcode, SpiMode, WriteOpcode, WritePageSize,
+};
+
+/// QSPI peripheral, driving an external NOR flash.
+pub type Qspi = embassy_nrf::qspi::Qspi<'static>;
+
+/// Address at which the external flash is mapped, offset by [`Config::xip_offset`].
+#[cfg(context = "nrf52840")]
+pub const XIP_BASE: usize = 0x1200_0000;
+/// Address at which the external flash is mapped, offset by [`Config::xip_offset`].
+#[cfg(context = "nrf5340-app")]
+pub const XIP_BASE: usize = 0x1000_0000;
+
+/// Creates the QSPI peripheral, driving the flash connected to the given pins.
+///
+/// `config.capacity` must be set to the capacity of the flash, in bytes.
+#[expect(clippy::too_many_arguments)]
+pub fn new(
+    qspi: Peri<'static, peripherals::QSPI>,
+    sck: Peri<'static, impl Pin>,
+    csn: Peri<'static, impl Pin>,
+    io0: Peri<'static, impl Pin>,
+    io1: Peri<'static, impl Pin>,
+    io2: Peri<'static, impl Pin>,
+    io3: Peri<'static, impl Pin>,
+    config: Config,
+) -> Qspi {
+    Qspi::new(qspi, Irqs, sck, csn, io0, io1, io2, io3, config)
+}
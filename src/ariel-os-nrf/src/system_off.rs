@@ -0,0 +1,147 @@
+//! Provides system off, from which the MCU wakes up with a reset.
+
+use core::{convert::Infallible, time::Duration};
+
+use embassy_nrf::{Peri, gpio::Pin, pac};
+
+#[cfg(any(context = "nrf52833", context = "nrf52840", context = "nrf53"))]
+use embassy_nrf::gpio::Port;
+
+pub use ariel_os_embassy_common::system_off::{Error, WakeEdge};
+
+/// GPIO pin that can wake the MCU up from system off.
+#[derive(Clone, Copy)]
+pub struct WakePin {
+    #[cfg(any(context = "nrf52833", context = "nrf52840", context = "nrf53"))]
+    port: Port,
+    pin: u8,
+}
+
+#[doc(hidden)]
+pub fn wake_pin<P: Pin>(pin: &Peri<'_, P>) -> WakePin {
+    WakePin {
+        #[cfg(any(context = "nrf52833", context = "nrf52840", context = "nrf53"))]
+        port: pin.port(),
+        pin: pin.pin(),
+    }
+}
+
+/// Low-power comparator (LPCOMP) configuration waking the MCU up from system off.
+#[cfg(any(context = "nrf51", context = "nrf52"))]
+#[derive(Debug, Clone, Copy)]
+pub struct Lpcomp {
+    input: u8,
+    reference_eighths: u8,
+    edge: WakeEdge,
+}
+
+#[cfg(any(context = "nrf51", context = "nrf52"))]
+impl Lpcomp {
+    /// Returns a configuration comparing analog input `AIN<input>` to `reference_eighths`
+    /// eighths of the supply voltage, waking the MCU up when the input crosses the reference
+    /// in the direction of `edge`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `input` is not in `0..8` or `reference_eighths` is not in `1..8`.
+    #[must_use]
+    pub const fn new(input: u8, reference_eighths: u8, edge: WakeEdge) -> Self {
+        assert!(input < 8, "invalid LPCOMP input");
+        assert!(
+            reference_eighths >= 1 && reference_eighths < 8,
+            "invalid LPCOMP reference"
+        );
+
+        Self {
+            input,
+            reference_eighths,
+            edge,
+        }
+    }
+}
+
+/// Source waking the MCU up from system off.
+#[derive(Clone, Copy)]
+pub enum WakeSource {
+    #[doc(hidden)]
+    Pin(WakePin, WakeEdge),
+    #[doc(hidden)]
+    Timer(Duration),
+    /// The low-power comparator.
+    #[cfg(any(context = "nrf51", context = "nrf52"))]
+    Lpcomp(Lpcomp),
+}
+
+// NOTE(hal): on the nRF5340 network core, system off is controlled by the application core.
+const SUPPORTED: bool = !cfg!(context = "nrf5340-net");
+
+#[doc(hidden)]
+pub fn system_off(wake_sources: impl IntoIterator<Item = WakeSource>) -> Result<Infallible, Error> {
+    if !SUPPORTED {
+        return Err(Error::Unsupported);
+    }
+
+    for wake_source in wake_sources {
+        match wake_source {
+            WakeSource::Pin(pin, edge) => enable_pin_sense(pin, edge),
+            // NOTE(hal): the RTCs are not clocked in system off.
+            WakeSource::Timer(_) => return Err(Error::UnsupportedWakeSource),
+            #[cfg(any(context = "nrf51", context = "nrf52"))]
+            WakeSource::Lpcomp(lpcomp) => enable_lpcomp(lpcomp),
+        }
+    }
+
+    cfg_select! {
+        any(context = "nrf51", context = "nrf52") => {
+            pac::POWER.systemoff().write(|w| w.set_systemoff(true));
+        }
+        any(context = "nrf5340-app", context = "nrf91") => {
+            pac::REGULATORS.systemoff().write(|w| w.set_systemoff(true));
+        }
+        _ => {}
+    }
+
+    // System off is only emulated when in debug interface mode, in which case execution
+    // continues.
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+fn enable_pin_sense(wake_pin: WakePin, edge: WakeEdge) {
+    let sense = match edge {
+        WakeEdge::Rising => pac::gpio::vals::Sense::HIGH,
+        WakeEdge::Falling => pac::gpio::vals::Sense::LOW,
+    };
+
+    let port = cfg_select! {
+        any(context = "nrf52833", context = "nrf52840", context = "nrf53") => {
+            if matches!(wake_pin.port, Port::Port1) { pac::P1 } else { pac::P0 }
+        }
+        _ => { pac::P0 }
+    };
+
+    port.pin_cnf(usize::from(wake_pin.pin))
+        .modify(|w| w.set_sense(sense));
+}
+
+#[cfg(any(context = "nrf51", context = "nrf52"))]
+fn enable_lpcomp(lpcomp: Lpcomp) {
+    // Values of the ANADETECT register.
+    const UP: u32 = 1;
+    const DOWN: u32 = 2;
+
+    let lpcomp_regs = pac::LPCOMP;
+    lpcomp_regs.psel().write(|w| w.0 = u32::from(lpcomp.input));
+    lpcomp_regs
+        .refsel()
+        .write(|w| w.0 = u32::from(lpcomp.reference_eighths - 1));
+    lpcomp_regs.anadetect().write(|w| {
+        w.0 = match lpcomp.edge {
+            WakeEdge::Rising => UP,
+            WakeEdge::Falling => DOWN,
+        }
+    });
+    lpcomp_regs.enable().write(|w| w.0 = 1);
+    lpcomp_regs.tasks_start().write_value(1);
+}
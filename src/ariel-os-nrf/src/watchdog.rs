@@ -0,0 +1,47 @@
+use embassy_nrf::wdt::{self, WatchdogHandle};
+
+// NOTE(hal): the watchdog counter is clocked from the 32.768 kHz low-frequency clock.
+const CLOCK_HZ: u32 = 32_768;
+
+/// Longest supported timeout, in milliseconds.
+// The reload value is 32-bit wide.
+pub const MAX_TIMEOUT_MS: u32 = 131_071_999;
+
+/// Hardware watchdog, started by [`init()`].
+pub struct Watchdog {
+    handle: WatchdogHandle,
+}
+
+impl Watchdog {
+    /// Feeds the watchdog, restarting its timeout.
+    pub fn feed(&mut self) {
+        self.handle.pet();
+    }
+}
+
+/// Starts the watchdog with a timeout of `timeout_ms` milliseconds.
+///
+/// The timeout is capped to [`MAX_TIMEOUT_MS`].
+///
+/// # Panics
+///
+/// Panics if the watchdog peripheral has been previously taken, or if the watchdog is already
+/// running with a different configuration (it cannot be stopped once started, and survives
+/// soft resets).
+pub fn init(peripherals: &mut crate::OptionalPeripherals, timeout_ms: u32) -> Watchdog {
+    let wdt = cfg_select! {
+        context = "nrf5340-app" => { peripherals.WDT0.take().unwrap() }
+        _ => { peripherals.WDT.take().unwrap() }
+    };
+
+    let mut config = wdt::Config::default();
+    let timeout_ms = timeout_ms.min(MAX_TIMEOUT_MS);
+    // Does not overflow as the timeout is capped.
+    config.timeout_ticks = timeout_ms / 1000 * CLOCK_HZ + timeout_ms % 1000 * CLOCK_HZ / 1000;
+
+    let Ok((_watchdog, [handle])) = wdt::Watchdog::try_new(wdt, config) else {
+        panic!("the watchdog is already running with a different configuration");
+    };
+
+    Watchdog { handle }
+}
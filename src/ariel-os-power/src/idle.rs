@@ -0,0 +1,141 @@
+use core::cell::Cell;
+
+use critical_section::Mutex;
+
+/// Mode entered by the MCU when idle, i.e., when no thread or task is ready to run.
+///
+/// Modes are ordered from the shallowest to the deepest.
+/// Deeper modes consume less power, at the cost of a longer wake-up latency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum IdleMode {
+    /// The CPU is stopped, and resumes execution with a constant, minimal latency.
+    ///
+    /// On nRF, this keeps the system in its constant latency sub-mode.
+    Sleep,
+    /// The CPU is stopped, and parts of the system may be powered down while idle.
+    ///
+    /// On nRF, this is the low-power sub-mode.
+    /// On Cortex-M MCUs other than STM32, the CPU enters its deep sleep state; on RP, this
+    /// additionally stops the clocks not enabled in the `SLEEP_EN` registers.
+    /// On STM32, deep sleep would enter stop mode, in which the timer of the time driver is not
+    /// clocked, so this is currently the same as [`IdleMode::Sleep`], as on other architectures.
+    DeepSleep,
+}
+
+impl IdleMode {
+    const ALL: [Self; 2] = [Self::Sleep, Self::DeepSleep];
+    const COUNT: usize = Self::ALL.len();
+    const DEEPEST: Self = Self::DeepSleep;
+}
+
+// Number of active constraints for each mode.
+static CONSTRAINTS: Mutex<Cell<[usize; IdleMode::COUNT]>> =
+    Mutex::new(Cell::new([0; IdleMode::COUNT]));
+
+/// Constrains the idle mode, obtained with [`constrain_idle_mode()`].
+///
+/// The constraint is lifted when this is dropped.
+#[must_use = "the constraint is lifted when dropped"]
+#[derive(Debug)]
+pub struct IdleConstraint {
+    deepest: IdleMode,
+}
+
+impl Drop for IdleConstraint {
+    fn drop(&mut self) {
+        update_constraints(self.deepest, |count| count - 1);
+    }
+}
+
+/// Prevents the MCU from entering an idle mode deeper than `deepest`, until the returned
+/// [`IdleConstraint`] is dropped.
+///
+/// This is intended for drivers that cannot cope with the wake-up latency of deeper modes while
+/// they are active, e.g., when they need to service interrupts quickly.
+/// When multiple constraints are active, the shallowest one applies.
+pub fn constrain_idle_mode(deepest: IdleMode) -> IdleConstraint {
+    update_constraints(deepest, |count| count + 1);
+    IdleConstraint { deepest }
+}
+
+/// Returns the deepest idle mode currently allowed.
+#[must_use]
+pub fn idle_mode() -> IdleMode {
+    critical_section::with(|cs| allowed_mode(&CONSTRAINTS.borrow(cs).get()))
+}
+
+fn update_constraints(mode: IdleMode, f: impl FnOnce(usize) -> usize) {
+    critical_section::with(|cs| {
+        let cell = CONSTRAINTS.borrow(cs);
+        let mut constraints = cell.get();
+        let previous = allowed_mode(&constraints);
+
+        if let Some(count) = constraints.get_mut(mode as usize) {
+            *count = f(*count);
+        }
+        cell.set(constraints);
+
+        let allowed = allowed_mode(&constraints);
+        if allowed != previous {
+            apply(allowed);
+        }
+    });
+}
+
+/// Returns the shallowest constrained mode.
+fn allowed_mode(constraints: &[usize; IdleMode::COUNT]) -> IdleMode {
+    IdleMode::ALL
+        .into_iter()
+        .zip(constraints)
+        .find(|(_, count)| **count > 0)
+        .map_or(IdleMode::DEEPEST, |(mode, _)| mode)
+}
+
+/// Configures the CPU to enter the deepest idle mode currently allowed on its next wait for
+/// interrupt.
+///
+/// This is called by the system right before halting the CPU when no thread or task is ready to
+/// run.
+#[doc(hidden)]
+pub fn prepare_idle() {
+    let mode = idle_mode();
+
+    cfg_select! {
+        all(context = "cortex-m", not(context = "stm32")) => {
+            #[expect(unsafe_code)]
+            // SAFETY: only the SLEEPDEEP bit is modified, which is otherwise only set when
+            // entering system off.
+            let mut scb = unsafe { cortex_m::Peripherals::steal() }.SCB;
+            match mode {
+                IdleMode::Sleep => scb.clear_sleepdeep(),
+                IdleMode::DeepSleep => scb.set_sleepdeep(),
+            }
+        }
+        _ => {
+            let _ = mode;
+        }
+    }
+}
+
+/// Applies the idle mode allowed at startup.
+pub(crate) fn init() {
+    apply(idle_mode());
+}
+
+/// Configures the hardware for `mode`.
+fn apply(mode: IdleMode) {
+    cfg_select! {
+        context = "nrf" => {
+            use embassy_nrf::pac;
+
+            match mode {
+                IdleMode::Sleep => pac::POWER.tasks_constlat().write_value(1),
+                IdleMode::DeepSleep => pac::POWER.tasks_lowpwr().write_value(1),
+            }
+        }
+        _ => {
+            let _ = mode;
+        }
+    }
+}
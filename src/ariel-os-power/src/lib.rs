@@ -3,6 +3,35 @@
 #![deny(missing_docs)]
 #![cfg_attr(not(context = "native"), no_std)]
 
+mod idle;
+mod noinit;
 mod reset;
+pub mod retained;
+mod watchdog;
 
+pub use idle::*;
 pub use reset::*;
+pub use watchdog::*;
+
+/// Latches the records retained from the previous boot.
+///
+/// This is called once by the system at startup.
+#[doc(hidden)]
+pub fn init() {
+    #[cfg(context = "native")]
+    {
+        noinit::load();
+
+        // There is no panic handler to hook into on native.
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(std::boxed::Box::new(move |info| {
+            record_panic();
+            hook(info);
+        }));
+    }
+
+    watchdog::latch();
+    reset::latch();
+    retained::latch();
+    idle::init();
+}
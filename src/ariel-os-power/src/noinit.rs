@@ -0,0 +1,188 @@
+//! Provides a small RAM region that is not initialized at boot, and therefore retains its
+//! contents across resets (but not across power losses).
+//!
+//! The region is split into records, each protected by a magic number and a checksum, as its
+//! contents are arbitrary after a power loss.
+
+#![expect(unsafe_code)]
+
+use crate::retained::SIZE as APPLICATION_DATA_SIZE;
+
+/// Record of the watchdog client that starved the watchdog.
+pub(crate) const WATCHDOG: Record = Record::new(0, 6, 0x5744_4f47); // "WDOG"
+/// Record of the reset reason, when known by software.
+pub(crate) const RESET_REASON: Record = Record::new(WATCHDOG.end(), 1, 0x5253_5452); // "RSTR"
+/// Record of the data retained by the application, prefixed by its length in bytes.
+pub(crate) const APPLICATION: Record = Record::new(
+    RESET_REASON.end(),
+    1 + APPLICATION_DATA_SIZE / 4,
+    0x4150_5044, // "APPD"
+);
+
+/// Number of 32-bit words in the retained region.
+const WORDS: usize = APPLICATION.end();
+
+cfg_select! {
+    context = "cortex-m" => {
+        // `.uninit` is a NOLOAD output section provided by the `cortex-m-rt` linker script.
+        #[unsafe(link_section = ".uninit.ariel-os-power.retained")]
+        static mut RETAINED: core::mem::MaybeUninit<[u32; WORDS]> =
+            core::mem::MaybeUninit::uninit();
+    }
+    context = "esp" => {
+        #[esp_hal::ram(unstable(rtc_fast, persistent))]
+        static mut RETAINED: [u32; WORDS] = [0; WORDS];
+    }
+    _ => {
+        // On native, the contents are persisted to a file instead, see `load()`.
+        static mut RETAINED: [u32; WORDS] = [0; WORDS];
+    }
+}
+
+/// Reads word `index` of the retained region.
+///
+/// # Panics
+///
+/// Panics if `index` is out of bounds.
+fn read(index: usize) -> u32 {
+    assert!(index < WORDS, "retained word index out of bounds");
+    let base = (&raw const RETAINED).cast::<u32>();
+    // SAFETY: `index` is in bounds, and any bit pattern left in memory is a valid `u32`. The
+    // region is only accessed through volatile word-sized operations, so the compiler does not
+    // assume anything about its contents.
+    unsafe { base.add(index).read_volatile() }
+}
+
+/// Writes `value` to word `index` of the retained region.
+///
+/// # Panics
+///
+/// Panics if `index` is out of bounds.
+fn write(index: usize, value: u32) {
+    assert!(index < WORDS, "retained word index out of bounds");
+    let base = (&raw mut RETAINED).cast::<u32>();
+    // SAFETY: `index` is in bounds; see `read()`.
+    unsafe { base.add(index).write_volatile(value) }
+}
+
+/// A record in the retained region, made of a magic number, a checksum, and a payload.
+pub(crate) struct Record {
+    offset: usize,
+    len: usize,
+    magic: u32,
+}
+
+impl Record {
+    /// Returns a record starting at word `offset`, with a payload of `len` words.
+    const fn new(offset: usize, len: usize, magic: u32) -> Self {
+        Self { offset, len, magic }
+    }
+
+    /// Returns the index of the word following the record.
+    const fn end(&self) -> usize {
+        self.payload_offset() + self.len
+    }
+
+    const fn payload_offset(&self) -> usize {
+        self.offset + 2
+    }
+
+    /// Writes `payload` to the record, padding it with zeros, and makes it valid.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `payload` is larger than the record.
+    pub(crate) fn write(&self, payload: &[u32]) {
+        assert!(payload.len() <= self.len, "retained record payload too large");
+
+        critical_section::with(|_| {
+            // Invalidate the record while it is being written.
+            write(self.offset, 0);
+            for index in 0..self.len {
+                write(
+                    self.payload_offset() + index,
+                    payload.get(index).copied().unwrap_or_default(),
+                );
+            }
+            write(self.offset + 1, self.checksum());
+            write(self.offset, self.magic);
+
+            persist();
+        });
+    }
+
+    /// Copies the payload of the record to `payload` if the record is valid.
+    ///
+    /// Returns whether the record is valid.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `payload` is larger than the record.
+    pub(crate) fn read(&self, payload: &mut [u32]) -> bool {
+        assert!(payload.len() <= self.len, "retained record payload too large");
+
+        critical_section::with(|_| {
+            if read(self.offset) != self.magic || read(self.offset + 1) != self.checksum() {
+                return false;
+            }
+            for (index, word) in payload.iter_mut().enumerate() {
+                *word = read(self.payload_offset() + index);
+            }
+            true
+        })
+    }
+
+    /// Makes the record invalid.
+    pub(crate) fn invalidate(&self) {
+        critical_section::with(|_| {
+            write(self.offset, 0);
+
+            persist();
+        });
+    }
+
+    fn checksum(&self) -> u32 {
+        (0..self.len).fold(self.magic, |acc, index| {
+            acc.rotate_left(5) ^ read(self.payload_offset() + index)
+        })
+    }
+}
+
+/// Loads the retained region from the file emulating it.
+#[cfg(context = "native")]
+pub(crate) fn load() {
+    let Ok(contents) = std::fs::read(file_path()) else {
+        return;
+    };
+    for (index, word) in contents.as_chunks::<4>().0.iter().take(WORDS).enumerate() {
+        write(index, u32::from_le_bytes(*word));
+    }
+}
+
+/// Persists the retained region to the file emulating it.
+#[cfg(context = "native")]
+fn persist() {
+    let contents: std::vec::Vec<u8> = (0..WORDS).flat_map(|index| read(index).to_le_bytes()).collect();
+    if let Err(err) = std::fs::write(file_path(), contents) {
+        std::eprintln!("could not persist the retained RAM region: {err}");
+    }
+}
+
+#[cfg(not(context = "native"))]
+fn persist() {}
+
+/// Returns the path of the file emulating the retained region.
+///
+/// This can be set with the `ARIEL_NATIVE_RETAINED_FILE` environment variable, and defaults to
+/// the path of the executable with a `.retained` extension.
+#[cfg(context = "native")]
+fn file_path() -> std::path::PathBuf {
+    std::env::var_os("ARIEL_NATIVE_RETAINED_FILE").map_or_else(
+        || {
+            std::env::current_exe()
+                .unwrap_or_default()
+                .with_extension("retained")
+        },
+        std::path::PathBuf::from,
+    )
+}
@@ -1,7 +1,71 @@
+use core::cell::Cell;
+
+use critical_section::Mutex;
+
+use crate::noinit;
+
+/// Reason of the last reset of the MCU, as returned by [`reset_reason()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum ResetReason {
+    /// The MCU has been powered on.
+    ///
+    /// Some MCUs cannot distinguish a power-on from a brownout, in which case this is reported.
+    PowerOn,
+    /// The supply voltage dropped below the brownout threshold.
+    Brownout,
+    /// The reset pin has been asserted.
+    Pin,
+    /// The hardware watchdog expired.
+    Watchdog,
+    /// The firmware requested a reset, e.g., with [`reboot()`].
+    Software,
+    /// The firmware panicked during the previous boot.
+    Panic,
+    /// The CPU locked up.
+    Lockup,
+    /// The MCU woke up from a low-power mode in which RAM is not retained.
+    WakeUp,
+    /// The reason could not be determined.
+    Unknown,
+}
+
+// Reset reasons that the hardware cannot report, recorded in retained RAM instead.
+const SOFTWARE: u32 = 1;
+const PANIC: u32 = 2;
+const WAKE_UP: u32 = 3;
+
+static RESET_REASON: Mutex<Cell<ResetReason>> = Mutex::new(Cell::new(ResetReason::Unknown));
+
+/// Returns the reason of the last reset of the MCU.
+///
+/// A reset caused by the watchdog supervision is reported as [`ResetReason::Watchdog`] even on
+/// platforms where the hardware does not report it.
+/// Panics are reported as [`ResetReason::Panic`], regardless of how the MCU was reset after the
+/// panic.
+#[must_use]
+pub fn reset_reason() -> ResetReason {
+    critical_section::with(|cs| RESET_REASON.borrow(cs).get())
+}
+
 /// Reboots the MCU.
 ///
 /// This function initiates a software reset of the microcontroller and never returns.
 pub fn reboot() -> ! {
+    noinit::RESET_REASON.write(&[SOFTWARE]);
+    reset()
+}
+
+/// Resets the MCU after it has been woken up from a low-power mode it cannot resume from, so that
+/// the reset gets reported as [`ResetReason::WakeUp`].
+#[doc(hidden)]
+pub fn reset_after_wake_up() -> ! {
+    noinit::RESET_REASON.write(&[WAKE_UP]);
+    reset()
+}
+
+fn reset() -> ! {
     cfg_select! {
         context = "cortex-m" => {
             cortex_m::peripheral::SCB::sys_reset()
@@ -21,3 +85,243 @@ pub fn reboot() -> ! {
         }
     }
 }
+
+/// Records that the firmware panicked, so that it gets reported after the upcoming reset.
+#[doc(hidden)]
+pub fn record_panic() {
+    noinit::RESET_REASON.write(&[PANIC]);
+}
+
+/// Determines the reason of the last reset, and clears the hardware reset flags so that they do
+/// not accumulate across resets.
+pub(crate) fn latch() {
+    let hardware_reason = take_hardware_reset_reason();
+
+    let mut payload = [0];
+    let recorded = noinit::RESET_REASON.read(&mut payload);
+    noinit::RESET_REASON.invalidate();
+
+    let reason = match payload {
+        [PANIC] if recorded => ResetReason::Panic,
+        [SOFTWARE] if recorded => ResetReason::Software,
+        [WAKE_UP] if recorded => ResetReason::WakeUp,
+        _ if crate::watchdog::has_starved() => ResetReason::Watchdog,
+        _ => hardware_reason,
+    };
+
+    critical_section::with(|cs| RESET_REASON.borrow(cs).set(reason));
+}
+
+#[cfg(context = "nrf")]
+fn take_hardware_reset_reason() -> ResetReason {
+    use embassy_nrf::pac;
+
+    // Reset flags, by decreasing priority.
+    cfg_select! {
+        any(context = "nrf51", context = "nrf52") => {
+            const FLAGS: &[(u32, ResetReason)] = &[
+                (1 << 1, ResetReason::Watchdog),
+                (1 << 3, ResetReason::Lockup),
+                (1 << 2, ResetReason::Software),
+                // OFF, LPCOMP, DIF, NFC, and VBUS.
+                (0b1_1111 << 16, ResetReason::WakeUp),
+                (1 << 0, ResetReason::Pin),
+            ];
+            let resetreas = pac::POWER.resetreas();
+        }
+        context = "nrf53" => {
+            const FLAGS: &[(u32, ResetReason)] = &[
+                // DOG0 and DOG1.
+                (1 << 1 | 1 << 25, ResetReason::Watchdog),
+                (1 << 4, ResetReason::Lockup),
+                (1 << 3, ResetReason::Software),
+                // OFF, LPCOMP, DIF, NFC, and VBUS.
+                (0b111 << 5 | 1 << 24 | 1 << 26, ResetReason::WakeUp),
+                (1 << 0, ResetReason::Pin),
+            ];
+            let resetreas = pac::RESET.resetreas();
+        }
+        context = "nrf91" => {
+            const FLAGS: &[(u32, ResetReason)] = &[
+                (1 << 1, ResetReason::Watchdog),
+                (1 << 5, ResetReason::Lockup),
+                (1 << 4, ResetReason::Software),
+                // OFF and DIF.
+                (0b11 << 2, ResetReason::WakeUp),
+                (1 << 0, ResetReason::Pin),
+            ];
+            let resetreas = pac::POWER.resetreas();
+        }
+    }
+
+    let flags = resetreas.read().0;
+    // The flags are cleared by writing ones.
+    resetreas.write(|w| w.0 = flags);
+
+    if flags == 0 {
+        // NOTE(hal): brownouts are not distinguished from power-on resets.
+        return ResetReason::PowerOn;
+    }
+    decode_flags(flags, FLAGS)
+}
+
+#[cfg(context = "rp")]
+fn take_hardware_reset_reason() -> ResetReason {
+    use embassy_rp::pac;
+
+    // These registers are cleared by the next reset, there is no need to clear them.
+    let watchdog = pac::WATCHDOG.reason().read();
+    if watchdog.timer() {
+        return ResetReason::Watchdog;
+    }
+    if watchdog.force() {
+        return ResetReason::Software;
+    }
+
+    cfg_select! {
+        context = "rp2040" => {
+            let chip_reset = pac::VREG_AND_CHIP_RESET.chip_reset().read();
+            if chip_reset.had_por() {
+                // NOTE(hal): brownouts are not distinguished from power-on resets.
+                ResetReason::PowerOn
+            } else if chip_reset.had_run() {
+                ResetReason::Pin
+            } else {
+                ResetReason::Unknown
+            }
+        }
+        context = "rp235xa" => {
+            let chip_reset = pac::POWMAN.chip_reset().read();
+            if chip_reset.had_por() {
+                ResetReason::PowerOn
+            } else if chip_reset.had_bor() {
+                ResetReason::Brownout
+            } else if chip_reset.had_run_low() {
+                ResetReason::Pin
+            } else {
+                ResetReason::Unknown
+            }
+        }
+    }
+}
+
+#[cfg(context = "stm32")]
+fn take_hardware_reset_reason() -> ResetReason {
+    use embassy_stm32::pac;
+
+    // Reset flags, by decreasing priority.
+    // NOTE(hal): the reset pin flag is also set by every internal reset source, so it must come
+    // last.
+    cfg_select! {
+        any(context = "stm32f042k6", context = "stm32f303cb", context = "stm32f303re") => {
+            const RMVF: u32 = 1 << 24;
+            const FLAGS: &[(u32, ResetReason)] = &[
+                (1 << 27, ResetReason::PowerOn),
+                // IWDG and WWDG.
+                (0b11 << 29, ResetReason::Watchdog),
+                (1 << 28, ResetReason::Software),
+                (1 << 26, ResetReason::Pin),
+            ];
+            let csr = pac::RCC.csr();
+        }
+        any(context = "stm32f401re", context = "stm32f411re", context = "stm32f767zi") => {
+            const RMVF: u32 = 1 << 24;
+            const FLAGS: &[(u32, ResetReason)] = &[
+                // The brownout flag is also set on power-on.
+                (1 << 27, ResetReason::PowerOn),
+                (1 << 25, ResetReason::Brownout),
+                // IWDG and WWDG.
+                (0b11 << 29, ResetReason::Watchdog),
+                (1 << 28, ResetReason::Software),
+                (1 << 26, ResetReason::Pin),
+            ];
+            let csr = pac::RCC.csr();
+        }
+        any(context = "stm32h753zi", context = "stm32h755zi") => {
+            const RMVF: u32 = 1 << 16;
+            const FLAGS: &[(u32, ResetReason)] = &[
+                // The brownout flag is also set on power-on.
+                (1 << 23, ResetReason::PowerOn),
+                (1 << 21, ResetReason::Brownout),
+                // IWDG1, IWDG2, WWDG1, and WWDG2.
+                (0b1111 << 26, ResetReason::Watchdog),
+                // Requested by either core.
+                (0b11 << 24, ResetReason::Software),
+                (1 << 22, ResetReason::Pin),
+            ];
+            let csr = pac::RCC.rsr();
+        }
+        any(
+            context = "stm32c031c6",
+            context = "stm32g431rb",
+            context = "stm32l475vg",
+            context = "stm32u073kc",
+            context = "stm32u083mc",
+            context = "stm32u585ai",
+            context = "stm32wb55rg",
+            context = "stm32wba55cg",
+            context = "stm32wba65ri",
+            context = "stm32wle5jc",
+        ) => {
+            const RMVF: u32 = 1 << 23;
+            const FLAGS: &[(u32, ResetReason)] = &[
+                // NOTE(hal): power-on and brownout resets share the same flag.
+                (1 << 27, ResetReason::PowerOn),
+                // IWDG and WWDG.
+                (0b11 << 29, ResetReason::Watchdog),
+                (1 << 28, ResetReason::Software),
+                (1 << 26, ResetReason::Pin),
+            ];
+            let csr = cfg_select! {
+                context = "stm32c031c6" => { pac::RCC.csr2() }
+                _ => { pac::RCC.csr() }
+            };
+        }
+    }
+
+    let flags = csr.read().0;
+    csr.modify(|w| w.0 |= RMVF);
+
+    decode_flags(flags, FLAGS)
+}
+
+#[cfg(context = "esp")]
+fn take_hardware_reset_reason() -> ResetReason {
+    use esp_hal::{rtc_cntl, system::Cpu};
+
+    // The variants differ between chips, but their values are shared.
+    match rtc_cntl::reset_reason(Cpu::ProCpu).map(|reason| reason as u32) {
+        Some(0x01) => ResetReason::PowerOn,
+        Some(0x03 | 0x0c) => ResetReason::Software,
+        Some(0x05) => ResetReason::WakeUp,
+        Some(0x07..=0x09 | 0x0b | 0x0d | 0x10..=0x12) => ResetReason::Watchdog,
+        Some(0x0f) => ResetReason::Brownout,
+        _ => ResetReason::Unknown,
+    }
+}
+
+#[cfg(context = "native")]
+fn take_hardware_reset_reason() -> ResetReason {
+    // Anything else than a new process has been recorded in the emulated retained RAM.
+    ResetReason::PowerOn
+}
+
+#[cfg(not(any(
+    context = "nrf",
+    context = "rp",
+    context = "stm32",
+    context = "esp",
+    context = "native"
+)))]
+fn take_hardware_reset_reason() -> ResetReason {
+    ResetReason::Unknown
+}
+
+/// Returns the reason associated with the first of `reasons` whose mask matches `flags`.
+#[cfg(any(context = "nrf", context = "stm32"))]
+fn decode_flags(flags: u32, reasons: &[(u32, ResetReason)]) -> ResetReason {
+    reasons
+        .iter()
+        .find(|(mask, _)| flags & mask != 0)
+        .map_or(ResetReason::Unknown, |(_, reason)| *reason)
+}
@@ -0,0 +1,134 @@
+//! Provides data retained across resets.
+//!
+//! An application can [store](store()) a small amount of data, for instance a breadcrumb
+//! describing what it was doing, which is then available during the next boot only, with
+//! [`previous()`].
+//! The size of this data is configured with `CONFIG_POWER_RETAINED_DATA_SIZE`, in bytes.
+//!
+//! The data is retained in RAM that is not initialized at boot, and is therefore lost on power
+//! loss, as well as on resets that do not retain RAM (e.g., waking up from system off on some
+//! MCUs).
+//! On native, the data is retained in a file, which is named after the executable with a
+//! `.retained` extension unless `ARIEL_NATIVE_RETAINED_FILE` is set.
+
+use core::cell::Cell;
+
+use critical_section::Mutex;
+
+use crate::noinit;
+
+/// Maximum size in bytes of the retained data.
+pub const SIZE: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_POWER_RETAINED_DATA_SIZE",
+    32,
+    "size of the data retained across resets, in bytes"
+);
+
+const _: () = assert!(SIZE.is_multiple_of(4), "the retained data size must be a multiple of 4");
+
+const WORDS: usize = 1 + SIZE / 4;
+
+static PREVIOUS: Mutex<Cell<Option<RetainedData>>> = Mutex::new(Cell::new(None));
+
+/// Errors returned when storing retained data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The data is larger than [`SIZE`].
+    TooLarge,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::TooLarge => write!(f, "retained data too large"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// Data retained from the previous boot, obtained with [`previous()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetainedData {
+    data: [u8; SIZE],
+    len: usize,
+}
+
+impl RetainedData {
+    /// Returns the retained data.
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        self.data.get(..self.len).unwrap_or_default()
+    }
+}
+
+/// Stores `data` so that it is available during the next boot.
+///
+/// This replaces data previously stored during the current boot.
+///
+/// # Errors
+///
+/// Returns [`Error::TooLarge`] if `data` is larger than [`SIZE`].
+pub fn store(data: &[u8]) -> Result<(), Error> {
+    if data.len() > SIZE {
+        return Err(Error::TooLarge);
+    }
+
+    let mut payload = [0; WORDS];
+    let [len, words @ ..] = &mut payload;
+    // Cannot truncate as the length is at most `SIZE`.
+    #[expect(clippy::cast_possible_truncation)]
+    let data_len = data.len() as u32;
+    *len = data_len;
+    let (chunks, remainder) = data.as_chunks::<4>();
+    let mut last = [0; 4];
+    for (dst, src) in last.iter_mut().zip(remainder) {
+        *dst = *src;
+    }
+    for (word, chunk) in words.iter_mut().zip(chunks.iter().chain([&last])) {
+        *word = u32::from_le_bytes(*chunk);
+    }
+
+    noinit::APPLICATION.write(&payload);
+
+    Ok(())
+}
+
+/// Clears the data stored during the current boot, if any.
+pub fn clear() {
+    noinit::APPLICATION.invalidate();
+}
+
+/// Returns the data stored during the previous boot, if any.
+#[must_use]
+pub fn previous() -> Option<RetainedData> {
+    critical_section::with(|cs| PREVIOUS.borrow(cs).get())
+}
+
+/// Moves the data retained from the previous boot, if valid, to [`previous()`].
+pub(crate) fn latch() {
+    let previous = read_retained();
+    noinit::APPLICATION.invalidate();
+
+    critical_section::with(|cs| PREVIOUS.borrow(cs).set(previous));
+}
+
+fn read_retained() -> Option<RetainedData> {
+    let mut payload = [0; WORDS];
+    if !noinit::APPLICATION.read(&mut payload) {
+        return None;
+    }
+
+    let [len, words @ ..] = payload;
+    let len = usize::try_from(len).ok()?;
+    if len > SIZE {
+        return None;
+    }
+
+    let mut data = [0; SIZE];
+    for (chunk, word) in data.as_chunks_mut::<4>().0.iter_mut().zip(words) {
+        *chunk = word.to_le_bytes();
+    }
+
+    Some(RetainedData { data, len })
+}
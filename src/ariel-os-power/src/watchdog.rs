@@ -0,0 +1,138 @@
+use core::cell::Cell;
+
+use critical_section::Mutex;
+
+use crate::noinit;
+
+/// Maximum length in bytes of the client name retained across a watchdog reset.
+///
+/// Longer names are truncated.
+pub const MAX_WATCHDOG_CLIENT_NAME_LEN: usize = 16;
+
+// Layout of the payload of the retained record, in words.
+const THREAD_ID_WORD: usize = 0;
+const NAME_LEN_WORD: usize = 1;
+const NAME_WORDS: core::ops::Range<usize> = 2..6;
+const PAYLOAD_WORDS: usize = 6;
+
+const _: () = assert!(NAME_WORDS.end == PAYLOAD_WORDS);
+const _: () = assert!(NAME_WORDS.end - NAME_WORDS.start == MAX_WATCHDOG_CLIENT_NAME_LEN / 4);
+
+const NO_THREAD_ID: u32 = u32::MAX;
+
+static LAST_STARVATION: Mutex<Cell<Option<WatchdogStarvation>>> = Mutex::new(Cell::new(None));
+
+/// Identifies the watchdog client that missed its deadline, causing a watchdog reset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WatchdogStarvation {
+    name: [u8; MAX_WATCHDOG_CLIENT_NAME_LEN],
+    name_len: usize,
+    thread_id: Option<usize>,
+}
+
+impl WatchdogStarvation {
+    /// Returns the name of the client, truncated to [`MAX_WATCHDOG_CLIENT_NAME_LEN`] bytes.
+    #[must_use]
+    pub fn client_name(&self) -> &str {
+        self.name
+            .get(..self.name_len)
+            .and_then(|name| core::str::from_utf8(name).ok())
+            .unwrap_or_default()
+    }
+
+    /// Returns the id of the thread the client was registered from, if any.
+    #[must_use]
+    pub fn thread_id(&self) -> Option<usize> {
+        self.thread_id
+    }
+}
+
+/// Returns the watchdog client that starved the watchdog before the last reset, if any.
+///
+/// A client is only reported if the starvation was recorded during the previous boot.
+#[must_use]
+pub fn last_watchdog_starvation() -> Option<WatchdogStarvation> {
+    critical_section::with(|cs| LAST_STARVATION.borrow(cs).get())
+}
+
+/// Records that the client named `client_name` is starving the watchdog, so that it gets
+/// reported after the upcoming reset.
+#[doc(hidden)]
+pub fn record_watchdog_starvation(client_name: &str, thread_id: Option<usize>) {
+    // Truncate on a character boundary so that the name stays valid UTF-8.
+    let mut name_len = client_name.len().min(MAX_WATCHDOG_CLIENT_NAME_LEN);
+    while !client_name.is_char_boundary(name_len) {
+        name_len -= 1;
+    }
+
+    let mut name = [0; MAX_WATCHDOG_CLIENT_NAME_LEN];
+    for (dst, src) in name.iter_mut().zip(client_name.bytes().take(name_len)) {
+        *dst = src;
+    }
+
+    let mut payload = [0; PAYLOAD_WORDS];
+    if let Some(word) = payload.get_mut(THREAD_ID_WORD) {
+        *word = thread_id.map_or(NO_THREAD_ID, |id| u32::try_from(id).unwrap_or(NO_THREAD_ID));
+    }
+    if let Some(word) = payload.get_mut(NAME_LEN_WORD) {
+        // Cannot truncate as the length is at most `MAX_WATCHDOG_CLIENT_NAME_LEN`.
+        #[expect(clippy::cast_possible_truncation)]
+        let name_len = name_len as u32;
+        *word = name_len;
+    }
+    for (word, chunk) in payload
+        .iter_mut()
+        .skip(NAME_WORDS.start)
+        .zip(name.as_chunks::<4>().0)
+    {
+        *word = u32::from_le_bytes(*chunk);
+    }
+
+    noinit::WATCHDOG.write(&payload);
+}
+
+/// Moves the record retained from the previous boot, if valid, to [`last_watchdog_starvation()`].
+pub(crate) fn latch() {
+    let starvation = read_retained();
+    noinit::WATCHDOG.invalidate();
+
+    critical_section::with(|cs| LAST_STARVATION.borrow(cs).set(starvation));
+}
+
+/// Returns whether a watchdog starvation was recorded during the previous boot.
+pub(crate) fn has_starved() -> bool {
+    last_watchdog_starvation().is_some()
+}
+
+fn read_retained() -> Option<WatchdogStarvation> {
+    let mut payload = [0; PAYLOAD_WORDS];
+    if !noinit::WATCHDOG.read(&mut payload) {
+        return None;
+    }
+
+    let name_len = usize::try_from(*payload.get(NAME_LEN_WORD)?).ok()?;
+    if name_len > MAX_WATCHDOG_CLIENT_NAME_LEN {
+        return None;
+    }
+
+    let mut name = [0; MAX_WATCHDOG_CLIENT_NAME_LEN];
+    for (chunk, word) in name
+        .as_chunks_mut::<4>()
+        .0
+        .iter_mut()
+        .zip(payload.iter().skip(NAME_WORDS.start))
+    {
+        *chunk = word.to_le_bytes();
+    }
+
+    let thread_id = match *payload.get(THREAD_ID_WORD)? {
+        NO_THREAD_ID => None,
+        id => usize::try_from(id).ok(),
+    };
+
+    Some(WatchdogStarvation {
+        name,
+        name_len,
+        thread_id,
+    })
+}
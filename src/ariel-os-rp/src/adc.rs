@@ -0,0 +1,243 @@
+//! Provides support for the ADC.
+
+#![expect(unsafe_code)]
+
+use portable_atomic::{AtomicBool, Ordering};
+
+use ariel_os_embassy_common::adc::{Error, Sample, software_oversample};
+use embassy_rp::{
+    adc::{self, Async, InterruptHandler},
+    bind_interrupts,
+    gpio::Pull,
+    peripherals,
+};
+
+#[doc(hidden)]
+pub use embassy_rp::adc::AdcPin;
+
+// NOTE(hal): the ADC always converts with 12 bits, lower resolutions are obtained by discarding
+// the least significant bits.
+const HW_RESOLUTION_BITS: u8 = 12;
+
+/// ADC configuration.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// Resolution of the conversions.
+    pub resolution: ariel_os_embassy_common::adc::Resolution<Resolution>,
+    /// Oversampling ratio, carried out in software.
+    pub oversampling: ariel_os_embassy_common::adc::Oversampling<Oversampling>,
+    /// Voltage reference.
+    pub reference: ariel_os_embassy_common::adc::Reference<Reference>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            resolution: ariel_os_embassy_common::adc::Resolution::Bits12,
+            oversampling: ariel_os_embassy_common::adc::Oversampling::None,
+            reference: ariel_os_embassy_common::adc::Reference::Vdd,
+        }
+    }
+}
+
+/// ADC resolution.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Resolution {
+    /// 8-bit resolution.
+    Bits8,
+    /// 10-bit resolution.
+    Bits10,
+    /// 12-bit resolution.
+    Bits12,
+}
+
+impl From<Resolution> for u8 {
+    fn from(resolution: Resolution) -> u8 {
+        match resolution {
+            Resolution::Bits8 => 8,
+            Resolution::Bits10 => 10,
+            Resolution::Bits12 => 12,
+        }
+    }
+}
+
+impl From<ariel_os_embassy_common::adc::Resolution<Self>> for Resolution {
+    fn from(resolution: ariel_os_embassy_common::adc::Resolution<Self>) -> Self {
+        match resolution {
+            ariel_os_embassy_common::adc::Resolution::Hal(resolution) => resolution,
+            ariel_os_embassy_common::adc::Resolution::Bits8 => Self::Bits8,
+            ariel_os_embassy_common::adc::Resolution::Bits10 => Self::Bits10,
+            ariel_os_embassy_common::adc::Resolution::Bits12 => Self::Bits12,
+        }
+    }
+}
+
+/// ADC oversampling ratio, carried out in software.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Oversampling {
+    /// No oversampling.
+    None,
+    /// 2× oversampling.
+    X2,
+    /// 4× oversampling.
+    X4,
+    /// 8× oversampling.
+    X8,
+    /// 16× oversampling.
+    X16,
+}
+
+impl From<Oversampling> for u16 {
+    fn from(oversampling: Oversampling) -> u16 {
+        match oversampling {
+            Oversampling::None => 1,
+            Oversampling::X2 => 2,
+            Oversampling::X4 => 4,
+            Oversampling::X8 => 8,
+            Oversampling::X16 => 16,
+        }
+    }
+}
+
+impl From<ariel_os_embassy_common::adc::Oversampling<Self>> for Oversampling {
+    fn from(oversampling: ariel_os_embassy_common::adc::Oversampling<Self>) -> Self {
+        match oversampling {
+            ariel_os_embassy_common::adc::Oversampling::Hal(oversampling) => oversampling,
+            ariel_os_embassy_common::adc::Oversampling::None => Self::None,
+            ariel_os_embassy_common::adc::Oversampling::X2 => Self::X2,
+            ariel_os_embassy_common::adc::Oversampling::X4 => Self::X4,
+            ariel_os_embassy_common::adc::Oversampling::X8 => Self::X8,
+            ariel_os_embassy_common::adc::Oversampling::X16 => Self::X16,
+        }
+    }
+}
+
+/// ADC voltage reference.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Reference {
+    /// The ADC_AVDD supply, assumed to be 3.3 V.
+    Vdd,
+}
+
+impl From<ariel_os_embassy_common::adc::Reference<Self>> for Reference {
+    fn from(reference: ariel_os_embassy_common::adc::Reference<Self>) -> Self {
+        match reference {
+            ariel_os_embassy_common::adc::Reference::Hal(reference) => reference,
+            ariel_os_embassy_common::adc::Reference::Vdd => Self::Vdd,
+        }
+    }
+}
+
+/// An analog input of the ADC.
+pub struct Channel<'d> {
+    channel: adc::Channel<'d>,
+}
+
+impl<'d> Channel<'d> {
+    /// Returns a channel sampling the given analog-capable pin.
+    ///
+    /// The internal pull resistors of the pin are disabled.
+    pub fn new<P: AdcPin>(pin: impl crate::IntoPeripheral<'d, P>) -> Self {
+        Self {
+            channel: adc::Channel::new_pin(pin.into_hal_peripheral(), Pull::None),
+        }
+    }
+}
+
+/// Peripheral-specific ADC driver.
+pub struct ADC {
+    adc: adc::Adc<'static, Async>,
+    config: Config,
+}
+
+// Ensure this peripheral has only one active Instance.
+static ACTIVE_ADC: AtomicBool = AtomicBool::new(false);
+
+impl ADC {
+    /// Returns a driver for the ADC peripheral.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the peripheral is already in use.
+    #[expect(clippy::new_ret_no_self)]
+    #[expect(clippy::manual_assert, reason = "consistent with the other drivers")]
+    #[must_use]
+    pub fn new(config: Config) -> Adc {
+        bind_interrupts!(struct Irqs {
+            ADC_IRQ_FIFO => InterruptHandler;
+        });
+
+        // Check if we can initialize this peripheral (check if the value was previously false, set it to true).
+        if ACTIVE_ADC.swap(true, Ordering::AcqRel) {
+            panic!("ADC peripheral already initialized")
+        }
+
+        // FIXME(safety): enforce that the init code indeed has run
+        // SAFETY: We check with an AtomicBool that only one instance of this peripheral
+        // is active at once.
+        let adc_peripheral = unsafe { peripherals::ADC::steal() };
+
+        let adc = adc::Adc::new(adc_peripheral, Irqs, adc::Config::default());
+
+        Adc::ADC(Self { adc, config })
+    }
+
+    async fn read(&mut self, channel: &mut Channel<'_>) -> Result<Sample, Error> {
+        let resolution = u8::from(Resolution::from(self.config.resolution));
+        let ratio = u16::from(Oversampling::from(self.config.oversampling));
+        let Reference::Vdd = Reference::from(self.config.reference);
+
+        let raw = software_oversample(ratio, || async {
+            self.adc
+                .read(&mut channel.channel)
+                .await
+                .map(i32::from)
+                .map_err(|_| Error::Conversion)
+        })
+        .await?;
+
+        let raw = raw >> (HW_RESOLUTION_BITS - resolution);
+        let full_scale_raw = (1u32 << resolution) - 1;
+
+        Ok(Sample::new(raw, full_scale_raw, 3300))
+    }
+}
+
+impl Drop for ADC {
+    fn drop(&mut self) {
+        ACTIVE_ADC.store(false, Ordering::Release);
+    }
+}
+
+/// Peripheral-agnostic ADC driver.
+pub enum Adc {
+    /// ADC peripheral.
+    ADC(ADC),
+}
+
+impl Adc {
+    /// Performs a single-shot conversion on `channel`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Conversion`] if the conversion could not be carried out.
+    pub async fn read(&mut self, channel: &mut Channel<'_>) -> Result<Sample, Error> {
+        match self {
+            Self::ADC(adc) => adc.read(channel).await,
+        }
+    }
+}
+
+#[doc(hidden)]
+pub fn init(peripherals: &mut crate::OptionalPeripherals) {
+    // Take the ADC peripheral and do nothing with it.
+    let _ = peripherals.ADC.take().unwrap();
+}
@@ -4,6 +4,9 @@
 #![cfg_attr(nightly, feature(doc_cfg))]
 #![deny(missing_docs)]
 
+#[cfg(feature = "adc")]
+pub mod adc;
+
 pub mod gpio;
 
 #[doc(hidden)]
@@ -38,9 +41,15 @@ pub mod identity {
     pub type DeviceId = identity::NoDeviceId<identity::NotImplemented>;
 }
 
+#[cfg(feature = "pwm")]
+pub mod pwm;
+
 #[cfg(feature = "spi")]
 pub mod spi;
 
+#[cfg(feature = "system-off")]
+pub mod system_off;
+
 #[cfg(feature = "uart")]
 pub mod uart;
 
@@ -52,6 +61,10 @@ pub mod storage;
 #[doc(hidden)]
 pub mod usb;
 
+#[cfg(feature = "watchdog")]
+#[doc(hidden)]
+pub mod watchdog;
+
 #[doc(hidden)]
 pub use embassy_rp::OptionalPeripherals;
 
@@ -0,0 +1,364 @@
+//! Provides support for PWM outputs.
+
+#![expect(unsafe_code)]
+
+use core::marker::PhantomData;
+
+use portable_atomic::{AtomicBool, Ordering};
+
+use ariel_os_embassy_common::{
+    impl_pwm_builder_settings, impl_pwm_for_driver_enum,
+    pwm::{DutyCycle, Error, Hertz, Polarity, Settings, find_divider},
+};
+use embassy_rp::{
+    Peri, peripherals,
+    pwm::{ChannelAPin, ChannelBPin, Config, Pwm as InnerPwm, Slice},
+};
+
+/// Number of channels of each PWM slice.
+pub const CHANNELS: usize = 2;
+
+// NOTE(hal): the integer part of the clock divider is 8-bit wide, and the counter is 16-bit
+// wide; the counter top is one less than the number of ticks per period.
+const MAX_DIVIDER_LOG2: u8 = 7;
+const MAX_TICKS: u32 = 0xffff;
+
+/// Builder for a PWM driver, obtained by calling `builder()` on a slice-specific driver.
+///
+/// Each slice has two channels, each of which can only be output on specific GPIOs: channel `0`
+/// is the A channel of the slice, and channel `1` its B channel.
+pub struct PwmBuilder<T, A, B> {
+    settings: Settings,
+    pin_a: A,
+    pin_b: B,
+    _instance: PhantomData<T>,
+}
+
+impl_pwm_builder_settings!(T, A, B);
+
+impl<T: Instance, B> PwmBuilder<T, (), B> {
+    /// Outputs channel `0` (the A channel of the slice) to `pin`.
+    #[must_use]
+    pub fn channel_a<P: ChannelAPin<T::Slice>>(
+        self,
+        pin: impl crate::IntoPeripheral<'static, P>,
+    ) -> PwmBuilder<T, Peri<'static, P>, B> {
+        PwmBuilder {
+            settings: self.settings,
+            pin_a: pin.into_hal_peripheral(),
+            pin_b: self.pin_b,
+            _instance: PhantomData,
+        }
+    }
+}
+
+impl<T: Instance, A> PwmBuilder<T, A, ()> {
+    /// Outputs channel `1` (the B channel of the slice) to `pin`.
+    #[must_use]
+    pub fn channel_b<P: ChannelBPin<T::Slice>>(
+        self,
+        pin: impl crate::IntoPeripheral<'static, P>,
+    ) -> PwmBuilder<T, A, Peri<'static, P>> {
+        PwmBuilder {
+            settings: self.settings,
+            pin_a: self.pin_a,
+            pin_b: pin.into_hal_peripheral(),
+            _instance: PhantomData,
+        }
+    }
+}
+
+impl<T: Instance, A: ChannelAPin<T::Slice>> PwmBuilder<T, Peri<'static, A>, ()> {
+    /// Returns a PWM driver outputting channel `0` only, by finalizing the builder.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::FrequencyNotSupported`] if the frequency cannot be obtained.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slice is already in use.
+    pub fn build(self) -> Result<Pwm, Error> {
+        let (config, top) = config(&self.settings)?;
+        let pwm = InnerPwm::new_output_a(T::take(), self.pin_a, config.clone());
+        Ok(T::wrap(Inner::new(pwm, config, [true, false], top, &self.settings)))
+    }
+}
+
+impl<T: Instance, B: ChannelBPin<T::Slice>> PwmBuilder<T, (), Peri<'static, B>> {
+    /// Returns a PWM driver outputting channel `1` only, by finalizing the builder.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::FrequencyNotSupported`] if the frequency cannot be obtained.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slice is already in use.
+    pub fn build(self) -> Result<Pwm, Error> {
+        let (config, top) = config(&self.settings)?;
+        let pwm = InnerPwm::new_output_b(T::take(), self.pin_b, config.clone());
+        Ok(T::wrap(Inner::new(pwm, config, [false, true], top, &self.settings)))
+    }
+}
+
+impl<T: Instance, A: ChannelAPin<T::Slice>, B: ChannelBPin<T::Slice>>
+    PwmBuilder<T, Peri<'static, A>, Peri<'static, B>>
+{
+    /// Returns a PWM driver outputting both channels, by finalizing the builder.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::FrequencyNotSupported`] if the frequency cannot be obtained.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slice is already in use.
+    pub fn build(self) -> Result<Pwm, Error> {
+        let (config, top) = config(&self.settings)?;
+        let pwm = InnerPwm::new_output_ab(T::take(), self.pin_a, self.pin_b, config.clone());
+        Ok(T::wrap(Inner::new(pwm, config, [true, true], top, &self.settings)))
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+#[doc(hidden)]
+pub trait Instance: sealed::Sealed {
+    type Slice: Slice;
+
+    /// Returns the slice peripheral, panicking if it is already in use.
+    fn take() -> Peri<'static, Self::Slice>;
+
+    fn wrap(inner: Inner) -> Pwm;
+}
+
+/// Returns the slice configuration and the counter top for `settings`.
+fn config(settings: &Settings) -> Result<(Config, u16), Error> {
+    let (divider_log2, ticks) = find_divider(
+        embassy_rp::clocks::clk_sys_freq(),
+        settings.frequency,
+        MAX_DIVIDER_LOG2,
+        MAX_TICKS,
+    )
+    .ok_or(Error::FrequencyNotSupported)?;
+    // `ticks` is neither zero nor larger than `MAX_TICKS`.
+    let top = u16::try_from(ticks - 1).map_err(|_| Error::FrequencyNotSupported)?;
+
+    let mut config = Config::default();
+    config.divider = (1u8 << divider_log2).into();
+    config.top = top;
+
+    Ok((config, top))
+}
+
+// State shared by all the slice-specific drivers.
+#[doc(hidden)]
+pub struct Inner {
+    pwm: InnerPwm<'static>,
+    config: Config,
+    channels: [bool; CHANNELS],
+    polarity: Polarity,
+    top: u16,
+    duty_cycles: [DutyCycle; CHANNELS],
+}
+
+impl Inner {
+    fn new(
+        pwm: InnerPwm<'static>,
+        config: Config,
+        channels: [bool; CHANNELS],
+        top: u16,
+        settings: &Settings,
+    ) -> Self {
+        let mut inner = Self {
+            pwm,
+            config,
+            channels,
+            polarity: settings.polarity,
+            top,
+            duty_cycles: [settings.initial_duty_cycle; CHANNELS],
+        };
+        inner.apply();
+        inner
+    }
+
+    fn compare(&self, duty_cycle: DutyCycle) -> u16 {
+        // The output is high while the counter is lower than the compare value.
+        let high_ticks = duty_cycle.high_ticks(u32::from(self.top) + 1, self.polarity);
+        // Cannot truncate as `high_ticks` is not larger than `MAX_TICKS`.
+        #[expect(clippy::cast_possible_truncation)]
+        let compare = high_ticks as u16;
+        compare
+    }
+
+    fn apply(&mut self) {
+        let [duty_cycle_a, duty_cycle_b] = self.duty_cycles;
+        self.config.top = self.top;
+        self.config.compare_a = self.compare(duty_cycle_a);
+        self.config.compare_b = self.compare(duty_cycle_b);
+        self.pwm.set_config(&self.config);
+    }
+
+    fn set_duty_cycle(&mut self, channel: usize, duty_cycle: DutyCycle) -> Result<(), Error> {
+        if !self.channels.get(channel).copied().unwrap_or(false) {
+            return Err(Error::InvalidChannel);
+        }
+        let slot = self
+            .duty_cycles
+            .get_mut(channel)
+            .ok_or(Error::InvalidChannel)?;
+        *slot = duty_cycle;
+        self.apply();
+
+        Ok(())
+    }
+
+    fn set_frequency(&mut self, frequency: Hertz) -> Result<(), Error> {
+        let (config, top) = config(&Settings::new(frequency))?;
+
+        self.config.divider = config.divider;
+        self.top = top;
+        self.apply();
+
+        Ok(())
+    }
+}
+
+macro_rules! define_pwm_drivers {
+    ($( $peripheral:ident ),* $(,)?) => {
+        $(
+            /// Slice-specific PWM driver.
+            pub struct $peripheral {
+                inner: Inner,
+            }
+
+            // Ensure this peripheral has only one active Instance.
+            paste::paste! {
+                static [< ACTIVE_ $peripheral >]: AtomicBool = AtomicBool::new(false);
+            }
+
+            impl $peripheral {
+                /// Returns a [`PwmBuilder`] for this slice, producing a PWM signal at `frequency`.
+                #[must_use]
+                pub fn builder(frequency: Hertz) -> PwmBuilder<Self, (), ()> {
+                    PwmBuilder {
+                        settings: Settings::new(frequency),
+                        pin_a: (),
+                        pin_b: (),
+                        _instance: PhantomData,
+                    }
+                }
+
+                #[expect(clippy::unused_self)]
+                fn channel_count(&self) -> usize {
+                    CHANNELS
+                }
+
+                fn set_duty_cycle(
+                    &mut self,
+                    channel: usize,
+                    duty_cycle: DutyCycle,
+                ) -> Result<(), Error> {
+                    self.inner.set_duty_cycle(channel, duty_cycle)
+                }
+
+                fn set_frequency(&mut self, frequency: Hertz) -> Result<(), Error> {
+                    self.inner.set_frequency(frequency)
+                }
+            }
+
+            impl sealed::Sealed for $peripheral {}
+
+            impl Instance for $peripheral {
+                type Slice = peripherals::$peripheral;
+
+                fn take() -> Peri<'static, Self::Slice> {
+                    // Make this struct a compile-time-enforced singleton: having multiple statics
+                    // defined with the same name would result in a compile-time error.
+                    paste::paste! {
+                        #[allow(dead_code)]
+                        static [<PREVENT_MULTIPLE_ $peripheral>]: () = ();
+                    }
+
+                    // Check if we can initialize this peripheral (check if the value was previously false, set it to true).
+                    paste::paste! {
+                        if [< ACTIVE_ $peripheral >].swap(true, Ordering::AcqRel) {
+                            panic!("PWM slice already initialized")
+                        }
+                    }
+
+                    // FIXME(safety): enforce that the init code indeed has run
+                    // SAFETY: We check with an AtomicBool that only one instance of this peripheral
+                    // is active at once.
+                    unsafe { peripherals::$peripheral::steal() }
+                }
+
+                fn wrap(inner: Inner) -> Pwm {
+                    Pwm::$peripheral(Self { inner })
+                }
+            }
+
+            impl Drop for $peripheral {
+                fn drop(&mut self) {
+                    paste::paste! {
+                        [< ACTIVE_ $peripheral >].store(false, Ordering::Release);
+                    }
+                }
+            }
+        )*
+
+        /// Slice-agnostic PWM driver.
+        pub enum Pwm {
+            $(
+                #[doc = concat!(stringify!($peripheral), " slice.")]
+                $peripheral($peripheral),
+            )*
+        }
+
+        impl_pwm_for_driver_enum!(Pwm, $( $peripheral ),*);
+    }
+}
+
+// Define a driver per slice
+#[cfg(context = "rp2040")]
+define_pwm_drivers!(
+    PWM_SLICE0, PWM_SLICE1, PWM_SLICE2, PWM_SLICE3, PWM_SLICE4, PWM_SLICE5, PWM_SLICE6, PWM_SLICE7,
+);
+#[cfg(context = "rp235xa")]
+define_pwm_drivers!(
+    PWM_SLICE0,
+    PWM_SLICE1,
+    PWM_SLICE2,
+    PWM_SLICE3,
+    PWM_SLICE4,
+    PWM_SLICE5,
+    PWM_SLICE6,
+    PWM_SLICE7,
+    PWM_SLICE8,
+    PWM_SLICE9,
+    PWM_SLICE10,
+    PWM_SLICE11,
+);
+
+#[doc(hidden)]
+pub fn init(peripherals: &mut crate::OptionalPeripherals) {
+    // Take all PWM slices and do nothing with them.
+    let _ = peripherals.PWM_SLICE0.take().unwrap();
+    let _ = peripherals.PWM_SLICE1.take().unwrap();
+    let _ = peripherals.PWM_SLICE2.take().unwrap();
+    let _ = peripherals.PWM_SLICE3.take().unwrap();
+    let _ = peripherals.PWM_SLICE4.take().unwrap();
+    let _ = peripherals.PWM_SLICE5.take().unwrap();
+    let _ = peripherals.PWM_SLICE6.take().unwrap();
+    let _ = peripherals.PWM_SLICE7.take().unwrap();
+    #[cfg(context = "rp235xa")]
+    {
+        let _ = peripherals.PWM_SLICE8.take().unwrap();
+        let _ = peripherals.PWM_SLICE9.take().unwrap();
+        let _ = peripherals.PWM_SLICE10.take().unwrap();
+        let _ = peripherals.PWM_SLICE11.take().unwrap();
+    }
+}
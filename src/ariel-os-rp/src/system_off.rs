@@ -0,0 +1,70 @@
+//! Provides system off, from which the MCU wakes up with a reset.
+//!
+//! The MCU is put in its dormant state, in which all clocks are stopped, and is reset once woken
+//! up.
+
+use core::{convert::Infallible, time::Duration};
+
+use embassy_rp::{Peri, gpio::Pin, pac};
+
+pub use ariel_os_embassy_common::system_off::{Error, WakeEdge};
+
+/// GPIO pin that can wake the MCU up from system off.
+#[derive(Debug, Clone, Copy)]
+pub struct WakePin {
+    pin: u8,
+}
+
+#[doc(hidden)]
+pub fn wake_pin<P: Pin>(pin: &Peri<'_, P>) -> WakePin {
+    WakePin { pin: pin.pin() }
+}
+
+/// Source waking the MCU up from system off.
+#[derive(Debug, Clone, Copy)]
+pub enum WakeSource {
+    #[doc(hidden)]
+    Pin(WakePin, WakeEdge),
+    #[doc(hidden)]
+    Timer(Duration),
+}
+
+#[doc(hidden)]
+pub fn system_off(wake_sources: impl IntoIterator<Item = WakeSource>) -> Result<Infallible, Error> {
+    // Value of the DORMANT register stopping the crystal oscillator ("coma").
+    const DORMANT: u32 = 0x636f_6d61;
+
+    for wake_source in wake_sources {
+        match wake_source {
+            WakeSource::Pin(pin, edge) => enable_dormant_wake(pin, edge),
+            // FIXME: support the RTC on RP2040 and the POWMAN timer on RP235x.
+            WakeSource::Timer(_) => return Err(Error::UnsupportedWakeSource),
+        }
+    }
+
+    // NOTE(hal): the system clocks are all derived from the crystal oscillator, so stopping it
+    // halts the whole system until a wake source triggers.
+    pac::XOSC.dormant().write(|w| w.0 = DORMANT);
+
+    // Reset instead of restoring the clock tree.
+    ariel_os_power::reset_after_wake_up()
+}
+
+fn enable_dormant_wake(wake_pin: WakePin, edge: WakeEdge) {
+    // Each register covers 8 pins.
+    let index = usize::from(wake_pin.pin / 8);
+    let bit = usize::from(wake_pin.pin % 8);
+
+    // Clear the edges that may have previously been latched.
+    pac::IO_BANK0.intr(index).write(|w| {
+        w.set_edge_high(bit, true);
+        w.set_edge_low(bit, true);
+    });
+
+    pac::IO_BANK0
+        .dormant_wake_inte(index)
+        .modify(|w| match edge {
+            WakeEdge::Rising => w.set_edge_high(bit, true),
+            WakeEdge::Falling => w.set_edge_low(bit, true),
+        });
+}
@@ -0,0 +1,40 @@
+use embassy_rp::watchdog;
+use embassy_time::Duration;
+
+/// Longest supported timeout, in milliseconds.
+// NOTE(hal): the counter is 24-bit wide, and decrements twice per microsecond because of erratum
+// RP2040-E1.
+#[cfg(context = "rp2040")]
+pub const MAX_TIMEOUT_MS: u32 = 0x00ff_ffff / 2 / 1000;
+/// Longest supported timeout, in milliseconds.
+// NOTE(hal): the counter is 24-bit wide.
+#[cfg(context = "rp235xa")]
+pub const MAX_TIMEOUT_MS: u32 = 0x00ff_ffff / 1000;
+
+/// Hardware watchdog, started by [`init()`].
+pub struct Watchdog {
+    watchdog: watchdog::Watchdog,
+}
+
+impl Watchdog {
+    /// Feeds the watchdog, restarting its timeout.
+    pub fn feed(&mut self) {
+        self.watchdog.feed();
+    }
+}
+
+/// Starts the watchdog with a timeout of `timeout_ms` milliseconds.
+///
+/// The timeout is capped to [`MAX_TIMEOUT_MS`].
+///
+/// # Panics
+///
+/// Panics if the watchdog peripheral has been previously taken.
+pub fn init(peripherals: &mut crate::OptionalPeripherals, timeout_ms: u32) -> Watchdog {
+    let mut watchdog = watchdog::Watchdog::new(peripherals.WATCHDOG.take().unwrap());
+    watchdog.start(Duration::from_millis(u64::from(
+        timeout_ms.min(MAX_TIMEOUT_MS),
+    )));
+
+    Watchdog { watchdog }
+}
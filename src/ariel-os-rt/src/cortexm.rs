@@ -220,6 +220,7 @@ pub fn init() {
 
 #[allow(dead_code, reason = "conditional compilation")]
 pub fn wfi() {
+    ariel_os_power::prepare_idle();
     cortex_m::asm::wfi();
 }
 
@@ -6,7 +6,10 @@
     reason = "otherwise usage reporting functions influence the result"
 )]
 
-use core::{marker::PhantomData, ptr::write_volatile};
+use core::{
+    marker::PhantomData,
+    ptr::{read_volatile, write_volatile},
+};
 
 use crate::arch::sp;
 
@@ -132,32 +135,13 @@ impl Stack {
         self.highest - (sp() - STACK_PAINT_IGNORE)
     }
 
-    /// Returns the minimum free stack space since last repaint.
-    ///
-    /// This re-calculates and thus runs in `O(n)`!
-    #[must_use]
-    fn free_min(&self) -> usize {
-        let mut free = 0usize;
-        for pos in self.lowest..self.highest {
-            // SAFETY: dereferencing ptr to valid memory, read only
-            // See assumptions in Struct level documentation.
-            if unsafe { *(pos as *const u8) } == 0xCC {
-                free += 1;
-            } else {
-                // Stop at the paint border.
-                break;
-            }
-        }
-        free
-    }
-
     /// Returns the peak amount of stack used since last repaint.
     ///
     /// This re-calculates and thus runs in `O(n)`!
     #[must_use]
     #[inline(always)]
     pub fn peak_usage(&self) -> usize {
-        self.size() - self.free_min()
+        self.size() - free_min(self.lowest, self.highest)
     }
 
     /// Repaints the stack.
@@ -217,3 +201,37 @@ impl Stack {
         self.lowest == self.highest
     }
 }
+
+/// Returns the peak amount of stack used by a thread since its stack was painted.
+///
+/// Thread stacks are painted when the thread is created.
+/// Returns `None` if `thread_id` does not refer to a valid thread.
+///
+/// This re-calculates and thus runs in `O(n)`!
+#[cfg(feature = "threading")]
+#[must_use]
+pub fn thread_peak_usage(thread_id: ariel_os_threads::ThreadId) -> Option<usize> {
+    let (lowest, highest) = ariel_os_threads::stack_limits(thread_id)?;
+    Some(highest - lowest - free_min(lowest, highest))
+}
+
+/// Returns the minimum free stack space since last repaint, for the stack spanning from `lowest`
+/// to `highest`.
+///
+/// This re-calculates and thus runs in `O(n)`!
+#[must_use]
+fn free_min(lowest: usize, highest: usize) -> usize {
+    let mut free = 0usize;
+    for pos in lowest..highest {
+        // SAFETY: reading from valid memory, read only; the read is volatile as another thread
+        // may be using that stack concurrently.
+        // See assumptions in Struct level documentation.
+        if unsafe { read_volatile(pos as *const u8) } == STACK_PAINT_COLOR {
+            free += 1;
+        } else {
+            // Stop at the paint border.
+            break;
+        }
+    }
+    free
+}
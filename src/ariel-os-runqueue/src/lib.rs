@@ -58,6 +58,30 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_rq_add_before() {
+        let mut runqueue: RunQueue<8, 32> = RunQueue::new();
+        let deadlines = [30, 10, 20, 10];
+        let add = |runqueue: &mut RunQueue<8, 32>, n: u8| {
+            let deadline = deadlines[usize::from(n)];
+            runqueue.add_before(ThreadId::new(n), RunqueueId::new(1), |next| {
+                deadlines[usize::from(next)] > deadline
+            });
+        };
+
+        runqueue.add(ThreadId::new(4), RunqueueId::new(0));
+        for n in 0..4 {
+            add(&mut runqueue, n);
+        }
+
+        // Ordered by deadline, threads with the same deadline in insertion order.
+        for n in [1, 3, 2, 0] {
+            assert_eq!(runqueue.get_next(), Some(ThreadId::new(n)));
+            runqueue.pop_head(ThreadId::new(n), RunqueueId::new(1));
+        }
+        assert_eq!(runqueue.get_next(), Some(ThreadId::new(4)));
+    }
+
     #[test]
     fn test_rq_basic_twoprio() {
         let mut runqueue: RunQueue<8, 32> = RunQueue::new();
@@ -86,6 +86,23 @@ impl<const N_QUEUES: usize, const N_THREADS: usize> RunQueue<{ N_QUEUES }, { N_T
         self.queues.push(n.0, rq.0);
     }
 
+    /// Adds thread with tid `n` to runqueue number `rq`, before the first thread for which
+    /// `is_after` returns `true`, or at the tail if there is none.
+    ///
+    /// This keeps the runqueue sorted if it already is, e.g., by deadline.
+    pub fn add_before<F: FnMut(ThreadId) -> bool>(
+        &mut self,
+        n: ThreadId,
+        rq: RunqueueId,
+        mut is_after: F,
+    ) {
+        debug_assert!(usize::from(n) < N_THREADS);
+        debug_assert!(usize::from(rq) < N_QUEUES);
+        self.bitcache |= 1 << rq.0;
+        self.queues
+            .insert_before(n.0, rq.0, |next| is_after(ThreadId(next)));
+    }
+
     /// Returns the head of the runqueue without removing it.
     pub fn peek_head(&self, rq: RunqueueId) -> Option<ThreadId> {
         self.queues.peek_head(rq.0).map(ThreadId::new)
@@ -304,6 +321,37 @@ mod clist {
             }
         }
 
+        /// Inserts `n` into list `rq` before the first element for which `is_after` returns
+        /// `true`, or at the tail if there is none.
+        #[expect(clippy::missing_panics_doc, reason = "internal")]
+        pub fn insert_before<F: FnMut(u8) -> bool>(&mut self, n: u8, rq: u8, mut is_after: F) {
+            assert!(n < Self::sentinel());
+            if self.next_idxs[n as usize] != Self::sentinel() {
+                return;
+            }
+
+            let tail = self.tail[rq as usize];
+            if tail != Self::sentinel() {
+                // Walk the list from its head, which is the element after the tail.
+                let mut prev = tail;
+                loop {
+                    let curr = self.next_idxs[prev as usize];
+                    if is_after(curr) {
+                        // 1. n.next = curr
+                        self.next_idxs[n as usize] = curr;
+                        // 2. prev.next = n, which makes n the head if curr was the head
+                        self.next_idxs[prev as usize] = n;
+                        return;
+                    }
+                    if curr == tail {
+                        break;
+                    }
+                    prev = curr;
+                }
+            }
+            self.push(n, rq);
+        }
+
         /// Removes a thread from the list.
         ///
         /// If the thread was the only thread in its runqueue, `Some` is returned
@@ -458,6 +506,24 @@ mod clist {
             assert!(clist.is_empty(0));
         }
 
+        #[test]
+        fn test_clist_insert_before() {
+            let mut clist: CList<8, 32> = CList::new();
+            // Inserting into an empty list.
+            clist.insert_before(2, 0, |_| true);
+            // Inserting before the head.
+            clist.insert_before(0, 0, |next| next > 0);
+            // Inserting at the tail.
+            clist.insert_before(4, 0, |next| next > 4);
+            // Inserting in the middle.
+            clist.insert_before(3, 0, |next| next > 3);
+            clist.insert_before(1, 0, |next| next > 1);
+            for i in 0..5 {
+                assert_eq!(clist.pop_head(0), Some(i));
+            }
+            assert!(clist.is_empty(0));
+        }
+
         #[test]
         fn test_clist_peek_head() {
             let mut clist: CList<8, 32> = CList::new();
@@ -0,0 +1,89 @@
+//! Parses the Card-Specific Data (CSD) register.
+//!
+//! See section 5.3 of the SD Physical Layer Simplified Specification, and section 8.3 of the
+//! MMC specification.
+
+use crate::CardType;
+
+/// Contents of the CSD register, as read using `SEND_CSD`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Csd(u128);
+
+impl Csd {
+    pub(crate) fn new(bytes: [u8; 16]) -> Self {
+        Self(u128::from_be_bytes(bytes))
+    }
+
+    /// Returns the bits `msb` to `lsb` (inclusive) of the register.
+    fn bits(&self, msb: u32, lsb: u32) -> u32 {
+        let len = msb - lsb + 1;
+        #[expect(
+            clippy::cast_possible_truncation,
+            reason = "fields are at most 22 bits"
+        )]
+        let field = ((self.0 >> lsb) & ((1 << len) - 1)) as u32;
+        field
+    }
+
+    /// Returns the `CSD_STRUCTURE` field, the version of the register layout.
+    fn structure(&self) -> u32 {
+        self.bits(127, 126)
+    }
+
+    /// Returns the capacity of the card, in 512-byte blocks.
+    ///
+    /// Returns `None` if the layout of the register is not supported for `card_type`.
+    pub(crate) fn num_blocks(&self, card_type: CardType) -> Option<u64> {
+        match (card_type, self.structure()) {
+            // CSD version 2.0, `C_SIZE` counts 512 KiB units.
+            (CardType::Sdhc, 1) => Some((u64::from(self.bits(69, 48)) + 1) * 1024),
+            // CSD version 1.0, also used by all MMC versions.
+            (CardType::Sdsc, 0) | (CardType::Mmc, _) => {
+                let c_size = u64::from(self.bits(73, 62));
+                let c_size_mult = self.bits(49, 47);
+                let read_bl_len = self.bits(83, 80);
+                let bytes = (c_size + 1) << (c_size_mult + 2 + read_bl_len);
+                Some(bytes / 512)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_csd_v2() {
+        let csd = Csd::new([
+            0x40, 0x0e, 0x00, 0x32, 0x5b, 0x59, 0x00, 0x00, 0x1d, 0x8a, 0x7f, 0x80, 0x0a, 0x40,
+            0x00, 0x8b,
+        ]);
+        // C_SIZE = 0x1d8a
+        assert_eq!(csd.num_blocks(CardType::Sdhc), Some(7563 * 1024));
+        assert_eq!(csd.num_blocks(CardType::Sdsc), None);
+    }
+
+    #[test]
+    fn parse_csd_v1() {
+        // READ_BL_LEN = 9, C_SIZE = 0xf1f, C_SIZE_MULT = 7
+        let csd = Csd::new([
+            0x00, 0x26, 0x00, 0x32, 0x5f, 0x59, 0x83, 0xc7, 0xfe, 0xfb, 0xcf, 0xff, 0x92, 0x40,
+            0x40, 0xdf,
+        ]);
+        assert_eq!(csd.num_blocks(CardType::Sdsc), Some(3872 * 512));
+        assert_eq!(csd.num_blocks(CardType::Mmc), Some(3872 * 512));
+        assert_eq!(csd.num_blocks(CardType::Sdhc), None);
+    }
+
+    #[test]
+    fn parse_csd_v1_large_blocks() {
+        // 2 GB cards use READ_BL_LEN = 10: READ_BL_LEN = 10, C_SIZE = 0xfff, C_SIZE_MULT = 7
+        let csd = Csd::new([
+            0x00, 0x26, 0x00, 0x32, 0x5f, 0x5a, 0x83, 0xff, 0xfe, 0xfb, 0xcf, 0xff, 0x92, 0x40,
+            0x40, 0xdf,
+        ]);
+        assert_eq!(csd.num_blocks(CardType::Sdsc), Some(4096 * 512 * 2));
+    }
+}
@@ -0,0 +1,1073 @@
+//! Driver for SD and MMC cards over SPI, e.g., microSD cards of data loggers.
+//!
+//! [`SdCard`] implements [`BlockDevice`], through which blocks are read and written, and can thus
+//! be used with filesystems such as `ariel_os::fs::Fat`.
+//! It works on any [`SpiDevice`], and in particular on `ariel_os::spi::main::SpiDevice`.
+//!
+//! Supported cards are SDSC (version 1.x and 2.0), SDHC and SDXC cards, and MMC cards up to 2 GB.
+//!
+//! # Note
+//!
+//! Cards are only required to support clock frequencies up to 400 kHz until they are
+//! initialized; most cards do support higher frequencies, up to 25 MHz.
+//!
+//! As the [`SpiDevice`] abstraction asserts the chip select for each transaction only, the chip
+//! select is released between the phases of each command, e.g., while waiting for the card to
+//! respond, which SD cards in SPI mode tolerate in practice.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! use ariel_os::{gpio, spi::main::SpiDevice};
+//! use ariel_os_sdcard::SdCard;
+//!
+//! let cs_output = gpio::Output::new(peripherals.sd_cs, gpio::Level::High);
+//! let spi_device = SpiDevice::new(&spi_bus, cs_output);
+//! // Most card sockets pull the card detect pin to ground when a card is inserted.
+//! let card_detect = gpio::Input::new(peripherals.sd_detect, gpio::Pull::Up);
+//!
+//! let mut card = SdCard::with_card_detect(spi_device, card_detect);
+//! card.init().await?;
+//! let fat = ariel_os::fs::Fat::mount(card).await?;
+//! ```
+
+#![cfg_attr(not(test), no_std)]
+#![deny(missing_docs)]
+
+mod csd;
+
+use core::convert::Infallible;
+
+use aligned::{A1, Aligned};
+use block_device_driver::BlockDevice;
+use embassy_time::{Duration, Instant};
+use embedded_hal::digital::{ErrorType, InputPin};
+use embedded_hal_async::spi::{Operation, SpiDevice};
+
+use csd::Csd;
+
+/// Size of the blocks of the card, in bytes.
+pub const BLOCK_SIZE: usize = 512;
+
+/// Block length set on SDSC and MMC cards, which use byte addresses.
+#[expect(clippy::cast_possible_truncation, reason = "constant value")]
+const BLOCK_LEN: u32 = BLOCK_SIZE as u32;
+
+// Commands, see section 7.3.1.3 of the SD Physical Layer Simplified Specification.
+/// Resets the card, entering SPI mode.
+const GO_IDLE_STATE: u8 = 0;
+/// Starts the initialization of MMC cards.
+const SEND_OP_COND: u8 = 1;
+/// Checks the voltage range, only supported by version 2.0 cards.
+const SEND_IF_COND: u8 = 8;
+/// Reads the CSD register.
+const SEND_CSD: u8 = 9;
+/// Stops a multiple block read.
+const STOP_TRANSMISSION: u8 = 12;
+/// Sets the block length of SDSC and MMC cards.
+const SET_BLOCKLEN: u8 = 16;
+/// Reads a single block.
+const READ_SINGLE_BLOCK: u8 = 17;
+/// Reads blocks until [`STOP_TRANSMISSION`].
+const READ_MULTIPLE_BLOCK: u8 = 18;
+/// Writes a single block.
+const WRITE_BLOCK: u8 = 24;
+/// Writes blocks until [`STOP_TRAN_TOKEN`].
+const WRITE_MULTIPLE_BLOCK: u8 = 25;
+/// Starts the initialization of SD cards, as an application command.
+const SD_SEND_OP_COND: u8 = 41;
+/// Announces an application command.
+const APP_CMD: u8 = 55;
+/// Reads the OCR register.
+const READ_OCR: u8 = 58;
+
+/// Argument of [`SEND_IF_COND`]: 2.7–3.6 V, and a check pattern echoed by the card.
+const IF_COND_ARG: u32 = 0x1aa;
+/// High Capacity Support bit of [`SD_SEND_OP_COND`], and Card Capacity Status bit of the OCR.
+const HIGH_CAPACITY: u32 = 1 << 30;
+
+// R1 response bits.
+/// The card is initializing.
+const R1_IDLE: u8 = 0x01;
+/// The command is not supported by the card.
+const R1_ILLEGAL_COMMAND: u8 = 0x04;
+
+// Data tokens, see section 7.3.3 of the SD Physical Layer Simplified Specification.
+/// Starts a data block, except for multiple block writes.
+const START_BLOCK_TOKEN: u8 = 0xfe;
+/// Starts a data block of a multiple block write.
+const START_BLOCK_MULTIPLE_TOKEN: u8 = 0xfc;
+/// Ends a multiple block write.
+const STOP_TRAN_TOKEN: u8 = 0xfd;
+/// Data response of accepted data blocks, after masking.
+const DATA_ACCEPTED: u8 = 0x05;
+
+/// Number of `GO_IDLE_STATE` attempts before concluding no card is there.
+const RESET_ATTEMPTS: usize = 10;
+/// Number of bytes to wait for an R1 response (`N_CR`).
+const RESPONSE_BYTES: usize = 8;
+
+/// Maximum duration of the initialization of the card.
+const INIT_TIMEOUT: Duration = Duration::from_secs(1);
+/// Maximum duration before a data block is sent by the card.
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+/// Maximum duration of the card being busy programming data.
+const WRITE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Type of a card.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardType {
+    /// MMC card, using byte addresses.
+    Mmc,
+    /// Standard-capacity SD card (up to 2 GB), using byte addresses.
+    Sdsc,
+    /// High- or extended-capacity SD card (SDHC or SDXC), using block addresses.
+    Sdhc,
+}
+
+/// Errors returned by [`SdCard`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error<E> {
+    /// The SPI device returned an error.
+    Spi(E),
+    /// No card is inserted, or it does not respond.
+    NoCard,
+    /// The card has not been initialized, see [`SdCard::init()`].
+    NotInitialized,
+    /// The card is not supported, e.g., because of its voltage range.
+    UnsupportedCard,
+    /// The card did not respond in time.
+    Timeout,
+    /// The card returned an error in response to a command.
+    Command {
+        /// Index of the command.
+        command: u8,
+        /// R1 response of the card.
+        response: u8,
+    },
+    /// The card returned a data error token instead of a data block.
+    Read(u8),
+    /// The card rejected a data block, with the given data response.
+    Write(u8),
+    /// The blocks are outside of the card.
+    OutOfRange,
+}
+
+impl<E: core::fmt::Debug> core::fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Spi(err) => write!(f, "SPI error: {err:?}"),
+            Self::NoCard => f.write_str("no card"),
+            Self::NotInitialized => f.write_str("card not initialized"),
+            Self::UnsupportedCard => f.write_str("unsupported card"),
+            Self::Timeout => f.write_str("card timeout"),
+            Self::Command { command, response } => {
+                write!(f, "command {command} failed with response {response:#04x}")
+            }
+            Self::Read(token) => write!(f, "read failed with error token {token:#04x}"),
+            Self::Write(response) => write!(f, "write rejected with response {response:#04x}"),
+            Self::OutOfRange => f.write_str("blocks out of range"),
+        }
+    }
+}
+
+impl<E: core::fmt::Debug> core::error::Error for Error<E> {}
+
+/// Card detect input for sockets without card detect switch, always reporting a card.
+pub struct NoCardDetect;
+
+impl ErrorType for NoCardDetect {
+    type Error = Infallible;
+}
+
+impl InputPin for NoCardDetect {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        Ok(false)
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        Ok(true)
+    }
+}
+
+/// Information about an initialized card.
+#[derive(Debug, Clone, Copy)]
+struct Card {
+    card_type: CardType,
+    num_blocks: u64,
+}
+
+/// An SD or MMC card, accessed over SPI.
+pub struct SdCard<SPI, CD = NoCardDetect> {
+    spi: SPI,
+    card_detect: CD,
+    card: Option<Card>,
+}
+
+impl<SPI: SpiDevice> SdCard<SPI> {
+    /// Creates a driver for a card in a socket without card detect switch.
+    ///
+    /// The card must be initialized using [`init()`](Self::init) before being accessed.
+    #[must_use]
+    pub fn new(spi: SPI) -> Self {
+        Self::with_card_detect(spi, NoCardDetect)
+    }
+}
+
+impl<SPI: SpiDevice, CD: InputPin> SdCard<SPI, CD> {
+    /// Creates a driver for a card in a socket with a card detect switch.
+    ///
+    /// `card_detect` must be low when a card is inserted, as on most sockets when using a
+    /// pull-up, e.g., a `gpio::Input` created with `gpio::Pull::Up`.
+    ///
+    /// The card must be initialized using [`init()`](Self::init) before being accessed.
+    #[must_use]
+    pub fn with_card_detect(spi: SPI, card_detect: CD) -> Self {
+        Self {
+            spi,
+            card_detect,
+            card: None,
+        }
+    }
+
+    /// Returns whether a card is inserted, according to the card detect switch.
+    ///
+    /// When the card has been removed, it needs to be initialized again after being inserted.
+    pub fn is_present(&mut self) -> bool {
+        let present = matches!(self.card_detect.is_low(), Ok(true));
+        if !present {
+            self.card = None;
+        }
+        present
+    }
+
+    /// Returns the type of the card, if initialized.
+    #[must_use]
+    pub fn card_type(&self) -> Option<CardType> {
+        self.card.map(|card| card.card_type)
+    }
+
+    /// Returns the number of blocks of the card, if initialized.
+    #[must_use]
+    pub fn num_blocks(&self) -> Option<u64> {
+        self.card.map(|card| card.num_blocks)
+    }
+
+    /// Initializes the card, which needs to be done again when a card is inserted.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoCard`] if no card is inserted or responds, and
+    /// [`Error::UnsupportedCard`] if the card cannot be used.
+    pub async fn init(&mut self) -> Result<(), Error<SPI::Error>> {
+        self.card = None;
+        if !self.is_present() {
+            return Err(Error::NoCard);
+        }
+
+        // Let the card reach its operating voltage, with at least 74 clock cycles.
+        self.spi.write(&[0xff; 10]).await.map_err(Error::Spi)?;
+
+        let mut reset = false;
+        for _ in 0..RESET_ATTEMPTS {
+            if let Ok(R1_IDLE) = self.command(GO_IDLE_STATE, 0).await {
+                reset = true;
+                break;
+            }
+        }
+        if !reset {
+            return Err(Error::NoCard);
+        }
+
+        let card_type = self.init_card().await?;
+
+        if card_type != CardType::Sdhc {
+            self.checked_command(SET_BLOCKLEN, BLOCK_LEN).await?;
+        }
+
+        self.checked_command(SEND_CSD, 0).await?;
+        let mut csd = [0xff; 16];
+        self.read_data(&mut csd).await?;
+        let num_blocks = Csd::new(csd)
+            .num_blocks(card_type)
+            .ok_or(Error::UnsupportedCard)?;
+
+        ariel_os_log::debug!("sdcard: card initialized, {} blocks", num_blocks);
+        self.card = Some(Card {
+            card_type,
+            num_blocks,
+        });
+        Ok(())
+    }
+
+    /// Runs the initialization process of the card, returning its type.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnsupportedCard`] if the card does not support the supply voltage.
+    async fn init_card(&mut self) -> Result<CardType, Error<SPI::Error>> {
+        let version_2 = match self.command(SEND_IF_COND, IF_COND_ARG).await? {
+            r1 if r1 & R1_ILLEGAL_COMMAND != 0 => false,
+            _ => {
+                let mut r7 = [0xff; 4];
+                self.spi
+                    .transfer_in_place(&mut r7)
+                    .await
+                    .map_err(Error::Spi)?;
+                if u32::from_be_bytes(r7) & 0xfff != IF_COND_ARG {
+                    return Err(Error::UnsupportedCard);
+                }
+                true
+            }
+        };
+
+        let deadline = Instant::now() + INIT_TIMEOUT;
+        let op_cond_arg = if version_2 { HIGH_CAPACITY } else { 0 };
+        let mut mmc = false;
+        loop {
+            let r1 = if mmc {
+                self.command(SEND_OP_COND, 0).await?
+            } else {
+                match self.command(APP_CMD, 0).await? {
+                    // Only MMC cards do not support application commands.
+                    r1 if r1 & R1_ILLEGAL_COMMAND != 0 && !version_2 => {
+                        mmc = true;
+                        continue;
+                    }
+                    _ => self.command(SD_SEND_OP_COND, op_cond_arg).await?,
+                }
+            };
+
+            match r1 {
+                0 => break,
+                R1_IDLE if Instant::now() < deadline => {}
+                R1_IDLE => return Err(Error::Timeout),
+                response => {
+                    let command = if mmc { SEND_OP_COND } else { SD_SEND_OP_COND };
+                    return Err(Error::Command { command, response });
+                }
+            }
+        }
+
+        if mmc {
+            return Ok(CardType::Mmc);
+        }
+        if !version_2 {
+            return Ok(CardType::Sdsc);
+        }
+
+        self.checked_command(READ_OCR, 0).await?;
+        let mut ocr = [0xff; 4];
+        self.spi
+            .transfer_in_place(&mut ocr)
+            .await
+            .map_err(Error::Spi)?;
+        if u32::from_be_bytes(ocr) & HIGH_CAPACITY != 0 {
+            Ok(CardType::Sdhc)
+        } else {
+            Ok(CardType::Sdsc)
+        }
+    }
+
+    /// Returns the initialized card, checking that it is still inserted.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoCard`] if the card has been removed, and [`Error::NotInitialized`] if
+    /// it has not been initialized.
+    fn card(&mut self) -> Result<Card, Error<SPI::Error>> {
+        if !self.is_present() {
+            return Err(Error::NoCard);
+        }
+        self.card.ok_or(Error::NotInitialized)
+    }
+
+    /// Returns the address of the first of `count` blocks starting at `block`, as used in
+    /// commands.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::OutOfRange`] if the blocks are outside of the card.
+    fn address(&mut self, block: u32, count: usize) -> Result<u32, Error<SPI::Error>> {
+        let card = self.card()?;
+        let end = u64::try_from(count)
+            .ok()
+            .and_then(|count| u64::from(block).checked_add(count));
+        if end.is_none_or(|end| end > card.num_blocks) {
+            return Err(Error::OutOfRange);
+        }
+
+        if card.card_type == CardType::Sdhc {
+            Ok(block)
+        } else {
+            block.checked_mul(BLOCK_LEN).ok_or(Error::OutOfRange)
+        }
+    }
+
+    /// Sends a command, and returns its R1 response.
+    ///
+    /// Other response bytes, and data blocks, are to be read afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Timeout`] if the card does not respond.
+    async fn command(&mut self, command: u8, arg: u32) -> Result<u8, Error<SPI::Error>> {
+        // The card only accepts commands once done with the previous ones.
+        if command != GO_IDLE_STATE {
+            self.wait_not_busy(WRITE_TIMEOUT).await?;
+        }
+
+        self.spi
+            .write(&command_frame(command, arg))
+            .await
+            .map_err(Error::Spi)?;
+
+        for _ in 0..RESPONSE_BYTES {
+            let r1 = self.read_byte().await?;
+            if r1 & 0x80 == 0 {
+                return Ok(r1);
+            }
+        }
+        Err(Error::Timeout)
+    }
+
+    /// Sends a command, checking that its R1 response does not report an error.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Command`] if the card reports an error.
+    async fn checked_command(&mut self, command: u8, arg: u32) -> Result<(), Error<SPI::Error>> {
+        match self.command(command, arg).await? {
+            0 => Ok(()),
+            response => Err(Error::Command { command, response }),
+        }
+    }
+
+    /// Stops a multiple block read.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Command`] if the card reports an error.
+    async fn stop_transmission(&mut self) -> Result<(), Error<SPI::Error>> {
+        // The byte following the command is a stuff byte.
+        self.spi
+            .transaction(&mut [
+                Operation::Write(&command_frame(STOP_TRANSMISSION, 0)),
+                Operation::Write(&[0xff]),
+            ])
+            .await
+            .map_err(Error::Spi)?;
+
+        for _ in 0..RESPONSE_BYTES {
+            match self.read_byte().await? {
+                r1 if r1 & 0x80 != 0 => {}
+                0 => return self.wait_not_busy(WRITE_TIMEOUT).await,
+                response => {
+                    return Err(Error::Command {
+                        command: STOP_TRANSMISSION,
+                        response,
+                    });
+                }
+            }
+        }
+        Err(Error::Timeout)
+    }
+
+    /// Reads a data block into `data`, discarding its CRC.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Read`] if the card sends an error token, and [`Error::Timeout`] if it
+    /// sends nothing.
+    async fn read_data(&mut self, data: &mut [u8]) -> Result<(), Error<SPI::Error>> {
+        let deadline = Instant::now() + READ_TIMEOUT;
+        loop {
+            match self.read_byte().await? {
+                START_BLOCK_TOKEN => break,
+                0xff if Instant::now() < deadline => {}
+                0xff => return Err(Error::Timeout),
+                token => return Err(Error::Read(token)),
+            }
+        }
+
+        // Keep the data line high while reading.
+        data.fill(0xff);
+        let mut crc = [0xff; 2];
+        self.spi
+            .transaction(&mut [
+                Operation::TransferInPlace(data),
+                Operation::TransferInPlace(&mut crc),
+            ])
+            .await
+            .map_err(Error::Spi)
+    }
+
+    /// Writes a data block, starting with `token`, and waits for it to be programmed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Write`] if the card rejects the block.
+    async fn write_data(&mut self, token: u8, data: &[u8]) -> Result<(), Error<SPI::Error>> {
+        // CRCs are not checked by cards in SPI mode, unless enabled.
+        self.spi
+            .transaction(&mut [
+                Operation::Write(&[0xff, token]),
+                Operation::Write(data),
+                Operation::Write(&[0xff; 2]),
+            ])
+            .await
+            .map_err(Error::Spi)?;
+
+        match self.read_byte().await? & 0x1f {
+            DATA_ACCEPTED => self.wait_not_busy(WRITE_TIMEOUT).await,
+            response => Err(Error::Write(response)),
+        }
+    }
+
+    /// Waits for the card to release the data line, which it holds low while busy.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Timeout`] if the card is still busy after `timeout`.
+    async fn wait_not_busy(&mut self, timeout: Duration) -> Result<(), Error<SPI::Error>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.read_byte().await? == 0xff {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(Error::Timeout);
+            }
+        }
+    }
+
+    /// Reads a byte, keeping the data line high.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Spi`] if the SPI device fails.
+    async fn read_byte(&mut self) -> Result<u8, Error<SPI::Error>> {
+        let mut byte = [0xff];
+        self.spi
+            .transfer_in_place(&mut byte)
+            .await
+            .map_err(Error::Spi)?;
+        let [byte] = byte;
+        Ok(byte)
+    }
+}
+
+impl<SPI: SpiDevice, CD: InputPin> BlockDevice<BLOCK_SIZE> for SdCard<SPI, CD> {
+    type Error = Error<SPI::Error>;
+    type Align = A1;
+
+    async fn read(
+        &mut self,
+        block_address: u32,
+        data: &mut [Aligned<A1, [u8; BLOCK_SIZE]>],
+    ) -> Result<(), Self::Error> {
+        let address = self.address(block_address, data.len())?;
+        match data {
+            [] => Ok(()),
+            [block] => {
+                self.checked_command(READ_SINGLE_BLOCK, address).await?;
+                self.read_data(&mut **block).await
+            }
+            blocks => {
+                self.checked_command(READ_MULTIPLE_BLOCK, address).await?;
+                let mut res = Ok(());
+                for block in blocks {
+                    res = self.read_data(&mut **block).await;
+                    if res.is_err() {
+                        break;
+                    }
+                }
+                // The transmission is stopped even after errors, to get the card back to the
+                // transfer state.
+                self.stop_transmission().await?;
+                res
+            }
+        }
+    }
+
+    async fn write(
+        &mut self,
+        block_address: u32,
+        data: &[Aligned<A1, [u8; BLOCK_SIZE]>],
+    ) -> Result<(), Self::Error> {
+        let address = self.address(block_address, data.len())?;
+        match data {
+            [] => Ok(()),
+            [block] => {
+                self.checked_command(WRITE_BLOCK, address).await?;
+                self.write_data(START_BLOCK_TOKEN, &**block).await
+            }
+            blocks => {
+                self.checked_command(WRITE_MULTIPLE_BLOCK, address).await?;
+                let mut res = Ok(());
+                for block in blocks {
+                    res = self.write_data(START_BLOCK_MULTIPLE_TOKEN, &**block).await;
+                    if res.is_err() {
+                        break;
+                    }
+                }
+                // One byte is clocked before the card signals being busy.
+                self.spi
+                    .write(&[STOP_TRAN_TOKEN, 0xff])
+                    .await
+                    .map_err(Error::Spi)?;
+                self.wait_not_busy(WRITE_TIMEOUT).await?;
+                res
+            }
+        }
+    }
+
+    async fn size(&mut self) -> Result<u64, Self::Error> {
+        Ok(self.card()?.num_blocks * BLOCK_SIZE as u64)
+    }
+}
+
+/// Returns the frame of a command, made of its index, its argument and its CRC7.
+fn command_frame(command: u8, arg: u32) -> [u8; 6] {
+    let index = 0x40 | command;
+    let [a, b, c, d] = arg.to_be_bytes();
+    [index, a, b, c, d, crc7(&[index, a, b, c, d])]
+}
+
+/// Computes the CRC7 of a command, returned along with the end bit.
+fn crc7(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for byte in data {
+        let mut byte = *byte;
+        for _ in 0..8 {
+            crc <<= 1;
+            if (byte ^ crc) & 0x80 != 0 {
+                crc ^= 0x09;
+            }
+            byte <<= 1;
+        }
+    }
+    (crc << 1) | 1
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, VecDeque};
+
+    use embassy_futures::block_on;
+    use embedded_hal_async::spi::ErrorType as SpiErrorType;
+
+    use super::*;
+
+    /// Card simulated at the byte level, implementing the SPI mode protocol.
+    struct SimulatedCard {
+        card_type: CardType,
+        csd: [u8; 16],
+        /// Number of `SD_SEND_OP_COND` or `SEND_OP_COND` commands before leaving the idle state.
+        init_polls: usize,
+        blocks: BTreeMap<u32, [u8; BLOCK_SIZE]>,
+        idle: bool,
+        app_command: bool,
+        state: State,
+        command: Vec<u8>,
+        miso: VecDeque<u8>,
+        /// Indexes of the commands received.
+        commands: Vec<u8>,
+    }
+
+    enum State {
+        Command,
+        ReadMultiple {
+            block: u32,
+        },
+        AwaitingToken {
+            block: u32,
+            multiple: bool,
+        },
+        ReceivingData {
+            block: u32,
+            multiple: bool,
+            data: Vec<u8>,
+        },
+    }
+
+    impl SimulatedCard {
+        fn new(card_type: CardType) -> Self {
+            let csd = match card_type {
+                // C_SIZE = 0x1d8a
+                CardType::Sdhc => [
+                    0x40, 0x0e, 0x00, 0x32, 0x5b, 0x59, 0x00, 0x00, 0x1d, 0x8a, 0x7f, 0x80, 0x0a,
+                    0x40, 0x00, 0x8b,
+                ],
+                // READ_BL_LEN = 9, C_SIZE = 0xf1f, C_SIZE_MULT = 7
+                CardType::Sdsc | CardType::Mmc => [
+                    0x00, 0x26, 0x00, 0x32, 0x5f, 0x59, 0x83, 0xc7, 0xfe, 0xfb, 0xcf, 0xff, 0x92,
+                    0x40, 0x40, 0xdf,
+                ],
+            };
+            Self {
+                card_type,
+                csd,
+                init_polls: 3,
+                blocks: BTreeMap::new(),
+                idle: false,
+                app_command: false,
+                state: State::Command,
+                command: Vec::new(),
+                miso: VecDeque::new(),
+                commands: Vec::new(),
+            }
+        }
+
+        fn block(&self, block: u32) -> [u8; BLOCK_SIZE] {
+            self.blocks.get(&block).copied().unwrap_or([0; BLOCK_SIZE])
+        }
+
+        fn respond(&mut self, bytes: &[u8]) {
+            // N_CR of one byte.
+            self.miso.push_back(0xff);
+            self.miso.extend(bytes);
+        }
+
+        fn send_block(&mut self, data: &[u8]) {
+            self.miso.extend([0xff, 0xff, START_BLOCK_TOKEN]);
+            self.miso.extend(data);
+            self.miso.extend([0x12, 0x34]);
+        }
+
+        fn exchange(&mut self, mosi: u8) -> u8 {
+            if let State::ReadMultiple { block } = self.state
+                && self.miso.is_empty()
+            {
+                self.send_block(&self.block(block));
+                self.state = State::ReadMultiple { block: block + 1 };
+            }
+            let miso = self.miso.pop_front().unwrap_or(0xff);
+
+            match &mut self.state {
+                State::Command | State::ReadMultiple { .. } => {
+                    if !self.command.is_empty() || mosi & 0xc0 == 0x40 {
+                        self.command.push(mosi);
+                    }
+                    if self.command.len() == 6 {
+                        let frame = std::mem::take(&mut self.command);
+                        self.handle_command(&frame);
+                    }
+                }
+                State::AwaitingToken { block, multiple } => match mosi {
+                    START_BLOCK_TOKEN if !*multiple => {
+                        self.state = State::ReceivingData {
+                            block: *block,
+                            multiple: false,
+                            data: Vec::new(),
+                        };
+                    }
+                    START_BLOCK_MULTIPLE_TOKEN if *multiple => {
+                        self.state = State::ReceivingData {
+                            block: *block,
+                            multiple: true,
+                            data: Vec::new(),
+                        };
+                    }
+                    STOP_TRAN_TOKEN if *multiple => {
+                        self.miso.extend([0xff, 0x00, 0x00]);
+                        self.state = State::Command;
+                    }
+                    0xff => {}
+                    token => panic!("unexpected token {token:#04x}"),
+                },
+                State::ReceivingData {
+                    block,
+                    multiple,
+                    data,
+                } => {
+                    data.push(mosi);
+                    // Data followed by its CRC.
+                    if data.len() == BLOCK_SIZE + 2 {
+                        let (block, multiple) = (*block, *multiple);
+                        let mut content = [0; BLOCK_SIZE];
+                        content.copy_from_slice(&data[..BLOCK_SIZE]);
+                        self.blocks.insert(block, content);
+                        // Data accepted, then busy.
+                        self.miso.extend([0xe5, 0x00, 0x00, 0x00]);
+                        self.state = if multiple {
+                            State::AwaitingToken {
+                                block: block + 1,
+                                multiple: true,
+                            }
+                        } else {
+                            State::Command
+                        };
+                    }
+                }
+            }
+            miso
+        }
+
+        fn handle_command(&mut self, frame: &[u8]) {
+            let command = frame[0] & 0x3f;
+            let arg = u32::from_be_bytes(frame[1..5].try_into().unwrap());
+            self.commands.push(command);
+
+            // CRCs are checked for these commands, even in SPI mode.
+            match command {
+                GO_IDLE_STATE => assert_eq!(frame[5], 0x95),
+                SEND_IF_COND => assert_eq!(frame[5], 0x87),
+                _ => {}
+            }
+
+            let app_command = std::mem::take(&mut self.app_command);
+            let r1 = u8::from(self.idle);
+            let illegal = r1 | R1_ILLEGAL_COMMAND;
+
+            match command {
+                GO_IDLE_STATE => {
+                    self.idle = true;
+                    self.state = State::Command;
+                    self.miso.clear();
+                    self.respond(&[R1_IDLE]);
+                }
+                SEND_IF_COND if self.card_type == CardType::Sdhc => {
+                    let [_, _, voltage, pattern] = arg.to_be_bytes();
+                    self.respond(&[r1, 0x00, 0x00, voltage, pattern]);
+                }
+                APP_CMD if self.card_type != CardType::Mmc => {
+                    self.app_command = true;
+                    self.respond(&[r1]);
+                }
+                SD_SEND_OP_COND if app_command => self.op_cond(),
+                SEND_OP_COND if self.card_type == CardType::Mmc => self.op_cond(),
+                READ_OCR => {
+                    let ccs = if self.card_type == CardType::Sdhc {
+                        0xc0
+                    } else {
+                        0x80
+                    };
+                    self.respond(&[r1, ccs, 0xff, 0x80, 0x00]);
+                }
+                SET_BLOCKLEN if self.card_type != CardType::Sdhc => {
+                    assert_eq!(arg, BLOCK_LEN);
+                    self.respond(&[r1]);
+                }
+                SEND_CSD => {
+                    self.respond(&[r1]);
+                    self.send_block(&self.csd.clone());
+                }
+                READ_SINGLE_BLOCK => {
+                    self.respond(&[r1]);
+                    self.send_block(&self.block(self.block_of(arg)));
+                }
+                READ_MULTIPLE_BLOCK => {
+                    self.respond(&[r1]);
+                    self.state = State::ReadMultiple {
+                        block: self.block_of(arg),
+                    };
+                }
+                STOP_TRANSMISSION => {
+                    self.state = State::Command;
+                    self.miso.clear();
+                    // Stuff byte, then R1b.
+                    self.miso.extend([0xaa, 0xff, r1, 0x00, 0x00]);
+                }
+                WRITE_BLOCK | WRITE_MULTIPLE_BLOCK => {
+                    self.respond(&[r1]);
+                    self.state = State::AwaitingToken {
+                        block: self.block_of(arg),
+                        multiple: command == WRITE_MULTIPLE_BLOCK,
+                    };
+                }
+                _ => self.respond(&[illegal]),
+            }
+        }
+
+        /// Returns the block addressed by the argument of a command.
+        fn block_of(&self, arg: u32) -> u32 {
+            if self.card_type == CardType::Sdhc {
+                arg
+            } else {
+                assert_eq!(arg % BLOCK_LEN, 0, "unaligned byte address");
+                arg / BLOCK_LEN
+            }
+        }
+
+        fn op_cond(&mut self) {
+            if self.init_polls == 0 {
+                self.idle = false;
+            } else {
+                self.init_polls -= 1;
+            }
+            self.respond(&[u8::from(self.idle)]);
+        }
+    }
+
+    impl SpiErrorType for SimulatedCard {
+        type Error = Infallible;
+    }
+
+    impl SpiDevice for SimulatedCard {
+        async fn transaction(
+            &mut self,
+            operations: &mut [Operation<'_, u8>],
+        ) -> Result<(), Self::Error> {
+            for operation in operations {
+                match operation {
+                    Operation::Write(data) => {
+                        for byte in *data {
+                            self.exchange(*byte);
+                        }
+                    }
+                    Operation::TransferInPlace(data) => {
+                        for byte in data.iter_mut() {
+                            *byte = self.exchange(*byte);
+                        }
+                    }
+                    Operation::Read(_) => panic!("the data line must be kept high when reading"),
+                    Operation::Transfer(_, _) => unimplemented!(),
+                    Operation::DelayNs(_) => {}
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Card detect switch, low when a card is inserted.
+    struct Switch(bool);
+
+    impl ErrorType for Switch {
+        type Error = Infallible;
+    }
+
+    impl InputPin for Switch {
+        fn is_high(&mut self) -> Result<bool, Self::Error> {
+            Ok(!self.0)
+        }
+
+        fn is_low(&mut self) -> Result<bool, Self::Error> {
+            Ok(self.0)
+        }
+    }
+
+    fn blocks<const N: usize>(first: u8) -> [Aligned<A1, [u8; BLOCK_SIZE]>; N] {
+        core::array::from_fn(|i| {
+            Aligned([first.wrapping_add(u8::try_from(i).unwrap()); BLOCK_SIZE])
+        })
+    }
+
+    #[test]
+    fn crc() {
+        assert_eq!(command_frame(GO_IDLE_STATE, 0)[5], 0x95);
+        assert_eq!(command_frame(SEND_IF_COND, IF_COND_ARG)[5], 0x87);
+    }
+
+    #[test]
+    fn init() {
+        for (card_type, num_blocks) in [
+            (CardType::Sdhc, 7563 * 1024),
+            (CardType::Sdsc, 3872 * 512),
+            (CardType::Mmc, 3872 * 512),
+        ] {
+            let mut card = SdCard::new(SimulatedCard::new(card_type));
+            block_on(card.init()).unwrap();
+            assert_eq!(card.card_type(), Some(card_type));
+            assert_eq!(card.num_blocks(), Some(num_blocks));
+            assert_eq!(block_on(card.size()), Ok(num_blocks * 512));
+        }
+    }
+
+    #[test]
+    fn read_write_single_block() {
+        for card_type in [CardType::Sdhc, CardType::Sdsc] {
+            let mut card = SdCard::new(SimulatedCard::new(card_type));
+            block_on(card.init()).unwrap();
+
+            block_on(card.write(3, &blocks::<1>(0x42))).unwrap();
+            assert_eq!(card.spi.block(3), [0x42; BLOCK_SIZE]);
+            assert_eq!(card.spi.block(4), [0; BLOCK_SIZE]);
+
+            let mut read = blocks::<1>(0);
+            block_on(card.read(3, &mut read)).unwrap();
+            assert_eq!(*read[0], [0x42; BLOCK_SIZE]);
+            assert!(
+                card.spi
+                    .commands
+                    .ends_with(&[WRITE_BLOCK, READ_SINGLE_BLOCK])
+            );
+        }
+    }
+
+    #[test]
+    fn read_write_multiple_blocks() {
+        let mut card = SdCard::new(SimulatedCard::new(CardType::Sdhc));
+        block_on(card.init()).unwrap();
+
+        block_on(card.write(10, &blocks::<3>(1))).unwrap();
+        for (block, content) in [(9, 0), (10, 1), (11, 2), (12, 3), (13, 0)] {
+            assert_eq!(card.spi.block(block), [content; BLOCK_SIZE]);
+        }
+
+        let mut read = blocks::<4>(0xff);
+        block_on(card.read(9, &mut read)).unwrap();
+        for (block, content) in read.iter().zip([0, 1, 2, 3]) {
+            assert_eq!(**block, [content; BLOCK_SIZE]);
+        }
+        assert!(card.spi.commands.ends_with(&[
+            WRITE_MULTIPLE_BLOCK,
+            READ_MULTIPLE_BLOCK,
+            STOP_TRANSMISSION
+        ]));
+
+        // The card is back in the transfer state.
+        let mut read = blocks::<1>(0xff);
+        block_on(card.read(11, &mut read)).unwrap();
+        assert_eq!(*read[0], [2; BLOCK_SIZE]);
+    }
+
+    #[test]
+    fn out_of_range() {
+        let mut card = SdCard::new(SimulatedCard::new(CardType::Sdsc));
+        block_on(card.init()).unwrap();
+
+        let last = u32::try_from(card.num_blocks().unwrap()).unwrap() - 1;
+        let mut read = blocks::<2>(0);
+        assert_eq!(block_on(card.read(last, &mut read)), Err(Error::OutOfRange));
+        block_on(card.read(last - 1, &mut read)).unwrap();
+    }
+
+    #[test]
+    fn not_initialized() {
+        let mut card = SdCard::new(SimulatedCard::new(CardType::Sdhc));
+        let mut read = blocks::<1>(0);
+        assert_eq!(
+            block_on(card.read(0, &mut read)),
+            Err(Error::NotInitialized)
+        );
+    }
+
+    #[test]
+    fn card_detect() {
+        let mut card = SdCard::with_card_detect(SimulatedCard::new(CardType::Sdhc), Switch(false));
+        assert_eq!(block_on(card.init()), Err(Error::NoCard));
+
+        card.card_detect.0 = true;
+        block_on(card.init()).unwrap();
+
+        // Removing the card requires initializing it again.
+        card.card_detect.0 = false;
+        assert!(!card.is_present());
+        card.card_detect.0 = true;
+        let mut read = blocks::<1>(0);
+        assert_eq!(
+            block_on(card.read(0, &mut read)),
+            Err(Error::NotInitialized)
+        );
+    }
+
+    #[test]
+    fn no_response() {
+        let mut card = SdCard::new(SimulatedCard::new(CardType::Sdhc));
+        // A card that never leaves the idle state.
+        card.spi.init_polls = usize::MAX;
+        assert_eq!(block_on(card.init()), Err(Error::Timeout));
+    }
+}
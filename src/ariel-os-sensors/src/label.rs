@@ -47,6 +47,8 @@ pub enum Label {
     Temperature,
     /// Vertical speed.
     VerticalSpeed,
+    /// Voltage.
+    Voltage,
     /// X axis.
     X,
     /// Y axis.
@@ -76,6 +78,7 @@ impl core::fmt::Display for Label {
             Self::Heading => write!(f, "Heading"),
             Self::Temperature => write!(f, "Temperature"),
             Self::VerticalSpeed => write!(f, "Vertical speed"),
+            Self::Voltage => write!(f, "Voltage"),
             Self::X => write!(f, "X"),
             Self::Y => write!(f, "Y"),
             Self::Z => write!(f, "Z"),
@@ -0,0 +1,84 @@
+//! Splits command lines into arguments.
+
+use heapless::Vec;
+
+/// Maximum number of arguments on a command line, including the command name.
+pub(crate) const MAX_ARGS: usize = 16;
+
+/// Errors returned when splitting a command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SplitError {
+    /// There are more than [`MAX_ARGS`] arguments.
+    TooManyArguments,
+    /// A double quote is not closed.
+    UnterminatedQuote,
+}
+
+impl core::fmt::Display for SplitError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::TooManyArguments => write!(f, "too many arguments (at most {MAX_ARGS})"),
+            Self::UnterminatedQuote => write!(f, "unterminated quote"),
+        }
+    }
+}
+
+/// Splits a command line into whitespace-separated arguments.
+///
+/// Arguments containing whitespace can be enclosed in double quotes.
+///
+/// # Errors
+///
+/// Returns an error when the command line cannot be split into at most [`MAX_ARGS`] arguments.
+pub(crate) fn split(line: &str) -> Result<Vec<&str, MAX_ARGS>, SplitError> {
+    let mut args = Vec::new();
+    let mut rest = line.trim_start();
+
+    while !rest.is_empty() {
+        let (arg, remainder) = if let Some(quoted) = rest.strip_prefix('"') {
+            quoted
+                .split_once('"')
+                .ok_or(SplitError::UnterminatedQuote)?
+        } else {
+            rest.split_once(char::is_whitespace).unwrap_or((rest, ""))
+        };
+
+        args.push(arg).map_err(|_| SplitError::TooManyArguments)?;
+        rest = remainder.trim_start();
+    }
+
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_words() {
+        assert_eq!(split("").unwrap(), [] as [&str; 0]);
+        assert_eq!(split("  ps ").unwrap(), ["ps"]);
+        assert_eq!(
+            split("storage get  key").unwrap(),
+            ["storage", "get", "key"]
+        );
+    }
+
+    #[test]
+    fn split_quoted() {
+        assert_eq!(
+            split(r#"storage set key "two words""#).unwrap(),
+            ["storage", "set", "key", "two words"],
+        );
+        assert_eq!(split(r#""" x"#).unwrap(), ["", "x"]);
+        assert_eq!(split(r#"a "b"#), Err(SplitError::UnterminatedQuote));
+    }
+
+    #[test]
+    fn split_too_many() {
+        let line = "a ".repeat(MAX_ARGS);
+        assert_eq!(split(&line).unwrap().len(), MAX_ARGS);
+        let line = "a ".repeat(MAX_ARGS + 1);
+        assert_eq!(split(&line), Err(SplitError::TooManyArguments));
+    }
+}
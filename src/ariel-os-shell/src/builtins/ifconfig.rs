@@ -0,0 +1,107 @@
+//! Shows the configuration of the network interface.
+//!
+//! The network stack can only be accessed from the system executor, so a task takes a snapshot of
+//! its configuration when requested by the shell thread.
+
+use core::fmt::Write;
+
+use ariel_os_embassy::reexports::embassy_net::HardwareAddress;
+#[cfg(feature = "ipv4")]
+use ariel_os_embassy::reexports::embassy_net::StaticConfigV4;
+#[cfg(feature = "ipv6")]
+use ariel_os_embassy::reexports::embassy_net::StaticConfigV6;
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+
+use crate::{COMMANDS, Command, Error};
+
+#[linkme::distributed_slice(COMMANDS)]
+static IFCONFIG: Command = Command::new(
+    "ifconfig",
+    "",
+    "Shows the configuration of the network interface",
+    ifconfig,
+);
+
+/// Snapshot of the configuration of the network interface.
+struct Interface {
+    link_up: bool,
+    hardware_address: HardwareAddress,
+    #[cfg(feature = "ipv4")]
+    ipv4: Option<StaticConfigV4>,
+    #[cfg(feature = "ipv6")]
+    ipv6: Option<StaticConfigV6>,
+}
+
+static REQUEST: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+static RESPONSE: Signal<CriticalSectionRawMutex, Option<Interface>> = Signal::new();
+
+#[ariel_os_macros::task(autostart)]
+async fn network_snapshot() {
+    loop {
+        REQUEST.wait().await;
+
+        let interface = ariel_os_embassy::net::network_stack()
+            .await
+            .map(|stack| Interface {
+                link_up: stack.is_link_up(),
+                hardware_address: stack.hardware_address(),
+                #[cfg(feature = "ipv4")]
+                ipv4: stack.config_v4(),
+                #[cfg(feature = "ipv6")]
+                ipv6: stack.config_v6(),
+            });
+
+        RESPONSE.signal(interface);
+    }
+}
+
+fn ifconfig(out: &mut dyn Write, args: &[&str]) -> Result<(), Error> {
+    if !args.is_empty() {
+        return Err(Error::InvalidArguments);
+    }
+
+    REQUEST.signal(());
+    let Some(interface) = ariel_os_threads::block_on(RESPONSE.wait()) else {
+        writeln!(out, "no network interface")?;
+        return Err(Error::Failed);
+    };
+
+    let link = if interface.link_up { "up" } else { "down" };
+    writeln!(
+        out,
+        "link {link}, hardware address {}",
+        interface.hardware_address
+    )?;
+
+    #[cfg(feature = "ipv4")]
+    match interface.ipv4 {
+        Some(config) => {
+            write!(out, "inet {}", config.address)?;
+            if let Some(gateway) = config.gateway {
+                write!(out, " gateway {gateway}")?;
+            }
+            for dns_server in config.dns_servers {
+                write!(out, " dns {dns_server}")?;
+            }
+            writeln!(out)?;
+        }
+        None => writeln!(out, "inet not configured")?,
+    }
+
+    #[cfg(feature = "ipv6")]
+    match interface.ipv6 {
+        Some(config) => {
+            write!(out, "inet6 {}", config.address)?;
+            if let Some(gateway) = config.gateway {
+                write!(out, " gateway {gateway}")?;
+            }
+            for dns_server in config.dns_servers {
+                write!(out, " dns {dns_server}")?;
+            }
+            writeln!(out)?;
+        }
+        None => writeln!(out, "inet6 not configured")?,
+    }
+
+    Ok(())
+}
@@ -0,0 +1,49 @@
+//! Provides the built-in shell commands.
+//!
+//! The `shell_command` attribute macro cannot be used in this crate itself, so these commands are
+//! registered manually.
+
+#[cfg(feature = "net")]
+mod ifconfig;
+mod ps;
+#[cfg(feature = "sensors")]
+mod sensors;
+#[cfg(feature = "storage")]
+mod storage;
+
+use core::fmt::Write;
+
+use crate::{COMMANDS, Command, Error, Synopsis};
+
+#[linkme::distributed_slice(COMMANDS)]
+static HELP: Command = Command::new("help", "", "Lists the available commands", help);
+
+#[linkme::distributed_slice(COMMANDS)]
+static REBOOT: Command = Command::new("reboot", "", "Reboots the device", reboot);
+
+fn help(out: &mut dyn Write, args: &[&str]) -> Result<(), Error> {
+    if !args.is_empty() {
+        return Err(Error::InvalidArguments);
+    }
+
+    let width = crate::commands()
+        .map(|command| Synopsis(command).len())
+        .max()
+        .unwrap_or_default();
+
+    for command in crate::commands() {
+        let synopsis = Synopsis(command);
+        let padding = width - synopsis.len();
+        writeln!(out, "{synopsis}{:padding$}  {}", "", command.help())?;
+    }
+
+    Ok(())
+}
+
+fn reboot(_out: &mut dyn Write, args: &[&str]) -> Result<(), Error> {
+    if !args.is_empty() {
+        return Err(Error::InvalidArguments);
+    }
+
+    ariel_os_power::reboot()
+}
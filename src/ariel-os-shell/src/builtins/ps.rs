@@ -0,0 +1,61 @@
+//! Lists threads.
+
+use core::fmt::Write;
+
+use ariel_os_threads::{THREAD_COUNT, ThreadId, ThreadState};
+
+use crate::{COMMANDS, Command, Error};
+
+#[linkme::distributed_slice(COMMANDS)]
+static PS: Command = Command::new(
+    "ps",
+    "",
+    "Lists threads, with their state, priority and peak stack usage",
+    ps,
+);
+
+fn ps(out: &mut dyn Write, args: &[&str]) -> Result<(), Error> {
+    if !args.is_empty() {
+        return Err(Error::InvalidArguments);
+    }
+
+    writeln!(out, "tid  state       prio  stack peak/size")?;
+
+    let current = ariel_os_threads::current_tid();
+    for thread_id in (0..THREAD_COUNT).filter_map(|id| u8::try_from(id).ok()) {
+        let thread_id = ThreadId::new(thread_id);
+        let Some(state) = ariel_os_threads::get_state(thread_id) else {
+            continue;
+        };
+        let priority = ariel_os_threads::get_priority(thread_id).map_or(0, usize::from);
+        let size = ariel_os_threads::stack_limits(thread_id)
+            .map_or(0, |(lowest, highest)| highest - lowest);
+        let peak = ariel_os_rt::stack::thread_peak_usage(thread_id).unwrap_or_default();
+        let marker = if current == Some(thread_id) { '*' } else { ' ' };
+
+        writeln!(
+            out,
+            "{:>3}{marker} {:<11} {priority:>4}  {peak:>5}/{size}",
+            usize::from(thread_id),
+            state_name(state),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn state_name(state: ThreadState) -> &'static str {
+    match state {
+        ThreadState::Invalid => "invalid",
+        ThreadState::Running => "running",
+        ThreadState::Parked => "parked",
+        ThreadState::LockBlocked => "lock",
+        ThreadState::FlagBlocked(_) => "flags",
+        ThreadState::ChannelRxBlocked(_) => "channel rx",
+        ThreadState::ChannelTxBlocked(_) => "channel tx",
+        ThreadState::WaitQueueBlocked => "wait queue",
+        ThreadState::QueueRxBlocked => "queue rx",
+        ThreadState::QueueTxBlocked => "queue tx",
+        ThreadState::JoinBlocked => "join",
+    }
+}
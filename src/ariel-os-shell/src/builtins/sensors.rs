@@ -0,0 +1,88 @@
+//! Reads all registered sensors.
+
+use core::fmt::Write;
+
+use ariel_os_sensors::{Reading as _, Sensor, sensor::ReadingChannel};
+use ariel_os_sensors_registry::REGISTRY;
+
+use crate::{COMMANDS, Command, Error};
+
+const DEFAULT_SENSOR_DISPLAY_NAME: &str = "unknown";
+const DEFAULT_SENSOR_LABEL: &str = "no label";
+
+#[linkme::distributed_slice(COMMANDS)]
+static SENSORS: Command = Command::new("sensors", "", "Reads all registered sensors", sensors);
+
+fn sensors(out: &mut dyn Write, args: &[&str]) -> Result<(), Error> {
+    if !args.is_empty() {
+        return Err(Error::InvalidArguments);
+    }
+
+    // Trigger measurements for each sensor driver in parallel.
+    for sensor in REGISTRY.sensors() {
+        if let Err(err) = sensor.trigger_measurement() {
+            writeln!(out, "{}: {err}", Name(sensor))?;
+        }
+    }
+
+    // Then, collect and display the readings one at a time.
+    for sensor in REGISTRY.sensors() {
+        match ariel_os_threads::block_on(sensor.wait_for_reading()) {
+            Ok(samples) => {
+                for (channel, sample) in samples.samples() {
+                    write!(out, "{}: ", Name(sensor))?;
+                    match sample.value() {
+                        Ok(value) => write_value(out, value, channel)?,
+                        Err(err) => write!(out, "{err}")?,
+                    }
+                    writeln!(out, " ({})", channel.label())?;
+                }
+            }
+            Err(err) => writeln!(out, "{}: {err}", Name(sensor))?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Formats the display name and label of a sensor driver instance.
+struct Name(&'static dyn Sensor);
+
+impl core::fmt::Display for Name {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let display_name = self.0.display_name().unwrap_or(DEFAULT_SENSOR_DISPLAY_NAME);
+        let label = self.0.label().unwrap_or(DEFAULT_SENSOR_LABEL);
+        write!(f, "{display_name} ({label})")
+    }
+}
+
+/// Writes a sample value, applying the scaling of its channel.
+///
+/// # Errors
+///
+/// Returns an error if writing to the output fails.
+fn write_value(out: &mut dyn Write, value: i32, channel: ReadingChannel) -> core::fmt::Result {
+    let scaling = channel.scaling();
+    let unit = channel.unit();
+
+    if scaling >= 0 {
+        write!(out, "{value}")?;
+        for _ in 0..scaling {
+            out.write_char('0')?;
+        }
+    } else if let Some(divisor) = 10u64.checked_pow(u32::from(scaling.unsigned_abs())) {
+        let decimals = usize::from(scaling.unsigned_abs());
+        let magnitude = u64::from(value.unsigned_abs());
+        let sign = if value < 0 { "-" } else { "" };
+        write!(
+            out,
+            "{sign}{}.{:0decimals$}",
+            magnitude / divisor,
+            magnitude % divisor,
+        )?;
+    } else {
+        write!(out, "{value}e{scaling}")?;
+    }
+
+    write!(out, " {unit}")
+}
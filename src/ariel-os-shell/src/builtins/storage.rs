@@ -0,0 +1,80 @@
+//! Accesses values in storage.
+//!
+//! Only string values can be read and written from the shell.
+
+use core::fmt::Write;
+
+use crate::{COMMANDS, Command, Error};
+
+/// Type of the values read from storage, limiting their length to 128 bytes.
+type Value = heapless::String<128>;
+
+#[cfg(not(context = "stm32"))]
+const USAGE: &str = "ls [prefix] | get <key> | set <key> <value> | rm <key>";
+// STM32 flash drivers do not allow removing values.
+#[cfg(context = "stm32")]
+const USAGE: &str = "ls [prefix] | get <key> | set <key> <value>";
+
+#[linkme::distributed_slice(COMMANDS)]
+static STORAGE: Command = Command::new(
+    "storage",
+    USAGE,
+    "Lists keys, or gets, sets or removes string values from storage",
+    storage,
+);
+
+fn storage(out: &mut dyn Write, args: &[&str]) -> Result<(), Error> {
+    use ariel_os_threads::block_on;
+
+    match args {
+        ["ls"] => list(out, "")?,
+        ["ls", prefix] => list(out, prefix)?,
+        ["get", key] => match block_on(ariel_os_storage::get::<Value>(key)) {
+            Ok(Some(value)) => writeln!(out, "{value}")?,
+            Ok(None) => {
+                writeln!(out, "{key}: not found")?;
+                return Err(Error::Failed);
+            }
+            Err(err) => return failed(out, &err),
+        },
+        ["set", key, value] => {
+            block_on(ariel_os_storage::insert(key, *value)).or_else(|err| failed(out, &err))?;
+        }
+        #[cfg(not(context = "stm32"))]
+        ["rm", key] => {
+            block_on(ariel_os_storage::remove(key)).or_else(|err| failed(out, &err))?;
+        }
+        _ => return Err(Error::InvalidArguments),
+    }
+
+    Ok(())
+}
+
+/// Lists the keys starting with `prefix`.
+///
+/// # Errors
+///
+/// Returns an error if reading from storage fails.
+fn list(out: &mut dyn Write, prefix: &str) -> Result<(), Error> {
+    use ariel_os_threads::block_on;
+
+    let mut storage = block_on(ariel_os_storage::lock());
+    let mut keys = storage.keys(prefix);
+    loop {
+        match block_on(keys.next()) {
+            Ok(Some(key)) => writeln!(out, "{key}")?,
+            Ok(None) => return Ok(()),
+            Err(err) => return failed(out, &err),
+        }
+    }
+}
+
+/// Reports a storage error.
+///
+/// # Errors
+///
+/// Always returns an error, as the command failed.
+fn failed(out: &mut dyn Write, err: &impl core::fmt::Debug) -> Result<(), Error> {
+    writeln!(out, "storage error: {err:?}")?;
+    Err(Error::Failed)
+}
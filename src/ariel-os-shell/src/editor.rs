@@ -0,0 +1,444 @@
+//! Provides line editing with history, for VT100-compatible terminals.
+//!
+//! Only printable ASCII characters are accepted in command lines.
+
+#![expect(
+    clippy::missing_errors_doc,
+    reason = "errors can only come from writing to the output"
+)]
+
+use core::fmt::{self, Write};
+
+use heapless::{Deque, Vec};
+
+/// Maximum length of a command line, in bytes.
+pub(crate) const LINE_LEN: usize = 128;
+
+/// Number of command lines kept in the history.
+const HISTORY_LEN: usize = 8;
+
+const ESC: u8 = 0x1b;
+
+/// A command line.
+pub(crate) type Line = Vec<u8, LINE_LEN>;
+
+/// State of the escape sequence parser.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Escape {
+    None,
+    /// `ESC` has been received.
+    Esc,
+    /// Control Sequence Introducer (`ESC [`), with its first numeric parameter if any.
+    Csi(Option<u8>),
+    /// Single Shift Three (`ESC O`).
+    Ss3,
+}
+
+/// Action resulting from a key press or an escape sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Insert(u8),
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Home,
+    End,
+    HistoryPrevious,
+    HistoryNext,
+    KillToEnd,
+    KillToStart,
+    Cancel,
+    Submit,
+}
+
+/// Line editor with history.
+///
+/// Input bytes are fed one at a time with [`Editor::feed()`], which echoes them and writes the
+/// required terminal control sequences to the provided output.
+pub(crate) struct Editor {
+    prompt: &'static str,
+    line: Line,
+    /// Position of the cursor in [`Self::line`].
+    cursor: usize,
+    /// Most recent lines first.
+    history: Deque<Line, HISTORY_LEN>,
+    /// Index in [`Self::history`] of the line being recalled, if any.
+    recalled: Option<usize>,
+    /// Line being edited before starting to browse the history.
+    draft: Line,
+    escape: Escape,
+    last_was_cr: bool,
+}
+
+impl Editor {
+    /// Creates a new editor, showing `prompt` in front of the command line.
+    pub(crate) const fn new(prompt: &'static str) -> Self {
+        Self {
+            prompt,
+            line: Vec::new(),
+            cursor: 0,
+            history: Deque::new(),
+            recalled: None,
+            draft: Vec::new(),
+            escape: Escape::None,
+            last_was_cr: false,
+        }
+    }
+
+    /// Writes the prompt.
+    pub(crate) fn prompt(&self, out: &mut impl Write) -> fmt::Result {
+        out.write_str(self.prompt)
+    }
+
+    /// Processes a byte received from the terminal.
+    ///
+    /// Returns the command line once it has been submitted, which is then added to the history.
+    pub(crate) fn feed(
+        &mut self,
+        byte: u8,
+        out: &mut impl Write,
+    ) -> Result<Option<Line>, fmt::Error> {
+        let Some(action) = self.decode(byte) else {
+            return Ok(None);
+        };
+
+        match action {
+            Action::Insert(byte) => self.insert(byte, out)?,
+            Action::Backspace => {
+                if self.cursor > 0 {
+                    self.cursor -= 1;
+                    self.line.remove(self.cursor);
+                    out.write_char('\x08')?;
+                    self.redraw_from(self.cursor, out)?;
+                }
+            }
+            Action::Delete => {
+                if self.cursor < self.line.len() {
+                    self.line.remove(self.cursor);
+                    self.redraw_from(self.cursor, out)?;
+                }
+            }
+            Action::Left => {
+                if self.cursor > 0 {
+                    self.cursor -= 1;
+                    move_left(out, 1)?;
+                }
+            }
+            Action::Right => {
+                if let Some(&byte) = self.line.get(self.cursor) {
+                    self.cursor += 1;
+                    out.write_char(char::from(byte))?;
+                }
+            }
+            Action::Home => {
+                move_left(out, self.cursor)?;
+                self.cursor = 0;
+            }
+            Action::End => {
+                write_bytes(out, self.line.get(self.cursor..).unwrap_or_default())?;
+                self.cursor = self.line.len();
+            }
+            Action::KillToEnd => {
+                self.line.truncate(self.cursor);
+                out.write_str("\x1b[K")?;
+            }
+            Action::KillToStart => {
+                let mut line = Line::new();
+                // Cannot fail as the new line is shorter.
+                let _ = line.extend_from_slice(self.line.get(self.cursor..).unwrap_or_default());
+                self.replace_line(line, 0, out)?;
+            }
+            Action::HistoryPrevious => {
+                let index = self.recalled.map_or(0, |index| index + 1);
+                if let Some(line) = self.history.iter().nth(index).cloned() {
+                    if self.recalled.is_none() {
+                        self.draft = self.line.clone();
+                    }
+                    self.recalled = Some(index);
+                    let cursor = line.len();
+                    self.replace_line(line, cursor, out)?;
+                }
+            }
+            Action::HistoryNext => match self.recalled {
+                None => {}
+                Some(0) => {
+                    self.recalled = None;
+                    let line = core::mem::take(&mut self.draft);
+                    let cursor = line.len();
+                    self.replace_line(line, cursor, out)?;
+                }
+                Some(index) => {
+                    self.recalled = Some(index - 1);
+                    let line = self
+                        .history
+                        .iter()
+                        .nth(index - 1)
+                        .cloned()
+                        .unwrap_or_default();
+                    let cursor = line.len();
+                    self.replace_line(line, cursor, out)?;
+                }
+            },
+            Action::Cancel => {
+                out.write_str("^C\n")?;
+                self.reset();
+                self.prompt(out)?;
+            }
+            Action::Submit => {
+                out.write_char('\n')?;
+                let line = core::mem::take(&mut self.line);
+                self.reset();
+                self.remember(&line);
+                return Ok(Some(line));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Decodes a received byte, keeping track of escape sequences.
+    fn decode(&mut self, byte: u8) -> Option<Action> {
+        let last_was_cr = core::mem::replace(&mut self.last_was_cr, false);
+
+        match self.escape {
+            Escape::None => {}
+            Escape::Esc => {
+                self.escape = match byte {
+                    b'[' => Escape::Csi(None),
+                    b'O' => Escape::Ss3,
+                    _ => Escape::None,
+                };
+                return None;
+            }
+            Escape::Csi(parameter) => {
+                self.escape = Escape::None;
+                return match byte {
+                    b'0'..=b'9' | b';' => {
+                        self.escape = Escape::Csi(parameter.or(Some(byte)));
+                        None
+                    }
+                    b'A' => Some(Action::HistoryPrevious),
+                    b'B' => Some(Action::HistoryNext),
+                    b'C' => Some(Action::Right),
+                    b'D' => Some(Action::Left),
+                    b'H' => Some(Action::Home),
+                    b'F' => Some(Action::End),
+                    b'~' => match parameter {
+                        Some(b'1' | b'7') => Some(Action::Home),
+                        Some(b'3') => Some(Action::Delete),
+                        Some(b'4' | b'8') => Some(Action::End),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+            }
+            Escape::Ss3 => {
+                self.escape = Escape::None;
+                return match byte {
+                    b'H' => Some(Action::Home),
+                    b'F' => Some(Action::End),
+                    _ => None,
+                };
+            }
+        }
+
+        match byte {
+            b' '..=b'~' => Some(Action::Insert(byte)),
+            b'\r' => {
+                self.last_was_cr = true;
+                Some(Action::Submit)
+            }
+            // Terminals may send CR LF when Enter is pressed.
+            b'\n' if last_was_cr => None,
+            b'\n' => Some(Action::Submit),
+            // Backspace and Delete, which are sent by different terminals for the backspace key.
+            0x08 | 0x7f => Some(Action::Backspace),
+            // Ctrl-A
+            0x01 => Some(Action::Home),
+            // Ctrl-B
+            0x02 => Some(Action::Left),
+            // Ctrl-C
+            0x03 => Some(Action::Cancel),
+            // Ctrl-D
+            0x04 => Some(Action::Delete),
+            // Ctrl-E
+            0x05 => Some(Action::End),
+            // Ctrl-F
+            0x06 => Some(Action::Right),
+            // Ctrl-K
+            0x0b => Some(Action::KillToEnd),
+            // Ctrl-N
+            0x0e => Some(Action::HistoryNext),
+            // Ctrl-P
+            0x10 => Some(Action::HistoryPrevious),
+            // Ctrl-U
+            0x15 => Some(Action::KillToStart),
+            ESC => {
+                self.escape = Escape::Esc;
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn insert(&mut self, byte: u8, out: &mut impl Write) -> fmt::Result {
+        if self.line.insert(self.cursor, byte).is_err() {
+            // Ring the bell when the line is full.
+            return out.write_char('\x07');
+        }
+        self.cursor += 1;
+
+        if self.cursor == self.line.len() {
+            out.write_char(char::from(byte))
+        } else {
+            self.redraw_from(self.cursor - 1, out)
+        }
+    }
+
+    /// Rewrites the line starting from `from`, where the terminal cursor is expected to be, then
+    /// moves the terminal cursor back to [`Self::cursor`].
+    fn redraw_from(&self, from: usize, out: &mut impl Write) -> fmt::Result {
+        write_bytes(out, self.line.get(from..).unwrap_or_default())?;
+        out.write_str("\x1b[K")?;
+        move_left(out, self.line.len() - self.cursor)
+    }
+
+    /// Replaces the whole line, placing the cursor at `cursor`.
+    fn replace_line(&mut self, line: Line, cursor: usize, out: &mut impl Write) -> fmt::Result {
+        move_left(out, self.cursor)?;
+        self.line = line;
+        self.cursor = cursor;
+        self.redraw_from(0, out)
+    }
+
+    /// Clears the line being edited.
+    fn reset(&mut self) {
+        self.line.clear();
+        self.cursor = 0;
+        self.recalled = None;
+        self.draft.clear();
+    }
+
+    /// Adds a line to the history, unless it is blank or identical to the most recent one.
+    fn remember(&mut self, line: &Line) {
+        if line.iter().all(u8::is_ascii_whitespace) || self.history.front() == Some(line) {
+            return;
+        }
+        if self.history.is_full() {
+            self.history.pop_back();
+        }
+        // Cannot fail as there is room left.
+        let _ = self.history.push_front(line.clone());
+    }
+}
+
+/// Returns the command line as a string slice.
+pub(crate) fn as_str(line: &Line) -> &str {
+    // Only ASCII characters are ever inserted.
+    core::str::from_utf8(line).unwrap_or_default()
+}
+
+fn write_bytes(out: &mut impl Write, bytes: &[u8]) -> fmt::Result {
+    out.write_str(core::str::from_utf8(bytes).unwrap_or_default())
+}
+
+fn move_left(out: &mut impl Write, columns: usize) -> fmt::Result {
+    if columns > 0 {
+        write!(out, "\x1b[{columns}D")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed_all(editor: &mut Editor, input: &[u8], out: &mut String) -> Option<Line> {
+        let mut submitted = None;
+        for &byte in input {
+            if let Some(line) = editor.feed(byte, out).unwrap() {
+                submitted = Some(line);
+            }
+        }
+        submitted
+    }
+
+    fn submit(editor: &mut Editor, input: &[u8]) -> String {
+        let mut out = String::new();
+        let line = feed_all(editor, input, &mut out).unwrap();
+        String::from(as_str(&line))
+    }
+
+    #[test]
+    fn plain_line() {
+        let mut editor = Editor::new("> ");
+        let mut out = String::new();
+        let line = feed_all(&mut editor, b"ps\r\n", &mut out).unwrap();
+        assert_eq!(as_str(&line), "ps");
+        // The LF following the CR must not submit another, empty line.
+        assert_eq!(out, "ps\n");
+    }
+
+    #[test]
+    fn editing() {
+        let mut editor = Editor::new("> ");
+        // Backspace, then insertion and deletion in the middle of the line.
+        assert_eq!(submit(&mut editor, b"helo\x7fo\x1b[D\x1b[Dl\r"), "hello");
+        assert_eq!(submit(&mut editor, b"abc\x1b[H\x1b[3~\r"), "bc");
+        assert_eq!(submit(&mut editor, b"abc\x01\x06\x0b\r"), "a");
+        assert_eq!(submit(&mut editor, b"abc\x02\x15\r"), "c");
+        assert_eq!(submit(&mut editor, b"abc\x01\x05d\r"), "abcd");
+    }
+
+    #[test]
+    fn cancel() {
+        let mut editor = Editor::new("> ");
+        let mut out = String::new();
+        assert_eq!(feed_all(&mut editor, b"reboot\x03", &mut out), None);
+        assert!(out.ends_with("^C\n> "));
+        assert_eq!(submit(&mut editor, b"ps\r"), "ps");
+    }
+
+    #[test]
+    fn line_too_long() {
+        let mut editor = Editor::new("> ");
+        let mut out = String::new();
+        let input = [b'a'; LINE_LEN + 1];
+        assert_eq!(feed_all(&mut editor, &input, &mut out), None);
+        assert!(out.ends_with('\x07'));
+        assert_eq!(submit(&mut editor, b"\r").len(), LINE_LEN);
+    }
+
+    #[test]
+    fn history() {
+        let mut editor = Editor::new("> ");
+        submit(&mut editor, b"first\r");
+        submit(&mut editor, b"second\r");
+        submit(&mut editor, b"second\r");
+        submit(&mut editor, b" \r");
+
+        assert_eq!(submit(&mut editor, b"\x1b[A\r"), "second");
+        assert_eq!(submit(&mut editor, b"\x1b[A\x1b[A\r"), "first");
+        // Going past the oldest entry keeps it.
+        assert_eq!(submit(&mut editor, b"\x10\x10\x10\x10\r"), "first");
+        // Going back down restores the line that was being edited.
+        assert_eq!(submit(&mut editor, b"dra\x1b[A\x1b[Bft\r"), "draft");
+        // A recalled line can be edited.
+        assert_eq!(submit(&mut editor, b"\x1b[A\x7f\x7f\r"), "dra");
+    }
+
+    #[test]
+    fn history_is_bounded() {
+        let mut editor = Editor::new("> ");
+        for i in 0..=HISTORY_LEN {
+            let mut input = format!("{i}").into_bytes();
+            input.push(b'\r');
+            submit(&mut editor, &input);
+        }
+
+        let mut input = [0x10; HISTORY_LEN + 2].to_vec();
+        input.push(b'\r');
+        assert_eq!(submit(&mut editor, &input), "1");
+    }
+}
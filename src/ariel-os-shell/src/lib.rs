@@ -0,0 +1,250 @@
+//! Provides an interactive shell, running on the debug UART or on a USB CDC-ACM serial port.
+//!
+//! The shell runs in its own thread, with a stack size of `CONFIG_SHELL_STACKSIZE` bytes.
+//! Command lines can be edited with the usual keys (arrow keys, Home/End, Backspace/Delete,
+//! Ctrl-A/E/K/U, etc.), and recently run command lines can be recalled with the up and down
+//! arrow keys.
+//! Arguments containing whitespace can be enclosed in double quotes.
+//!
+//! # Built-in commands
+//!
+//! | Command    | Description                                                    | Cargo feature |
+//! | ---------- | -------------------------------------------------------------- | ------------- |
+//! | `help`     | Lists the available commands.                                  |               |
+//! | `ps`       | Lists threads, with their state, priority and peak stack use.  |               |
+//! | `reboot`   | Reboots the device.                                            |               |
+//! | `sensors`  | Reads all registered sensors.                                  | `sensors`     |
+//! | `storage`  | Lists keys, or gets, sets or removes string values in storage. | `storage`     |
+//! | `ifconfig` | Shows the configuration of the network interface.              | `net`         |
+//!
+//! # Custom commands
+//!
+//! Additional commands are registered with the `ariel_os::shell_command` attribute macro.
+//! The output of the command is written to the provided [`core::fmt::Write`] implementation, and
+//! the arguments following the command name are passed as a slice:
+//!
+//! ```ignore
+//! use core::fmt::Write;
+//!
+//! use ariel_os::shell::Error;
+//!
+//! #[ariel_os::shell_command(usage = "<text>...", help = "Prints its arguments")]
+//! fn echo(out: &mut dyn Write, args: &[&str]) -> Result<(), Error> {
+//!     if args.is_empty() {
+//!         return Err(Error::InvalidArguments);
+//!     }
+//!     for arg in args {
+//!         write!(out, "{arg} ")?;
+//!     }
+//!     writeln!(out)?;
+//!     Ok(())
+//! }
+//! ```
+
+#![cfg_attr(not(test), no_std)]
+#![deny(missing_docs)]
+// Command lines are only read when a transport is enabled.
+#![cfg_attr(not(any(feature = "debug-uart", feature = "usb")), allow(dead_code))]
+
+mod args;
+mod builtins;
+mod editor;
+#[cfg(any(feature = "debug-uart", feature = "usb"))]
+mod transport;
+
+use core::{fmt, iter::FusedIterator};
+
+/// Function implementing a shell command.
+///
+/// It is passed the output of the shell and the arguments following the command name.
+pub type Handler = fn(&mut dyn fmt::Write, &[&str]) -> Result<(), Error>;
+
+/// A shell command.
+///
+/// Commands are registered with the `ariel_os::shell_command` attribute macro.
+pub struct Command {
+    name: &'static str,
+    usage: &'static str,
+    help: &'static str,
+    handler: Handler,
+}
+
+impl Command {
+    // Not part of the public API, use the `ariel_os::shell_command` attribute macro instead.
+    #[doc(hidden)]
+    #[must_use]
+    pub const fn new(
+        name: &'static str,
+        usage: &'static str,
+        help: &'static str,
+        handler: Handler,
+    ) -> Self {
+        Self {
+            name,
+            usage,
+            help,
+            handler,
+        }
+    }
+
+    /// Returns the name of the command.
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the synopsis of the arguments of the command.
+    #[must_use]
+    pub fn usage(&self) -> &'static str {
+        self.usage
+    }
+
+    /// Returns the short description of the command.
+    #[must_use]
+    pub fn help(&self) -> &'static str {
+        self.help
+    }
+}
+
+/// Errors returned by shell commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error {
+    /// The arguments are invalid.
+    ///
+    /// The usage of the command is then shown by the shell.
+    InvalidArguments,
+    /// The command failed.
+    ///
+    /// The command is expected to have written the reason to the output.
+    Failed,
+    /// Writing to the output failed.
+    Output,
+}
+
+impl From<fmt::Error> for Error {
+    fn from(_: fmt::Error) -> Self {
+        Self::Output
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidArguments => write!(f, "invalid arguments"),
+            Self::Failed => write!(f, "command failed"),
+            Self::Output => write!(f, "output error"),
+        }
+    }
+}
+
+/// Stores the registered shell commands.
+///
+/// To register a command, insert a [`Command`] into this [distributed slice](linkme), which the
+/// `ariel_os::shell_command` attribute macro takes care of.
+// Exclude this from the users' documentation, to force users to use `commands()` instead.
+#[doc(hidden)]
+#[linkme::distributed_slice]
+pub static COMMANDS: [Command] = [..];
+
+// Used by the `shell_command` attribute macro.
+#[doc(hidden)]
+pub mod macro_reexports {
+    pub use linkme;
+}
+
+/// Formats the name of a command followed by its usage.
+struct Synopsis<'a>(&'a Command);
+
+impl Synopsis<'_> {
+    fn len(&self) -> usize {
+        if self.0.usage.is_empty() {
+            self.0.name.len()
+        } else {
+            self.0.name.len() + 1 + self.0.usage.len()
+        }
+    }
+}
+
+impl fmt::Display for Synopsis<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.usage.is_empty() {
+            write!(f, "{}", self.0.name)
+        } else {
+            write!(f, "{} {}", self.0.name, self.0.usage)
+        }
+    }
+}
+
+/// Returns an iterator over the registered commands.
+pub fn commands() -> impl ExactSizeIterator<Item = &'static Command> + FusedIterator {
+    COMMANDS.iter()
+}
+
+/// Runs a command line, writing the output of the command to `out`.
+///
+/// # Errors
+///
+/// Returns an error if writing to the output fails.
+fn run(line: &str, out: &mut dyn fmt::Write) -> fmt::Result {
+    let args = match args::split(line) {
+        Ok(args) => args,
+        Err(err) => return writeln!(out, "error: {err}"),
+    };
+    let Some((&name, args)) = args.split_first() else {
+        return Ok(());
+    };
+
+    let Some(command) = commands().find(|command| command.name == name) else {
+        return writeln!(
+            out,
+            "{name}: command not found, type `help` to list the available commands"
+        );
+    };
+
+    match (command.handler)(out, args) {
+        Ok(()) | Err(Error::Failed) => Ok(()),
+        Err(Error::InvalidArguments) => writeln!(out, "usage: {}", Synopsis(command)),
+        Err(err) => writeln!(out, "{name}: {err}"),
+    }
+}
+
+#[cfg(any(feature = "debug-uart", feature = "usb"))]
+mod thread {
+    use core::fmt::Write as _;
+
+    use crate::{editor::Editor, transport::Terminal};
+
+    const STACKSIZE: usize = ariel_os_utils::usize_from_env_or!(
+        "CONFIG_SHELL_STACKSIZE",
+        6144,
+        "shell thread stack size (in bytes)"
+    );
+
+    const PROMPT: &str = "> ";
+
+    #[ariel_os_macros::thread(autostart, stacksize = STACKSIZE)]
+    fn shell() {
+        let mut terminal = Terminal::new();
+        let mut editor = Editor::new(PROMPT);
+        let mut buffer = [0; 16];
+
+        // Errors when writing to the terminal are ignored: there is nowhere to report them.
+        let _ = writeln!(
+            terminal,
+            "\nAriel OS shell, type `help` to list the available commands"
+        );
+        let _ = editor.prompt(&mut terminal);
+
+        loop {
+            let len = terminal.read(&mut buffer);
+
+            for &byte in buffer.get(..len).unwrap_or_default() {
+                if let Ok(Some(line)) = editor.feed(byte, &mut terminal) {
+                    let _ = super::run(crate::editor::as_str(&line), &mut terminal);
+                    let _ = editor.prompt(&mut terminal);
+                }
+            }
+        }
+    }
+}
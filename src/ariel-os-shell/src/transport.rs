@@ -0,0 +1,138 @@
+//! Provides the terminal the shell runs on.
+
+use core::fmt;
+
+#[cfg(all(feature = "debug-uart", not(feature = "usb")))]
+use debug_uart as backend;
+#[cfg(feature = "usb")]
+use usb as backend;
+
+/// Terminal the shell is running on.
+pub(crate) struct Terminal {
+    _private: (),
+}
+
+impl Terminal {
+    pub(crate) fn new() -> Self {
+        Self { _private: () }
+    }
+
+    /// Waits for data from the terminal, and reads it into `buffer`.
+    ///
+    /// Returns the number of bytes read.
+    pub(crate) fn read(&mut self, buffer: &mut [u8]) -> usize {
+        backend::read(buffer)
+    }
+}
+
+impl fmt::Write for Terminal {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // Terminals expect CR LF line endings.
+        let mut lines = s.split('\n');
+        if let Some(line) = lines.next() {
+            backend::write(line.as_bytes())?;
+        }
+        for line in lines {
+            backend::write(b"\r\n")?;
+            backend::write(line.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "debug-uart", not(feature = "usb")))]
+mod debug_uart {
+    use core::fmt;
+
+    use ariel_os_embassy::debug_uart;
+
+    pub(super) fn read(buffer: &mut [u8]) -> usize {
+        ariel_os_threads::block_on(debug_uart::read(buffer))
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error if the UART driver fails to send the data.
+    pub(super) fn write(bytes: &[u8]) -> fmt::Result {
+        debug_uart::write(bytes).map_err(|_| fmt::Error)
+    }
+}
+
+#[cfg(feature = "usb")]
+mod usb {
+    //! Uses a USB CDC-ACM class, which is exchanging data with the shell thread through pipes.
+
+    use core::fmt;
+
+    use ariel_os_embassy::reexports::embassy_usb::class::cdc_acm::{CdcAcmClass, State};
+    use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, pipe::Pipe};
+    use static_cell::StaticCell;
+
+    const MAX_FULL_SPEED_PACKET_SIZE: u8 = 64;
+    const PACKET_SIZE: usize = MAX_FULL_SPEED_PACKET_SIZE as usize;
+
+    static RX: Pipe<CriticalSectionRawMutex, PACKET_SIZE> = Pipe::new();
+    static TX: Pipe<CriticalSectionRawMutex, { 4 * PACKET_SIZE }> = Pipe::new();
+
+    #[ariel_os_macros::task(autostart, usb_builder_hook)]
+    async fn usb_serial() {
+        static STATE: StaticCell<State<'_>> = StaticCell::new();
+
+        let class = USB_BUILDER_HOOK
+            .with(|builder| {
+                CdcAcmClass::new(
+                    builder,
+                    STATE.init_with(State::new),
+                    MAX_FULL_SPEED_PACKET_SIZE.into(),
+                )
+            })
+            .await;
+        let (mut sender, mut receiver) = class.split();
+
+        let receive = async {
+            let mut buffer = [0; PACKET_SIZE];
+            loop {
+                receiver.wait_connection().await;
+                while let Ok(len) = receiver.read_packet(&mut buffer).await {
+                    RX.write_all(buffer.get(..len).unwrap_or_default()).await;
+                }
+            }
+        };
+
+        let send = async {
+            let mut buffer = [0; PACKET_SIZE];
+            loop {
+                sender.wait_connection().await;
+                loop {
+                    let len = TX.read(&mut buffer).await;
+                    // Output is held back while no terminal is connected.
+                    if sender
+                        .write_packet(buffer.get(..len).unwrap_or_default())
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            }
+        };
+
+        embassy_futures::join::join(receive, send).await;
+    }
+
+    pub(super) fn read(buffer: &mut [u8]) -> usize {
+        ariel_os_threads::block_on(RX.read(buffer))
+    }
+
+    /// # Errors
+    ///
+    /// Never returns an error, output is held back until a terminal is connected.
+    #[expect(
+        clippy::unnecessary_wraps,
+        reason = "same signature as other transports"
+    )]
+    pub(super) fn write(bytes: &[u8]) -> fmt::Result {
+        ariel_os_threads::block_on(TX.write_all(bytes));
+        Ok(())
+    }
+}
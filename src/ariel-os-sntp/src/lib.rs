@@ -0,0 +1,255 @@
+//! Provides an SNTP client synchronizing the [wall clock](ariel_os_wall_clock).
+//!
+//! Once the network is up, the wall clock is synchronized with the configured NTP server, and then
+//! periodically re-synchronized.
+//! The [status](status()) of the synchronization is available to the application.
+//!
+//! # Configuration
+//!
+//! The client is configured through the following environment variables:
+//!
+//! | Variable                       | Default        |
+//! | --                             | --             |
+//! | `CONFIG_SNTP_SERVER`           | `pool.ntp.org` |
+//! | `CONFIG_SNTP_SERVER_PORT`      | `123`          |
+//! | `CONFIG_SNTP_SYNC_INTERVAL_S`  | `3600`         |
+//! | `CONFIG_SNTP_RETRY_INTERVAL_S` | `10`           |
+//!
+//! The server is either a host name, resolved through DNS, or an IP address.
+
+#![no_std]
+#![deny(missing_docs)]
+
+mod packet;
+
+use core::cell::Cell;
+
+use ariel_os_log::{debug, info, warn};
+use ariel_os_wall_clock::{TimeSource, UtcDateTime};
+use embassy_net::{
+    IpAddress, IpEndpoint, Stack,
+    dns::DnsQueryType,
+    udp::{PacketMetadata, UdpSocket},
+};
+use embassy_sync::blocking_mutex::CriticalSectionMutex;
+use embassy_time::{Duration, Instant, Timer, WithTimeout as _};
+
+const SERVER: &str = ariel_os_utils::str_from_env_or!(
+    "CONFIG_SNTP_SERVER",
+    "pool.ntp.org",
+    "host name or IP address of the NTP server"
+);
+const SERVER_PORT: u16 =
+    ariel_os_utils::u16_from_env_or!("CONFIG_SNTP_SERVER_PORT", 123, "UDP port of the NTP server");
+const SYNC_INTERVAL: Duration = Duration::from_secs(ariel_os_utils::u32_from_env_or!(
+    "CONFIG_SNTP_SYNC_INTERVAL_S",
+    3600,
+    "interval between synchronizations, in seconds"
+) as u64);
+const RETRY_INTERVAL: Duration = Duration::from_secs(ariel_os_utils::u32_from_env_or!(
+    "CONFIG_SNTP_RETRY_INTERVAL_S",
+    10,
+    "interval between attempts after a failed synchronization, in seconds"
+) as u64);
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+// Leaves room for the optional authenticator (RFC 4330, Section 4).
+const MAX_PACKET_LEN: usize = packet::LEN + 20;
+
+/// Status of the synchronization with the NTP server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct SyncStatus {
+    /// Instant of the last successful synchronization, if any.
+    pub last_sync: Option<Instant>,
+    /// Offset applied to the wall clock by the last successful synchronization, in
+    /// microseconds, if any.
+    ///
+    /// This is `None` after the first synchronization if the wall clock had not been set before.
+    pub last_offset_micros: Option<i64>,
+    /// Number of failed synchronization attempts since the last successful one.
+    pub failures: u32,
+}
+
+static STATUS: CriticalSectionMutex<Cell<SyncStatus>> =
+    CriticalSectionMutex::new(Cell::new(SyncStatus {
+        last_sync: None,
+        last_offset_micros: None,
+        failures: 0,
+    }));
+
+/// Returns the status of the synchronization.
+#[must_use]
+pub fn status() -> SyncStatus {
+    STATUS.lock(Cell::get)
+}
+
+/// Returns whether the wall clock has been synchronized at least once.
+#[must_use]
+pub fn is_synchronized() -> bool {
+    status().last_sync.is_some()
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+enum Error {
+    Dns,
+    Send,
+    Timeout,
+    Packet(packet::Error),
+    OutOfRange,
+}
+
+#[ariel_os_macros::task(autostart)]
+async fn sntp_task() {
+    let stack = ariel_os_embassy::net::network_stack().await.unwrap();
+    stack.wait_config_up().await;
+
+    let mut rx_meta = [PacketMetadata::EMPTY; 1];
+    let mut rx_buffer = [0; 2 * MAX_PACKET_LEN];
+    let mut tx_meta = [PacketMetadata::EMPTY; 1];
+    let mut tx_buffer = [0; packet::LEN];
+
+    let mut socket = UdpSocket::new(
+        stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    // Port 0 binds to an ephemeral port.
+    socket.bind(0).unwrap();
+
+    info!("sntp: synchronizing with {}:{}", SERVER, SERVER_PORT);
+
+    loop {
+        let interval = match synchronize(stack, &socket).await {
+            Ok(offset_micros) => {
+                STATUS.lock(|status| {
+                    status.set(SyncStatus {
+                        last_sync: Some(Instant::now()),
+                        last_offset_micros: offset_micros,
+                        failures: 0,
+                    });
+                });
+                SYNC_INTERVAL
+            }
+            Err(err) => {
+                warn!("sntp: synchronization failed: {:?}", err);
+                STATUS.lock(|status| {
+                    let mut new_status = status.get();
+                    new_status.failures = new_status.failures.saturating_add(1);
+                    status.set(new_status);
+                });
+                RETRY_INTERVAL
+            }
+        };
+
+        Timer::after(interval).await;
+    }
+}
+
+/// Synchronizes the wall clock, returning the offset applied to it, in microseconds.
+///
+/// # Errors
+///
+/// Returns an error if querying the server fails, or if its response is invalid.
+async fn synchronize(stack: Stack<'_>, socket: &UdpSocket<'_>) -> Result<Option<i64>, Error> {
+    let server = resolve(stack).await?;
+
+    // Only used to match the response against the request, as the client does not need to know
+    // the time for that (RFC 4330, Section 5); being unpredictable, it also keeps off-path
+    // attackers from spoofing responses.
+    let nonce = rand_core::RngCore::next_u64(&mut ariel_os_random::crypto_rng());
+    let sent_at = Instant::now();
+    socket
+        .send_to(&packet::request(nonce), server)
+        .await
+        .map_err(|_| Error::Send)?;
+
+    let (response, received_at) = receive(socket, server, nonce)
+        .with_timeout(RESPONSE_TIMEOUT)
+        .await
+        .map_err(|_| Error::Timeout)??;
+
+    // Round-trip delay, excluding the processing time of the server.
+    let round_trip_nanos = i128::from((received_at - sent_at).as_micros()) * 1000;
+    let processing_nanos = response.transmit_nanos - response.receive_nanos;
+    let delay_nanos = (round_trip_nanos - processing_nanos).max(0);
+
+    let time_nanos = response.transmit_nanos + delay_nanos / 2;
+    let time = UtcDateTime::from_unix_timestamp_nanos(time_nanos).map_err(|_| Error::OutOfRange)?;
+
+    let offset_micros = ariel_os_wall_clock::now().map(|previous| {
+        let elapsed_nanos = i128::from(received_at.elapsed().as_micros()) * 1000;
+        let offset_nanos = time_nanos + elapsed_nanos - previous.unix_timestamp_nanos();
+        i64::try_from(offset_nanos / 1000).unwrap_or(i64::MAX)
+    });
+
+    ariel_os_wall_clock::set_at(time, received_at, TimeSource::Sntp);
+
+    debug!(
+        "sntp: synchronized, offset: {:?} us, delay: {} us",
+        offset_micros,
+        delay_nanos / 1000
+    );
+
+    Ok(offset_micros)
+}
+
+/// Waits for the response to the request sent with `nonce`, discarding any other datagram.
+///
+/// # Errors
+///
+/// Returns [`Error::Packet`] if the response is invalid.
+async fn receive(
+    socket: &UdpSocket<'_>,
+    server: IpEndpoint,
+    nonce: u64,
+) -> Result<(packet::Response, Instant), Error> {
+    let mut buf = [0; MAX_PACKET_LEN];
+
+    loop {
+        let Ok((len, meta)) = socket.recv_from(&mut buf).await else {
+            // Too large to be an SNTP response.
+            continue;
+        };
+        let received_at = Instant::now();
+
+        let Some(response) = buf.get(..len) else {
+            continue;
+        };
+        if meta.endpoint != server {
+            continue;
+        }
+
+        match packet::parse_response(response, nonce) {
+            Ok(response) => return Ok((response, received_at)),
+            // Late response to a previous request.
+            Err(packet::Error::Mismatch) => {}
+            Err(err) => return Err(Error::Packet(err)),
+        }
+    }
+}
+
+/// Returns the endpoint of the configured server, resolving its host name if needed.
+///
+/// # Errors
+///
+/// Returns [`Error::Dns`] if resolving the host name fails.
+async fn resolve(stack: Stack<'_>) -> Result<IpEndpoint, Error> {
+    let address = if let Ok(address) = SERVER.parse::<core::net::IpAddr>() {
+        IpAddress::from(address)
+    } else {
+        let addresses = match stack.dns_query(SERVER, DnsQueryType::A).await {
+            Ok(addresses) if !addresses.is_empty() => addresses,
+            _ => stack
+                .dns_query(SERVER, DnsQueryType::Aaaa)
+                .await
+                .map_err(|_| Error::Dns)?,
+        };
+        *addresses.first().ok_or(Error::Dns)?
+    };
+
+    Ok(IpEndpoint::new(address, SERVER_PORT))
+}
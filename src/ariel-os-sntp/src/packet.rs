@@ -0,0 +1,162 @@
+//! Encoding and decoding of SNTP packets, as specified in RFC 4330.
+
+/// Length of an SNTP packet without extension fields.
+pub(crate) const LEN: usize = 48;
+
+// Version 4, client mode, as the first byte of a request.
+const REQUEST_HEADER: u8 = (4 << 3) | 3;
+const MODE_SERVER: u8 = 4;
+const LEAP_INDICATOR_UNSYNCHRONIZED: u8 = 3;
+
+/// Seconds between the NTP epoch (1900-01-01) and the UNIX epoch (1970-01-01).
+const NTP_TO_UNIX_SECS: i128 = 2_208_988_800;
+/// Length of an NTP era, in seconds.
+const ERA_SECS: i128 = 1 << 32;
+
+const ORIGIN_TIMESTAMP: core::ops::Range<usize> = 24..32;
+const RECEIVE_TIMESTAMP: core::ops::Range<usize> = 32..40;
+const TRANSMIT_TIMESTAMP: core::ops::Range<usize> = 40..48;
+
+/// Error when decoding a response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub(crate) enum Error {
+    /// The packet is not a valid server response.
+    InvalidResponse,
+    /// The response does not answer the request.
+    Mismatch,
+    /// The server is not synchronized, or asks not to be queried ("kiss-o'-death").
+    Unsynchronized,
+}
+
+/// Timestamps of a server response, in nanoseconds since the UNIX epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Response {
+    /// Time at which the server received the request.
+    pub(crate) receive_nanos: i128,
+    /// Time at which the server sent the response.
+    pub(crate) transmit_nanos: i128,
+}
+
+/// Returns a request, with `nonce` as its transmit timestamp.
+///
+/// The server echoes the nonce as the origin timestamp of its response, which allows matching
+/// the response against the request.
+pub(crate) fn request(nonce: u64) -> [u8; LEN] {
+    let mut packet = [0; LEN];
+    packet[0] = REQUEST_HEADER;
+    packet[TRANSMIT_TIMESTAMP].copy_from_slice(&nonce.to_be_bytes());
+    packet
+}
+
+/// Decodes the response to the request sent with `nonce`.
+///
+/// # Errors
+///
+/// Returns [`Error::Mismatch`] if the response is not the one to that request, and another error
+/// if it is not a valid response.
+pub(crate) fn parse_response(packet: &[u8], nonce: u64) -> Result<Response, Error> {
+    let header = *packet.first().ok_or(Error::InvalidResponse)?;
+    if packet.len() < LEN || header & 0b111 != MODE_SERVER {
+        return Err(Error::InvalidResponse);
+    }
+
+    if timestamp(packet, ORIGIN_TIMESTAMP) != Some(nonce) {
+        return Err(Error::Mismatch);
+    }
+
+    let stratum = packet.get(1).copied().ok_or(Error::InvalidResponse)?;
+    if header >> 6 == LEAP_INDICATOR_UNSYNCHRONIZED || stratum == 0 {
+        return Err(Error::Unsynchronized);
+    }
+
+    let receive = timestamp(packet, RECEIVE_TIMESTAMP).ok_or(Error::InvalidResponse)?;
+    let transmit = timestamp(packet, TRANSMIT_TIMESTAMP).ok_or(Error::InvalidResponse)?;
+    if transmit == 0 {
+        return Err(Error::InvalidResponse);
+    }
+
+    Ok(Response {
+        receive_nanos: unix_nanos(receive),
+        transmit_nanos: unix_nanos(transmit),
+    })
+}
+
+fn timestamp(packet: &[u8], range: core::ops::Range<usize>) -> Option<u64> {
+    Some(u64::from_be_bytes(packet.get(range)?.try_into().ok()?))
+}
+
+/// Converts an NTP timestamp to nanoseconds since the UNIX epoch.
+fn unix_nanos(timestamp: u64) -> i128 {
+    let mut secs = i128::from(timestamp >> 32);
+    // Timestamps with the most significant bit cleared are in the next era, starting in 2036
+    // (RFC 4330, Section 3).
+    if secs & (1 << 31) == 0 {
+        secs += ERA_SECS;
+    }
+    let nanos = (i128::from(timestamp & 0xffff_ffff) * 1_000_000_000) >> 32;
+
+    (secs - NTP_TO_UNIX_SECS) * 1_000_000_000 + nanos
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(header: u8, stratum: u8, origin: u64, receive: u64, transmit: u64) -> [u8; LEN] {
+        let mut packet = [0; LEN];
+        packet[0] = header;
+        packet[1] = stratum;
+        packet[ORIGIN_TIMESTAMP].copy_from_slice(&origin.to_be_bytes());
+        packet[RECEIVE_TIMESTAMP].copy_from_slice(&receive.to_be_bytes());
+        packet[TRANSMIT_TIMESTAMP].copy_from_slice(&transmit.to_be_bytes());
+        packet
+    }
+
+    #[test]
+    fn test_request() {
+        let packet = request(0x0102_0304_0506_0708);
+        assert_eq!(packet[0], 0x23);
+        assert_eq!(&packet[40..], &[1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn test_unix_nanos() {
+        // 2025-01-01T00:00:00.5Z
+        assert_eq!(
+            unix_nanos((3_944_678_400 << 32) | 0x8000_0000),
+            1_735_689_600_500_000_000
+        );
+        // 2036-02-07T06:28:16Z, the start of the next era.
+        assert_eq!(unix_nanos(0), 2_085_978_496_000_000_000);
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let packet = response(0x24, 2, 42, 3_944_678_400 << 32, 3_944_678_401 << 32);
+        assert_eq!(
+            parse_response(&packet, 42),
+            Ok(Response {
+                receive_nanos: 1_735_689_600_000_000_000,
+                transmit_nanos: 1_735_689_601_000_000_000,
+            })
+        );
+        assert_eq!(parse_response(&packet, 43), Err(Error::Mismatch));
+        assert_eq!(
+            parse_response(&packet[..40], 42),
+            Err(Error::InvalidResponse)
+        );
+
+        // Client mode.
+        let packet = response(0x23, 2, 42, 1 << 63, 1 << 63);
+        assert_eq!(parse_response(&packet, 42), Err(Error::InvalidResponse));
+
+        // Kiss-o'-death.
+        let packet = response(0x24, 0, 42, 1 << 63, 1 << 63);
+        assert_eq!(parse_response(&packet, 42), Err(Error::Unsynchronized));
+
+        // Unsynchronized leap indicator.
+        let packet = response(0xe4, 2, 42, 1 << 63, 1 << 63);
+        assert_eq!(parse_response(&packet, 42), Err(Error::Unsynchronized));
+    }
+}
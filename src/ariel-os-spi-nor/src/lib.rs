@@ -0,0 +1,615 @@
+//! Driver for SPI NOR flash chips, e.g., external flash for storing logs.
+//!
+//! The parameters of the flash (capacity, page size, and erase instructions) are discovered at
+//! runtime using the JEDEC SFDP standard, which allows supporting most flash chips without
+//! chip-specific code.
+//!
+//! [`SpiNor`] implements [`NorFlash`], and can thus be used as a storage backend, using
+//! `ariel_os::storage::Storage::new()`, or for a filesystem, using
+//! `ariel_os::fs::Littlefs::serve()`.
+//! It works on any [`SpiDevice`], and in particular on `ariel_os::spi::main::SpiDevice`.
+//!
+//! # Note
+//!
+//! Flash chips whose blocks are write-protected at power-up need to be unprotected before being
+//! written to.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! use ariel_os::{gpio, spi::main::SpiDevice};
+//! use ariel_os_spi_nor::SpiNor;
+//!
+//! let cs_output = gpio::Output::new(peripherals.flash_cs, gpio::Level::High);
+//! let spi_device = SpiDevice::new(&spi_bus, cs_output);
+//!
+//! let flash = SpiNor::probe(spi_device).await?;
+//! let range = 0..flash.capacity() as u32;
+//! let mut storage = ariel_os::storage::Storage::new(flash, range);
+//! ```
+
+#![cfg_attr(not(test), no_std)]
+#![deny(missing_docs)]
+
+mod sfdp;
+
+use embassy_time::{Duration, Instant};
+use embedded_hal_async::spi::{Operation, SpiDevice};
+use embedded_storage_async::nor_flash::{
+    ErrorType, MultiwriteNorFlash, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash,
+};
+
+use sfdp::{AddressMode, Parameters};
+
+// Instructions, common to all flash chips.
+/// Enables writing, for the next program or erase instruction.
+const WRITE_ENABLE: u8 = 0x06;
+/// Reads the status register.
+const READ_STATUS: u8 = 0x05;
+/// Reads data, with a dummy byte.
+const FAST_READ: u8 = 0x0b;
+/// Programs data, within a page.
+const PAGE_PROGRAM: u8 = 0x02;
+/// Reads the JEDEC manufacturer and device IDs.
+const READ_JEDEC_ID: u8 = 0x9f;
+/// Reads the SFDP, with 3-byte addresses and a dummy byte.
+const READ_SFDP: u8 = 0x5a;
+/// Releases the flash from deep power-down.
+const RELEASE_POWER_DOWN: u8 = 0xab;
+/// Enters the 4-byte address mode.
+const ENTER_4_BYTE_ADDRESS_MODE: u8 = 0xb7;
+
+/// Write In Progress bit of the status register.
+const STATUS_BUSY: u8 = 0x01;
+
+/// Maximum duration of a page program.
+const PROGRAM_TIMEOUT: Duration = Duration::from_millis(50);
+/// Maximum duration of an erase, of up to a 64 KiB block.
+const ERASE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Errors returned by [`SpiNor`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Error<E> {
+    /// The SPI device returned an error.
+    Spi(E),
+    /// No flash responded, or it does not provide SFDP parameters.
+    NotFound,
+    /// The flash is not supported, e.g., because it does not support erasing 4 KiB sectors.
+    UnsupportedFlash,
+    /// The flash did not finish programming or erasing in time.
+    Timeout,
+    /// The arguments are not aligned.
+    NotAligned,
+    /// The arguments are out of bounds.
+    OutOfBounds,
+}
+
+impl<E: core::fmt::Debug> NorFlashError for Error<E> {
+    fn kind(&self) -> NorFlashErrorKind {
+        match self {
+            Self::NotAligned => NorFlashErrorKind::NotAligned,
+            Self::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+            _ => NorFlashErrorKind::Other,
+        }
+    }
+}
+
+impl<E: core::fmt::Debug> core::fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Spi(err) => write!(f, "SPI error: {err:?}"),
+            Self::NotFound => f.write_str("flash not found"),
+            Self::UnsupportedFlash => f.write_str("unsupported flash"),
+            Self::Timeout => f.write_str("flash timeout"),
+            Self::NotAligned => f.write_str("arguments not aligned"),
+            Self::OutOfBounds => f.write_str("arguments out of bounds"),
+        }
+    }
+}
+
+impl<E: core::fmt::Debug> core::error::Error for Error<E> {}
+
+/// An SPI NOR flash, whose parameters are discovered using SFDP.
+pub struct SpiNor<SPI> {
+    spi: SPI,
+    jedec_id: [u8; 3],
+    parameters: Parameters,
+}
+
+impl<SPI: SpiDevice> SpiNor<SPI> {
+    /// Probes the flash, reading its parameters.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NotFound`] if no flash responds or it does not provide SFDP parameters,
+    /// and [`Error::UnsupportedFlash`] if it cannot be used.
+    pub async fn probe(mut spi: SPI) -> Result<Self, Error<SPI::Error>> {
+        spi.write(&[RELEASE_POWER_DOWN]).await.map_err(Error::Spi)?;
+
+        let mut jedec_id = [0; 3];
+        spi.transaction(&mut [
+            Operation::Write(&[READ_JEDEC_ID]),
+            Operation::Read(&mut jedec_id),
+        ])
+        .await
+        .map_err(Error::Spi)?;
+        if matches!(jedec_id, [0x00, 0x00, 0x00] | [0xff, 0xff, 0xff]) {
+            return Err(Error::NotFound);
+        }
+
+        let mut headers = [0; sfdp::HEADERS_LEN];
+        read_sfdp(&mut spi, 0, &mut headers).await?;
+        let (bfpt_address, bfpt_dwords) = sfdp::parse_headers(&headers).ok_or(Error::NotFound)?;
+
+        let mut bfpt_bytes = [0; sfdp::BFPT_MAX_DWORDS * 4];
+        let bfpt_bytes = bfpt_bytes
+            .get_mut(..bfpt_dwords * 4)
+            .ok_or(Error::UnsupportedFlash)?;
+        read_sfdp(&mut spi, bfpt_address, bfpt_bytes).await?;
+        let mut bfpt = [0; sfdp::BFPT_MAX_DWORDS];
+        for (dword, bytes) in bfpt.iter_mut().zip(bfpt_bytes.as_chunks::<4>().0) {
+            *dword = u32::from_le_bytes(*bytes);
+        }
+        let parameters = bfpt
+            .get(..bfpt_dwords)
+            .and_then(sfdp::parse_bfpt)
+            .ok_or(Error::UnsupportedFlash)?;
+
+        if parameters.address_mode == AddressMode::EnterFour {
+            // Some flash chips require enabling writes first.
+            spi.write(&[WRITE_ENABLE]).await.map_err(Error::Spi)?;
+            spi.write(&[ENTER_4_BYTE_ADDRESS_MODE])
+                .await
+                .map_err(Error::Spi)?;
+        }
+
+        ariel_os_log::debug!(
+            "spi-nor: flash {:x} {:x} {:x}, {} bytes",
+            jedec_id[0],
+            jedec_id[1],
+            jedec_id[2],
+            parameters.capacity
+        );
+        Ok(Self {
+            spi,
+            jedec_id,
+            parameters,
+        })
+    }
+
+    /// Returns the JEDEC manufacturer ID, followed by the device ID, of the flash.
+    #[must_use]
+    pub fn jedec_id(&self) -> [u8; 3] {
+        self.jedec_id
+    }
+
+    /// Returns the size of the pages of the flash, which a single program instruction can
+    /// write.
+    #[must_use]
+    pub fn page_size(&self) -> u32 {
+        self.parameters.page_size
+    }
+
+    /// Releases the SPI device.
+    pub fn release(self) -> SPI {
+        self.spi
+    }
+
+    /// Checks that `len` bytes at `offset` are within the flash.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::OutOfBounds`] otherwise.
+    fn check_bounds(&self, offset: u32, len: usize) -> Result<(), Error<SPI::Error>> {
+        let end = u32::try_from(len)
+            .ok()
+            .and_then(|len| offset.checked_add(len));
+        if end.is_none_or(|end| end > self.parameters.capacity) {
+            return Err(Error::OutOfBounds);
+        }
+        Ok(())
+    }
+
+    /// Returns the header of an instruction accessing `address`, followed by `dummy` bytes.
+    fn header(&self, opcode: u8, address: u32, dummy: usize) -> heapless::Vec<u8, 6> {
+        let address = address.to_be_bytes();
+        let address = address
+            .get(4 - self.parameters.address_mode.len()..)
+            .unwrap_or(&address);
+
+        let mut header = heapless::Vec::new();
+        // The header is at most 6 bytes long.
+        let _ = header.push(opcode);
+        let _ = header.extend_from_slice(address);
+        for _ in 0..dummy {
+            let _ = header.push(0xff);
+        }
+        header
+    }
+
+    /// Sends a program or erase instruction, and waits for it to complete.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Timeout`] if the flash is still busy after `timeout`.
+    async fn program(
+        &mut self,
+        header: &[u8],
+        data: &[u8],
+        timeout: Duration,
+    ) -> Result<(), Error<SPI::Error>> {
+        self.spi.write(&[WRITE_ENABLE]).await.map_err(Error::Spi)?;
+        self.spi
+            .transaction(&mut [Operation::Write(header), Operation::Write(data)])
+            .await
+            .map_err(Error::Spi)?;
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let mut status = [0];
+            self.spi
+                .transaction(&mut [
+                    Operation::Write(&[READ_STATUS]),
+                    Operation::Read(&mut status),
+                ])
+                .await
+                .map_err(Error::Spi)?;
+            let [status] = status;
+            if status & STATUS_BUSY == 0 {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(Error::Timeout);
+            }
+            embassy_futures::yield_now().await;
+        }
+    }
+}
+
+/// Reads SFDP data at `address`.
+///
+/// # Errors
+///
+/// Returns [`Error::Spi`] if the SPI device fails.
+async fn read_sfdp<SPI: SpiDevice>(
+    spi: &mut SPI,
+    address: u32,
+    data: &mut [u8],
+) -> Result<(), Error<SPI::Error>> {
+    let [_, a, b, c] = address.to_be_bytes();
+    spi.transaction(&mut [
+        Operation::Write(&[READ_SFDP, a, b, c, 0xff]),
+        Operation::Read(data),
+    ])
+    .await
+    .map_err(Error::Spi)
+}
+
+impl<SPI: SpiDevice> ErrorType for SpiNor<SPI> {
+    type Error = Error<SPI::Error>;
+}
+
+impl<SPI: SpiDevice> ReadNorFlash for SpiNor<SPI> {
+    const READ_SIZE: usize = 1;
+
+    async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        self.check_bounds(offset, bytes.len())?;
+        let header = self.header(FAST_READ, offset, 1);
+        self.spi
+            .transaction(&mut [Operation::Write(&header), Operation::Read(bytes)])
+            .await
+            .map_err(Error::Spi)
+    }
+
+    fn capacity(&self) -> usize {
+        self.parameters.capacity as usize
+    }
+}
+
+impl<SPI: SpiDevice> NorFlash for SpiNor<SPI> {
+    const WRITE_SIZE: usize = 1;
+    const ERASE_SIZE: usize = 4096;
+
+    async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        if from > to || to > self.parameters.capacity {
+            return Err(Error::OutOfBounds);
+        }
+        if !(from as usize).is_multiple_of(Self::ERASE_SIZE)
+            || !(to as usize).is_multiple_of(Self::ERASE_SIZE)
+        {
+            return Err(Error::NotAligned);
+        }
+
+        let mut address = from;
+        while address < to {
+            // Use the largest erase instruction fitting the remaining range, the smallest
+            // erasing a single sector.
+            let erase_type = self
+                .parameters
+                .erase_types
+                .iter()
+                .find(|erase| address.is_multiple_of(erase.size) && to - address >= erase.size)
+                .copied()
+                .ok_or(Error::NotAligned)?;
+
+            let header = self.header(erase_type.opcode, address, 0);
+            self.program(&header, &[], ERASE_TIMEOUT).await?;
+            address += erase_type.size;
+        }
+        Ok(())
+    }
+
+    async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.check_bounds(offset, bytes.len())?;
+
+        let mut offset = offset;
+        let mut bytes = bytes;
+        while !bytes.is_empty() {
+            // A page program wraps around at the end of the page.
+            let page_left = self.parameters.page_size - offset % self.parameters.page_size;
+            let (chunk, rest) = bytes.split_at(bytes.len().min(page_left as usize));
+
+            let header = self.header(PAGE_PROGRAM, offset, 0);
+            self.program(&header, chunk, PROGRAM_TIMEOUT).await?;
+
+            #[expect(clippy::cast_possible_truncation, reason = "chunks fit in a page")]
+            let chunk_len = chunk.len() as u32;
+            offset += chunk_len;
+            bytes = rest;
+        }
+        Ok(())
+    }
+}
+
+// NOR flash allows programming already-programmed bits to 0.
+impl<SPI: SpiDevice> MultiwriteNorFlash for SpiNor<SPI> {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use core::convert::Infallible;
+
+    use embassy_futures::block_on;
+    use embedded_hal_async::spi::ErrorType as SpiErrorType;
+
+    use super::*;
+
+    const JEDEC_ID: [u8; 3] = [0xef, 0x40, 0x18];
+
+    /// Flash simulated at the instruction level.
+    struct SimulatedFlash {
+        sfdp: Vec<u8>,
+        /// Sparse memory, erased bytes being absent.
+        memory: BTreeMap<u32, u8>,
+        four_byte_addresses: bool,
+        write_enabled: bool,
+        /// Number of status reads reporting the flash as busy after programming or erasing.
+        busy: usize,
+        /// Erase instructions received, with their addresses.
+        erases: Vec<(u8, u32)>,
+    }
+
+    impl SimulatedFlash {
+        /// Creates a flash of `2^density_exponent` bits, with 4 KiB, 32 KiB and 64 KiB erases.
+        fn new(density_exponent: u32, address_bytes: u32) -> Self {
+            let bfpt: [u32; 11] = [
+                0xfff9_20e5 | (address_bytes << 17),
+                0x8000_0000 | density_exponent,
+                0x6b08_eb44,
+                0xbb42_3b08,
+                0xffff_fffe,
+                0xff00_ffff,
+                0xeb44_ffff,
+                0x520f_200c,
+                0xff00_d810,
+                0x0000_0000,
+                0x0000_0080,
+            ];
+            let mut sfdp = vec![
+                0x53, 0x46, 0x44, 0x50, 0x06, 0x01, 0x00, 0xff, 0x00, 0x06, 0x01, 0x0b, 0x30, 0x00,
+                0x00, 0xff,
+            ];
+            sfdp.resize(0x30, 0xff);
+            sfdp.extend(bfpt.iter().flat_map(|dword| dword.to_le_bytes()));
+
+            Self {
+                sfdp,
+                memory: BTreeMap::new(),
+                four_byte_addresses: address_bytes == 0b10,
+                write_enabled: false,
+                busy: 0,
+                erases: Vec::new(),
+            }
+        }
+
+        fn byte(&self, address: u32) -> u8 {
+            self.memory.get(&address).copied().unwrap_or(0xff)
+        }
+
+        fn execute(&mut self, header: &[u8], data: &mut [u8]) {
+            let address_len = if self.four_byte_addresses { 4 } else { 3 };
+            let address = || {
+                header[1..=address_len]
+                    .iter()
+                    .fold(0, |address, byte| (address << 8) | u32::from(*byte))
+            };
+
+            match header[0] {
+                RELEASE_POWER_DOWN => {}
+                READ_JEDEC_ID => data.copy_from_slice(&JEDEC_ID),
+                READ_SFDP => {
+                    // SFDP always uses 3-byte addresses.
+                    let [_, a, b, c, _] = header.try_into().unwrap();
+                    let address = usize::from_be_bytes([0, 0, 0, 0, 0, a, b, c]);
+                    data.copy_from_slice(&self.sfdp[address..address + data.len()]);
+                }
+                WRITE_ENABLE => self.write_enabled = true,
+                ENTER_4_BYTE_ADDRESS_MODE => self.four_byte_addresses = true,
+                READ_STATUS => {
+                    data[0] = u8::from(self.write_enabled) << 1 | u8::from(self.busy > 0);
+                    self.busy = self.busy.saturating_sub(1);
+                }
+                FAST_READ => {
+                    assert_eq!(header.len(), address_len + 2);
+                    for (byte, address) in data.iter_mut().zip(address()..) {
+                        *byte = self.byte(address);
+                    }
+                }
+                PAGE_PROGRAM => {
+                    assert!(std::mem::take(&mut self.write_enabled));
+                    let address = address();
+                    assert!(
+                        address / 256 == (address + u32::try_from(data.len()).unwrap() - 1) / 256,
+                        "page program across pages"
+                    );
+                    for (byte, address) in data.iter().zip(address..) {
+                        // Programming only clears bits.
+                        let programmed = self.byte(address) & byte;
+                        self.memory.insert(address, programmed);
+                    }
+                    self.busy = 2;
+                }
+                opcode @ (0x20 | 0x52 | 0xd8) => {
+                    assert!(std::mem::take(&mut self.write_enabled));
+                    let size = match opcode {
+                        0x20 => 4096,
+                        0x52 => 32768,
+                        _ => 65536,
+                    };
+                    let address = address();
+                    assert_eq!(address % size, 0);
+                    self.memory
+                        .retain(|byte, _| !(address..address + size).contains(byte));
+                    self.erases.push((opcode, address));
+                    self.busy = 2;
+                }
+                opcode => panic!("unexpected instruction {opcode:#04x}"),
+            }
+        }
+    }
+
+    impl SpiErrorType for SimulatedFlash {
+        type Error = Infallible;
+    }
+
+    impl SpiDevice for SimulatedFlash {
+        async fn transaction(
+            &mut self,
+            operations: &mut [Operation<'_, u8>],
+        ) -> Result<(), Self::Error> {
+            match operations {
+                [Operation::Write(header)] => self.execute(header, &mut []),
+                [Operation::Write(header), Operation::Read(data)] => self.execute(header, data),
+                [Operation::Write(header), Operation::Write(data)] => {
+                    self.execute(header, &mut data.to_vec());
+                }
+                _ => panic!("unexpected transaction"),
+            }
+            Ok(())
+        }
+    }
+
+    fn probe(density_exponent: u32, address_bytes: u32) -> SpiNor<SimulatedFlash> {
+        block_on(SpiNor::probe(SimulatedFlash::new(
+            density_exponent,
+            address_bytes,
+        )))
+        .unwrap()
+    }
+
+    #[test]
+    fn probe_flash() {
+        // 1 Mbit flash.
+        let flash = probe(20, 0b00);
+        assert_eq!(flash.jedec_id(), JEDEC_ID);
+        assert_eq!(flash.capacity(), 128 * 1024);
+        assert_eq!(flash.page_size(), 256);
+    }
+
+    #[test]
+    fn probe_missing_flash() {
+        let mut flash = SimulatedFlash::new(20, 0b00);
+        flash.sfdp[0] = 0xff;
+        assert!(matches!(
+            block_on(SpiNor::probe(flash)),
+            Err(Error::NotFound)
+        ));
+    }
+
+    #[test]
+    fn write_read() {
+        let mut flash = probe(20, 0b00);
+        let data: Vec<u8> = (0..=255).cycle().skip(7).take(600).collect();
+
+        // Across pages, and programming already-programmed bytes.
+        block_on(flash.write(0x1f0, &data)).unwrap();
+        block_on(flash.write(0x1f0, &[0x0f])).unwrap();
+
+        let mut read = vec![0; 602];
+        block_on(flash.read(0x1ef, &mut read)).unwrap();
+        assert_eq!(read[0], 0xff);
+        assert_eq!(read[1], data[0] & 0x0f);
+        assert_eq!(read[2..601], data[1..]);
+        assert_eq!(read[601], 0xff);
+    }
+
+    #[test]
+    fn erase() {
+        let mut flash = probe(20, 0b00);
+        block_on(flash.write(0x7ffe, &[0; 4])).unwrap();
+
+        // 4 KiB sectors up to the 32 KiB block, then sectors again.
+        block_on(flash.erase(0x5000, 0x1_2000)).unwrap();
+        assert_eq!(
+            flash.spi.erases,
+            [
+                (0x20, 0x5000),
+                (0x20, 0x6000),
+                (0x20, 0x7000),
+                (0x52, 0x8000),
+                (0x20, 0x1_0000),
+                (0x20, 0x1_1000),
+            ]
+        );
+        assert_eq!(flash.spi.byte(0x7fff), 0xff);
+        assert_eq!(flash.spi.byte(0x8000), 0xff);
+
+        flash.spi.erases.clear();
+        block_on(flash.erase(0, 0x1_0000)).unwrap();
+        assert_eq!(flash.spi.erases, [(0xd8, 0)]);
+        assert_eq!(flash.spi.byte(0x7ffe), 0xff);
+    }
+
+    #[test]
+    fn out_of_bounds() {
+        let mut flash = probe(20, 0b00);
+        let mut read = [0; 2];
+        assert_eq!(
+            block_on(flash.read(128 * 1024 - 1, &mut read)),
+            Err(Error::OutOfBounds)
+        );
+        assert_eq!(
+            block_on(flash.erase(0x1000, 0x1800)),
+            Err(Error::NotAligned)
+        );
+        assert_eq!(
+            block_on(flash.erase(0, 128 * 1024 + 4096)),
+            Err(Error::OutOfBounds)
+        );
+    }
+
+    #[test]
+    fn four_byte_addresses() {
+        // 256 Mbit flash, entering the 4-byte address mode.
+        let mut flash = probe(28, 0b01);
+        assert!(flash.spi.four_byte_addresses);
+        assert_eq!(flash.capacity(), 32 * 1024 * 1024);
+
+        block_on(flash.write(0x1ff_fffe, &[1, 2])).unwrap();
+        let mut read = [0; 2];
+        block_on(flash.read(0x1ff_fffe, &mut read)).unwrap();
+        assert_eq!(read, [1, 2]);
+    }
+}
@@ -0,0 +1,247 @@
+//! Parses the Serial Flash Discoverable Parameters (SFDP) of a flash.
+//!
+//! See the JEDEC JESD216 standard; only the Basic Flash Parameter Table (BFPT) is used.
+
+/// `SFDP` signature, at the start of the SFDP header.
+pub(crate) const SIGNATURE: u32 = 0x5044_4653;
+
+/// Size of the SFDP header followed by the first parameter header, in bytes.
+pub(crate) const HEADERS_LEN: usize = 16;
+
+/// Maximum number of dwords of the BFPT used, as defined by JESD216B.
+pub(crate) const BFPT_MAX_DWORDS: usize = 16;
+
+/// Minimum number of dwords of the BFPT, as defined by the original JESD216.
+const BFPT_MIN_DWORDS: usize = 9;
+
+/// Flash capacity above which 4-byte addresses are needed.
+const THREE_BYTE_ADDRESS_LIMIT: u32 = 1 << 24;
+
+/// Size of the sectors erased by the 4 KiB erase instruction.
+const SECTOR_SIZE: u32 = 4096;
+
+/// Addressing used by the flash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum AddressMode {
+    /// 3-byte addresses.
+    Three,
+    /// 4-byte addresses, which the flash uses from power-up.
+    Four,
+    /// 4-byte addresses, after entering the 4-byte address mode.
+    EnterFour,
+}
+
+impl AddressMode {
+    /// Returns the number of address bytes.
+    pub(crate) fn len(self) -> usize {
+        match self {
+            Self::Three => 3,
+            Self::Four | Self::EnterFour => 4,
+        }
+    }
+}
+
+/// Erase instruction, erasing an aligned block of `size` bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct EraseType {
+    pub(crate) size: u32,
+    pub(crate) opcode: u8,
+}
+
+/// Parameters of a flash, from its BFPT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Parameters {
+    /// Capacity in bytes.
+    pub(crate) capacity: u32,
+    pub(crate) address_mode: AddressMode,
+    /// Size of the pages written by a single page program instruction.
+    pub(crate) page_size: u32,
+    /// Erase instructions, from the largest to the smallest block, which is [`SECTOR_SIZE`].
+    pub(crate) erase_types: heapless::Vec<EraseType, 5>,
+}
+
+/// Parses the SFDP header and the first parameter header, returning the location of the BFPT:
+/// its address and its length in dwords.
+///
+/// Returns `None` if the headers are not valid.
+pub(crate) fn parse_headers(headers: &[u8; HEADERS_LEN]) -> Option<(u32, usize)> {
+    let [
+        s0,
+        s1,
+        s2,
+        s3,
+        _minor,
+        major,
+        _nph,
+        _access_protocol,
+        id_lsb,
+        _param_minor,
+        param_major,
+        dwords,
+        p0,
+        p1,
+        p2,
+        _id_msb,
+    ] = *headers;
+
+    // The first parameter header is always the one of the BFPT.
+    if u32::from_le_bytes([s0, s1, s2, s3]) != SIGNATURE || major != 1 || param_major != 1 {
+        return None;
+    }
+    if id_lsb != 0 || usize::from(dwords) < BFPT_MIN_DWORDS {
+        return None;
+    }
+    Some((
+        u32::from_le_bytes([p0, p1, p2, 0]),
+        usize::from(dwords).min(BFPT_MAX_DWORDS),
+    ))
+}
+
+/// Parses the BFPT, given as dwords.
+///
+/// Returns `None` if the flash is not supported: it must support erasing 4 KiB sectors, and
+/// 3-byte addresses if its capacity is at most 16 MiB.
+pub(crate) fn parse_bfpt(bfpt: &[u32]) -> Option<Parameters> {
+    let dword = |n: usize| bfpt.get(n - 1).copied();
+
+    let dword1 = dword(1)?;
+    // Uniform 4 KiB erase.
+    if dword1 & 0b11 != 0b01 {
+        return None;
+    }
+    let sector_erase_opcode = byte(dword1, 1);
+
+    let density = dword(2)?;
+    let bits = if density & (1 << 31) == 0 {
+        u64::from(density) + 1
+    } else {
+        1u64.checked_shl(density & !(1 << 31))?
+    };
+    let capacity = u32::try_from(bits / 8).ok()?;
+
+    let address_mode = match ((dword1 >> 17) & 0b11, capacity > THREE_BYTE_ADDRESS_LIMIT) {
+        (0b00 | 0b01, false) => AddressMode::Three,
+        (0b01, true) => AddressMode::EnterFour,
+        (0b10, _) => AddressMode::Four,
+        _ => return None,
+    };
+
+    let page_size = dword(11).map_or(256, |dword11| 1 << ((dword11 >> 4) & 0xf));
+
+    let mut erase_types = heapless::Vec::<EraseType, 5>::new();
+    for dword in [dword(8)?, dword(9)?] {
+        for (size, opcode) in [
+            (byte(dword, 0), byte(dword, 1)),
+            (byte(dword, 2), byte(dword, 3)),
+        ] {
+            // Unused erase types have a size of 0.
+            let size = 1u32.checked_shl(u32::from(size)).filter(|_| size != 0);
+            if let Some(size) = size.filter(|size| *size > SECTOR_SIZE && *size <= capacity) {
+                erase_types.push(EraseType { size, opcode }).ok()?;
+            }
+        }
+    }
+    erase_types
+        .push(EraseType {
+            size: SECTOR_SIZE,
+            opcode: sector_erase_opcode,
+        })
+        .ok()?;
+    erase_types.sort_unstable_by_key(|erase| core::cmp::Reverse(erase.size));
+
+    Some(Parameters {
+        capacity,
+        address_mode,
+        page_size,
+        erase_types,
+    })
+}
+
+/// Returns byte `n` of `dword`, starting from the least significant one.
+fn byte(dword: u32, n: u32) -> u8 {
+    ((dword >> (8 * n)) & 0xff) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// BFPT of a 128 Mbit flash supporting 4 KiB, 32 KiB and 64 KiB erases.
+    const BFPT_128M: [u32; 11] = [
+        0xfff9_20e5,
+        0x07ff_ffff,
+        0x6b08_eb44,
+        0xbb42_3b08,
+        0xffff_fffe,
+        0xff00_ffff,
+        0xeb44_ffff,
+        0x520f_200c,
+        0xff00_d810,
+        0x0000_0000,
+        0x0000_0080,
+    ];
+
+    #[test]
+    fn headers() {
+        let headers = [
+            0x53, 0x46, 0x44, 0x50, 0x06, 0x01, 0x01, 0xff, 0x00, 0x06, 0x01, 0x10, 0x80, 0x00,
+            0x00, 0xff,
+        ];
+        assert_eq!(parse_headers(&headers), Some((0x80, 16)));
+
+        let mut invalid = headers;
+        invalid[0] = 0xff;
+        assert_eq!(parse_headers(&invalid), None);
+    }
+
+    #[test]
+    fn bfpt() {
+        let parameters = parse_bfpt(&BFPT_128M).unwrap();
+        assert_eq!(parameters.capacity, 16 * 1024 * 1024);
+        assert_eq!(parameters.address_mode, AddressMode::Three);
+        assert_eq!(parameters.page_size, 256);
+        assert_eq!(
+            parameters.erase_types.as_slice(),
+            [
+                EraseType {
+                    size: 65536,
+                    opcode: 0xd8
+                },
+                EraseType {
+                    size: 32768,
+                    opcode: 0x52
+                },
+                EraseType {
+                    size: 4096,
+                    opcode: 0x20
+                },
+            ]
+        );
+
+        // The original JESD216 BFPT does not specify the page size.
+        let parameters = parse_bfpt(&BFPT_128M[..9]).unwrap();
+        assert_eq!(parameters.page_size, 256);
+    }
+
+    #[test]
+    fn bfpt_large() {
+        let mut bfpt = BFPT_128M;
+        // 2^30 bits, 3- or 4-byte addresses.
+        bfpt[1] = 0x8000_001e;
+        bfpt[0] |= 0b01 << 17;
+        let parameters = parse_bfpt(&bfpt).unwrap();
+        assert_eq!(parameters.capacity, 128 * 1024 * 1024);
+        assert_eq!(parameters.address_mode, AddressMode::EnterFour);
+
+        // 3-byte addresses only.
+        bfpt[0] &= !(0b11 << 17);
+        assert_eq!(parse_bfpt(&bfpt), None);
+    }
+
+    #[test]
+    fn bfpt_without_sector_erase() {
+        let mut bfpt = BFPT_128M;
+        bfpt[0] |= 0b11;
+        assert_eq!(parse_bfpt(&bfpt), None);
+    }
+}
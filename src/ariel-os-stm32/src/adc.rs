@@ -0,0 +1,250 @@
+//! Provides support for the ADC.
+
+#![expect(unsafe_code)]
+
+#[cfg(not(any(adc_v2, adc_v3, adc_g0)))]
+compile_error!("the ADC of this STM32 chip is not supported yet");
+
+use core::marker::PhantomData;
+
+use portable_atomic::{AtomicBool, Ordering};
+
+use ariel_os_embassy_common::adc::{Error, Sample, software_oversample};
+use embassy_stm32::{
+    adc::{self, AdcChannel, AnyAdcChannel},
+    peripherals,
+};
+
+#[doc(hidden)]
+pub use embassy_stm32::adc::AdcChannel as AdcPin;
+
+/// ADC configuration.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct Config {
+    /// Resolution of the conversions.
+    pub resolution: ariel_os_embassy_common::adc::Resolution<Resolution>,
+    /// Oversampling ratio, carried out in software.
+    pub oversampling: ariel_os_embassy_common::adc::Oversampling<Oversampling>,
+    /// Voltage reference.
+    pub reference: ariel_os_embassy_common::adc::Reference<Reference>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            resolution: ariel_os_embassy_common::adc::Resolution::Bits12,
+            oversampling: ariel_os_embassy_common::adc::Oversampling::None,
+            reference: ariel_os_embassy_common::adc::Reference::Vdd,
+        }
+    }
+}
+
+/// ADC resolution.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Resolution {
+    /// 6-bit resolution.
+    Bits6,
+    /// 8-bit resolution.
+    Bits8,
+    /// 10-bit resolution.
+    Bits10,
+    /// 12-bit resolution.
+    Bits12,
+}
+
+impl From<Resolution> for u8 {
+    fn from(resolution: Resolution) -> u8 {
+        match resolution {
+            Resolution::Bits6 => 6,
+            Resolution::Bits8 => 8,
+            Resolution::Bits10 => 10,
+            Resolution::Bits12 => 12,
+        }
+    }
+}
+
+impl From<ariel_os_embassy_common::adc::Resolution<Self>> for Resolution {
+    fn from(resolution: ariel_os_embassy_common::adc::Resolution<Self>) -> Self {
+        match resolution {
+            ariel_os_embassy_common::adc::Resolution::Hal(resolution) => resolution,
+            ariel_os_embassy_common::adc::Resolution::Bits8 => Self::Bits8,
+            ariel_os_embassy_common::adc::Resolution::Bits10 => Self::Bits10,
+            ariel_os_embassy_common::adc::Resolution::Bits12 => Self::Bits12,
+        }
+    }
+}
+
+fn from_resolution(resolution: Resolution) -> adc::Resolution {
+    match resolution {
+        Resolution::Bits6 => adc::Resolution::BITS6,
+        Resolution::Bits8 => adc::Resolution::BITS8,
+        Resolution::Bits10 => adc::Resolution::BITS10,
+        Resolution::Bits12 => adc::Resolution::BITS12,
+    }
+}
+
+/// ADC oversampling ratio, carried out in software.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Oversampling {
+    /// No oversampling.
+    None,
+    /// 2× oversampling.
+    X2,
+    /// 4× oversampling.
+    X4,
+    /// 8× oversampling.
+    X8,
+    /// 16× oversampling.
+    X16,
+}
+
+impl From<Oversampling> for u16 {
+    fn from(oversampling: Oversampling) -> u16 {
+        match oversampling {
+            Oversampling::None => 1,
+            Oversampling::X2 => 2,
+            Oversampling::X4 => 4,
+            Oversampling::X8 => 8,
+            Oversampling::X16 => 16,
+        }
+    }
+}
+
+impl From<ariel_os_embassy_common::adc::Oversampling<Self>> for Oversampling {
+    fn from(oversampling: ariel_os_embassy_common::adc::Oversampling<Self>) -> Self {
+        match oversampling {
+            ariel_os_embassy_common::adc::Oversampling::Hal(oversampling) => oversampling,
+            ariel_os_embassy_common::adc::Oversampling::None => Self::None,
+            ariel_os_embassy_common::adc::Oversampling::X2 => Self::X2,
+            ariel_os_embassy_common::adc::Oversampling::X4 => Self::X4,
+            ariel_os_embassy_common::adc::Oversampling::X8 => Self::X8,
+            ariel_os_embassy_common::adc::Oversampling::X16 => Self::X16,
+        }
+    }
+}
+
+/// ADC voltage reference.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Reference {
+    /// The VREF+/VDDA supply, assumed to be 3.3 V.
+    Vdd,
+}
+
+impl From<ariel_os_embassy_common::adc::Reference<Self>> for Reference {
+    fn from(reference: ariel_os_embassy_common::adc::Reference<Self>) -> Self {
+        match reference {
+            ariel_os_embassy_common::adc::Reference::Hal(reference) => reference,
+            ariel_os_embassy_common::adc::Reference::Vdd => Self::Vdd,
+        }
+    }
+}
+
+/// An analog input of the ADC.
+pub struct Channel<'d> {
+    channel: AnyAdcChannel<peripherals::ADC1>,
+    _lt: PhantomData<&'d ()>,
+}
+
+impl<'d> Channel<'d> {
+    /// Returns a channel sampling the given analog-capable pin.
+    pub fn new<P: AdcPin<peripherals::ADC1>>(pin: impl crate::IntoPeripheral<'d, P>) -> Self {
+        Self {
+            channel: pin.into_hal_peripheral().degrade_adc(),
+            _lt: PhantomData,
+        }
+    }
+}
+
+/// Peripheral-specific ADC driver.
+pub struct ADC1 {
+    adc: adc::Adc<'static, peripherals::ADC1>,
+    config: Config,
+}
+
+// Ensure this peripheral has only one active Instance.
+static ACTIVE_ADC1: AtomicBool = AtomicBool::new(false);
+
+impl ADC1 {
+    /// Returns a driver for the ADC1 peripheral.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the peripheral is already in use.
+    #[expect(clippy::new_ret_no_self)]
+    #[expect(clippy::manual_assert, reason = "consistent with the other drivers")]
+    #[must_use]
+    pub fn new(config: Config) -> Adc {
+        // Check if we can initialize this peripheral (check if the value was previously false, set it to true).
+        if ACTIVE_ADC1.swap(true, Ordering::AcqRel) {
+            panic!("ADC1 peripheral already initialized")
+        }
+
+        // FIXME(safety): enforce that the init code indeed has run
+        // SAFETY: We check with an AtomicBool that only one instance of this peripheral
+        // is active at once.
+        let adc_peripheral = unsafe { peripherals::ADC1::steal() };
+
+        let mut adc = adc::Adc::new(adc_peripheral);
+        adc.set_resolution(from_resolution(Resolution::from(config.resolution)));
+
+        Adc::ADC1(Self { adc, config })
+    }
+
+    async fn read(&mut self, channel: &mut Channel<'_>) -> Result<Sample, Error> {
+        let resolution = u8::from(Resolution::from(self.config.resolution));
+        let ratio = u16::from(Oversampling::from(self.config.oversampling));
+        let Reference::Vdd = Reference::from(self.config.reference);
+
+        // NOTE(hal): a single conversion takes a few microseconds at most, so the blocking API is
+        // used instead of requiring a DMA channel.
+        let raw = software_oversample(ratio, || {
+            let raw = self.adc.blocking_read(&mut channel.channel);
+            core::future::ready(Ok::<_, Error>(i32::from(raw)))
+        })
+        .await?;
+
+        let full_scale_raw = (1u32 << resolution) - 1;
+
+        Ok(Sample::new(raw, full_scale_raw, 3300))
+    }
+}
+
+impl Drop for ADC1 {
+    fn drop(&mut self) {
+        ACTIVE_ADC1.store(false, Ordering::Release);
+    }
+}
+
+/// Peripheral-agnostic ADC driver.
+// FIXME: support the other ADC instances found on some chips.
+pub enum Adc {
+    /// ADC1 peripheral.
+    ADC1(ADC1),
+}
+
+impl Adc {
+    /// Performs a single-shot conversion on `channel`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Conversion`] if the conversion could not be carried out.
+    pub async fn read(&mut self, channel: &mut Channel<'_>) -> Result<Sample, Error> {
+        match self {
+            Self::ADC1(adc) => adc.read(channel).await,
+        }
+    }
+}
+
+#[doc(hidden)]
+pub fn init(peripherals: &mut crate::OptionalPeripherals) {
+    // Take the ADC peripheral and do nothing with it.
+    let _ = peripherals.ADC1.take().unwrap();
+}
@@ -7,6 +7,9 @@
 
 mod rcc;
 
+#[cfg(feature = "adc")]
+pub mod adc;
+
 pub mod gpio;
 
 #[doc(hidden)]
@@ -24,9 +27,19 @@ pub mod i2c;
 #[doc(hidden)]
 pub mod identity;
 
+#[cfg(feature = "pwm")]
+pub mod pwm;
+
+#[cfg(feature = "qspi")]
+#[cfg(capability = "hw/stm32-quadspi")]
+pub mod qspi;
+
 #[cfg(feature = "spi")]
 pub mod spi;
 
+#[cfg(feature = "system-off")]
+pub mod system_off;
+
 #[cfg(feature = "uart")]
 pub mod uart;
 
@@ -38,6 +51,10 @@ pub mod storage;
 #[doc(hidden)]
 pub mod usb;
 
+#[cfg(feature = "watchdog")]
+#[doc(hidden)]
+pub mod watchdog;
+
 #[cfg(feature = "ethernet")]
 #[doc(hidden)]
 pub mod ethernet;
@@ -0,0 +1,385 @@
+//! Provides support for PWM outputs, using the timer peripherals.
+
+#![expect(unsafe_code)]
+
+use core::marker::PhantomData;
+
+use portable_atomic::{AtomicBool, Ordering};
+
+use ariel_os_embassy_common::{
+    impl_pwm_builder_settings, impl_pwm_for_driver_enum,
+    pwm::{DutyCycle, Error, Hertz, Polarity, Settings},
+};
+use embassy_stm32::{
+    Peri,
+    gpio::OutputType,
+    peripherals,
+    time::Hertz as StmHertz,
+    timer::{
+        Ch1, Ch2, Ch3, Ch4, Channel, GeneralInstance4Channel, TimerPin,
+        low_level::CountingMode,
+        simple_pwm::{PwmPin, SimplePwm},
+    },
+};
+
+/// Number of channels of each timer.
+pub const CHANNELS: usize = 4;
+
+/// Builder for a PWM driver, obtained by calling `builder()` on a timer-specific driver.
+///
+/// Channels `0` to `3` are the channels 1 to 4 of the timer, each of which can only be output
+/// on specific GPIOs.
+pub struct PwmBuilder<T: Instance> {
+    settings: Settings,
+    ch1: Option<PwmPin<'static, T::Timer, Ch1>>,
+    ch2: Option<PwmPin<'static, T::Timer, Ch2>>,
+    ch3: Option<PwmPin<'static, T::Timer, Ch3>>,
+    ch4: Option<PwmPin<'static, T::Timer, Ch4>>,
+    _instance: PhantomData<T>,
+}
+
+impl_pwm_builder_settings!(T: Instance);
+
+impl<T: Instance> PwmBuilder<T> {
+    /// Outputs channel `0` (channel 1 of the timer) to `pin`.
+    #[must_use]
+    pub fn ch1<P: TimerPin<T::Timer, Ch1>>(
+        mut self,
+        pin: impl crate::IntoPeripheral<'static, P>,
+    ) -> Self {
+        self.ch1 = Some(PwmPin::new(pin.into_hal_peripheral(), OutputType::PushPull));
+        self
+    }
+
+    /// Outputs channel `1` (channel 2 of the timer) to `pin`.
+    #[must_use]
+    pub fn ch2<P: TimerPin<T::Timer, Ch2>>(
+        mut self,
+        pin: impl crate::IntoPeripheral<'static, P>,
+    ) -> Self {
+        self.ch2 = Some(PwmPin::new(pin.into_hal_peripheral(), OutputType::PushPull));
+        self
+    }
+
+    /// Outputs channel `2` (channel 3 of the timer) to `pin`.
+    #[must_use]
+    pub fn ch3<P: TimerPin<T::Timer, Ch3>>(
+        mut self,
+        pin: impl crate::IntoPeripheral<'static, P>,
+    ) -> Self {
+        self.ch3 = Some(PwmPin::new(pin.into_hal_peripheral(), OutputType::PushPull));
+        self
+    }
+
+    /// Outputs channel `3` (channel 4 of the timer) to `pin`.
+    #[must_use]
+    pub fn ch4<P: TimerPin<T::Timer, Ch4>>(
+        mut self,
+        pin: impl crate::IntoPeripheral<'static, P>,
+    ) -> Self {
+        self.ch4 = Some(PwmPin::new(pin.into_hal_peripheral(), OutputType::PushPull));
+        self
+    }
+
+    /// Returns a PWM driver by finalizing the builder.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::FrequencyNotSupported`] if the frequency cannot be obtained.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the timer is already in use.
+    pub fn build(self) -> Result<Pwm, Error> {
+        let frequency = hertz(self.settings.frequency)?;
+        let channels = [
+            self.ch1.is_some(),
+            self.ch2.is_some(),
+            self.ch3.is_some(),
+            self.ch4.is_some(),
+        ];
+
+        let pwm = SimplePwm::new(
+            T::take(),
+            self.ch1,
+            self.ch2,
+            self.ch3,
+            self.ch4,
+            frequency,
+            CountingMode::EdgeAlignedUp,
+        );
+
+        let mut inner = Inner {
+            pwm,
+            channels,
+            polarity: self.settings.polarity,
+            duty_cycles: [self.settings.initial_duty_cycle; CHANNELS],
+        };
+        inner.apply();
+
+        Ok(T::wrap(inner))
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+#[doc(hidden)]
+pub trait Instance: sealed::Sealed + Sized {
+    type Timer: GeneralInstance4Channel;
+
+    /// Returns the timer peripheral, panicking if it is already in use.
+    fn take() -> Peri<'static, Self::Timer>;
+
+    fn wrap(inner: Inner<Self::Timer>) -> Pwm;
+}
+
+// NOTE(hal): the highest obtainable frequency depends on the timer clock; the timer driver
+// panics if the frequency is zero.
+fn hertz(frequency: Hertz) -> Result<StmHertz, Error> {
+    match frequency.to_Hz() {
+        0 => Err(Error::FrequencyNotSupported),
+        hz => Ok(StmHertz(hz)),
+    }
+}
+
+const TIMER_CHANNELS: [Channel; CHANNELS] =
+    [Channel::Ch1, Channel::Ch2, Channel::Ch3, Channel::Ch4];
+
+// State shared by all the timer-specific drivers.
+#[doc(hidden)]
+pub struct Inner<T: GeneralInstance4Channel> {
+    pwm: SimplePwm<'static, T>,
+    channels: [bool; CHANNELS],
+    polarity: Polarity,
+    duty_cycles: [DutyCycle; CHANNELS],
+}
+
+impl<T: GeneralInstance4Channel> Inner<T> {
+    fn apply(&mut self) {
+        let max_duty_cycle = u32::from(self.pwm.max_duty_cycle());
+
+        for ((timer_channel, enabled), duty_cycle) in TIMER_CHANNELS
+            .into_iter()
+            .zip(self.channels)
+            .zip(self.duty_cycles)
+        {
+            if enabled {
+                let high_ticks = duty_cycle.high_ticks(max_duty_cycle, self.polarity);
+                let mut channel = self.pwm.channel(timer_channel);
+                // Cannot truncate as `high_ticks` is not larger than the maximum duty cycle.
+                #[expect(clippy::cast_possible_truncation)]
+                channel.set_duty_cycle(high_ticks as u16);
+                channel.enable();
+            }
+        }
+    }
+
+    fn set_duty_cycle(&mut self, channel: usize, duty_cycle: DutyCycle) -> Result<(), Error> {
+        if !self.channels.get(channel).copied().unwrap_or(false) {
+            return Err(Error::InvalidChannel);
+        }
+        let slot = self
+            .duty_cycles
+            .get_mut(channel)
+            .ok_or(Error::InvalidChannel)?;
+        *slot = duty_cycle;
+        self.apply();
+
+        Ok(())
+    }
+
+    fn set_frequency(&mut self, frequency: Hertz) -> Result<(), Error> {
+        self.pwm.set_frequency(hertz(frequency)?);
+        // The maximum duty cycle has changed, update the compare values.
+        self.apply();
+
+        Ok(())
+    }
+}
+
+macro_rules! define_pwm_drivers {
+    ($( $peripheral:ident ),* $(,)?) => {
+        $(
+            /// Timer-specific PWM driver.
+            pub struct $peripheral {
+                inner: Inner<peripherals::$peripheral>,
+            }
+
+            // Ensure this peripheral has only one active Instance.
+            paste::paste! {
+                static [< ACTIVE_ $peripheral >]: AtomicBool = AtomicBool::new(false);
+            }
+
+            impl $peripheral {
+                /// Returns a [`PwmBuilder`] for this timer, producing a PWM signal at `frequency`.
+                #[must_use]
+                pub fn builder(frequency: Hertz) -> PwmBuilder<Self> {
+                    PwmBuilder {
+                        settings: Settings::new(frequency),
+                        ch1: None,
+                        ch2: None,
+                        ch3: None,
+                        ch4: None,
+                        _instance: PhantomData,
+                    }
+                }
+
+                #[expect(clippy::unused_self)]
+                fn channel_count(&self) -> usize {
+                    CHANNELS
+                }
+
+                fn set_duty_cycle(
+                    &mut self,
+                    channel: usize,
+                    duty_cycle: DutyCycle,
+                ) -> Result<(), Error> {
+                    self.inner.set_duty_cycle(channel, duty_cycle)
+                }
+
+                fn set_frequency(&mut self, frequency: Hertz) -> Result<(), Error> {
+                    self.inner.set_frequency(frequency)
+                }
+            }
+
+            impl sealed::Sealed for $peripheral {}
+
+            impl Instance for $peripheral {
+                type Timer = peripherals::$peripheral;
+
+                fn take() -> Peri<'static, Self::Timer> {
+                    // Make this struct a compile-time-enforced singleton: having multiple statics
+                    // defined with the same name would result in a compile-time error.
+                    paste::paste! {
+                        #[allow(dead_code)]
+                        static [<PREVENT_MULTIPLE_ $peripheral>]: () = ();
+                    }
+
+                    // Check if we can initialize this peripheral (check if the value was previously false, set it to true).
+                    paste::paste! {
+                        if [< ACTIVE_ $peripheral >].swap(true, Ordering::AcqRel) {
+                            panic!("PWM timer already initialized")
+                        }
+                    }
+
+                    // FIXME(safety): enforce that the init code indeed has run
+                    // SAFETY: We check with an AtomicBool that only one instance of this peripheral
+                    // is active at once.
+                    unsafe { peripherals::$peripheral::steal() }
+                }
+
+                fn wrap(inner: Inner<Self::Timer>) -> Pwm {
+                    Pwm::$peripheral(Self { inner })
+                }
+            }
+
+            impl Drop for $peripheral {
+                fn drop(&mut self) {
+                    paste::paste! {
+                        [< ACTIVE_ $peripheral >].store(false, Ordering::Release);
+                    }
+                }
+            }
+        )*
+
+        /// Timer-agnostic PWM driver.
+        pub enum Pwm {
+            $(
+                #[doc = concat!(stringify!($peripheral), " timer.")]
+                $peripheral($peripheral),
+            )*
+        }
+
+        impl_pwm_for_driver_enum!(Pwm, $( $peripheral ),*);
+    }
+}
+
+// Define a driver per timer.
+// NOTE: the timer selected by Embassy for its time driver is not available and therefore not
+// listed here.
+#[cfg(context = "stm32c031c6")]
+define_pwm_drivers!(TIM1);
+#[cfg(context = "stm32f042k6")]
+define_pwm_drivers!(TIM1, TIM2);
+#[cfg(any(context = "stm32f303cb", context = "stm32f303re"))]
+define_pwm_drivers!(TIM2, TIM3, TIM4);
+#[cfg(any(context = "stm32f401re", context = "stm32f411re"))]
+define_pwm_drivers!(TIM2, TIM3, TIM4, TIM5);
+#[cfg(context = "stm32g431rb")]
+define_pwm_drivers!(TIM2, TIM3, TIM4);
+#[cfg(context = "stm32f767zi")]
+define_pwm_drivers!(TIM2, TIM3, TIM4, TIM5);
+#[cfg(any(context = "stm32h755zi", context = "stm32h753zi"))]
+define_pwm_drivers!(TIM2, TIM3, TIM4, TIM5);
+#[cfg(context = "stm32l475vg")]
+define_pwm_drivers!(TIM2, TIM3, TIM4, TIM5);
+#[cfg(any(context = "stm32u073kc", context = "stm32u083mc"))]
+define_pwm_drivers!(TIM2, TIM3);
+#[cfg(context = "stm32u585ai")]
+define_pwm_drivers!(TIM2, TIM3, TIM4, TIM5);
+#[cfg(context = "stm32wb55rg")]
+define_pwm_drivers!(TIM1);
+#[cfg(context = "stm32wba55cg")]
+define_pwm_drivers!(TIM1, TIM2);
+#[cfg(context = "stm32wle5jc")]
+define_pwm_drivers!(TIM1);
+
+#[doc(hidden)]
+pub fn init(peripherals: &mut crate::OptionalPeripherals) {
+    // This macro has to be defined in this function so that the `peripherals` variables exists.
+    macro_rules! take_all_pwm_peripherals {
+        ($( $peripheral:ident ),*) => {
+            $(
+                let _ = peripherals.$peripheral.take().unwrap();
+            )*
+        }
+    }
+
+    // Take all PWM timers and do nothing with them.
+    cfg_select! {
+        context = "stm32c031c6" => {
+            take_all_pwm_peripherals!(TIM1);
+        }
+        context = "stm32f042k6" => {
+            take_all_pwm_peripherals!(TIM1, TIM2);
+        }
+        any(context = "stm32f303cb", context = "stm32f303re") => {
+            take_all_pwm_peripherals!(TIM2, TIM3, TIM4);
+        }
+        any(context = "stm32f401re", context = "stm32f411re") => {
+            take_all_pwm_peripherals!(TIM2, TIM3, TIM4, TIM5);
+        }
+        context = "stm32g431rb" => {
+            take_all_pwm_peripherals!(TIM2, TIM3, TIM4);
+        }
+        context = "stm32f767zi" => {
+            take_all_pwm_peripherals!(TIM2, TIM3, TIM4, TIM5);
+        }
+        any(context = "stm32h755zi", context = "stm32h753zi") => {
+            take_all_pwm_peripherals!(TIM2, TIM3, TIM4, TIM5);
+        }
+        context = "stm32l475vg" => {
+            take_all_pwm_peripherals!(TIM2, TIM3, TIM4, TIM5);
+        }
+        any(context = "stm32u073kc", context = "stm32u083mc") => {
+            take_all_pwm_peripherals!(TIM2, TIM3);
+        }
+        context = "stm32u585ai" => {
+            take_all_pwm_peripherals!(TIM2, TIM3, TIM4, TIM5);
+        }
+        context = "stm32wb55rg" => {
+            take_all_pwm_peripherals!(TIM1);
+        }
+        context = "stm32wba55cg" => {
+            take_all_pwm_peripherals!(TIM1, TIM2);
+        }
+        context = "stm32wle5jc" => {
+            take_all_pwm_peripherals!(TIM1);
+        }
+        _ => {
+            compile_error!("this STM32 chip is not supported");
+        }
+    }
+}
@@ -0,0 +1,333 @@
+//! Provides the QUADSPI peripheral, to access external NOR flash chips.
+//!
+//! [`Qspi`] implements `embedded_storage_async::nor_flash::NorFlash` for flash chips supporting
+//! the common SPI NOR instructions, with 4 KiB sector erases and 256-byte pages, and can thus be
+//! used as a storage backend.
+//! Data is transferred using DMA; the flash can instead be mapped for execute-in-place (XIP)
+//! reads at [`XIP_BASE`], using [`Qspi::into_memory_mapped()`].
+
+use embassy_stm32::{
+    Peri,
+    mode::Async,
+    peripherals::QUADSPI,
+    qspi::{
+        self, BK1D0Pin, BK1D1Pin, BK1D2Pin, BK1D3Pin, BK1NSSPin, QuadDma, SckPin, TransferConfig,
+        enums::{DummyCycles, QspiWidth},
+    },
+};
+use embassy_time::{Duration, Instant};
+use embedded_storage_async::nor_flash::{
+    ErrorType, MultiwriteNorFlash, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash,
+};
+
+pub use embassy_stm32::qspi::enums::{
+    AddressSize, ChipSelectHighTime, FIFOThresholdLevel, MemorySize,
+};
+
+/// Address at which the external flash is mapped, see [`Qspi::into_memory_mapped()`].
+pub const XIP_BASE: usize = 0x9000_0000;
+
+// Instructions, common to all flash chips.
+/// Enables writing, for the next program or erase instruction.
+const WRITE_ENABLE: u8 = 0x06;
+/// Reads the status register.
+const READ_STATUS: u8 = 0x05;
+/// Reads data, with 8 dummy cycles.
+const FAST_READ: u8 = 0x0b;
+/// Reads data on four lines, with 8 dummy cycles.
+const FAST_READ_QUAD_OUTPUT: u8 = 0x6b;
+/// Programs data, within a page.
+const PAGE_PROGRAM: u8 = 0x02;
+/// Erases a 4 KiB sector.
+const SECTOR_ERASE: u8 = 0x20;
+
+/// Write In Progress bit of the status register.
+const STATUS_BUSY: u8 = 0x01;
+
+/// Size of the pages of the flash, which a single program instruction can write.
+const PAGE_SIZE: u32 = 256;
+
+/// Maximum duration of a page program.
+const PROGRAM_TIMEOUT: Duration = Duration::from_millis(50);
+/// Maximum duration of a sector erase.
+const ERASE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Instruction used to read the flash.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ReadMode {
+    /// Fast read on a single line, supported by all flash chips.
+    FastRead,
+    /// Fast read with data on four lines, which requires the Quad Enable bit of the flash to be
+    /// set.
+    FastReadQuadOutput,
+}
+
+/// QUADSPI configuration.
+#[derive(Clone, Copy)]
+#[non_exhaustive]
+pub struct Config {
+    /// Capacity of the flash, in bytes.
+    pub capacity: u32,
+    /// Size of the flash, as configured in the peripheral, which must match `capacity`.
+    pub memory_size: MemorySize,
+    /// Size of the addresses sent to the flash.
+    pub address_size: AddressSize,
+    /// Divider of the AHB clock, minus one, giving the frequency of the flash clock.
+    pub prescaler: u8,
+    /// Minimum number of cycles the chip select stays high between instructions.
+    pub cs_high_time: ChipSelectHighTime,
+    /// Instruction used to read the flash.
+    pub read_mode: ReadMode,
+}
+
+impl Config {
+    /// Returns a configuration for a flash of `capacity` bytes, with `memory_size` matching it.
+    #[must_use]
+    pub fn new(capacity: u32, memory_size: MemorySize) -> Self {
+        Self {
+            capacity,
+            memory_size,
+            address_size: AddressSize::_24bit,
+            prescaler: 128,
+            cs_high_time: ChipSelectHighTime::_5Cycle,
+            read_mode: ReadMode::FastRead,
+        }
+    }
+}
+
+/// Errors returned by [`Qspi`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// The flash did not finish programming or erasing in time.
+    Timeout,
+    /// The arguments are not aligned.
+    NotAligned,
+    /// The arguments are out of bounds.
+    OutOfBounds,
+}
+
+impl NorFlashError for Error {
+    fn kind(&self) -> NorFlashErrorKind {
+        match self {
+            Self::NotAligned => NorFlashErrorKind::NotAligned,
+            Self::OutOfBounds => NorFlashErrorKind::OutOfBounds,
+            Self::Timeout => NorFlashErrorKind::Other,
+        }
+    }
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Timeout => f.write_str("flash timeout"),
+            Self::NotAligned => f.write_str("arguments not aligned"),
+            Self::OutOfBounds => f.write_str("arguments out of bounds"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// QUADSPI peripheral, driving an external NOR flash connected to its first bank.
+pub struct Qspi {
+    qspi: qspi::Qspi<'static, QUADSPI, Async>,
+    config: Config,
+}
+
+impl Qspi {
+    /// Creates the QUADSPI peripheral, driving the flash connected to the given pins, and
+    /// transferring data using `dma`.
+    #[expect(clippy::too_many_arguments)]
+    #[must_use]
+    pub fn new(
+        qspi: Peri<'static, QUADSPI>,
+        sck: Peri<'static, impl SckPin<QUADSPI>>,
+        nss: Peri<'static, impl BK1NSSPin<QUADSPI>>,
+        io0: Peri<'static, impl BK1D0Pin<QUADSPI>>,
+        io1: Peri<'static, impl BK1D1Pin<QUADSPI>>,
+        io2: Peri<'static, impl BK1D2Pin<QUADSPI>>,
+        io3: Peri<'static, impl BK1D3Pin<QUADSPI>>,
+        dma: Peri<'static, impl QuadDma<QUADSPI>>,
+        config: Config,
+    ) -> Self {
+        let mut qspi_config = qspi::Config::default();
+        qspi_config.memory_size = config.memory_size;
+        qspi_config.address_size = config.address_size;
+        qspi_config.prescaler = config.prescaler;
+        qspi_config.cs_high_time = config.cs_high_time;
+        qspi_config.fifo_threshold = FIFOThresholdLevel::_16Bytes;
+
+        let qspi = qspi::Qspi::new_bank1(qspi, io0, io1, io2, io3, sck, nss, dma, qspi_config);
+        Self { qspi, config }
+    }
+
+    /// Maps the flash to memory, for it to be read at [`XIP_BASE`].
+    ///
+    /// The flash can then no longer be written or erased.
+    #[must_use]
+    pub fn into_memory_mapped(mut self) -> MemoryMapped {
+        let transfer = self.read_transfer(0);
+        self.qspi.enable_memory_map(&transfer);
+        MemoryMapped { _qspi: self.qspi }
+    }
+
+    /// Returns the transfer reading the flash at `address`.
+    fn read_transfer(&self, address: u32) -> TransferConfig {
+        let (instruction, dwidth) = match self.config.read_mode {
+            ReadMode::FastRead => (FAST_READ, QspiWidth::SING),
+            ReadMode::FastReadQuadOutput => (FAST_READ_QUAD_OUTPUT, QspiWidth::QUAD),
+        };
+        TransferConfig {
+            iwidth: QspiWidth::SING,
+            awidth: QspiWidth::SING,
+            dwidth,
+            instruction,
+            address: Some(address),
+            dummy: DummyCycles::_8,
+        }
+    }
+
+    /// Checks that `len` bytes at `offset` are within the flash.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::OutOfBounds`] otherwise.
+    fn check_bounds(&self, offset: u32, len: usize) -> Result<(), Error> {
+        let end = u32::try_from(len)
+            .ok()
+            .and_then(|len| offset.checked_add(len));
+        if end.is_none_or(|end| end > self.config.capacity) {
+            return Err(Error::OutOfBounds);
+        }
+        Ok(())
+    }
+
+    /// Sends a program or erase instruction at `address`, and waits for it to complete.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Timeout`] if the flash is still busy after `timeout`.
+    async fn program(
+        &mut self,
+        instruction: u8,
+        address: u32,
+        data: &[u8],
+        timeout: Duration,
+    ) -> Result<(), Error> {
+        self.qspi
+            .blocking_command(command(WRITE_ENABLE, None, QspiWidth::NONE));
+        if data.is_empty() {
+            self.qspi
+                .blocking_command(command(instruction, Some(address), QspiWidth::NONE));
+        } else {
+            let transfer = command(instruction, Some(address), QspiWidth::SING);
+            self.qspi.write_dma(data, transfer).await;
+        }
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let mut status = [0];
+            self.qspi
+                .blocking_read(&mut status, command(READ_STATUS, None, QspiWidth::SING));
+            let [status] = status;
+            if status & STATUS_BUSY == 0 {
+                return Ok(());
+            }
+            if Instant::now() >= deadline {
+                return Err(Error::Timeout);
+            }
+            embassy_futures::yield_now().await;
+        }
+    }
+}
+
+/// Returns the transfer of a single-line instruction, with data of `dwidth`.
+fn command(instruction: u8, address: Option<u32>, dwidth: QspiWidth) -> TransferConfig {
+    TransferConfig {
+        iwidth: QspiWidth::SING,
+        awidth: if address.is_some() {
+            QspiWidth::SING
+        } else {
+            QspiWidth::NONE
+        },
+        dwidth,
+        instruction,
+        address,
+        dummy: DummyCycles::_0,
+    }
+}
+
+impl ErrorType for Qspi {
+    type Error = Error;
+}
+
+impl ReadNorFlash for Qspi {
+    const READ_SIZE: usize = 1;
+
+    async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        self.check_bounds(offset, bytes.len())?;
+        if !bytes.is_empty() {
+            let transfer = self.read_transfer(offset);
+            self.qspi.read_dma(bytes, transfer).await;
+        }
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.config.capacity as usize
+    }
+}
+
+impl NorFlash for Qspi {
+    const WRITE_SIZE: usize = 1;
+    const ERASE_SIZE: usize = 4096;
+
+    async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        if from > to || to > self.config.capacity {
+            return Err(Error::OutOfBounds);
+        }
+        if !(from as usize).is_multiple_of(Self::ERASE_SIZE)
+            || !(to as usize).is_multiple_of(Self::ERASE_SIZE)
+        {
+            return Err(Error::NotAligned);
+        }
+
+        for address in (from..to).step_by(Self::ERASE_SIZE) {
+            self.program(SECTOR_ERASE, address, &[], ERASE_TIMEOUT)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.check_bounds(offset, bytes.len())?;
+
+        let mut offset = offset;
+        let mut bytes = bytes;
+        while !bytes.is_empty() {
+            // A page program wraps around at the end of the page.
+            let page_left = PAGE_SIZE - offset % PAGE_SIZE;
+            let (chunk, rest) = bytes.split_at(bytes.len().min(page_left as usize));
+
+            self.program(PAGE_PROGRAM, offset, chunk, PROGRAM_TIMEOUT)
+                .await?;
+
+            #[expect(clippy::cast_possible_truncation, reason = "chunks fit in a page")]
+            let chunk_len = chunk.len() as u32;
+            offset += chunk_len;
+            bytes = rest;
+        }
+        Ok(())
+    }
+}
+
+// NOR flash allows programming already-programmed bits to 0.
+impl MultiwriteNorFlash for Qspi {}
+
+/// External flash mapped to memory, readable at [`XIP_BASE`] for as long as this is not dropped.
+pub struct MemoryMapped {
+    _qspi: qspi::Qspi<'static, QUADSPI, Async>,
+}
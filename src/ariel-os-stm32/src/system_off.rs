@@ -0,0 +1,222 @@
+//! Provides system off, from which the MCU wakes up with a reset.
+//!
+//! The MCU is put in standby mode.
+//! Only the `WKUP1` wake-up pin (`PA0`) and the RTC wake-up timer can wake the MCU up.
+
+use core::{convert::Infallible, time::Duration};
+
+use embassy_stm32::{Peri, gpio::Pin};
+
+pub use ariel_os_embassy_common::system_off::{Error, WakeEdge};
+
+/// GPIO pin that can wake the MCU up from system off.
+#[derive(Debug, Clone, Copy)]
+pub struct WakePin {
+    #[cfg_attr(
+        not(any(
+            context = "stm32c031c6",
+            context = "stm32g431rb",
+            context = "stm32l475vg",
+            context = "stm32u073kc",
+            context = "stm32u083mc",
+            context = "stm32wb55rg",
+            context = "stm32wle5jc",
+        )),
+        expect(dead_code, reason = "system off is not supported")
+    )]
+    is_wkup1: bool,
+}
+
+#[doc(hidden)]
+pub fn wake_pin<P: Pin>(pin: &Peri<'_, P>) -> WakePin {
+    WakePin {
+        // PA0
+        is_wkup1: pin.port() == 0 && pin.pin() == 0,
+    }
+}
+
+/// Source waking the MCU up from system off.
+#[derive(Debug, Clone, Copy)]
+pub enum WakeSource {
+    #[doc(hidden)]
+    Pin(WakePin, WakeEdge),
+    #[doc(hidden)]
+    Timer(Duration),
+}
+
+cfg_select! {
+    any(
+        context = "stm32c031c6",
+        context = "stm32g431rb",
+        context = "stm32l475vg",
+        context = "stm32u073kc",
+        context = "stm32u083mc",
+        context = "stm32wb55rg",
+        context = "stm32wle5jc",
+    ) => {
+        #[doc(hidden)]
+        pub fn system_off(
+            wake_sources: impl IntoIterator<Item = WakeSource>,
+        ) -> Result<Infallible, Error> {
+            use embassy_stm32::pac;
+
+            // Standby mode in the LPMS field of PWR_CR1.
+            const LPMS_STANDBY: u32 = 0b011;
+            const LPMS_MASK: u32 = 0b111;
+            // EWUP1 in PWR_CR3, WP1 in PWR_CR4, and CWUF1 in PWR_SCR.
+            const WKUP1: u32 = 1 << 0;
+
+            let mut timer = None;
+
+            for wake_source in wake_sources {
+                match wake_source {
+                    WakeSource::Pin(pin, edge) if pin.is_wkup1 => {
+                        pac::PWR.cr4().modify(|w| match edge {
+                            WakeEdge::Rising => w.0 &= !WKUP1,
+                            WakeEdge::Falling => w.0 |= WKUP1,
+                        });
+                        pac::PWR.cr3().modify(|w| w.0 |= WKUP1);
+                    }
+                    WakeSource::Pin(..) => return Err(Error::UnsupportedWakeSource),
+                    WakeSource::Timer(duration) => {
+                        // Keep the earliest timer.
+                        let duration = timer.map_or(duration, |t: Duration| t.min(duration));
+                        timer = Some(duration);
+                    }
+                }
+            }
+
+            if let Some(duration) = timer {
+                enable_wake_up_timer(duration)?;
+            }
+
+            // Clear the wake-up flag, which would otherwise prevent entering standby.
+            pac::PWR.scr().write(|w| w.0 = WKUP1);
+            pac::PWR
+                .cr1()
+                .modify(|w| w.0 = (w.0 & !LPMS_MASK) | LPMS_STANDBY);
+
+            #[expect(unsafe_code)]
+            // SAFETY: only the SLEEPDEEP bit is modified, and execution never resumes.
+            let mut scb = unsafe { cortex_m::Peripherals::steal() }.SCB;
+            scb.set_sleepdeep();
+
+            loop {
+                cortex_m::asm::dsb();
+                cortex_m::asm::wfi();
+            }
+        }
+
+        #[cfg(context = "stm32c031c6")]
+        fn enable_wake_up_timer(_duration: Duration) -> Result<(), Error> {
+            // NOTE(hal): the RTC of the STM32C0 family has no wake-up timer.
+            Err(Error::UnsupportedWakeSource)
+        }
+
+        /// Configures the RTC wake-up timer to wake the MCU up after `duration`, rounded up to the
+        /// second.
+        ///
+        /// # Errors
+        ///
+        /// Returns [`Error::UnsupportedWakeSource`] if `duration` is too long, or if the RTC is not
+        /// clocked.
+        #[cfg(not(context = "stm32c031c6"))]
+        fn enable_wake_up_timer(duration: Duration) -> Result<(), Error> {
+            use embassy_stm32::pac;
+
+            // WUCKSEL selecting the 1 Hz clock (ck_spre), and the same clock with 2^16 added to the
+            // counter, in RTC_CR.
+            const WUCKSEL_1HZ: u32 = 0b100;
+            const WUCKSEL_1HZ_EXTENDED: u32 = 0b110;
+            const WUCKSEL_MASK: u32 = 0b111;
+            // WUTE and WUTIE in RTC_CR.
+            const WUTE: u32 = 1 << 10;
+            const WUTIE: u32 = 1 << 14;
+            // WUTWF in RTC_ISR or RTC_ICSR.
+            const WUTWF: u32 = 1 << 2;
+            // DBP in PWR_CR1, and EIWUL (EIWF on some families) in PWR_CR3.
+            const DBP: u32 = 1 << 8;
+            const EIWUL: u32 = 1 << 15;
+            // WUTWF is set within a few RTC clock cycles, provided the RTC is clocked.
+            const WUTWF_POLLS: u32 = 100_000;
+            const EXTENDED_SECS: u64 = 1 << 16;
+
+            let secs = (duration.as_secs() + u64::from(duration.subsec_nanos() > 0)).max(1);
+            let (wucksel, counter) = if secs <= EXTENDED_SECS {
+                (WUCKSEL_1HZ, secs - 1)
+            } else {
+                (WUCKSEL_1HZ_EXTENDED, secs - 1 - EXTENDED_SECS)
+            };
+            let counter = u16::try_from(counter).map_err(|_| Error::UnsupportedWakeSource)?;
+
+            // Allow writing to the RTC registers.
+            pac::PWR.cr1().modify(|w| w.0 |= DBP);
+            pac::RTC.wpr().write(|w| w.0 = 0xca);
+            pac::RTC.wpr().write(|w| w.0 = 0x53);
+
+            pac::RTC.cr().modify(|w| w.0 &= !(WUTE | WUTIE));
+            let writable = (0..WUTWF_POLLS).any(|_| wake_up_timer_status() & WUTWF != 0);
+
+            if writable {
+                pac::RTC.wutr().write(|w| w.0 = u32::from(counter));
+                clear_wake_up_timer_flag();
+                pac::RTC
+                    .cr()
+                    .modify(|w| w.0 = (w.0 & !WUCKSEL_MASK) | wucksel | WUTE | WUTIE);
+            }
+
+            pac::RTC.wpr().write(|w| w.0 = 0xff);
+
+            if !writable {
+                return Err(Error::UnsupportedWakeSource);
+            }
+
+            // Let the RTC events wake the MCU up from standby.
+            pac::PWR.cr3().modify(|w| w.0 |= EIWUL);
+
+            Ok(())
+        }
+
+        #[cfg(not(context = "stm32c031c6"))]
+        fn wake_up_timer_status() -> u32 {
+            use embassy_stm32::pac;
+
+            cfg_select! {
+                any(context = "stm32l475vg", context = "stm32wb55rg") => {
+                    pac::RTC.isr().read().0
+                }
+                _ => {
+                    pac::RTC.icsr().read().0
+                }
+            }
+        }
+
+        #[cfg(not(context = "stm32c031c6"))]
+        fn clear_wake_up_timer_flag() {
+            use embassy_stm32::pac;
+
+            cfg_select! {
+                any(context = "stm32l475vg", context = "stm32wb55rg") => {
+                    // WUTF in RTC_ISR, cleared by writing zero.
+                    const WUTF: u32 = 1 << 10;
+                    pac::RTC.isr().modify(|w| w.0 &= !WUTF);
+                }
+                _ => {
+                    // CWUTF in RTC_SCR.
+                    const CWUTF: u32 = 1 << 2;
+                    pac::RTC.scr().write(|w| w.0 = CWUTF);
+                }
+            }
+        }
+    }
+    _ => {
+        #[doc(hidden)]
+        pub fn system_off(
+            wake_sources: impl IntoIterator<Item = WakeSource>,
+        ) -> Result<Infallible, Error> {
+            // FIXME: support the other families, which have different PWR registers.
+            let _ = wake_sources;
+            Err(Error::Unsupported)
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use embassy_stm32::{peripherals, wdg::IndependentWatchdog};
+
+cfg_select! {
+    any(context = "stm32h755zi", context = "stm32h753zi") => {
+        type Iwdg = peripherals::IWDG1;
+    }
+    _ => {
+        type Iwdg = peripherals::IWDG;
+    }
+}
+
+/// Longest supported timeout, in milliseconds.
+// NOTE(hal): the independent watchdog is clocked from the ~32 kHz LSI, with a prescaler of at
+// most 256 and a 12-bit reload value.
+pub const MAX_TIMEOUT_MS: u32 = 32_000;
+
+/// Hardware watchdog, started by [`init()`].
+pub struct Watchdog {
+    watchdog: IndependentWatchdog<'static, Iwdg>,
+}
+
+impl Watchdog {
+    /// Feeds the watchdog, restarting its timeout.
+    pub fn feed(&mut self) {
+        self.watchdog.pet();
+    }
+}
+
+/// Starts the independent watchdog (IWDG) with a timeout of `timeout_ms` milliseconds.
+///
+/// The timeout is capped to [`MAX_TIMEOUT_MS`].
+///
+/// # Panics
+///
+/// Panics if the watchdog peripheral has been previously taken.
+pub fn init(peripherals: &mut crate::OptionalPeripherals, timeout_ms: u32) -> Watchdog {
+    let iwdg = cfg_select! {
+        any(context = "stm32h755zi", context = "stm32h753zi") => { peripherals.IWDG1.take().unwrap() }
+        _ => { peripherals.IWDG.take().unwrap() }
+    };
+
+    let timeout_us = timeout_ms.min(MAX_TIMEOUT_MS) * 1000;
+    let mut watchdog = IndependentWatchdog::new(iwdg, timeout_us);
+    watchdog.unleash();
+
+    Watchdog { watchdog }
+}
@@ -1,13 +1,115 @@
-use std::{env, path::PathBuf};
+use std::{
+    env,
+    fmt::Write as _,
+    path::{Path, PathBuf},
+};
+
+use ariel_os_buildutils::native_flash;
 
 const KIBIBYTES: u32 = 1024;
 
+/// Name of the partition used by the global storage functions, which always exists.
+const DEFAULT_PARTITION: &str = "default";
+
+/// Default size of a partition or queue on native, two pages of the emulated flash.
+const NATIVE_PARTITION_SIZE: u32 = 2 * native_flash::PAGE_SIZE;
+
+/// Kind of flash range reserved for the storage.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Region {
+    /// A key-value [`Partition`].
+    Partition,
+    /// A [`Queue`].
+    Queue,
+    /// A [`RawPartition`].
+    Raw,
+}
+
+impl Region {
+    /// Returns the prefix of the linker symbols delimiting a range of this kind.
+    fn symbol_prefix(self) -> &'static str {
+        match self {
+            Self::Partition => "__storage",
+            Self::Queue => "__storage_queue",
+            Self::Raw => "__storage_raw",
+        }
+    }
+
+    /// Returns the prefix of the statics defined for ranges of this kind.
+    fn static_prefix(self) -> &'static str {
+        match self {
+            Self::Partition => "PARTITION",
+            Self::Queue => "QUEUE",
+            Self::Raw => "RAW_PARTITION",
+        }
+    }
+
+    /// Returns the name of the type of the statics defined for ranges of this kind.
+    fn type_name(self) -> &'static str {
+        match self {
+            Self::Partition => "Partition",
+            Self::Queue => "Queue",
+            Self::Raw => "RawPartition",
+        }
+    }
+
+    /// Returns how ranges of this kind are referred to in documentation and error messages.
+    fn description(self) -> &'static str {
+        match self {
+            Self::Partition => "partition",
+            Self::Queue => "queue",
+            Self::Raw => "raw partition",
+        }
+    }
+}
+
 fn main() {
+    select_cache();
+
+    let partitions = partitions();
+    let queues = queues();
+    // Popping a record writes to its header a second time, which STM32 flash does not support.
+    assert!(
+        queues.is_empty() || !is_in_current_contexts(&["stm32"]),
+        "storage queues are not supported on STM32, whose flash does not support writing to the \
+         same word twice"
+    );
+    let raw_partitions = raw_partitions();
+    // Queues are laid out after the partitions, followed by the raw partitions.
+    let declared = partitions
+        .iter()
+        .map(|(name, size)| (Region::Partition, name.as_str(), *size))
+        .chain(
+            queues
+                .iter()
+                .map(|(name, size)| (Region::Queue, name.as_str(), *size)),
+        )
+        .chain(
+            raw_partitions
+                .iter()
+                .map(|(name, size)| (Region::Raw, name.as_str(), *size)),
+        )
+        .collect::<Vec<_>>();
+    let out = &PathBuf::from(env::var_os("OUT_DIR").unwrap());
+
+    println!("cargo:rerun-if-env-changed=CARGO_CFG_CONTEXT");
+    println!("cargo:rerun-if-env-changed=CONFIG_STORAGE_PARTITIONS");
+    println!("cargo:rerun-if-env-changed=CONFIG_STORAGE_QUEUES");
+    println!("cargo:rerun-if-env-changed=CONFIG_STORAGE_RAW_PARTITIONS");
+
+    // On native, the flash is emulated using a file, with the layout shared with
+    // `ariel-os-native`.
+    if is_in_current_contexts(&["native"]) {
+        write_native_layout(&declared, out);
+        return;
+    }
+
     // NOTE(hal): values of `flash_page_size` from the datasheets, confirmed by HAL's constants.
     // Important: only homogeneous flash organizations are currently supported.
     // Trying to restrict the storage size to the subset of homogeneous flash would not work as it
     // could be pushed out of it by a large enough binary.
-    let (storage_size_total, flash_page_size) = if is_in_current_contexts(&[
+    // Partitions and queues declared without a size get two pages.
+    let (default_size, flash_page_size) = if is_in_current_contexts(&[
         "stm32f303cb",
         "stm32f303re",
         "stm32g431rb",
@@ -31,23 +133,287 @@ fn main() {
         panic!("MCU not supported");
     };
 
-    // `sequential-storage` needs at least two flash pages.
-    assert!(storage_size_total / flash_page_size >= 2);
+    let regions = sized_regions(&declared, default_size, flash_page_size);
+    emit_page_count(&regions, flash_page_size);
 
-    // Put the linker script somewhere the linker can find it
-    let out = &PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    // Each partition, queue and raw partition gets its own symbols and size.
+    let mut partition_sections = String::new();
+    for &(region, name, size) in &regions {
+        let prefix = region.symbol_prefix();
+        writeln!(
+            partition_sections,
+            "        {prefix}_{name}_start = .;\n        \
+             . += {size};\n        \
+             {prefix}_{name}_end = .;"
+        )
+        .unwrap();
+    }
 
+    // Put the linker script somewhere the linker can find it
     let mut storage_template = std::fs::read_to_string("storage.ld.in").unwrap();
     storage_template = storage_template.replace("${ALIGNMENT}", &format!("{flash_page_size}"));
-    storage_template = storage_template.replace("${SIZE}", &format!("{storage_size_total}"));
+    storage_template = storage_template.replace("${PARTITIONS}", partition_sections.trim_end());
 
     std::fs::write(out.join("storage.x"), &storage_template).unwrap();
+    std::fs::write(out.join("partitions.rs"), regions_code(&regions, false)).unwrap();
 
-    println!("cargo:rerun-if-env-changed=CARGO_CFG_CONTEXT");
     println!("cargo:rerun-if-changed=storage.ld.in");
     println!("cargo:rustc-link-search={}", out.display());
 }
 
+/// Lays out the partitions, queues and raw partitions consecutively in the flash emulated on
+/// native.
+///
+/// # Panics
+///
+/// Panics if they do not fit into the emulated flash.
+fn write_native_layout(declared: &[(Region, &str, Option<u32>)], out: &Path) {
+    let regions = sized_regions(declared, NATIVE_PARTITION_SIZE, native_flash::PAGE_SIZE);
+    let total = regions.iter().map(|&(_, _, size)| size).sum::<u32>();
+    assert!(
+        total <= native_flash::CAPACITY,
+        "storage partitions, queues and raw partitions take {total} bytes, \
+         at most {} available in the emulated flash",
+        native_flash::CAPACITY
+    );
+    emit_page_count(&regions, native_flash::PAGE_SIZE);
+    std::fs::write(out.join("partitions.rs"), regions_code(&regions, true)).unwrap();
+}
+
+/// Returns the declared partitions, queues and raw partitions with their sizes, using
+/// `default_size` for the partitions and queues declared without a size.
+///
+/// # Panics
+///
+/// Panics if a raw partition is declared without a size, or if a size is not a multiple of
+/// `page_size`, or is smaller than two pages for partitions and queues, which
+/// `sequential-storage` needs.
+fn sized_regions<'a>(
+    declared: &[(Region, &'a str, Option<u32>)],
+    default_size: u32,
+    page_size: u32,
+) -> Vec<(Region, &'a str, u32)> {
+    declared
+        .iter()
+        .map(|&(region, name, size)| {
+            let (size, min_pages) = if region == Region::Raw {
+                let size = size.unwrap_or_else(|| {
+                    panic!("the `{name}` raw partition must be declared with a size")
+                });
+                (size, 1)
+            } else {
+                (size.unwrap_or(default_size), 2)
+            };
+            assert!(
+                size.is_multiple_of(page_size) && size >= min_pages * page_size,
+                "the size of the `{name}` {} must be a multiple of the flash page size \
+                 ({page_size}) of at least {min_pages} page(s), found {size}",
+                region.description()
+            );
+            (region, name, size)
+        })
+        .collect()
+}
+
+/// Sizes the caches, shared by all partitions and queues, for the largest one.
+fn emit_page_count(regions: &[(Region, &str, u32)], page_size: u32) {
+    let page_count = regions
+        .iter()
+        .filter(|&&(region, _, _)| region != Region::Raw)
+        .map(|&(_, _, size)| size / page_size)
+        .max()
+        .unwrap_or(2);
+    println!("cargo:rustc-env=ARIEL_STORAGE_PAGE_COUNT={page_count}");
+}
+
+/// Selects the cache used by the storage from `CONFIG_STORAGE_CACHE`.
+///
+/// # Panics
+///
+/// Panics if the cache is unknown.
+fn select_cache() {
+    const CACHES: &[&str] = &["none", "page-state", "page-pointer", "key-pointer"];
+
+    let cache = env::var("CONFIG_STORAGE_CACHE").unwrap_or_else(|_| "page-pointer".to_owned());
+    assert!(
+        CACHES.contains(&cache.as_str()),
+        "invalid CONFIG_STORAGE_CACHE `{cache}`, expected one of {CACHES:?}"
+    );
+
+    println!("cargo:rustc-cfg=storage_cache=\"{cache}\"");
+    println!("cargo:rerun-if-env-changed=CONFIG_STORAGE_CACHE");
+}
+
+/// Returns the names and sizes of the storage partitions, starting with the default partition,
+/// followed by the ones declared in `CONFIG_STORAGE_PARTITIONS`.
+///
+/// # Panics
+///
+/// Panics if a partition name is invalid, or starts with `queue_` or `raw_`.
+fn partitions() -> Vec<(String, Option<u32>)> {
+    let mut partitions = vec![(DEFAULT_PARTITION.to_owned(), None)];
+    declared_names("CONFIG_STORAGE_PARTITIONS", &mut partitions);
+    // Queue and raw partition symbols are prefixed with `queue_` and `raw_`, which must not clash
+    // with partition symbols.
+    for (name, _) in &partitions {
+        assert!(
+            !name.starts_with("queue_") && !name.starts_with("raw_"),
+            "storage partition names cannot start with `queue_` or `raw_`, found `{name}`"
+        );
+    }
+    partitions
+}
+
+/// Returns the names and sizes of the storage queues declared in `CONFIG_STORAGE_QUEUES`.
+///
+/// # Panics
+///
+/// Panics if a queue name is invalid.
+fn queues() -> Vec<(String, Option<u32>)> {
+    let mut queues = Vec::new();
+    declared_names("CONFIG_STORAGE_QUEUES", &mut queues);
+    queues
+}
+
+/// Returns the names and sizes of the raw partitions declared in
+/// `CONFIG_STORAGE_RAW_PARTITIONS`.
+///
+/// # Panics
+///
+/// Panics if a raw partition name is invalid.
+fn raw_partitions() -> Vec<(String, Option<u32>)> {
+    let mut raw_partitions = Vec::new();
+    declared_names("CONFIG_STORAGE_RAW_PARTITIONS", &mut raw_partitions);
+    raw_partitions
+}
+
+/// Appends the names declared in the environment variable `var` to `names`, along with their
+/// sizes, skipping duplicates.
+///
+/// Names are separated by whitespace or commas, and are used in linker symbols, so they may only
+/// contain lowercase ASCII letters, digits and underscores.
+/// A name can be followed by a colon and a size in bytes, in decimal or in hexadecimal prefixed
+/// with `0x`, e.g., `log:0x4000`.
+///
+/// # Panics
+///
+/// Panics if a name or size is invalid, or if different sizes are declared for the same name.
+fn declared_names(var: &str, names: &mut Vec<(String, Option<u32>)>) {
+    let declared = env::var(var).unwrap_or_default();
+    for entry in declared
+        .split([' ', '\t', '\n', ','])
+        .filter(|s| !s.is_empty())
+    {
+        let (name, size) = match entry.split_once(':') {
+            Some((name, size)) => (
+                name,
+                Some(
+                    parse_size(size)
+                        .unwrap_or_else(|| panic!("invalid size `{size}` of `{name}` in {var}")),
+                ),
+            ),
+            None => (entry, None),
+        };
+        assert!(
+            name.bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'_'),
+            "invalid name `{name}` in {var}"
+        );
+        // Modules may declare the names they need independently, and applications may then size
+        // them.
+        match names.iter_mut().find(|(n, _)| n == name) {
+            None => names.push((name.to_owned(), size)),
+            Some((_, declared_size @ None)) => *declared_size = size,
+            Some((_, Some(declared_size))) => assert!(
+                size.is_none_or(|size| size == *declared_size),
+                "different sizes declared for `{name}` in {var}"
+            ),
+        }
+    }
+}
+
+/// Parses a size in decimal, or in hexadecimal prefixed with `0x`.
+fn parse_size(size: &str) -> Option<u32> {
+    match size.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => size.parse().ok(),
+    }
+}
+
+/// Generates the partition, queue and raw partition tables, with each of them getting its range
+/// from its linker symbols, or, on native, being laid out consecutively.
+///
+/// # Panics
+///
+/// Never panics, writing to a [`String`] cannot fail.
+fn regions_code(regions: &[(Region, &str, u32)], native: bool) -> String {
+    let mut code = String::new();
+
+    if !native {
+        code.push_str("unsafe extern \"C\" {\n");
+        for &(region, name, _) in regions {
+            let prefix = region.symbol_prefix();
+            writeln!(code, "    static {prefix}_{name}_start: u32;").unwrap();
+            writeln!(code, "    static {prefix}_{name}_end: u32;").unwrap();
+        }
+        code.push_str("}\n\n");
+    }
+
+    let mut native_start = 0;
+    for &(region, name, size) in regions {
+        let range = if native {
+            let range = format!("|| {native_start}..{}", native_start + size);
+            native_start += size;
+            range
+        } else {
+            let prefix = region.symbol_prefix();
+            format!(
+                "|| linker_range(\
+                 &raw const {prefix}_{name}_start, \
+                 &raw const {prefix}_{name}_end)"
+            )
+        };
+        writeln!(
+            code,
+            "/// The `{name}` storage {}.\n\
+             static {}_{}: {} = {}::new(\"{name}\", {range});\n",
+            region.description(),
+            region.static_prefix(),
+            name.to_uppercase(),
+            region.type_name(),
+            region.type_name(),
+        )
+        .unwrap();
+    }
+
+    for (region, doc) in [
+        (
+            Region::Partition,
+            "All storage partitions, starting with the default partition.",
+        ),
+        (Region::Queue, "All storage queues."),
+        (Region::Raw, "All raw storage partitions."),
+    ] {
+        let statics = regions
+            .iter()
+            .filter(|(r, _, _)| *r == region)
+            .map(|(_, name, _)| format!("&{}_{}", region.static_prefix(), name.to_uppercase()))
+            .collect::<Vec<_>>();
+        writeln!(
+            code,
+            "/// {doc}\n\
+             static {}S: [&{}; {}] = [{}];",
+            region.static_prefix(),
+            region.type_name(),
+            statics.len(),
+            statics.join(", ")
+        )
+        .unwrap();
+    }
+
+    code
+}
+
 /// Returns whether any of the current `cfg` contexts is one of the given contexts.
 fn is_in_current_contexts(contexts: &[&str]) -> bool {
     let Ok(context_var) = std::env::var("CARGO_CFG_CONTEXT") else {
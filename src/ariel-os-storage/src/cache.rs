@@ -0,0 +1,56 @@
+//! Selects the cache used to speed up accesses to the storage.
+//!
+//! The cache is selected using the `CONFIG_STORAGE_CACHE` environment variable, among the caches
+//! provided by [`sequential_storage`]:
+//!
+//! | `CONFIG_STORAGE_CACHE`   | Cache                | RAM usage                                |
+//! | ------------------------ | -------------------- | ---------------------------------------- |
+//! | `none`                   | [`NoCache`]          | None                                     |
+//! | `page-state`             | [`PageStateCache`]   | A byte per page                          |
+//! | `page-pointer` (default) | [`PagePointerCache`] | A few bytes per page                     |
+//! | `key-pointer`            | [`KeyPointerCache`]  | Same as `page-pointer`, plus cached keys |
+//!
+//! The number of keys cached by [`KeyPointerCache`] is set by the `CONFIG_STORAGE_CACHED_KEYS`
+//! environment variable, and defaults to 8.
+//! As queues have no keys, they use [`PagePointerCache`] instead of [`KeyPointerCache`].
+//!
+//! [`NoCache`]: sequential_storage::cache::NoCache
+//! [`PageStateCache`]: sequential_storage::cache::PageStateCache
+//! [`PagePointerCache`]: sequential_storage::cache::PagePointerCache
+//! [`KeyPointerCache`]: sequential_storage::cache::KeyPointerCache
+
+/// Number of flash pages of the storage, computed by the build script.
+#[cfg(not(storage_cache = "none"))]
+const PAGE_COUNT: usize = ariel_os_utils::usize_from_env_or!(
+    "ARIEL_STORAGE_PAGE_COUNT",
+    2,
+    "number of flash pages of the storage"
+);
+
+/// Number of keys cached by the key-pointer cache.
+#[cfg(storage_cache = "key-pointer")]
+const CACHED_KEYS: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_STORAGE_CACHED_KEYS",
+    8,
+    "number of keys cached by the storage"
+);
+
+/// Cache used by [`Storage`](crate::Storage).
+#[cfg(storage_cache = "none")]
+pub type Cache = sequential_storage::cache::NoCache;
+/// Cache used by [`Storage`](crate::Storage).
+#[cfg(storage_cache = "page-state")]
+pub type Cache = sequential_storage::cache::PageStateCache<PAGE_COUNT>;
+/// Cache used by [`Storage`](crate::Storage).
+#[cfg(storage_cache = "page-pointer")]
+pub type Cache = sequential_storage::cache::PagePointerCache<PAGE_COUNT>;
+/// Cache used by [`Storage`](crate::Storage).
+#[cfg(storage_cache = "key-pointer")]
+pub type Cache = sequential_storage::cache::KeyPointerCache<PAGE_COUNT, crate::MapKey, CACHED_KEYS>;
+
+/// Cache used by [`Queue`](crate::Queue)s.
+#[cfg(not(storage_cache = "key-pointer"))]
+pub type QueueCache = Cache;
+/// Cache used by [`Queue`](crate::Queue)s.
+#[cfg(storage_cache = "key-pointer")]
+pub type QueueCache = sequential_storage::cache::PagePointerCache<PAGE_COUNT>;
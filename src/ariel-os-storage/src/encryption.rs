@@ -0,0 +1,325 @@
+//! Authenticated encryption of stored values.
+//!
+//! Values stored through [`Storage::insert_encrypted()`](crate::Storage::insert_encrypted) are
+//! serialized using Postcard, then encrypted and authenticated using ChaCha20-Poly1305.
+//! The name of their key is authenticated as associated data, so that a record copied under
+//! another key fails to decrypt instead of being returned.
+//!
+//! Records are laid out as follows:
+//!
+//! | Tag    | Nonce    | Ciphertext | Authentication tag |
+//! | ------ | -------- | ---------- | ------------------ |
+//! | 1 byte | 12 bytes | (variable) | 16 bytes           |
+//!
+//! Nonces are drawn from the system-wide CSPRNG; as flash endurance limits the number of records
+//! written with a given key, collisions are not a concern.
+
+use chacha20poly1305::{AeadInPlace as _, ChaCha20Poly1305, KeyInit as _, Nonce, Tag};
+use hkdf::Hkdf;
+use postcard::{from_bytes, to_slice};
+use rand_core::RngCore;
+use sequential_storage::map::SerializationError;
+use serde::{Serialize, de::DeserializeOwned};
+use sha2::Sha256;
+
+use crate::schema::{deserialization_error, serialization_error};
+
+/// Marks the beginning of an encrypted record, to tell them apart from other values.
+const RECORD_TAG: u8 = 0xe5;
+
+/// Length of the nonce.
+const NONCE_LEN: usize = 12;
+
+/// Length of the authentication tag.
+const AUTH_TAG_LEN: usize = 16;
+
+/// Length of the header: tag and nonce.
+const HEADER_LEN: usize = 1 + NONCE_LEN;
+
+/// Length of encryption keys.
+pub const KEY_LEN: usize = 32;
+
+/// Context string of the key derivation, separating it from other uses of the same secret.
+const KDF_INFO: &[u8] = b"ariel-os-storage encryption key";
+
+/// Key used for encrypting stored values.
+///
+/// The key is not meant to be stored alongside the values it protects: derive it at startup
+/// with [`EncryptionKey::derive()`], or obtain it from a hardware-unique secret.
+pub struct EncryptionKey {
+    bytes: [u8; KEY_LEN],
+}
+
+impl EncryptionKey {
+    /// Creates a key from raw bytes, e.g., from a hardware-unique secret.
+    #[must_use]
+    pub const fn from_bytes(bytes: [u8; KEY_LEN]) -> Self {
+        Self { bytes }
+    }
+
+    /// Derives a key unique to this device from a provisioned `secret`, using HKDF-SHA256 salted
+    /// with the [device identifier](ariel_os_identity::device_id_bytes).
+    ///
+    /// The same secret can thus be provisioned to multiple devices without them sharing keys.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`KeyError`] if the device identifier is not available.
+    pub fn derive(secret: &[u8]) -> Result<Self, KeyError> {
+        let device_id = ariel_os_identity::device_id_bytes().map_err(|_| KeyError)?;
+        Ok(Self::derive_with_salt(secret, device_id.as_ref()))
+    }
+
+    /// Derives a key from `secret`, using HKDF-SHA256 with the given `salt`.
+    #[must_use]
+    pub fn derive_with_salt(secret: &[u8], salt: &[u8]) -> Self {
+        let mut bytes = [0; KEY_LEN];
+        // The output length is valid for SHA-256.
+        let _ = Hkdf::<Sha256>::new(Some(salt), secret).expand(KDF_INFO, &mut bytes);
+        Self { bytes }
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(&self.bytes.into())
+    }
+}
+
+/// Error returned when an [`EncryptionKey`] cannot be derived.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyError;
+
+impl core::fmt::Display for KeyError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("device identifier not available")
+    }
+}
+
+impl core::error::Error for KeyError {}
+
+/// Serializes and encrypts `value`, stored under `name`, into `buffer`.
+///
+/// Returns the number of bytes used.
+///
+/// # Errors
+///
+/// Returns an error if `buffer` is too small or if the value cannot be serialized.
+pub(crate) fn encrypt<V: Serialize>(
+    key: &EncryptionKey,
+    name: &str,
+    value: &V,
+    rng: &mut impl RngCore,
+    buffer: &mut [u8],
+) -> Result<usize, SerializationError> {
+    let (header, payload) = buffer
+        .split_at_mut_checked(HEADER_LEN)
+        .ok_or(SerializationError::BufferTooSmall)?;
+    let payload_capacity = payload
+        .len()
+        .checked_sub(AUTH_TAG_LEN)
+        .ok_or(SerializationError::BufferTooSmall)?;
+
+    let mut nonce = [0; NONCE_LEN];
+    rng.fill_bytes(&mut nonce);
+    for (dst, src) in header.iter_mut().zip([RECORD_TAG].iter().chain(&nonce)) {
+        *dst = *src;
+    }
+
+    let plaintext = payload.get_mut(..payload_capacity).unwrap_or_default();
+    let len = to_slice(value, plaintext)
+        .map_err(serialization_error)?
+        .len();
+
+    // Encrypt in place, and append the authentication tag to the ciphertext.
+    let (ciphertext, rest) = payload.split_at_mut(len);
+    let tag = key
+        .cipher()
+        .encrypt_in_place_detached(&Nonce::from(nonce), name.as_bytes(), ciphertext)
+        .map_err(|_| SerializationError::Custom(0))?;
+    rest.get_mut(..AUTH_TAG_LEN)
+        .ok_or(SerializationError::BufferTooSmall)?
+        .copy_from_slice(&tag);
+
+    Ok(HEADER_LEN + len + AUTH_TAG_LEN)
+}
+
+/// Decrypts and deserializes a `record` stored under `name`, using `buffer` for the plaintext.
+///
+/// # Errors
+///
+/// Returns an error if the record is not encrypted, if it fails authentication, e.g., because it
+/// was stored under another name or with another key, or if it cannot be deserialized.
+pub(crate) fn decrypt<V: DeserializeOwned>(
+    key: &EncryptionKey,
+    name: &str,
+    record: &[u8],
+    buffer: &mut [u8],
+) -> Result<V, DecryptError> {
+    let ([tag, nonce @ ..], rest) = record
+        .split_first_chunk::<HEADER_LEN>()
+        .ok_or(DecryptError::NotEncrypted)?;
+    if *tag != RECORD_TAG {
+        return Err(DecryptError::NotEncrypted);
+    }
+    let (ciphertext, auth_tag) = rest
+        .split_last_chunk::<AUTH_TAG_LEN>()
+        .ok_or(DecryptError::NotEncrypted)?;
+
+    let plaintext = buffer
+        .get_mut(..ciphertext.len())
+        .ok_or(DecryptError::Serialization(
+            SerializationError::BufferTooSmall,
+        ))?;
+    plaintext.copy_from_slice(ciphertext);
+
+    key.cipher()
+        .decrypt_in_place_detached(
+            &Nonce::from(*nonce),
+            name.as_bytes(),
+            plaintext,
+            &Tag::from(*auth_tag),
+        )
+        .map_err(|_| DecryptError::Authentication)?;
+
+    from_bytes(plaintext).map_err(|err| DecryptError::Serialization(deserialization_error(err)))
+}
+
+/// Errors that can occur when decrypting a record.
+#[derive(Debug, PartialEq)]
+pub(crate) enum DecryptError {
+    NotEncrypted,
+    Authentication,
+    Serialization(SerializationError),
+}
+
+/// Errors returned when accessing encrypted values.
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub enum EncryptedError<E> {
+    /// Accessing the storage failed.
+    Storage(sequential_storage::Error<E>),
+    /// The stored value was not stored encrypted.
+    NotEncrypted,
+    /// The stored value failed authentication: it was stored with another key, under another
+    /// name, or has been tampered with.
+    Authentication,
+}
+
+impl<E> From<sequential_storage::Error<E>> for EncryptedError<E> {
+    fn from(err: sequential_storage::Error<E>) -> Self {
+        Self::Storage(err)
+    }
+}
+
+impl<E> From<DecryptError> for EncryptedError<E> {
+    fn from(err: DecryptError) -> Self {
+        match err {
+            DecryptError::NotEncrypted => Self::NotEncrypted,
+            DecryptError::Authentication => Self::Authentication,
+            DecryptError::Serialization(err) => {
+                Self::Storage(sequential_storage::Error::SerializationError(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic RNG, good enough for tests.
+    struct CountingRng(u8);
+
+    impl RngCore for CountingRng {
+        fn next_u32(&mut self) -> u32 {
+            u32::from_le_bytes([self.next_byte(); 4])
+        }
+
+        fn next_u64(&mut self) -> u64 {
+            u64::from(self.next_u32())
+        }
+
+        fn fill_bytes(&mut self, dst: &mut [u8]) {
+            dst.fill_with(|| self.next_byte());
+        }
+    }
+
+    impl CountingRng {
+        fn next_byte(&mut self) -> u8 {
+            self.0 = self.0.wrapping_add(1);
+            self.0
+        }
+    }
+
+    fn key() -> EncryptionKey {
+        EncryptionKey::derive_with_salt(b"provisioned secret", b"device id")
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut record = [0; 64];
+        let len = encrypt(&key(), "wifi", b"hunter2", &mut CountingRng(0), &mut record).unwrap();
+        assert_eq!(len, HEADER_LEN + 7 + AUTH_TAG_LEN);
+
+        let record = record.get(..len).unwrap();
+        // The plaintext does not appear in the record.
+        assert!(!record.windows(7).any(|w| w == b"hunter2"));
+
+        let mut buffer = [0; 64];
+        let value: [u8; 7] = decrypt(&key(), "wifi", record, &mut buffer).unwrap();
+        assert_eq!(&value, b"hunter2");
+    }
+
+    #[test]
+    fn rejects_other_name_and_key() {
+        let mut record = [0; 64];
+        let len = encrypt(&key(), "wifi", &42u32, &mut CountingRng(0), &mut record).unwrap();
+        let record = record.get(..len).unwrap();
+        let mut buffer = [0; 64];
+
+        assert_eq!(
+            decrypt::<u32>(&key(), "other", record, &mut buffer),
+            Err(DecryptError::Authentication)
+        );
+
+        let other_key = EncryptionKey::derive_with_salt(b"provisioned secret", b"other device");
+        assert_eq!(
+            decrypt::<u32>(&other_key, "wifi", record, &mut buffer),
+            Err(DecryptError::Authentication)
+        );
+    }
+
+    #[test]
+    fn rejects_tampered_records() {
+        let mut record = [0; 64];
+        let len = encrypt(&key(), "wifi", &42u32, &mut CountingRng(0), &mut record).unwrap();
+        *record.get_mut(HEADER_LEN).unwrap() ^= 1;
+
+        let mut buffer = [0; 64];
+        assert_eq!(
+            decrypt::<u32>(&key(), "wifi", record.get(..len).unwrap(), &mut buffer),
+            Err(DecryptError::Authentication)
+        );
+    }
+
+    #[test]
+    fn rejects_unencrypted_records() {
+        let mut buffer = [0; 64];
+        assert_eq!(
+            decrypt::<u32>(&key(), "wifi", &[42], &mut buffer),
+            Err(DecryptError::NotEncrypted)
+        );
+        assert_eq!(
+            decrypt::<u32>(&key(), "wifi", &[0; 40], &mut buffer),
+            Err(DecryptError::NotEncrypted)
+        );
+    }
+
+    #[test]
+    fn reports_small_buffers() {
+        let mut record = [0; HEADER_LEN + AUTH_TAG_LEN + 2];
+        assert_eq!(
+            encrypt(&key(), "wifi", &[1u8; 8], &mut CountingRng(0), &mut record),
+            Err(SerializationError::BufferTooSmall)
+        );
+    }
+}
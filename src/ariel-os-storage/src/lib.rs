@@ -1,15 +1,42 @@
 //! Provides key-value pair persistent storage on flash.
 //!
-//! Currently the same type used for serializing must be used for deserializing.
+//! With [`get()`] and [`insert()`], the same type used for serializing must be used for
+//! deserializing.
 //! While not doing so won't cause unsafety, it might return garbage data, or panic.
+//!
+//! Values accessed through typed [`Key`]s, using [`get_versioned()`] and [`insert_versioned()`],
+//! are instead stored along with the identifier and version of their [`Schema`]: reading them
+//! with another type returns an error, and values stored with older versions of the type are
+//! upgraded using migrations.
+//!
+//! With the `encryption` feature, values stored using [`insert_encrypted()`] are encrypted and
+//! authenticated, see [`EncryptionKey`].
+//!
+//! The storage is split into [`Partition`]s, independent [`Storage`] instances in separate flash
+//! ranges, each with its own initialization and [`erase_all()`](Partition::erase_all()).
+//! The global functions of this crate use the default partition; additional partitions are
+//! declared in the build configuration, and accessed using [`partition()`].
+//!
+//! Persistent FIFO [`Queue`]s of records, e.g., for data logging, are also declared in the build
+//! configuration, each in its own flash range, and accessed using [`queue()`].
+//!
+//! [`RawPartition`]s reserve flash ranges for other users of the flash, e.g., a filesystem, and are
+//! accessed using [`raw_partition()`].
 
 #![cfg_attr(not(test), no_std)]
 #![deny(missing_docs)]
-#![expect(unsafe_code)]
+// Partition ranges are taken from linker symbols, except on native.
+#![cfg_attr(not(context = "native"), expect(unsafe_code))]
 // TODO: overhaul errors
 #![expect(clippy::missing_errors_doc)]
 
+mod cache;
+#[cfg(feature = "encryption")]
+mod encryption;
+mod partition;
 mod postcard_value;
+mod queue;
+mod schema;
 mod storage;
 
 use core::ops::Range;
@@ -23,21 +50,36 @@ use embassy_sync::{
     mutex::{Mutex, MutexGuard},
     once_lock::OnceLock,
 };
+use embedded_storage_async::nor_flash::ReadNorFlash as _;
 
+#[cfg(feature = "encryption")]
+pub use encryption::{EncryptedError, EncryptionKey, KEY_LEN, KeyError};
+pub use partition::{Partition, RawPartition, SharedFlash};
+pub use queue::{Queue, QueueStorage, Records};
+pub use schema::{Key, Migration, Schema, VersionedError};
+/// Errors returned by the storage.
+pub use sequential_storage::Error;
 pub use storage::*;
 
-static STORAGE: OnceLock<Mutex<CriticalSectionRawMutex, Storage<Flash>>> = OnceLock::new();
+// Defines a `PARTITION_<NAME>` static for each partition declared in `CONFIG_STORAGE_PARTITIONS`,
+// a `QUEUE_<NAME>` static for each queue declared in `CONFIG_STORAGE_QUEUES`, a
+// `RAW_PARTITION_<NAME>` static for each raw partition declared in
+// `CONFIG_STORAGE_RAW_PARTITIONS`, and the `PARTITIONS`, `QUEUES` and `RAW_PARTITIONS` tables.
+include!(concat!(env!("OUT_DIR"), "/partitions.rs"));
+
+static FLASH: OnceLock<Mutex<CriticalSectionRawMutex, Flash>> = OnceLock::new();
 
 const MARKER_KEY: &str = "ARIEL_INIT_MARK";
 const MARKER_VALUE: u8 = 0;
 
-/// Gets a [`Range`] from the linker that can be used for a global [`Storage`].
+/// Converts the addresses of two linker symbols delimiting a partition into a [`Range`] that can
+/// be used for a [`Storage`].
 ///
-/// This expects two symbols `__storage_start` and `__storage_end`.
 /// This function is also the place to configure a platform dependent `OFFSET`,
 /// which configures an offset between the linker flash address map and the
 /// flash driver address map.
-fn flash_range_from_linker() -> Range<u32> {
+#[cfg(not(context = "native"))]
+fn linker_range(start: *const u32, end: *const u32) -> Range<u32> {
     #[cfg(all(context = "nrf", not(context = "nrf5340-net")))]
     const OFFSET: usize = 0x0;
     #[cfg(context = "nrf5340-net")]
@@ -50,13 +92,8 @@ fn flash_range_from_linker() -> Range<u32> {
     #[cfg(not(context = "ariel-os"))]
     const OFFSET: usize = 0x0;
 
-    unsafe extern "C" {
-        static __storage_start: u32;
-        static __storage_end: u32;
-    }
-
-    let start = &raw const __storage_start as usize - OFFSET;
-    let end = &raw const __storage_end as usize - OFFSET;
+    let start = start as usize - OFFSET;
+    let end = end as usize - OFFSET;
 
     #[expect(clippy::cast_possible_truncation)]
     let (start, end) = (start as u32, end as u32);
@@ -64,16 +101,71 @@ fn flash_range_from_linker() -> Range<u32> {
     start..end
 }
 
-fn init_(p: &mut OptionalPeripherals) {
-    use ariel_os_log::info;
-    let flash_range = flash_range_from_linker();
-    info!("storage: using flash range {:?}", &flash_range);
+/// Returns the storage partition with the given name, if it has been declared.
+///
+/// Partitions are declared by appending their names to the `storage_partitions` laze variable.
+#[must_use]
+pub fn partition(name: &str) -> Option<&'static Partition> {
+    PARTITIONS.iter().copied().find(|p| p.name() == name)
+}
+
+/// Returns all storage partitions, starting with the default partition.
+#[must_use]
+pub fn partitions() -> &'static [&'static Partition] {
+    &PARTITIONS
+}
+
+/// Returns the storage queue with the given name, if it has been declared.
+///
+/// Queues are declared by appending their names to the `storage_queues` laze variable.
+#[must_use]
+pub fn queue(name: &str) -> Option<&'static Queue> {
+    QUEUES.iter().copied().find(|q| q.name() == name)
+}
 
+/// Returns all storage queues.
+#[must_use]
+pub fn queues() -> &'static [&'static Queue] {
+    &QUEUES
+}
+
+/// Returns the raw storage partition with the given name, if it has been declared.
+///
+/// Raw partitions are declared by appending their names and sizes, e.g., `fs:0x10000`, to the
+/// `storage_raw_partitions` laze variable.
+#[must_use]
+pub fn raw_partition(name: &str) -> Option<&'static RawPartition> {
+    RAW_PARTITIONS.iter().copied().find(|p| p.name() == name)
+}
+
+/// Returns all raw storage partitions.
+#[must_use]
+pub fn raw_partitions() -> &'static [&'static RawPartition] {
+    &RAW_PARTITIONS
+}
+
+/// Returns the default partition, used by the global storage functions.
+fn default_partition() -> &'static Partition {
+    &PARTITION_DEFAULT
+}
+
+fn init_(p: &mut OptionalPeripherals) {
     let flash = flash_init(p);
-    let _ = STORAGE.init(Mutex::new(Storage::new(flash, flash_range)));
+    let capacity = flash.capacity();
+    let flash = FLASH.get_or_init(|| Mutex::new(flash));
+
+    for partition in PARTITIONS {
+        partition.init(flash, capacity);
+    }
+    for queue in QUEUES {
+        queue.init(flash, capacity);
+    }
+    for raw_partition in RAW_PARTITIONS {
+        raw_partition.init(flash, capacity);
+    }
 }
 
-/// Initializes the global storage.
+/// Initializes the storage partitions, queues and raw partitions.
 ///
 /// Note: this is automatically called by the Ariel OS initialization code.
 ///
@@ -91,10 +183,11 @@ pub async fn init(p: &mut OptionalPeripherals) {
     #[cfg(context = "rp")]
     embassy_time::block_for(embassy_time::Duration::from_millis(10));
 
-    // Use a marker to ensure that this storage is initialized.
-    if Ok(Some(MARKER_VALUE)) != get::<u8>(MARKER_KEY).await {
-        ariel_os_log::info!("storage: initializing");
-        erase_all().await.unwrap();
+    for partition in PARTITIONS {
+        partition.init_marker().await;
+    }
+    for queue in QUEUES {
+        queue.init_check().await;
     }
 }
 
@@ -111,6 +204,8 @@ where
 /// Gets the last stored value from the flash that is associated with the given key.
 ///
 /// Note: Always [`get()`] the same value type that was [`insert()`]!
+/// Values stored through a typed [`Key`] are no exception, and need to be read with
+/// [`get_versioned()`] instead.
 ///
 /// If no value with the key is found, `None` is returned.
 pub async fn get<V>(key: &str) -> Result<Option<V>, sequential_storage::Error<FlashError>>
@@ -120,6 +215,84 @@ where
     lock().await.get(key).await
 }
 
+/// Stores a byte value that may be larger than [`DATA_BUFFER_SIZE`], e.g., a certificate.
+///
+/// See [`Storage::insert_large()`].
+pub async fn insert_large(
+    key: &str,
+    value: &[u8],
+) -> Result<(), sequential_storage::Error<FlashError>> {
+    lock().await.insert_large(key, value).await
+}
+
+/// Gets the last byte value stored with [`insert_large()`] into `buffer`.
+///
+/// Returns the length of the value, or `None` if no value with the key is found.
+pub async fn get_large(
+    key: &str,
+    buffer: &mut [u8],
+) -> Result<Option<usize>, sequential_storage::Error<FlashError>> {
+    lock().await.get_large(key, buffer).await
+}
+
+/// Stores a value through a typed [`Key`], along with the identifier and version of its
+/// [`Schema`].
+///
+/// It will overwrite the last value that has the same key.
+pub async fn insert_versioned<T: Schema>(
+    key: &Key<T>,
+    value: &T,
+) -> Result<(), sequential_storage::Error<FlashError>> {
+    lock().await.insert_versioned(key, value).await
+}
+
+/// Gets the last value stored through a typed [`Key`].
+///
+/// Values stored with an older version of the [`Schema`] are upgraded using the migrations of the
+/// key; the upgraded value is not written back, use [`migrate()`] for this.
+///
+/// If no value with the key is found, `None` is returned.
+pub async fn get_versioned<T: Schema>(
+    key: &Key<T>,
+) -> Result<Option<T>, VersionedError<FlashError>> {
+    lock().await.get_versioned(key).await
+}
+
+/// Upgrades the value stored through a typed [`Key`] to the current version of its [`Schema`],
+/// and writes it back if needed.
+///
+/// This allows upgrading values eagerly, e.g., at startup, instead of on every read.
+pub async fn migrate<T: Schema>(key: &Key<T>) -> Result<(), VersionedError<FlashError>> {
+    lock().await.migrate(key).await
+}
+
+/// Stores a value encrypted and authenticated with `encryption_key`, see [`EncryptionKey`].
+///
+/// It will overwrite the last value that has the same key.
+/// The key itself is stored in clear, and authenticated along with the value, so that values
+/// cannot be swapped between keys.
+#[cfg(feature = "encryption")]
+pub async fn insert_encrypted<V: Serialize>(
+    key: &str,
+    value: &V,
+    encryption_key: &EncryptionKey,
+) -> Result<(), sequential_storage::Error<FlashError>> {
+    default_partition()
+        .insert_encrypted(key, value, encryption_key)
+        .await
+}
+
+/// Gets and decrypts the last value stored with [`insert_encrypted()`].
+///
+/// If no value with the key is found, `None` is returned.
+#[cfg(feature = "encryption")]
+pub async fn get_encrypted<V: serde::de::DeserializeOwned>(
+    key: &str,
+    encryption_key: &EncryptionKey,
+) -> Result<Option<V>, EncryptedError<FlashError>> {
+    default_partition().get_encrypted(key, encryption_key).await
+}
+
 /// Deletes an item from flash.
 ///
 /// Additional calls to [`get()`] with the same key will return `None` until
@@ -137,16 +310,26 @@ pub async fn remove(key: &str) -> Result<(), sequential_storage::Error<FlashErro
     lock().await.remove(key).await
 }
 
-/// Resets the flash in the entire flash range.
+/// Deletes a value stored with [`insert_large()`], along with its chunks.
+///
+/// See [`Storage::remove_large()`].
+// STM32 flash drivers do not implement `MultiwriteNorFlash`.
+#[cfg(not(context = "stm32"))]
+pub async fn remove_large(key: &str) -> Result<(), sequential_storage::Error<FlashError>> {
+    lock().await.remove_large(key).await
+}
+
+/// Resets the flash range of the default partition.
+///
+/// Other partitions are left untouched, see [`Partition::erase_all()`].
 pub async fn erase_all() -> Result<(), sequential_storage::Error<FlashError>> {
-    let mut s = lock().await;
-    s.erase_all().await?;
-    s.insert(MARKER_KEY, MARKER_VALUE).await
+    default_partition().erase_all().await
 }
 
-/// Gets a [`MutexGuard`] of the global [`Storage`] object.
+/// Gets a [`MutexGuard`] of the [`Storage`] object of the default partition.
 ///
-/// This can be used to implement atomic RMW (like counters).
+/// This can be used to implement atomic RMW (like counters), or to iterate over the stored keys
+/// using [`Storage::keys()`].
 /// *It is not needed for using the global [`get()`], [`insert()`] and [`remove()`] functions.*
 ///
 /// Note: don't forget to drop the mutex guard returned by this.
@@ -163,6 +346,6 @@ pub async fn erase_all() -> Result<(), sequential_storage::Error<FlashError>> {
 ///     s.insert("counter", value + 1).await.unwrap();
 /// }
 /// ```
-pub async fn lock() -> MutexGuard<'static, CriticalSectionRawMutex, storage::Storage<Flash>> {
-    STORAGE.get().await.lock().await
+pub async fn lock() -> MutexGuard<'static, CriticalSectionRawMutex, storage::Storage<SharedFlash>> {
+    default_partition().lock().await
 }
@@ -0,0 +1,346 @@
+//! Provides storage partitions, independent [`Storage`] instances sharing the flash, and raw
+//! partitions, flash ranges left to other users of the flash.
+
+use core::ops::Range;
+
+use ariel_os_hal::hal::storage::{Flash, FlashError};
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex,
+    mutex::{Mutex, MutexGuard},
+    once_lock::OnceLock,
+};
+#[cfg(not(context = "stm32"))]
+use embedded_storage_async::nor_flash::MultiwriteNorFlash;
+use embedded_storage_async::nor_flash::{ErrorType, NorFlash, ReadNorFlash};
+
+#[cfg(feature = "encryption")]
+use crate::{EncryptedError, EncryptionKey};
+use crate::{Key, MARKER_KEY, MARKER_VALUE, PostcardValue, Schema, Storage, VersionedError};
+#[cfg(feature = "encryption")]
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Flash shared between the storage partitions.
+///
+/// Each operation locks the flash driver, so that partitions can be accessed concurrently.
+pub struct SharedFlash {
+    flash: &'static Mutex<CriticalSectionRawMutex, Flash>,
+    capacity: usize,
+}
+
+impl SharedFlash {
+    pub(crate) fn new(
+        flash: &'static Mutex<CriticalSectionRawMutex, Flash>,
+        capacity: usize,
+    ) -> Self {
+        Self { flash, capacity }
+    }
+}
+
+impl ErrorType for SharedFlash {
+    type Error = FlashError;
+}
+
+impl ReadNorFlash for SharedFlash {
+    const READ_SIZE: usize = <Flash as ReadNorFlash>::READ_SIZE;
+
+    async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        self.flash.lock().await.read(offset, bytes).await
+    }
+
+    fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
+
+impl NorFlash for SharedFlash {
+    const WRITE_SIZE: usize = <Flash as NorFlash>::WRITE_SIZE;
+    const ERASE_SIZE: usize = <Flash as NorFlash>::ERASE_SIZE;
+
+    async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        self.flash.lock().await.erase(from, to).await
+    }
+
+    async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.flash.lock().await.write(offset, bytes).await
+    }
+}
+
+// STM32 flash drivers do not implement `MultiwriteNorFlash`.
+#[cfg(not(context = "stm32"))]
+impl MultiwriteNorFlash for SharedFlash {}
+
+/// A storage partition, declared in the build configuration.
+///
+/// Each partition is an independent [`Storage`] instance in its own flash range: erasing one
+/// partition does not affect the others.
+pub struct Partition {
+    name: &'static str,
+    range: fn() -> Range<u32>,
+    storage: OnceLock<Mutex<CriticalSectionRawMutex, Storage<SharedFlash>>>,
+}
+
+impl Partition {
+    pub(crate) const fn new(name: &'static str, range: fn() -> Range<u32>) -> Self {
+        Self {
+            name,
+            range,
+            storage: OnceLock::new(),
+        }
+    }
+
+    /// Returns the name of this partition.
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the flash range of this partition.
+    #[must_use]
+    pub fn range(&self) -> Range<u32> {
+        (self.range)()
+    }
+
+    /// Creates the [`Storage`] instance of this partition.
+    pub(crate) fn init(
+        &self,
+        flash: &'static Mutex<CriticalSectionRawMutex, Flash>,
+        capacity: usize,
+    ) {
+        let range = (self.range)();
+        ariel_os_log::info!(
+            "storage: partition {} using flash range {:?}",
+            self.name,
+            &range
+        );
+
+        let _ = self.storage.init(Mutex::new(Storage::new(
+            SharedFlash::new(flash, capacity),
+            range,
+        )));
+    }
+
+    /// Erases this partition if it has not been initialized yet.
+    ///
+    /// # Panics
+    ///
+    /// Panics when erasing the partition fails.
+    pub(crate) async fn init_marker(&'static self) {
+        // Use a marker to ensure that this partition is initialized.
+        if Ok(Some(MARKER_VALUE)) != self.get::<u8>(MARKER_KEY).await {
+            ariel_os_log::info!("storage: initializing partition {}", self.name);
+            self.erase_all().await.unwrap();
+        }
+    }
+
+    /// Stores a key-value pair into this partition.
+    ///
+    /// It will overwrite the last value that has the same key.
+    pub async fn insert<'d, V>(
+        &'static self,
+        key: &str,
+        value: V,
+    ) -> Result<(), sequential_storage::Error<FlashError>>
+    where
+        V: Serialize + Deserialize<'d> + Into<PostcardValue<V>>,
+    {
+        self.lock().await.insert::<V>(key, value).await
+    }
+
+    /// Gets the last value stored in this partition that is associated with the given key.
+    ///
+    /// Note: Always [`get()`](Self::get) the same value type that was
+    /// [`insert()`](Self::insert)!
+    /// Values stored through a typed [`Key`] are no exception, and need to be read with
+    /// [`get_versioned()`](Self::get_versioned) instead.
+    ///
+    /// If no value with the key is found, `None` is returned.
+    pub async fn get<V>(
+        &'static self,
+        key: &str,
+    ) -> Result<Option<V>, sequential_storage::Error<FlashError>>
+    where
+        V: Serialize + for<'d> Deserialize<'d> + Into<PostcardValue<V>>,
+    {
+        self.lock().await.get(key).await
+    }
+
+    /// Stores a byte value that may be larger than
+    /// [`DATA_BUFFER_SIZE`](crate::DATA_BUFFER_SIZE) into this partition.
+    ///
+    /// See [`insert_large()`](crate::insert_large).
+    pub async fn insert_large(
+        &'static self,
+        key: &str,
+        value: &[u8],
+    ) -> Result<(), sequential_storage::Error<FlashError>> {
+        self.lock().await.insert_large(key, value).await
+    }
+
+    /// Gets the last byte value stored with [`insert_large()`](Self::insert_large) in this
+    /// partition into `buffer`.
+    ///
+    /// See [`get_large()`](crate::get_large).
+    pub async fn get_large(
+        &'static self,
+        key: &str,
+        buffer: &mut [u8],
+    ) -> Result<Option<usize>, sequential_storage::Error<FlashError>> {
+        self.lock().await.get_large(key, buffer).await
+    }
+
+    /// Stores a value through a typed [`Key`] into this partition.
+    ///
+    /// See [`insert_versioned()`](crate::insert_versioned).
+    pub async fn insert_versioned<T: Schema>(
+        &'static self,
+        key: &Key<T>,
+        value: &T,
+    ) -> Result<(), sequential_storage::Error<FlashError>> {
+        self.lock().await.insert_versioned(key, value).await
+    }
+
+    /// Gets the last value stored in this partition through a typed [`Key`].
+    ///
+    /// See [`get_versioned()`](crate::get_versioned).
+    pub async fn get_versioned<T: Schema>(
+        &'static self,
+        key: &Key<T>,
+    ) -> Result<Option<T>, VersionedError<FlashError>> {
+        self.lock().await.get_versioned(key).await
+    }
+
+    /// Upgrades the value stored in this partition through a typed [`Key`].
+    ///
+    /// See [`migrate()`](crate::migrate).
+    pub async fn migrate<T: Schema>(
+        &'static self,
+        key: &Key<T>,
+    ) -> Result<(), VersionedError<FlashError>> {
+        self.lock().await.migrate(key).await
+    }
+
+    /// Stores a value encrypted with `encryption_key` into this partition.
+    ///
+    /// See [`insert_encrypted()`](crate::insert_encrypted).
+    #[cfg(feature = "encryption")]
+    pub async fn insert_encrypted<V: Serialize>(
+        &'static self,
+        key: &str,
+        value: &V,
+        encryption_key: &EncryptionKey,
+    ) -> Result<(), sequential_storage::Error<FlashError>> {
+        self.lock()
+            .await
+            .insert_encrypted(key, value, encryption_key)
+            .await
+    }
+
+    /// Gets and decrypts the last value stored encrypted in this partition.
+    ///
+    /// See [`get_encrypted()`](crate::get_encrypted).
+    #[cfg(feature = "encryption")]
+    pub async fn get_encrypted<V: DeserializeOwned>(
+        &'static self,
+        key: &str,
+        encryption_key: &EncryptionKey,
+    ) -> Result<Option<V>, EncryptedError<FlashError>> {
+        self.lock().await.get_encrypted(key, encryption_key).await
+    }
+
+    /// Deletes an item from this partition.
+    ///
+    /// See [`remove()`](crate::remove).
+    // STM32 flash drivers do not implement `MultiwriteNorFlash`.
+    #[cfg(not(context = "stm32"))]
+    pub async fn remove(
+        &'static self,
+        key: &str,
+    ) -> Result<(), sequential_storage::Error<FlashError>> {
+        self.lock().await.remove(key).await
+    }
+
+    /// Deletes a value stored with [`insert_large()`](Self::insert_large) from this partition.
+    ///
+    /// See [`remove_large()`](crate::remove_large).
+    // STM32 flash drivers do not implement `MultiwriteNorFlash`.
+    #[cfg(not(context = "stm32"))]
+    pub async fn remove_large(
+        &'static self,
+        key: &str,
+    ) -> Result<(), sequential_storage::Error<FlashError>> {
+        self.lock().await.remove_large(key).await
+    }
+
+    /// Resets the flash range of this partition, leaving other partitions untouched.
+    pub async fn erase_all(&'static self) -> Result<(), sequential_storage::Error<FlashError>> {
+        let mut s = self.lock().await;
+        s.erase_all().await?;
+        s.insert(MARKER_KEY, MARKER_VALUE).await
+    }
+
+    /// Gets a [`MutexGuard`] of the [`Storage`] object of this partition.
+    ///
+    /// See [`lock()`](crate::lock).
+    pub async fn lock(
+        &'static self,
+    ) -> MutexGuard<'static, CriticalSectionRawMutex, Storage<SharedFlash>> {
+        self.storage.get().await.lock().await
+    }
+}
+
+/// A raw storage partition, declared in the build configuration.
+///
+/// This reserves a flash range for another user of the flash than the key-value storage, e.g., a
+/// filesystem, which accesses it through [`flash()`](Self::flash).
+pub struct RawPartition {
+    name: &'static str,
+    range: fn() -> Range<u32>,
+    flash: OnceLock<(&'static Mutex<CriticalSectionRawMutex, Flash>, usize)>,
+}
+
+impl RawPartition {
+    pub(crate) const fn new(name: &'static str, range: fn() -> Range<u32>) -> Self {
+        Self {
+            name,
+            range,
+            flash: OnceLock::new(),
+        }
+    }
+
+    /// Returns the name of this raw partition.
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the flash range of this raw partition.
+    #[must_use]
+    pub fn range(&self) -> Range<u32> {
+        (self.range)()
+    }
+
+    /// Makes the flash available to the user of this raw partition.
+    pub(crate) fn init(
+        &self,
+        flash: &'static Mutex<CriticalSectionRawMutex, Flash>,
+        capacity: usize,
+    ) {
+        ariel_os_log::info!(
+            "storage: raw partition {} using flash range {:?}",
+            self.name,
+            &(self.range)()
+        );
+
+        let _ = self.flash.init((flash, capacity));
+    }
+
+    /// Returns the flash, shared with the other partitions, waiting for the storage to be
+    /// initialized.
+    ///
+    /// Only the [`range()`](Self::range) of this raw partition must be written to or erased.
+    pub async fn flash(&self) -> SharedFlash {
+        let &(flash, capacity) = self.flash.get().await;
+        SharedFlash::new(flash, capacity)
+    }
+}
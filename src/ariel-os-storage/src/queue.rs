@@ -0,0 +1,340 @@
+//! Provides persistent FIFO queues of records, each in its own flash range.
+//!
+//! Queues are built on [`sequential_storage::queue`], and are independent from the key-value
+//! [`Partition`](crate::Partition)s: they suit data logging, where records are appended and later
+//! consumed in order.
+//!
+//! Queues are not supported on STM32, whose flash does not support writing to the same word
+//! twice, as needed for popping records: the build fails when queues are declared there.
+
+use core::ops::Range;
+
+use ariel_os_hal::hal::storage::{Flash, FlashError};
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex,
+    mutex::{Mutex, MutexGuard},
+    once_lock::OnceLock,
+};
+use embedded_storage_async::nor_flash::{ErrorType, MultiwriteNorFlash, NorFlash};
+use postcard::{from_bytes, to_slice};
+use sequential_storage::{erase_all, map::SerializationError, queue};
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{
+    DATA_BUFFER_SIZE, SharedFlash,
+    cache::QueueCache,
+    schema::{deserialization_error, serialization_error},
+};
+
+/// Prefix of each record, which allows detecting flash ranges not holding a queue, e.g., after a
+/// change of the flash layout.
+const RECORD_MARKER: u8 = 0xa5;
+
+/// Object holding an instance of a persistent queue.
+///
+/// Records are serialized using Postcard, and must fit in [`DATA_BUFFER_SIZE`] bytes along with
+/// a marker byte.
+///
+/// You should probably look into using the [`Queue`]s declared in the build configuration.
+pub struct QueueStorage<F> {
+    flash: F,
+    queue_range: Range<u32>,
+    cache: QueueCache,
+}
+
+impl<F: NorFlash> QueueStorage<F> {
+    /// Creates a new [`QueueStorage`] instance.
+    pub const fn new(flash: F, queue_range: Range<u32>) -> QueueStorage<F> {
+        Self {
+            flash,
+            queue_range,
+            cache: QueueCache::new(),
+        }
+    }
+
+    /// Appends a record to the back of the queue.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`sequential_storage::Error::FullStorage`] if the queue is full, see
+    /// [`push_dropping_oldest()`](Self::push_dropping_oldest) for bounded logs.
+    pub async fn push<V: Serialize>(
+        &mut self,
+        value: &V,
+    ) -> Result<(), sequential_storage::Error<<F as ErrorType>::Error>> {
+        self.push_inner(value, false).await
+    }
+
+    /// Appends a record to the back of the queue, dropping the oldest records if the queue is
+    /// full.
+    ///
+    /// Records are dropped a flash page at a time.
+    pub async fn push_dropping_oldest<V: Serialize>(
+        &mut self,
+        value: &V,
+    ) -> Result<(), sequential_storage::Error<<F as ErrorType>::Error>> {
+        self.push_inner(value, true).await
+    }
+
+    async fn push_inner<V: Serialize>(
+        &mut self,
+        value: &V,
+        allow_overwrite_old_data: bool,
+    ) -> Result<(), sequential_storage::Error<<F as ErrorType>::Error>> {
+        let mut data_buffer = [RECORD_MARKER; DATA_BUFFER_SIZE];
+        let payload = data_buffer.get_mut(1..).unwrap_or_default();
+        let len = to_slice(value, payload)
+            .map_err(|err| sequential_storage::Error::SerializationError(serialization_error(err)))?
+            .len();
+
+        queue::push(
+            &mut self.flash,
+            self.queue_range.clone(),
+            &mut self.cache,
+            data_buffer.get(..=len).unwrap_or_default(),
+            allow_overwrite_old_data,
+        )
+        .await
+    }
+
+    /// Gets the record at the front of the queue, without removing it.
+    ///
+    /// Note: Always read records with the same type they were pushed with.
+    ///
+    /// If the queue is empty, `None` is returned.
+    pub async fn peek<V: DeserializeOwned>(
+        &mut self,
+    ) -> Result<Option<V>, sequential_storage::Error<<F as ErrorType>::Error>> {
+        let mut data_buffer = [0; DATA_BUFFER_SIZE];
+        queue::peek(
+            &mut self.flash,
+            self.queue_range.clone(),
+            &mut self.cache,
+            &mut data_buffer,
+        )
+        .await?
+        .map(|record| deserialize(record))
+        .transpose()
+    }
+
+    /// Returns an iterator over the records of the queue, from front to back, without removing
+    /// them.
+    pub async fn records(
+        &mut self,
+    ) -> Result<Records<'_, F>, sequential_storage::Error<<F as ErrorType>::Error>> {
+        let iter = queue::iter(&mut self.flash, self.queue_range.clone(), &mut self.cache).await?;
+        Ok(Records {
+            iter,
+            data_buffer: [0; DATA_BUFFER_SIZE],
+        })
+    }
+
+    /// Removes all records, resetting the flash in the entire flash range of this
+    /// [`QueueStorage`] instance.
+    pub async fn erase_all(
+        &mut self,
+    ) -> Result<(), sequential_storage::Error<<F as ErrorType>::Error>> {
+        let res = erase_all(&mut self.flash, self.queue_range.clone()).await;
+        // The cache does not reflect the state of the flash anymore.
+        self.cache = QueueCache::new();
+        res
+    }
+
+    /// Returns whether the flash range of this [`QueueStorage`] instance holds a valid queue,
+    /// all records of which start with the record marker.
+    async fn holds_queue(&mut self) -> bool {
+        // Reading fails on flash ranges not holding sequential-storage items.
+        let Ok(mut iter) =
+            queue::iter(&mut self.flash, self.queue_range.clone(), &mut self.cache).await
+        else {
+            return false;
+        };
+
+        let mut data_buffer = [0; DATA_BUFFER_SIZE];
+        loop {
+            match iter.next(&mut data_buffer).await {
+                Ok(Some(entry)) if entry.first() == Some(&RECORD_MARKER) => {}
+                Ok(None) => return true,
+                // Items of a key-value partition, or a corrupted range.
+                Ok(Some(_)) | Err(_) => return false,
+            }
+        }
+    }
+}
+
+impl<F: MultiwriteNorFlash> QueueStorage<F> {
+    /// Removes and returns the record at the front of the queue.
+    ///
+    /// Note: Always read records with the same type they were pushed with.
+    ///
+    /// If the queue is empty, `None` is returned.
+    pub async fn pop<V: DeserializeOwned>(
+        &mut self,
+    ) -> Result<Option<V>, sequential_storage::Error<<F as ErrorType>::Error>> {
+        let mut data_buffer = [0; DATA_BUFFER_SIZE];
+        queue::pop(
+            &mut self.flash,
+            self.queue_range.clone(),
+            &mut self.cache,
+            &mut data_buffer,
+        )
+        .await?
+        .map(|record| deserialize(record))
+        .transpose()
+    }
+}
+
+/// Iterator over the records of a queue, returned by [`QueueStorage::records()`].
+pub struct Records<'a, F: NorFlash> {
+    iter: queue::QueueIterator<'a, F, QueueCache>,
+    data_buffer: [u8; DATA_BUFFER_SIZE],
+}
+
+impl<F: NorFlash> Records<'_, F> {
+    /// Returns the next record, or `None` when all records have been returned.
+    pub async fn next<V: DeserializeOwned>(
+        &mut self,
+    ) -> Result<Option<V>, sequential_storage::Error<<F as ErrorType>::Error>> {
+        let Some(entry) = self.iter.next(&mut self.data_buffer).await? else {
+            return Ok(None);
+        };
+        deserialize(&entry).map(Some)
+    }
+}
+
+/// Deserializes a record.
+///
+/// # Errors
+///
+/// Returns [`SerializationError::InvalidFormat`] if the record does not start with the record
+/// marker, and an error if it cannot be deserialized as `V`.
+fn deserialize<V: DeserializeOwned, E>(record: &[u8]) -> Result<V, sequential_storage::Error<E>> {
+    let [RECORD_MARKER, payload @ ..] = record else {
+        return Err(sequential_storage::Error::SerializationError(
+            SerializationError::InvalidFormat,
+        ));
+    };
+    from_bytes(payload)
+        .map_err(|err| sequential_storage::Error::SerializationError(deserialization_error(err)))
+}
+
+/// A persistent queue, declared in the build configuration.
+///
+/// Each queue lives in its own flash range, next to the storage partitions.
+pub struct Queue {
+    name: &'static str,
+    range: fn() -> Range<u32>,
+    storage: OnceLock<Mutex<CriticalSectionRawMutex, QueueStorage<SharedFlash>>>,
+}
+
+impl Queue {
+    pub(crate) const fn new(name: &'static str, range: fn() -> Range<u32>) -> Self {
+        Self {
+            name,
+            range,
+            storage: OnceLock::new(),
+        }
+    }
+
+    /// Returns the name of this queue.
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the flash range of this queue.
+    #[must_use]
+    pub fn range(&self) -> Range<u32> {
+        (self.range)()
+    }
+
+    /// Creates the [`QueueStorage`] instance of this queue.
+    pub(crate) fn init(
+        &self,
+        flash: &'static Mutex<CriticalSectionRawMutex, Flash>,
+        capacity: usize,
+    ) {
+        let range = (self.range)();
+        ariel_os_log::info!(
+            "storage: queue {} using flash range {:?}",
+            self.name,
+            &range
+        );
+
+        let _ = self.storage.init(Mutex::new(QueueStorage::new(
+            SharedFlash::new(flash, capacity),
+            range,
+        )));
+    }
+
+    /// Erases this queue if its flash range does not hold a valid queue, e.g., on first boot or
+    /// after a change of the flash layout.
+    ///
+    /// # Panics
+    ///
+    /// Panics when erasing the queue fails.
+    pub(crate) async fn init_check(&'static self) {
+        let mut storage = self.lock().await;
+
+        if !storage.holds_queue().await {
+            ariel_os_log::info!("storage: initializing queue {}", self.name);
+            storage.erase_all().await.unwrap();
+        }
+    }
+
+    /// Appends a record to the back of this queue.
+    ///
+    /// See [`QueueStorage::push()`].
+    pub async fn push<V: Serialize>(
+        &'static self,
+        value: &V,
+    ) -> Result<(), sequential_storage::Error<FlashError>> {
+        self.lock().await.push(value).await
+    }
+
+    /// Appends a record to the back of this queue, dropping the oldest records if it is full.
+    ///
+    /// See [`QueueStorage::push_dropping_oldest()`].
+    pub async fn push_dropping_oldest<V: Serialize>(
+        &'static self,
+        value: &V,
+    ) -> Result<(), sequential_storage::Error<FlashError>> {
+        self.lock().await.push_dropping_oldest(value).await
+    }
+
+    /// Gets the record at the front of this queue, without removing it.
+    ///
+    /// See [`QueueStorage::peek()`].
+    pub async fn peek<V: DeserializeOwned>(
+        &'static self,
+    ) -> Result<Option<V>, sequential_storage::Error<FlashError>> {
+        self.lock().await.peek().await
+    }
+
+    /// Removes and returns the record at the front of this queue.
+    ///
+    /// See [`QueueStorage::pop()`].
+    // STM32 flash drivers do not implement `MultiwriteNorFlash`, queues cannot be declared there.
+    #[cfg(not(context = "stm32"))]
+    pub async fn pop<V: DeserializeOwned>(
+        &'static self,
+    ) -> Result<Option<V>, sequential_storage::Error<FlashError>> {
+        self.lock().await.pop().await
+    }
+
+    /// Removes all records from this queue.
+    pub async fn erase_all(&'static self) -> Result<(), sequential_storage::Error<FlashError>> {
+        self.lock().await.erase_all().await
+    }
+
+    /// Gets a [`MutexGuard`] of the [`QueueStorage`] object of this queue.
+    ///
+    /// This is needed for iterating over the records, using [`QueueStorage::records()`].
+    ///
+    /// Note: don't forget to drop the mutex guard returned by this.
+    pub async fn lock(
+        &'static self,
+    ) -> MutexGuard<'static, CriticalSectionRawMutex, QueueStorage<SharedFlash>> {
+        self.storage.get().await.lock().await
+    }
+}
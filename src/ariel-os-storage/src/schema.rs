@@ -0,0 +1,535 @@
+//! Typed keys storing values along with the identifier and version of their schema.
+//!
+//! Values stored through a [`Key`] are prefixed with a header holding the [`Schema::ID`] and
+//! [`Schema::VERSION`] of their type, followed by their Postcard serialization.
+//! This allows detecting when a value was stored with another type, and upgrading values stored
+//! with an older version of a type using the [`Migration`]s registered on the key.
+//!
+//! Values stored with a plain `insert()`, e.g., by firmware predating the key, can be upgraded as
+//! well, by registering a migration created with [`Migration::from_unversioned()`].
+//!
+//! ```ignore
+//! #[derive(Serialize, Deserialize)]
+//! struct ConfigV1 {
+//!     interval_s: u16,
+//! }
+//!
+//! impl Schema for ConfigV1 {
+//!     const ID: u32 = 0x434f_4e46;
+//!     const VERSION: u16 = 1;
+//! }
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Config {
+//!     interval_ms: u32,
+//!     enabled: bool,
+//! }
+//!
+//! impl Schema for Config {
+//!     const ID: u32 = 0x434f_4e46;
+//!     const VERSION: u16 = 2;
+//! }
+//!
+//! impl From<ConfigV1> for Config {
+//!     fn from(old: ConfigV1) -> Self {
+//!         Self { interval_ms: u32::from(old.interval_s) * 1000, enabled: true }
+//!     }
+//! }
+//!
+//! static CONFIG: Key<Config> = Key::new("config", &[Migration::new::<ConfigV1, Config>()]);
+//!
+//! let config = storage::get_versioned(&CONFIG).await?;
+//! ```
+
+use core::marker::PhantomData;
+
+use postcard::{from_bytes, to_slice};
+use sequential_storage::map::SerializationError;
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::DATA_BUFFER_SIZE;
+
+/// Marks the beginning of a header.
+const HEADER_TAG: u8 = 0xa5;
+
+/// Length of the header: tag, schema identifier and schema version.
+const HEADER_LEN: usize = 1 + 4 + 2;
+
+/// A type whose values can be stored through a [`Key`].
+pub trait Schema: Serialize + DeserializeOwned {
+    /// Identifier of the schema.
+    ///
+    /// All versions of a type must share the same identifier, and other types stored under the
+    /// same key must use a different one.
+    const ID: u32;
+    /// Version of the schema, which must be increased whenever the serialized form of the type
+    /// changes.
+    ///
+    /// Versions start at 1: version 0 stands for values stored without a header, see
+    /// [`Migration::from_unversioned()`].
+    const VERSION: u16;
+}
+
+/// A key for values of type `T`, which are upgraded from older versions of `T` when read.
+pub struct Key<T> {
+    name: &'static str,
+    migrations: &'static [Migration],
+    _schema: PhantomData<fn() -> T>,
+}
+
+impl<T: Schema> Key<T> {
+    /// Creates a new [`Key`], with the migrations used to upgrade values stored with older
+    /// versions of the schema.
+    ///
+    /// The order of `migrations` does not matter.
+    #[must_use]
+    pub const fn new(name: &'static str, migrations: &'static [Migration]) -> Self {
+        Self {
+            name,
+            migrations,
+            _schema: PhantomData,
+        }
+    }
+
+    /// Returns the name of this key.
+    #[must_use]
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Serializes `value` along with its header into `buffer`.
+    ///
+    /// Returns the number of bytes used.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `buffer` is too small or if the value cannot be serialized.
+    pub(crate) fn encode(value: &T, buffer: &mut [u8]) -> Result<usize, SerializationError> {
+        let (header, payload) = buffer
+            .split_at_mut_checked(HEADER_LEN)
+            .ok_or(SerializationError::BufferTooSmall)?;
+        Header::of::<T>().write(header);
+
+        let used = to_slice(value, payload).map_err(serialization_error)?;
+
+        Ok(HEADER_LEN + used.len())
+    }
+
+    /// Deserializes a stored `record`, upgrading it to the current version of the schema if
+    /// needed.
+    ///
+    /// Returns the value and whether it has been upgraded.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the record was not stored with a version of the schema, if no
+    /// migration is available to upgrade it, or if it cannot be deserialized.
+    pub(crate) fn decode(&self, record: &[u8]) -> Result<(T, bool), DecodeError> {
+        let (header, payload) = match Header::read(record) {
+            Some((header, payload)) if header.id == T::ID => (header, payload),
+            // Without a header of the schema, this is taken as a value stored with a plain
+            // `insert()` if the key can migrate those.
+            _ if self.migrates_unversioned() => (Header::unversioned::<T>(), record),
+            Some((header, _)) => {
+                return Err(DecodeError::SchemaMismatch {
+                    id: header.id,
+                    version: header.version,
+                });
+            }
+            None => return Err(DecodeError::NotVersioned),
+        };
+
+        if header.version > T::VERSION {
+            return Err(DecodeError::SchemaMismatch {
+                id: header.id,
+                version: header.version,
+            });
+        }
+
+        if header.version == T::VERSION {
+            let value = from_bytes(payload).map_err(deserialization_error)?;
+            return Ok((value, false));
+        }
+
+        let mut buffer = [0; DATA_BUFFER_SIZE];
+        let len = upgrade(self.migrations, header, T::VERSION, payload, &mut buffer)?;
+        let value =
+            from_bytes(buffer.get(..len).unwrap_or_default()).map_err(deserialization_error)?;
+
+        Ok((value, true))
+    }
+
+    /// Returns whether values stored without a header can be upgraded.
+    fn migrates_unversioned(&self) -> bool {
+        self.migrations
+            .iter()
+            .any(|m| m.id == T::ID && m.from == UNVERSIONED)
+    }
+}
+
+/// Version standing for values stored without a header.
+const UNVERSIONED: u16 = 0;
+
+/// Upgrades values stored with a version of a [`Schema`] to a more recent version.
+pub struct Migration {
+    id: u32,
+    from: u16,
+    to: u16,
+    migrate: fn(&[u8], &mut [u8]) -> Result<usize, SerializationError>,
+}
+
+impl Migration {
+    /// Creates a new [`Migration`] from `Old` to `New`, using `New`'s [`From`] implementation.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `Old` and `New` do not have the same [`Schema::ID`], if `Old` has version 0, or
+    /// if `New` does not have a more recent [`Schema::VERSION`] than `Old`.
+    /// When used to initialize a `static`, this is checked at compile time.
+    #[must_use]
+    pub const fn new<Old: Schema, New: Schema + From<Old>>() -> Self {
+        assert!(
+            Old::ID == New::ID,
+            "migrations must be between versions of the same schema"
+        );
+        assert!(
+            Old::VERSION != UNVERSIONED,
+            "version 0 is reserved for values stored without a header"
+        );
+        assert!(
+            Old::VERSION < New::VERSION,
+            "migrations must be to a more recent version"
+        );
+
+        Self {
+            id: Old::ID,
+            from: Old::VERSION,
+            to: New::VERSION,
+            migrate: migrate::<Old, New>,
+        }
+    }
+
+    /// Creates a new [`Migration`] from values of type `Old` stored with a plain `insert()`, i.e.,
+    /// without a header, to `New`, using `New`'s [`From`] implementation.
+    ///
+    /// This allows moving a value stored by previous firmware to a [`Key`].
+    /// Records of the key without a header of `New`'s [`Schema::ID`] are then deserialized as
+    /// `Old`, as if they had been stored with version 0 of the schema; a plain value that happens
+    /// to start with such a header would be misread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `New` has version 0.
+    /// When used to initialize a `static`, this is checked at compile time.
+    #[must_use]
+    pub const fn from_unversioned<Old: Serialize + DeserializeOwned, New: Schema + From<Old>>()
+    -> Self {
+        assert!(
+            New::VERSION != UNVERSIONED,
+            "version 0 is reserved for values stored without a header"
+        );
+
+        Self {
+            id: New::ID,
+            from: UNVERSIONED,
+            to: New::VERSION,
+            migrate: migrate::<Old, New>,
+        }
+    }
+}
+
+/// Deserializes a value of type `Old` from `payload`, and serializes it as `New` into `buffer`.
+///
+/// # Errors
+///
+/// Returns an error if the value cannot be deserialized or serialized.
+fn migrate<Old: DeserializeOwned, New: Serialize + From<Old>>(
+    payload: &[u8],
+    buffer: &mut [u8],
+) -> Result<usize, SerializationError> {
+    let old: Old = from_bytes(payload).map_err(deserialization_error)?;
+    let used = to_slice(&New::from(old), buffer).map_err(serialization_error)?;
+    Ok(used.len())
+}
+
+/// Applies `migrations` to `payload` until it reaches version `target`, writing the result into
+/// `buffer`.
+///
+/// Returns the number of bytes of `buffer` used.
+///
+/// # Errors
+///
+/// Returns an error if no migration is available for one of the intermediate versions, or if a
+/// migration fails.
+fn upgrade(
+    migrations: &[Migration],
+    header: Header,
+    target: u16,
+    payload: &[u8],
+    buffer: &mut [u8; DATA_BUFFER_SIZE],
+) -> Result<usize, DecodeError> {
+    let mut len = payload.len();
+    buffer
+        .get_mut(..len)
+        .ok_or(SerializationError::BufferTooSmall)?
+        .copy_from_slice(payload);
+
+    let mut version = header.version;
+    while version < target {
+        let migration = migrations
+            .iter()
+            .find(|m| m.id == header.id && m.from == version)
+            .ok_or(DecodeError::MissingMigration { version })?;
+
+        let mut next = [0; DATA_BUFFER_SIZE];
+        len = (migration.migrate)(buffer.get(..len).unwrap_or_default(), &mut next)?;
+        *buffer = next;
+        // Migrations always increase the version, which ensures termination.
+        version = migration.to;
+    }
+
+    if version != target {
+        // A migration skipped over the target version.
+        return Err(DecodeError::MissingMigration { version });
+    }
+
+    Ok(len)
+}
+
+/// Header prefixing versioned values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Header {
+    id: u32,
+    version: u16,
+}
+
+impl Header {
+    fn of<T: Schema>() -> Self {
+        Self {
+            id: T::ID,
+            version: T::VERSION,
+        }
+    }
+
+    /// Returns the header standing for values of the schema of `T` stored without a header.
+    fn unversioned<T: Schema>() -> Self {
+        Self {
+            id: T::ID,
+            version: UNVERSIONED,
+        }
+    }
+
+    /// Writes this header into `buffer`, which must be [`HEADER_LEN`] long.
+    fn write(self, buffer: &mut [u8]) {
+        let tag = [HEADER_TAG].into_iter();
+        let id = self.id.to_le_bytes().into_iter();
+        let version = self.version.to_le_bytes().into_iter();
+
+        for (dst, src) in buffer.iter_mut().zip(tag.chain(id).chain(version)) {
+            *dst = src;
+        }
+    }
+
+    /// Reads the header at the beginning of `record`, and returns it along with the payload
+    /// following it.
+    fn read(record: &[u8]) -> Option<(Self, &[u8])> {
+        let ([tag, id @ .., v0, v1], payload) = record.split_first_chunk::<HEADER_LEN>()?;
+        if *tag != HEADER_TAG {
+            return None;
+        }
+
+        let header = Self {
+            id: u32::from_le_bytes(*id),
+            version: u16::from_le_bytes([*v0, *v1]),
+        };
+
+        Some((header, payload))
+    }
+}
+
+/// Errors that can occur when decoding a versioned value.
+#[derive(Debug, PartialEq)]
+pub(crate) enum DecodeError {
+    NotVersioned,
+    SchemaMismatch { id: u32, version: u16 },
+    MissingMigration { version: u16 },
+    Serialization(SerializationError),
+}
+
+impl From<SerializationError> for DecodeError {
+    fn from(err: SerializationError) -> Self {
+        Self::Serialization(err)
+    }
+}
+
+/// Errors returned when accessing values through a [`Key`].
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub enum VersionedError<E> {
+    /// Accessing the storage failed.
+    Storage(sequential_storage::Error<E>),
+    /// The stored value was not stored through a [`Key`], and the key has no migration from
+    /// values stored without a header, see [`Migration::from_unversioned()`].
+    NotVersioned,
+    /// The stored value has another schema, or a more recent version of the schema.
+    SchemaMismatch {
+        /// Schema identifier of the stored value.
+        id: u32,
+        /// Schema version of the stored value.
+        version: u16,
+    },
+    /// No migration is available to upgrade the stored value from this version.
+    MissingMigration {
+        /// Version to migrate from.
+        version: u16,
+    },
+}
+
+impl<E> From<sequential_storage::Error<E>> for VersionedError<E> {
+    fn from(err: sequential_storage::Error<E>) -> Self {
+        Self::Storage(err)
+    }
+}
+
+impl<E> From<DecodeError> for VersionedError<E> {
+    fn from(err: DecodeError) -> Self {
+        match err {
+            DecodeError::NotVersioned => Self::NotVersioned,
+            DecodeError::SchemaMismatch { id, version } => Self::SchemaMismatch { id, version },
+            DecodeError::MissingMigration { version } => Self::MissingMigration { version },
+            DecodeError::Serialization(err) => {
+                Self::Storage(sequential_storage::Error::SerializationError(err))
+            }
+        }
+    }
+}
+
+pub(crate) fn serialization_error(err: postcard::Error) -> SerializationError {
+    match err {
+        postcard::Error::SerializeBufferFull => SerializationError::BufferTooSmall,
+        _ => SerializationError::Custom(0),
+    }
+}
+
+pub(crate) fn deserialization_error(err: postcard::Error) -> SerializationError {
+    match err {
+        postcard::Error::DeserializeUnexpectedEnd => SerializationError::InvalidData,
+        _ => SerializationError::Custom(0),
+    }
+}
+
+#[cfg(test)]
+#[allow(
+    clippy::missing_errors_doc,
+    clippy::unnecessary_wraps,
+    reason = "test migrations must have the signature of migrations"
+)]
+mod tests {
+    use super::*;
+
+    const ID: u32 = 0x1234_5678;
+
+    /// Appends a byte holding the length of the payload.
+    fn append_len(payload: &[u8], buffer: &mut [u8]) -> Result<usize, SerializationError> {
+        let len = payload.len();
+        buffer.get_mut(..len).unwrap().copy_from_slice(payload);
+        *buffer.get_mut(len).unwrap() = u8::try_from(len).unwrap();
+        Ok(len + 1)
+    }
+
+    /// Doubles each byte of the payload.
+    fn double(payload: &[u8], buffer: &mut [u8]) -> Result<usize, SerializationError> {
+        for (dst, src) in buffer.iter_mut().zip(payload) {
+            *dst = src * 2;
+        }
+        Ok(payload.len())
+    }
+
+    static MIGRATIONS: [Migration; 2] = [
+        Migration {
+            id: ID,
+            from: 2,
+            to: 3,
+            migrate: double,
+        },
+        Migration {
+            id: ID,
+            from: 1,
+            to: 2,
+            migrate: append_len,
+        },
+    ];
+
+    #[test]
+    fn header_round_trip() {
+        let header = Header {
+            id: ID,
+            version: 0x0102,
+        };
+        let mut record = [0; HEADER_LEN + 2];
+        header.write(record.get_mut(..HEADER_LEN).unwrap());
+        *record.last_mut().unwrap() = 42;
+
+        assert_eq!(
+            record,
+            [HEADER_TAG, 0x78, 0x56, 0x34, 0x12, 0x02, 0x01, 0, 42]
+        );
+        assert_eq!(Header::read(&record), Some((header, [0, 42].as_slice())));
+    }
+
+    #[test]
+    fn header_rejects_unversioned_records() {
+        assert_eq!(Header::read(&[HEADER_TAG, 0, 0]), None);
+        assert_eq!(
+            Header::read(&[0x00, 0x78, 0x56, 0x34, 0x12, 0x02, 0x01]),
+            None
+        );
+    }
+
+    #[test]
+    fn upgrade_applies_migrations_in_order() {
+        let header = Header { id: ID, version: 1 };
+        let mut buffer = [0; DATA_BUFFER_SIZE];
+
+        let len = upgrade(&MIGRATIONS, header, 3, &[1, 2], &mut buffer).unwrap();
+        assert_eq!(buffer.get(..len).unwrap(), [2, 4, 4]);
+
+        let header = Header { id: ID, version: 2 };
+        let len = upgrade(&MIGRATIONS, header, 3, &[1, 2], &mut buffer).unwrap();
+        assert_eq!(buffer.get(..len).unwrap(), [2, 4]);
+    }
+
+    #[test]
+    fn upgrade_reports_missing_migrations() {
+        let mut buffer = [0; DATA_BUFFER_SIZE];
+
+        let header = Header { id: ID, version: 0 };
+        assert_eq!(
+            upgrade(&MIGRATIONS, header, 3, &[], &mut buffer),
+            Err(DecodeError::MissingMigration { version: 0 })
+        );
+
+        // Migrations of other schemas are not used.
+        let header = Header {
+            id: ID + 1,
+            version: 1,
+        };
+        assert_eq!(
+            upgrade(&MIGRATIONS, header, 3, &[], &mut buffer),
+            Err(DecodeError::MissingMigration { version: 1 })
+        );
+
+        // Migrations must not skip over the target version.
+        let skipping = [Migration {
+            id: ID,
+            from: 1,
+            to: 3,
+            migrate: double,
+        }];
+        let header = Header { id: ID, version: 1 };
+        assert_eq!(
+            upgrade(&skipping, header, 2, &[], &mut buffer),
+            Err(DecodeError::MissingMigration { version: 3 })
+        );
+    }
+}
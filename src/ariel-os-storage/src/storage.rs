@@ -1,22 +1,53 @@
 //! Storage module wrapping [`sequential_storage`] in an object together with
 //! a flash range and backend.
-use core::ops::Range;
+use core::{fmt::Write as _, ops::Range};
 
 use arrayvec::ArrayString;
 use embedded_storage_async::nor_flash::{ErrorType, MultiwriteNorFlash, NorFlash};
 use sequential_storage::{
-    cache::NoCache,
     erase_all,
-    map::{Value, fetch_item, remove_item, store_item},
+    map::{SerializationError, Value, fetch_all_items, fetch_item, remove_item, store_item},
 };
 
+pub use crate::cache::Cache;
+#[cfg(feature = "encryption")]
+use crate::encryption::{self, EncryptedError, EncryptionKey};
 pub use crate::postcard_value::PostcardValue;
+use crate::schema::{Key, Schema, VersionedError};
 pub use serde::{Deserialize, Serialize};
 
 /// Maximum key length.
-pub const MAX_KEY_LEN: usize = 64usize;
-/// Data buffer length.
-pub const DATA_BUFFER_SIZE: usize = 128usize;
+///
+/// Can be configured using the `CONFIG_STORAGE_MAX_KEY_LEN` environment variable.
+pub const MAX_KEY_LEN: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_STORAGE_MAX_KEY_LEN",
+    64,
+    "maximum length of storage keys"
+);
+/// Data buffer length, which limits the size of a serialized key-value pair.
+///
+/// Can be configured using the `CONFIG_STORAGE_DATA_BUFFER_SIZE` environment variable.
+/// Each [`Storage`] instance holds a buffer of this size; values stored through
+/// [`Storage::insert_large()`] are split into chunks fitting into it.
+pub const DATA_BUFFER_SIZE: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_STORAGE_DATA_BUFFER_SIZE",
+    128,
+    "size of the buffers used for serializing storage items"
+);
+
+/// Key type used by the underlying map.
+pub type MapKey = ArrayString<MAX_KEY_LEN>;
+
+/// Separates the key of a large value from the index of a chunk, in the keys of its chunks.
+const CHUNK_SEPARATOR: char = '\u{1f}';
+/// Maximum number of chunks of a large value, limited by the length of their suffix.
+const MAX_CHUNKS: usize = 0x1_0000;
+/// Length of the suffix appended to the key of a large value for each of its chunks.
+const CHUNK_SUFFIX_LEN: usize = 5;
+/// Length of the length prefix of serialized keys.
+const KEY_LEN_PREFIX: usize = 2;
+/// Tag of the item holding the length of a large value.
+const LARGE_VALUE_TAG: u8 = 0xa1;
 
 /// Object holding an instance of a key-value pair storage.
 ///
@@ -25,6 +56,8 @@ pub const DATA_BUFFER_SIZE: usize = 128usize;
 pub struct Storage<F> {
     flash: F,
     storage_range: Range<u32>,
+    cache: Cache,
+    buffer: [u8; DATA_BUFFER_SIZE],
 }
 
 impl<F: NorFlash> Storage<F> {
@@ -33,26 +66,37 @@ impl<F: NorFlash> Storage<F> {
         Self {
             flash,
             storage_range,
+            cache: Cache::new(),
+            buffer: [0; DATA_BUFFER_SIZE],
         }
     }
 
+    /// Returns the flash backing this [`Storage`] instance.
+    ///
+    /// This allows accessing other parts of the flash, e.g., for firmware updates; the storage
+    /// range must not be written to through it, nor the ranges of other
+    /// [partitions](crate::partitions()) and [queues](crate::queues()), which callers need to
+    /// check.
+    pub fn flash_mut(&mut self) -> &mut F {
+        &mut self.flash
+    }
+
     /// Gets a [`Value`] from this [`Storage`] instance.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// Currently panics if `key.len() > MAX_KEY_LEN`.
+    /// Returns [`SerializationError::BufferTooSmall`] if `key` is longer than [`MAX_KEY_LEN`].
     pub async fn get_raw<V: for<'d> Value<'d>>(
         &mut self,
         key: &str,
     ) -> Result<Option<V>, sequential_storage::Error<<F as ErrorType>::Error>> {
-        let key = ArrayString::<MAX_KEY_LEN>::from(key).unwrap();
-        let mut data_buffer = [0; DATA_BUFFER_SIZE];
+        let key = map_key(key)?;
 
         fetch_item::<_, V, _>(
             &mut self.flash,
             self.storage_range.clone(),
-            &mut NoCache::new(),
-            &mut data_buffer,
+            &mut self.cache,
+            &mut self.buffer,
             &key,
         )
         .await
@@ -60,21 +104,20 @@ impl<F: NorFlash> Storage<F> {
 
     /// Inserts a [`Value`] into this [`Storage`] instance.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// Currently panics if `key.len() > MAX_KEY_LEN`.
+    /// Returns [`SerializationError::BufferTooSmall`] if `key` is longer than [`MAX_KEY_LEN`].
     pub async fn insert_raw<'d, V: Value<'d>>(
         &mut self,
         key: &str,
         value: V,
     ) -> Result<(), sequential_storage::Error<<F as ErrorType>::Error>> {
-        let key = ArrayString::<MAX_KEY_LEN>::from(key).unwrap();
-        let mut data_buffer = [0; DATA_BUFFER_SIZE];
+        let key = map_key(key)?;
         store_item(
             &mut self.flash,
             self.storage_range.clone(),
-            &mut NoCache::new(),
-            &mut data_buffer,
+            &mut self.cache,
+            &mut self.buffer,
             &key,
             &value,
         )
@@ -99,9 +142,13 @@ impl<F: NorFlash> Storage<F> {
     ///
     /// If no value with the key is found, `None` is returned.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// Currently panics if `key.len() > MAX_KEY_LEN`.
+    /// Returns [`SerializationError::BufferTooSmall`] if `key` is longer than [`MAX_KEY_LEN`].
+    ///
+    /// Values stored through a typed [`Key`] need to be read with [`Storage::get_versioned()`]
+    /// instead: as when reading a value with another type than it was stored with, this returns
+    /// garbage or an error.
     pub async fn get<V>(
         &mut self,
         key: &str,
@@ -109,25 +156,383 @@ impl<F: NorFlash> Storage<F> {
     where
         V: Serialize + for<'d> Deserialize<'d> + Into<PostcardValue<V>>,
     {
-        let key = ArrayString::<MAX_KEY_LEN>::from(key).unwrap();
-        let mut data_buffer = [0; DATA_BUFFER_SIZE];
+        let key = map_key(key)?;
 
-        let postcard_value = fetch_item::<_, PostcardValue<V>, _>(
+        let record = fetch_item::<_, &[u8], _>(
             &mut self.flash,
             self.storage_range.clone(),
-            &mut NoCache::new(),
-            &mut data_buffer,
+            &mut self.cache,
+            &mut self.buffer,
             &key,
         )
         .await?;
-        Ok(postcard_value.map(PostcardValue::into_inner))
+        let Some(record) = record else {
+            return Ok(None);
+        };
+
+        let (postcard_value, _) = PostcardValue::<V>::deserialize_from(record)
+            .map_err(sequential_storage::Error::SerializationError)?;
+        Ok(Some(postcard_value.into_inner()))
+    }
+
+    /// Stores a value through a typed [`Key`], along with the identifier and version of its
+    /// [`Schema`].
+    ///
+    /// It will overwrite the last value that has the same key.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SerializationError::BufferTooSmall`] if the name of `key` is longer than
+    /// [`MAX_KEY_LEN`].
+    pub async fn insert_versioned<T: Schema>(
+        &mut self,
+        key: &Key<T>,
+        value: &T,
+    ) -> Result<(), sequential_storage::Error<<F as ErrorType>::Error>> {
+        let mut value_buffer = [0; DATA_BUFFER_SIZE];
+        let len = Key::encode(value, &mut value_buffer)
+            .map_err(sequential_storage::Error::SerializationError)?;
+
+        self.insert_raw(key.name(), value_buffer.get(..len).unwrap_or_default())
+            .await
+    }
+
+    /// Gets the last value stored through a typed [`Key`].
+    ///
+    /// Values stored with an older version of the [`Schema`] are upgraded using the migrations of
+    /// the key; the upgraded value is not written back, use [`Storage::migrate()`] for this.
+    ///
+    /// If no value with the key is found, `None` is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SerializationError::BufferTooSmall`] if the name of `key` is longer than
+    /// [`MAX_KEY_LEN`].
+    pub async fn get_versioned<T: Schema>(
+        &mut self,
+        key: &Key<T>,
+    ) -> Result<Option<T>, VersionedError<<F as ErrorType>::Error>> {
+        Ok(self.fetch_versioned(key).await?.map(|(value, _)| value))
+    }
+
+    /// Upgrades the value stored through a typed [`Key`] to the current version of its
+    /// [`Schema`], and writes it back if needed.
+    ///
+    /// This allows upgrading values eagerly, e.g., at startup, instead of on every read.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SerializationError::BufferTooSmall`] if the name of `key` is longer than
+    /// [`MAX_KEY_LEN`].
+    pub async fn migrate<T: Schema>(
+        &mut self,
+        key: &Key<T>,
+    ) -> Result<(), VersionedError<<F as ErrorType>::Error>> {
+        if let Some((value, true)) = self.fetch_versioned(key).await? {
+            self.insert_versioned(key, &value).await?;
+        }
+        Ok(())
+    }
+
+    /// Gets and decodes the last value stored through a typed [`Key`].
+    ///
+    /// Returns the value and whether it has been upgraded.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SerializationError::BufferTooSmall`] if the name of `key` is longer than
+    /// [`MAX_KEY_LEN`].
+    async fn fetch_versioned<T: Schema>(
+        &mut self,
+        key: &Key<T>,
+    ) -> Result<Option<(T, bool)>, VersionedError<<F as ErrorType>::Error>> {
+        let name = map_key::<<F as ErrorType>::Error>(key.name())?;
+
+        let record = fetch_item::<_, &[u8], _>(
+            &mut self.flash,
+            self.storage_range.clone(),
+            &mut self.cache,
+            &mut self.buffer,
+            &name,
+        )
+        .await?;
+
+        match record {
+            Some(record) => Ok(Some(key.decode(record)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Stores a value encrypted and authenticated with `encryption_key`.
+    ///
+    /// It will overwrite the last value that has the same key.
+    /// The key itself is stored in clear, and authenticated along with the value.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SerializationError::BufferTooSmall`] if `key` is longer than [`MAX_KEY_LEN`], or
+    /// if the encrypted value does not fit in [`DATA_BUFFER_SIZE`].
+    #[cfg(feature = "encryption")]
+    pub async fn insert_encrypted<V: Serialize>(
+        &mut self,
+        key: &str,
+        value: &V,
+        encryption_key: &EncryptionKey,
+    ) -> Result<(), sequential_storage::Error<<F as ErrorType>::Error>> {
+        let mut record_buffer = [0; DATA_BUFFER_SIZE];
+        let len = encryption::encrypt(
+            encryption_key,
+            key,
+            value,
+            &mut ariel_os_random::crypto_rng(),
+            &mut record_buffer,
+        )
+        .map_err(sequential_storage::Error::SerializationError)?;
+
+        self.insert_raw(key, record_buffer.get(..len).unwrap_or_default())
+            .await
+    }
+
+    /// Gets and decrypts the last value stored with [`Storage::insert_encrypted()`].
+    ///
+    /// If no value with the key is found, `None` is returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`EncryptedError::Authentication`] if the value was stored with another encryption
+    /// key or under another key, or has been tampered with, and
+    /// [`SerializationError::BufferTooSmall`] if `key` is longer than [`MAX_KEY_LEN`].
+    #[cfg(feature = "encryption")]
+    pub async fn get_encrypted<V: serde::de::DeserializeOwned>(
+        &mut self,
+        key: &str,
+        encryption_key: &EncryptionKey,
+    ) -> Result<Option<V>, EncryptedError<<F as ErrorType>::Error>> {
+        let name = map_key::<<F as ErrorType>::Error>(key)?;
+
+        let record = fetch_item::<_, &[u8], _>(
+            &mut self.flash,
+            self.storage_range.clone(),
+            &mut self.cache,
+            &mut self.buffer,
+            &name,
+        )
+        .await?;
+
+        let Some(record) = record else {
+            return Ok(None);
+        };
+        let mut plaintext_buffer = [0; DATA_BUFFER_SIZE];
+        Ok(Some(encryption::decrypt(
+            encryption_key,
+            key,
+            record,
+            &mut plaintext_buffer,
+        )?))
     }
 
     /// Resets the flash in the entire flash range of this [`Storage`] instance.
     pub async fn erase_all(
         &mut self,
     ) -> Result<(), sequential_storage::Error<<F as ErrorType>::Error>> {
-        erase_all(&mut self.flash, self.storage_range.clone()).await
+        let res = erase_all(&mut self.flash, self.storage_range.clone()).await;
+        // The cache does not reflect the state of the flash anymore.
+        self.cache = Cache::new();
+        res
+    }
+
+    /// Returns an iterator over the keys stored in this [`Storage`] instance that start with
+    /// `prefix`, e.g., `"wifi/"`.
+    ///
+    /// Use an empty prefix to iterate over all keys.
+    /// Keys are returned in storage order, each key being returned once; the key used internally
+    /// to mark the storage as initialized is skipped.
+    /// The chunks of values stored with [`Storage::insert_large()`] are skipped as well.
+    ///
+    /// <div class="warning">
+    /// This is slow!
+    ///
+    /// All items in flash have to be read for every key returned, as the underlying map can
+    /// contain multiple items with the same key.
+    /// </div>
+    #[must_use]
+    pub fn keys<'a>(&'a mut self, prefix: &'a str) -> Keys<'a, F> {
+        Keys {
+            storage: self,
+            prefix,
+            position: 0,
+        }
+    }
+
+    /// Returns the first current key starting with `prefix` after the item at `position`, along
+    /// with the position of its item.
+    ///
+    /// Returns `None` if there is no such key.
+    async fn next_key(
+        &mut self,
+        position: usize,
+        prefix: &str,
+    ) -> Result<Option<(MapKey, usize)>, sequential_storage::Error<<F as ErrorType>::Error>> {
+        let mut from = position;
+        'scan: loop {
+            let mut items = fetch_all_items::<MapKey, _, _>(
+                &mut self.flash,
+                self.storage_range.clone(),
+                &mut self.cache,
+                &mut self.buffer,
+            )
+            .await?;
+
+            let mut found: Option<(MapKey, usize)> = None;
+            let mut index = 0;
+            // Values are read as bytes, as items can have different value types.
+            while let Some((key, _)) = items.next::<MapKey, &[u8]>(&mut self.buffer).await? {
+                index += 1;
+                match &found {
+                    // Only the last item with a given key is current, look for another one.
+                    Some((found_key, found_index)) if *found_key == key => {
+                        from = *found_index;
+                        continue 'scan;
+                    }
+                    None if index > from && is_listed(&key, prefix) => found = Some((key, index)),
+                    _ => {}
+                }
+            }
+
+            return Ok(found);
+        }
+    }
+
+    /// Stores a byte value that may be larger than [`DATA_BUFFER_SIZE`], e.g., a certificate.
+    ///
+    /// The value is split into chunks stored as separate items, so that it is not limited by
+    /// the buffer of this [`Storage`] instance.
+    /// It will overwrite the last value that has the same key, and needs to be read with
+    /// [`Storage::get_large()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SerializationError::BufferTooSmall`] if `key` is too long for its chunks to fit
+    /// into [`DATA_BUFFER_SIZE`] or [`MAX_KEY_LEN`], and [`SerializationError::InvalidData`] if
+    /// `value` has too many chunks.
+    pub async fn insert_large(
+        &mut self,
+        key: &str,
+        value: &[u8],
+    ) -> Result<(), sequential_storage::Error<<F as ErrorType>::Error>> {
+        let chunk_len = chunk_len::<F>(key)?;
+        let len = u32::try_from(value.len())
+            .ok()
+            .filter(|_| value.len().div_ceil(chunk_len) <= MAX_CHUNKS)
+            .ok_or(sequential_storage::Error::SerializationError(
+                SerializationError::InvalidData,
+            ))?;
+
+        // The chunks are written first, so that the value is only visible once complete.
+        for (index, chunk) in value.chunks(chunk_len).enumerate() {
+            self.insert_raw(&chunk_key(key, index)?, chunk).await?;
+        }
+
+        let mut header = [LARGE_VALUE_TAG; 5];
+        if let Some(header_len) = header.get_mut(1..) {
+            header_len.copy_from_slice(&len.to_le_bytes());
+        }
+        self.insert_raw(key, &header[..]).await
+    }
+
+    /// Gets the last byte value stored with [`Storage::insert_large()`] into `buffer`.
+    ///
+    /// Returns the length of the value, or `None` if no value with the key is found.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SerializationError::BufferTooSmall`] if `buffer` is too small for the value,
+    /// [`SerializationError::InvalidFormat`] if the value has not been stored with
+    /// [`Storage::insert_large()`], and [`SerializationError::InvalidData`] if a chunk of the
+    /// value is missing.
+    pub async fn get_large(
+        &mut self,
+        key: &str,
+        buffer: &mut [u8],
+    ) -> Result<Option<usize>, sequential_storage::Error<<F as ErrorType>::Error>> {
+        let Some(len) = self.large_len(key).await? else {
+            return Ok(None);
+        };
+        let value = buffer
+            .get_mut(..len)
+            .ok_or(sequential_storage::Error::SerializationError(
+                SerializationError::BufferTooSmall,
+            ))?;
+
+        for (index, chunk) in value.chunks_mut(chunk_len::<F>(key)?).enumerate() {
+            let chunk_key = chunk_key(key, index)?;
+            let stored = fetch_item::<_, &[u8], _>(
+                &mut self.flash,
+                self.storage_range.clone(),
+                &mut self.cache,
+                &mut self.buffer,
+                &chunk_key,
+            )
+            .await?
+            .filter(|stored| stored.len() == chunk.len())
+            .ok_or(sequential_storage::Error::SerializationError(
+                SerializationError::InvalidData,
+            ))?;
+            chunk.copy_from_slice(stored);
+        }
+
+        Ok(Some(len))
+    }
+
+    /// Returns the length of the value stored with [`Storage::insert_large()`] under `key`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SerializationError::InvalidFormat`] if the value has not been stored with
+    /// [`Storage::insert_large()`].
+    async fn large_len(
+        &mut self,
+        key: &str,
+    ) -> Result<Option<usize>, sequential_storage::Error<<F as ErrorType>::Error>> {
+        let name = map_key(key)?;
+        let header = fetch_item::<_, &[u8], _>(
+            &mut self.flash,
+            self.storage_range.clone(),
+            &mut self.cache,
+            &mut self.buffer,
+            &name,
+        )
+        .await?;
+
+        match header {
+            None => Ok(None),
+            Some(&[LARGE_VALUE_TAG, a, b, c, d]) => {
+                Ok(usize::try_from(u32::from_le_bytes([a, b, c, d])).ok())
+            }
+            Some(_) => Err(sequential_storage::Error::SerializationError(
+                SerializationError::InvalidFormat,
+            )),
+        }
+    }
+}
+
+/// Iterator over the keys of a [`Storage`] instance, returned by [`Storage::keys()`].
+pub struct Keys<'a, F> {
+    storage: &'a mut Storage<F>,
+    prefix: &'a str,
+    position: usize,
+}
+
+impl<F: NorFlash> Keys<'_, F> {
+    /// Returns the next key, or `None` when all keys have been returned.
+    pub async fn next(
+        &mut self,
+    ) -> Result<Option<MapKey>, sequential_storage::Error<<F as ErrorType>::Error>> {
+        let Some((key, position)) = self.storage.next_key(self.position, self.prefix).await? else {
+            return Ok(None);
+        };
+        self.position = position;
+        Ok(Some(key))
     }
 }
 
@@ -144,22 +549,255 @@ impl<F: MultiwriteNorFlash> Storage<F> {
     /// This is unlikely to be cached well.
     /// </div>
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// Currently panics if `key.len() > MAX_KEY_LEN`.
+    /// Returns [`SerializationError::BufferTooSmall`] if `key` is longer than [`MAX_KEY_LEN`].
     pub async fn remove(
         &mut self,
         key: &str,
     ) -> Result<(), sequential_storage::Error<<F as ErrorType>::Error>> {
-        let key = ArrayString::<MAX_KEY_LEN>::from(key).unwrap();
-        let mut data_buffer = [0; DATA_BUFFER_SIZE];
+        let key = map_key(key)?;
         remove_item(
             &mut self.flash,
             self.storage_range.clone(),
-            &mut NoCache::new(),
-            &mut data_buffer,
+            &mut self.cache,
+            &mut self.buffer,
             &key,
         )
         .await
     }
+
+    /// Deletes a value stored with [`Storage::insert_large()`], along with its chunks.
+    ///
+    /// <div class="warning">
+    /// This is really slow!
+    ///
+    /// All items in flash have to be read and deserialized for every chunk of the value.
+    /// </div>
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SerializationError::BufferTooSmall`] if `key` is longer than [`MAX_KEY_LEN`],
+    /// and [`SerializationError::InvalidFormat`] if the value has not been stored with
+    /// [`Storage::insert_large()`].
+    pub async fn remove_large(
+        &mut self,
+        key: &str,
+    ) -> Result<(), sequential_storage::Error<<F as ErrorType>::Error>> {
+        let Some(len) = self.large_len(key).await? else {
+            return Ok(());
+        };
+
+        // The value is removed first, so that a partially removed value cannot be read.
+        self.remove(key).await?;
+        for index in 0..len.div_ceil(chunk_len::<F>(key)?) {
+            self.remove(&chunk_key(key, index)?).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns whether `key` is returned by [`Storage::keys()`] for `prefix`.
+fn is_listed(key: &str, prefix: &str) -> bool {
+    key.starts_with(prefix) && key != crate::MARKER_KEY && !key.contains(CHUNK_SEPARATOR)
+}
+
+/// Returns the length of the chunks of a large value stored under `key`.
+///
+/// Items are read in words of the flash, so chunks are sized for their items to be aligned.
+///
+/// # Errors
+///
+/// Returns [`SerializationError::BufferTooSmall`] if the keys of the chunks are longer than
+/// [`MAX_KEY_LEN`], or leave no room for data in [`DATA_BUFFER_SIZE`].
+fn chunk_len<F: NorFlash>(key: &str) -> Result<usize, sequential_storage::Error<F::Error>> {
+    let chunk_key_len = key.len() + CHUNK_SUFFIX_LEN;
+    let align = F::READ_SIZE.max(F::WRITE_SIZE);
+    (DATA_BUFFER_SIZE - DATA_BUFFER_SIZE % align)
+        .checked_sub(KEY_LEN_PREFIX + chunk_key_len)
+        .filter(|len| *len > 0 && chunk_key_len <= MAX_KEY_LEN)
+        .ok_or(sequential_storage::Error::SerializationError(
+            SerializationError::BufferTooSmall,
+        ))
+}
+
+/// Returns the key of the chunk at `index` of a large value stored under `key`.
+///
+/// # Errors
+///
+/// Returns [`SerializationError::BufferTooSmall`] if the key is longer than [`MAX_KEY_LEN`].
+fn chunk_key<E>(key: &str, index: usize) -> Result<MapKey, sequential_storage::Error<E>> {
+    let mut chunk_key = map_key(key)?;
+    write!(chunk_key, "{CHUNK_SEPARATOR}{index:04x}").map_err(|_| {
+        sequential_storage::Error::SerializationError(SerializationError::BufferTooSmall)
+    })?;
+    Ok(chunk_key)
+}
+
+/// Converts `key` to the key type used by the underlying map.
+///
+/// # Errors
+///
+/// Returns [`SerializationError::BufferTooSmall`] if `key` is longer than [`MAX_KEY_LEN`].
+fn map_key<E>(key: &str) -> Result<MapKey, sequential_storage::Error<E>> {
+    MapKey::from(key).map_err(|_| {
+        sequential_storage::Error::SerializationError(SerializationError::BufferTooSmall)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::block_on;
+    use embedded_storage_async::nor_flash::{NorFlashErrorKind, ReadNorFlash};
+
+    use super::*;
+    use crate::Migration;
+
+    const ERASE_SIZE: usize = 1024;
+
+    struct RamFlash([u8; 2 * ERASE_SIZE]);
+
+    impl ErrorType for RamFlash {
+        type Error = NorFlashErrorKind;
+    }
+
+    impl ReadNorFlash for RamFlash {
+        const READ_SIZE: usize = 1;
+
+        async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            let offset = offset as usize;
+            let source = self
+                .0
+                .get(offset..offset + bytes.len())
+                .ok_or(NorFlashErrorKind::OutOfBounds)?;
+            bytes.copy_from_slice(source);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            self.0.len()
+        }
+    }
+
+    impl NorFlash for RamFlash {
+        const WRITE_SIZE: usize = 4;
+        const ERASE_SIZE: usize = ERASE_SIZE;
+
+        async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            self.0
+                .get_mut(from as usize..to as usize)
+                .ok_or(NorFlashErrorKind::OutOfBounds)?
+                .fill(0xff);
+            Ok(())
+        }
+
+        async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            let offset = offset as usize;
+            self.0
+                .get_mut(offset..offset + bytes.len())
+                .ok_or(NorFlashErrorKind::OutOfBounds)?
+                .copy_from_slice(bytes);
+            Ok(())
+        }
+    }
+
+    fn storage() -> Storage<RamFlash> {
+        #[expect(clippy::cast_possible_truncation, reason = "the flash is small")]
+        let capacity = (2 * ERASE_SIZE) as u32;
+        Storage::new(RamFlash([0xff; 2 * ERASE_SIZE]), 0..capacity)
+    }
+
+    /// Counter stored as a plain `u32` by previous firmware.
+    #[derive(Debug, PartialEq)]
+    struct Counter(u64);
+
+    impl Serialize for Counter {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_u64(self.0)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Counter {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            u64::deserialize(deserializer).map(Self)
+        }
+    }
+
+    impl Schema for Counter {
+        const ID: u32 = 0x434e_5452;
+        const VERSION: u16 = 1;
+    }
+
+    impl From<u32> for Counter {
+        fn from(old: u32) -> Self {
+            Self(u64::from(old))
+        }
+    }
+
+    static COUNTER: Key<Counter> =
+        Key::new("counter", &[Migration::from_unversioned::<u32, Counter>()]);
+
+    #[test]
+    fn plain_values_starting_with_header_tag() {
+        let mut storage = storage();
+
+        block_on(storage.insert("byte", 0xa5_u8)).unwrap();
+        block_on(storage.insert("varint", 0xa5_u32)).unwrap();
+        // Looks like the header of a `Counter`.
+        let array: [u8; 8] = [0xa5, 0x52, 0x54, 0x4e, 0x43, 0x01, 0x00, 0x2a];
+        block_on(storage.insert("array", array)).unwrap();
+
+        assert_eq!(block_on(storage.get::<u8>("byte")).unwrap(), Some(0xa5));
+        assert_eq!(block_on(storage.get::<u32>("varint")).unwrap(), Some(0xa5));
+        assert_eq!(block_on(storage.get("array")).unwrap(), Some(array));
+    }
+
+    #[test]
+    fn unversioned_values_are_migrated() {
+        let mut storage = storage();
+
+        block_on(storage.insert("counter", 0xa5_u32)).unwrap();
+        assert_eq!(
+            block_on(storage.fetch_versioned(&COUNTER)).unwrap(),
+            Some((Counter(0xa5), true))
+        );
+
+        block_on(storage.migrate(&COUNTER)).unwrap();
+        assert_eq!(
+            block_on(storage.fetch_versioned(&COUNTER)).unwrap(),
+            Some((Counter(0xa5), false))
+        );
+    }
+
+    #[test]
+    fn unversioned_values_need_a_migration() {
+        static STRICT: Key<Counter> = Key::new("strict", &[]);
+
+        let mut storage = storage();
+
+        block_on(storage.insert("strict", 42_u32)).unwrap();
+        assert_eq!(
+            block_on(storage.get_versioned(&STRICT)),
+            Err(VersionedError::NotVersioned)
+        );
+
+        block_on(storage.insert_versioned(&STRICT, &Counter(42))).unwrap();
+        assert_eq!(
+            block_on(storage.get_versioned(&STRICT)).unwrap(),
+            Some(Counter(42))
+        );
+    }
+
+    #[test]
+    fn chunk_keys_are_not_listed() {
+        let key = chunk_key::<()>("cert", 0x12).unwrap();
+        assert_eq!(key.as_str(), "cert\u{1f}0012");
+        assert_eq!(key.len(), "cert".len() + CHUNK_SUFFIX_LEN);
+
+        assert!(is_listed("cert", ""));
+        assert!(is_listed("cert", "ce"));
+        assert!(!is_listed("cert", "wifi/"));
+        assert!(!is_listed(&key, ""));
+        assert!(!is_listed(crate::MARKER_KEY, ""));
+    }
 }
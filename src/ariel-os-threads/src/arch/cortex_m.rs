@@ -102,6 +102,7 @@ impl Arch for Cpu {
     }
 
     fn wfi() {
+        ariel_os_power::prepare_idle();
         cortex_m::asm::wfi();
 
         // see https://cliffle.com/blog/stm32-wfi-bug/
@@ -68,14 +68,13 @@ impl Arch for Cpu {
                 }
             });
 
-            if res.is_err() {
+            // A joinable thread reports its panic to the thread joining it instead.
+            if res.is_err() && !crate::is_joinable(thread_id) {
                 ariel_os_log::error!("thread {:?} panicked, aborting.", thread_id);
                 std::process::abort();
             }
 
-            SCHEDULER.with_mut(|mut scheduler| {
-                scheduler.set_state(thread_id, ThreadState::Invalid);
-            });
+            crate::terminate(thread_id, res.is_err());
         });
 
         thread.data.thread = Some(handle.thread().clone());
@@ -85,6 +84,11 @@ impl Arch for Cpu {
         loop {
             SCHEDULER.with(|scheduler| {
                 for (n, thread) in scheduler.threads.iter().enumerate() {
+                    // With time slicing, the thread may have to wait for its turn.
+                    #[cfg(feature = "time-slicing")]
+                    if !scheduler.has_time_slice(thread.tid) {
+                        continue;
+                    }
                     if thread.state == ThreadState::Running {
                         if THREAD_RUNNABLE[n].swap(1, Ordering::Acquire) == 0 {
                             atomic_wait::wake_one(&THREAD_RUNNABLE[n]);
@@ -0,0 +1,246 @@
+//! Provides earliest-deadline-first (EDF) scheduling among threads of the same priority.
+//!
+//! Threads can declare an absolute deadline using [`set_deadline()`].
+//! Within a priority, threads that have a deadline are scheduled in order of their deadlines,
+//! ahead of the threads that have none, which are scheduled as usual.
+//! Deadlines do not take precedence over priorities: to be scheduled using EDF only, threads can
+//! be given a dedicated priority, higher than that of the other threads.
+//!
+//! A thread misses its deadline if it has not set its next deadline by then.
+//! Misses are detected by the scheduler, including those of threads that are blocked, or when the
+//! thread sets its next deadline, and are reported to the hook set using
+//! [`set_deadline_miss_hook()`].
+
+use core::cell::Cell;
+
+use ariel_os_log::warn;
+use critical_section::Mutex;
+use embassy_time::{Duration, Instant};
+
+use crate::{SCHEDULER, Scheduler, ThreadId, ThreadState};
+
+#[cfg(not(feature = "infini-core"))]
+use crate::RunqueueId;
+
+/// Function called when a thread misses its deadline.
+type DeadlineMissHook = fn(DeadlineMiss);
+
+/// Hook called when a thread misses its deadline.
+static DEADLINE_MISS_HOOK: Mutex<Cell<Option<DeadlineMissHook>>> = Mutex::new(Cell::new(None));
+
+/// A deadline missed by a thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DeadlineMiss {
+    /// Thread that missed its deadline.
+    pub thread_id: ThreadId,
+    /// The missed deadline.
+    pub deadline: Instant,
+    /// When the miss was detected, by the scheduler or when the thread set its next deadline.
+    pub detected_at: Instant,
+}
+
+impl DeadlineMiss {
+    /// Returns by how long the deadline was missed, when it was detected.
+    #[must_use]
+    pub fn lateness(&self) -> Duration {
+        self.detected_at - self.deadline
+    }
+}
+
+impl Scheduler {
+    /// Reports the deadlines that have passed and have not been reported yet, e.g., of threads
+    /// that blocked before setting their next deadline.
+    ///
+    /// Must be called whenever the scheduler runs.
+    pub(crate) fn check_deadlines(&mut self) {
+        let now = embassy_time_driver::now();
+        for thread in &mut self.threads {
+            if thread.state == ThreadState::Invalid || thread.deadline_missed {
+                continue;
+            }
+            if let Some(deadline) = thread.sched_deadline
+                && deadline < now
+            {
+                thread.deadline_missed = true;
+                report(DeadlineMiss {
+                    thread_id: thread.tid,
+                    deadline: Instant::from_ticks(deadline),
+                    detected_at: Instant::from_ticks(now),
+                });
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "infini-core"))]
+impl Scheduler {
+    /// Moves the current thread to its place in its runqueue after its deadline changed, and
+    /// triggers the scheduler if another thread must now run instead.
+    fn reorder_current(&mut self, thread_id: ThreadId, prio: RunqueueId) {
+        // On single-core, the current thread is the head of its runqueue.
+        #[cfg(feature = "single-core")]
+        {
+            self.runqueue.pop_head(thread_id, prio);
+            self.add_to_runqueue(thread_id, prio);
+            if self.runqueue.peek_head(prio) != Some(thread_id) {
+                crate::schedule();
+            }
+        }
+
+        // On multi-core, the current thread is not in the runqueue, and is re-added to it by
+        // the scheduler.
+        #[cfg(feature = "multi-core")]
+        if let Some(head) = self.runqueue.peek_head(prio)
+            && let Some(head_deadline) = self.get_unchecked(head).sched_deadline
+            && self
+                .get_unchecked(thread_id)
+                .sched_deadline
+                .is_none_or(|deadline| head_deadline < deadline)
+        {
+            crate::schedule();
+        }
+    }
+}
+
+/// Sets the absolute deadline of the current thread, or removes it with `None`.
+///
+/// The thread is then scheduled ahead of the threads of the same priority that have a later
+/// deadline or none.
+/// If the previous deadline of the thread has passed, it is reported as missed, unless the
+/// scheduler has already done so.
+///
+/// On native, threads are scheduled by the host operating system, so that only deadline misses
+/// are reported.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+pub fn set_deadline(deadline: Option<Instant>) {
+    let miss = SCHEDULER.with_mut(|mut scheduler| {
+        let thread = scheduler
+            .current()
+            .expect("Function should be called inside a thread context.");
+        let previous = core::mem::replace(
+            &mut thread.sched_deadline,
+            deadline.map(|deadline| deadline.as_ticks()),
+        );
+        let reported = core::mem::replace(&mut thread.deadline_missed, false);
+        let thread_id = thread.tid;
+
+        #[cfg(not(feature = "infini-core"))]
+        {
+            let prio = thread.prio;
+            scheduler.reorder_current(thread_id, prio);
+        }
+
+        let now = Instant::now();
+        previous
+            .filter(|_| !reported)
+            .map(Instant::from_ticks)
+            .filter(|previous| now > *previous)
+            .map(|previous| DeadlineMiss {
+                thread_id,
+                deadline: previous,
+                detected_at: now,
+            })
+    });
+
+    if let Some(miss) = miss {
+        report(miss);
+    }
+}
+
+/// Returns the absolute deadline of the current thread, if any.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+#[must_use]
+pub fn deadline() -> Option<Instant> {
+    SCHEDULER.with_mut(|mut scheduler| {
+        scheduler
+            .current()
+            .expect("Function should be called inside a thread context.")
+            .sched_deadline
+            .map(Instant::from_ticks)
+    })
+}
+
+/// Sets the hook called when a thread misses its deadline, replacing the previous one.
+///
+/// The hook is called either by the scheduler, with the scheduler locked, or from the thread that
+/// missed its deadline; it must therefore not use the threading API, and should, e.g., only log or
+/// count the miss.
+/// Without a hook, deadline misses are logged as warnings.
+pub fn set_deadline_miss_hook(hook: Option<fn(DeadlineMiss)>) {
+    critical_section::with(|cs| DEADLINE_MISS_HOOK.borrow(cs).set(hook));
+}
+
+/// Reports a deadline miss to the hook.
+fn report(miss: DeadlineMiss) {
+    match critical_section::with(|cs| DEADLINE_MISS_HOOK.borrow(cs).get()) {
+        Some(hook) => hook(miss),
+        None => warn!(
+            "{:?} missed its deadline by {}us",
+            miss.thread_id,
+            miss.lateness().as_micros()
+        ),
+    }
+}
+
+/// Periodic releases of the current thread, e.g., for control loops.
+///
+/// Each period starts with a release of the thread, and ends with its deadline.
+/// [`Periodic::wait()`] is called at the end of each iteration of the loop, to wait for the next
+/// period.
+#[derive(Debug)]
+pub struct Periodic {
+    period: Duration,
+    release: Instant,
+}
+
+impl Periodic {
+    /// Starts the periodic releases of the current thread, the first one being now.
+    ///
+    /// Sets the deadline of the current thread to the end of the first period.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero, or if this is called outside of a thread context.
+    #[must_use]
+    pub fn start(period: Duration) -> Self {
+        assert!(
+            period > Duration::from_ticks(0),
+            "period should not be zero"
+        );
+        let release = Instant::now();
+        set_deadline(Some(release + period));
+        Self { period, release }
+    }
+
+    /// Returns the period.
+    #[must_use]
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Waits for the next release (blocking), i.e., the end of the current period, and sets the
+    /// deadline of the current thread to the end of the next period.
+    ///
+    /// If the current period is already over, the deadline miss is reported, and the periods
+    /// that are over are skipped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        self.release += self.period;
+        while self.release + self.period <= now {
+            self.release += self.period;
+        }
+        set_deadline(Some(self.release + self.period));
+        crate::sleep_until(self.release);
+    }
+}
@@ -0,0 +1,290 @@
+//! Provides threads that can be joined, i.e., waited for until they terminate.
+//!
+//! This is not available on multi-core: a terminating thread keeps running on its stack until its
+//! core switches to another thread, so that the stack must not be returned to another core before.
+
+#![expect(unsafe_code)]
+
+use core::{
+    cell::RefCell,
+    pin::Pin,
+    ptr::NonNull,
+    task::{Context, Poll, Waker},
+};
+
+use critical_section::{CriticalSection, Mutex};
+
+use crate::{CoreAffinity, SCHEDULER, Scheduler, ThreadId, ThreadState};
+
+/// Termination status of a joinable thread, shared with its [`JoinHandle`].
+pub(crate) struct JoinState {
+    status: Mutex<RefCell<Status>>,
+}
+
+enum Status {
+    /// The thread has not terminated yet, and may be waited for.
+    Running(Option<Waiter>),
+    /// The thread function returned.
+    Returned,
+    /// The thread function panicked.
+    Panicked,
+}
+
+/// Thread or task waiting for a thread to terminate.
+enum Waiter {
+    Thread(ThreadId),
+    Task(Waker),
+}
+
+impl JoinState {
+    const fn new() -> Self {
+        Self {
+            status: Mutex::new(RefCell::new(Status::Running(None))),
+        }
+    }
+
+    /// Marks the thread as terminated, and wakes up the thread waiting for it, if any.
+    ///
+    /// Returns the waker of the task waiting for it, if any, which must be woken up once the
+    /// scheduler is not borrowed anymore.
+    pub(crate) fn complete(
+        &self,
+        cs: CriticalSection<'_>,
+        scheduler: &mut Scheduler,
+        panicked: bool,
+    ) -> Option<Waker> {
+        let status = if panicked {
+            Status::Panicked
+        } else {
+            Status::Returned
+        };
+        let Status::Running(waiter) =
+            core::mem::replace(&mut *self.status.borrow_ref_mut(cs), status)
+        else {
+            unreachable!("a thread only terminates once");
+        };
+        match waiter? {
+            Waiter::Thread(thread_id) => {
+                if scheduler.get_state(thread_id) == Some(ThreadState::JoinBlocked) {
+                    scheduler.set_state(thread_id, ThreadState::Running);
+                }
+                None
+            }
+            Waiter::Task(waker) => Some(waker),
+        }
+    }
+
+    /// Returns `Some(panicked)` once the thread has terminated, otherwise registers `waiter` to
+    /// be woken up when it terminates.
+    fn check(&self, cs: CriticalSection<'_>, waiter: impl FnOnce() -> Waiter) -> Option<bool> {
+        match &mut *self.status.borrow_ref_mut(cs) {
+            Status::Running(current) => {
+                *current = Some(waiter());
+                None
+            }
+            Status::Returned => Some(false),
+            Status::Panicked => Some(true),
+        }
+    }
+}
+
+/// Closure of a joinable thread and its return value, stored at the start of its stack buffer.
+struct Packet<F, T> {
+    state: JoinState,
+    func: Option<F>,
+    result: Option<T>,
+}
+
+/// Runs the closure of a thread created by [`spawn()`], storing its return value.
+///
+/// `packet` is the address of the [`Packet`] of the thread.
+fn run<F: FnOnce() -> T, T>(packet: usize) {
+    let packet = packet as *mut Packet<F, T>;
+    // SAFETY: the packet has been initialized by `spawn()`, and its closure and return value are
+    // only accessed by this thread until it terminates.
+    unsafe {
+        let func = (*packet).func.take().unwrap();
+        (*packet).result = Some(func());
+    }
+}
+
+/// Error returned when joining a thread that panicked.
+///
+/// This can only happen on native: on MCUs, panics halt the system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct JoinError;
+
+impl core::fmt::Display for JoinError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str("thread panicked")
+    }
+}
+
+impl core::error::Error for JoinError {}
+
+/// Handle to a thread created with [`spawn()`], allowing to wait for it to terminate.
+///
+/// The thread can be waited for from another thread using [`join()`](Self::join), or from async
+/// code by awaiting the handle.
+/// Both return the value returned by the thread, along with its stack buffer, which can then be
+/// reused, e.g., for another thread.
+///
+/// Dropping the handle detaches the thread: its stack buffer is then never returned, and its
+/// return value is never dropped.
+#[must_use = "dropping the handle detaches the thread"]
+pub struct JoinHandle<T> {
+    thread_id: ThreadId,
+    state: &'static JoinState,
+    result: NonNull<Option<T>>,
+    /// Whole stack buffer, `None` once returned.
+    stack: Option<NonNull<[u8]>>,
+}
+
+// SAFETY: the return value is only accessed after the thread has terminated, and the stack buffer
+// is only returned then.
+unsafe impl<T: Send> Send for JoinHandle<T> {}
+
+impl<T> JoinHandle<T> {
+    /// Returns the [`ThreadId`] of the thread.
+    ///
+    /// Once the thread has terminated, its [`ThreadId`] may be reused by another thread.
+    #[must_use]
+    pub fn thread_id(&self) -> ThreadId {
+        self.thread_id
+    }
+
+    /// Returns whether the thread has terminated.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        critical_section::with(|cs| {
+            !matches!(*self.state.status.borrow_ref(cs), Status::Running(_))
+        })
+    }
+
+    /// Waits for the thread to terminate (blocking).
+    ///
+    /// Returns the value returned by the thread, or [`JoinError`] if it panicked, along with its
+    /// stack buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    pub fn join(mut self) -> (Result<T, JoinError>, &'static mut [u8]) {
+        loop {
+            let panicked = critical_section::with(|cs| {
+                SCHEDULER.with_mut_cs(cs, |mut scheduler| {
+                    let thread_id = scheduler
+                        .current_tid()
+                        .expect("Function should be called inside a thread context.");
+                    let panicked = self.state.check(cs, || Waiter::Thread(thread_id));
+                    if panicked.is_none() {
+                        scheduler.set_state(thread_id, ThreadState::JoinBlocked);
+                    }
+                    panicked
+                })
+            });
+            if let Some(panicked) = panicked {
+                return self.take(panicked);
+            }
+        }
+    }
+
+    /// Takes the return value and the stack buffer of the terminated thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if they have already been taken.
+    fn take(&mut self, panicked: bool) -> (Result<T, JoinError>, &'static mut [u8]) {
+        let stack = self
+            .stack
+            .take()
+            .expect("`JoinHandle` polled after completion");
+        // SAFETY: the thread has terminated, so that its return value and stack buffer are not
+        // accessed anymore.
+        unsafe {
+            let result = if panicked {
+                Err(JoinError)
+            } else {
+                Ok((*self.result.as_ptr()).take().unwrap())
+            };
+            (result, &mut *stack.as_ptr())
+        }
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = (Result<T, JoinError>, &'static mut [u8]);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let panicked =
+            critical_section::with(|cs| self.state.check(cs, || Waiter::Task(cx.waker().clone())));
+        match panicked {
+            Some(panicked) => Poll::Ready(self.take(panicked)),
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// Creates a thread running `func`, which can be waited for using the returned [`JoinHandle`].
+///
+/// The closure and its return value are stored at the start of `stack`, the rest being used as
+/// stack by the thread.
+///
+/// # Panics
+///
+/// Panics if `stack` is too small to store the closure and its return value, or if more than
+/// [`THREAD_COUNT`](crate::THREAD_COUNT) concurrent threads have been created.
+pub fn spawn<F, T>(
+    func: F,
+    stack: &'static mut [u8],
+    prio: u8,
+    core_affinity: Option<CoreAffinity>,
+) -> JoinHandle<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let len = stack.len();
+    let buffer = NonNull::from(stack);
+    let base = buffer.cast::<u8>();
+
+    let offset = base.align_offset(align_of::<Packet<F, T>>());
+    let stack_offset = offset
+        .checked_add(size_of::<Packet<F, T>>())
+        .filter(|stack_offset| *stack_offset < len)
+        .expect("stack should be large enough to store the closure and its return value");
+
+    // SAFETY: the packet is aligned and within the buffer, and the thread stack is the rest of the
+    // buffer, which are exclusively borrowed until the thread terminates.
+    let (packet, thread_stack) = unsafe {
+        let packet = base.add(offset).cast::<Packet<F, T>>();
+        packet.write(Packet {
+            state: JoinState::new(),
+            func: Some(func),
+            result: None,
+        });
+        let thread_stack =
+            core::slice::from_raw_parts_mut(base.add(stack_offset).as_ptr(), len - stack_offset);
+        (packet, thread_stack)
+    };
+
+    // SAFETY: the packet lives in the `'static` buffer, and its state is never mutably borrowed.
+    let state = unsafe { &(*packet.as_ptr()).state };
+    let thread_id = crate::create_with_join(
+        run::<F, T>,
+        packet.as_ptr() as usize,
+        thread_stack,
+        prio,
+        core_affinity,
+        Some(state),
+    );
+
+    JoinHandle {
+        thread_id,
+        state,
+        // SAFETY: the packet is non-null.
+        result: unsafe { NonNull::new_unchecked(&raw mut (*packet.as_ptr()).result) },
+        stack: Some(buffer),
+    }
+}
@@ -2,8 +2,11 @@
 //!
 //! Implements a scheduler based on fixed priorities and preemption.
 //! Within one priority level, threads are scheduled cooperatively.
-//! This means that there is no time slicing that would equally distribute CPU time among same-priority threads.
+//! This means that by default, there is no time slicing that would equally distribute CPU time among same-priority threads.
 //! **Instead, you need to use [`yield_same()`] to explicitly yield to another thread with the same priority.**
+//! Round-robin time slicing can optionally be enabled using the `time-slicing` feature, see
+//! `set_time_slice()`.
+//! Threads can also be ordered by deadline using the `edf` feature, see `set_deadline()`.
 //! If no thread is ready, the core is prompted to enter deep sleep until a next thread is ready.
 //!
 //! Threads should be implemented using the `ariel_os_macros::thread` proc macro, which takes care
@@ -14,12 +17,20 @@
 //! Optionally, the stacksize and a priority between 1 and [`SCHED_PRIO_LEVELS`] can be configured.
 //! By default, the stack size is 2048 bytes and priority is 1.
 //!
+//! Threads can also be created at runtime using `spawn()`, which returns a `JoinHandle` for
+//! waiting for the thread to terminate and retrieving its return value.
+//! This is not available on multi-core, see the `join` module.
+//!
 //! # Synchronization
 //!
-//! The `threading` module supports three basic synchronization primitives:
+//! The `threading` module supports four basic synchronization primitives:
 //! - [`Channel`](sync::Channel): synchronous (blocking) channel for sending data between threads
+//! - [`Queue`](sync::Queue): bounded message queue for sending data between threads, tasks and
+//!   interrupt handlers
 //! - [`Lock`](sync::Lock): basic locking object
 //! - [`thread_flags`]: thread-flag implementation for signaling between threads
+//!
+//! Blocking operations have variants giving up after a timeout, which return a [`TimeoutError`].
 
 #![cfg_attr(not(any(test, context = "native")), no_std)]
 #![cfg_attr(target_arch = "xtensa", feature(asm_experimental_arch))]
@@ -38,11 +49,18 @@ mod arch;
 mod autostart_thread;
 mod blocker;
 mod core_affinity;
+#[cfg(feature = "edf")]
+mod edf;
 mod ensure_once;
+#[cfg(not(feature = "multi-core"))]
+mod join;
 mod thread;
 mod threadlist;
 mod timeout;
 
+#[cfg(feature = "time-slicing")]
+mod time_slice;
+
 #[cfg(feature = "multi-core")]
 mod smp;
 
@@ -67,8 +85,16 @@ pub mod events {
 pub use ariel_os_runqueue::{RunqueueId, ThreadId};
 pub use blocker::block_on;
 pub use core_affinity::CoreAffinity;
+#[cfg(feature = "edf")]
+pub use edf::{DeadlineMiss, Periodic, deadline, set_deadline, set_deadline_miss_hook};
+#[cfg(not(feature = "multi-core"))]
+pub use join::{JoinError, JoinHandle, spawn};
+pub use thread::ThreadState;
 pub use thread_flags as flags;
-pub use timeout::{sleep, sleep_until};
+pub use timeout::{TimeoutError, sleep, sleep_until};
+
+#[cfg(feature = "time-slicing")]
+pub use time_slice::{set_time_slice, time_slice};
 
 #[cfg(feature = "multi-core")]
 pub use smp::isr_stack_core1_get_limits;
@@ -79,7 +105,12 @@ use arch::{Arch, Cpu, ThreadData, schedule};
 use ariel_os_runqueue::RunQueue;
 
 use ensure_once::EnsureOnce;
-use thread::{Thread, ThreadState};
+#[cfg(not(feature = "multi-core"))]
+use join::JoinState;
+use thread::Thread;
+
+#[cfg(feature = "time-slicing")]
+use time_slice::TimeSlices;
 
 #[cfg(feature = "multi-core")]
 use smp::{Multicore, schedule_on_core};
@@ -121,6 +152,12 @@ struct Scheduler {
     /// `Some` when a thread is blocking another thread due to conflicting
     /// resource access.
     thread_blocklist: [Option<ThreadId>; THREAD_COUNT],
+    /// `Some` when a thread can be joined, to report its termination.
+    #[cfg(not(feature = "multi-core"))]
+    thread_joins: [Option<&'static JoinState>; THREAD_COUNT],
+    /// Time slices of the priorities, and the current time slice.
+    #[cfg(feature = "time-slicing")]
+    time_slices: TimeSlices,
 
     /// The currently running thread(s).
     #[cfg(feature = "multi-core")]
@@ -136,6 +173,10 @@ impl Scheduler {
             runqueue: RunQueue::new(),
             threads: [const { Thread::default() }; THREAD_COUNT],
             thread_blocklist: [const { None }; THREAD_COUNT],
+            #[cfg(not(feature = "multi-core"))]
+            thread_joins: [const { None }; THREAD_COUNT],
+            #[cfg(feature = "time-slicing")]
+            time_slices: TimeSlices::new(),
             #[cfg(feature = "multi-core")]
             current_threads: [None; CORE_COUNT],
             #[cfg(feature = "single-core")]
@@ -221,6 +262,12 @@ impl Scheduler {
             thread.core_affinity = _core_affinity.unwrap_or_default();
         }
 
+        #[cfg(feature = "edf")]
+        {
+            thread.sched_deadline = None;
+            thread.deadline_missed = false;
+        }
+
         Some(tid)
     }
 
@@ -279,10 +326,15 @@ impl Scheduler {
         let prio = thread.prio;
         if state == ThreadState::Running {
             #[cfg(not(feature = "infini-core"))]
-            self.runqueue.add(tid, prio);
+            self.add_to_runqueue(tid, prio);
             self.schedule_if_higher_prio(tid, prio);
 
-            #[cfg(feature = "infini-core")]
+            // With time slicing, the thread may have to wait for its turn.
+            #[cfg(all(feature = "infini-core", feature = "time-slicing"))]
+            if self.acquire_time_slice(tid, prio) {
+                Cpu::set_running(tid);
+            }
+            #[cfg(all(feature = "infini-core", not(feature = "time-slicing")))]
             Cpu::set_running(tid);
         } else if old_state == ThreadState::Running {
             // A running thread is only set to a non-running state
@@ -291,6 +343,8 @@ impl Scheduler {
 
             #[cfg(feature = "infini-core")]
             Cpu::set_stopped(tid);
+            #[cfg(all(feature = "infini-core", feature = "time-slicing"))]
+            self.release_time_slice(tid, prio);
 
             // On multi-core, the currently running thread is not in the runqueue
             // anyway, so we don't need to remove it here.
@@ -300,6 +354,15 @@ impl Scheduler {
 
             schedule();
         }
+
+        #[cfg(all(feature = "time-slicing", not(feature = "infini-core")))]
+        self.update_time_slice();
+
+        // On native, there is no scheduler run, so threads starting or stopping to run are the
+        // closest equivalent.
+        #[cfg(all(feature = "edf", feature = "infini-core"))]
+        self.check_deadlines();
+
         old_state
     }
 
@@ -330,6 +393,18 @@ impl Scheduler {
         }
         thread.prio = prio;
 
+        // With time slicing on native, the thread takes turns with the threads of its new
+        // priority instead.
+        #[cfg(all(feature = "infini-core", feature = "time-slicing"))]
+        if thread.state == ThreadState::Running {
+            self.release_time_slice(thread_id, old_prio);
+            if self.acquire_time_slice(thread_id, prio) {
+                Cpu::set_running(thread_id);
+            } else {
+                Cpu::set_stopped(thread_id);
+            }
+        }
+
         // with infini-core, no re-scheduling is needed.
         if cfg!(feature = "infini-core") {
             return;
@@ -360,7 +435,10 @@ impl Scheduler {
             } else {
                 self.runqueue.del(thread_id);
             }
-            self.runqueue.add(thread_id, prio);
+            self.add_to_runqueue(thread_id, prio);
+
+            #[cfg(feature = "time-slicing")]
+            self.update_time_slice();
 
             // Check & handle if the thread is among the current threads for single-core,
             // analogous to the above multi-core implementation.
@@ -379,17 +457,72 @@ impl Scheduler {
         }
     }
 
+    /// Adds a thread to runqueue `prio`.
+    ///
+    /// With EDF scheduling, threads that have a deadline are kept ordered by deadline, ahead of
+    /// the threads that have none.
+    #[cfg(not(feature = "infini-core"))]
+    fn add_to_runqueue(&mut self, tid: ThreadId, prio: RunqueueId) {
+        #[cfg(feature = "edf")]
+        if let Some(deadline) = self.get_unchecked(tid).sched_deadline {
+            let threads = &self.threads;
+            // Threads with the same deadline are scheduled in FIFO order.
+            self.runqueue.add_before(tid, prio, |next| {
+                threads[usize::from(next)]
+                    .sched_deadline
+                    .is_none_or(|next| next > deadline)
+            });
+            return;
+        }
+        self.runqueue.add(tid, prio);
+    }
+
+    /// Moves the head of runqueue `prio` behind the other threads of that runqueue.
+    ///
+    /// With EDF scheduling, a head that has a deadline is only moved behind the threads with the
+    /// same deadline.
+    ///
+    /// Returns `false` if the head did not change.
+    #[cfg(feature = "single-core")]
+    fn rotate_runqueue(&mut self, prio: RunqueueId) -> bool {
+        #[cfg(feature = "edf")]
+        if let Some(head) = self.runqueue.peek_head(prio)
+            && self.get_unchecked(head).sched_deadline.is_some()
+        {
+            self.runqueue.pop_head(head, prio);
+            self.add_to_runqueue(head, prio);
+            return self.runqueue.peek_head(prio) != Some(head);
+        }
+        self.runqueue.advance(prio)
+    }
+
     /// Triggers the scheduler if the thread has a higher priority than (one of)
     /// the running thread(s).
+    ///
+    /// With EDF scheduling, this is also the case if the thread has the same priority and an
+    /// earlier deadline.
     fn schedule_if_higher_prio(&mut self, _thread_id: ThreadId, prio: RunqueueId) {
         #[cfg(not(feature = "multi-core"))]
         match self.current().map(|t| t.prio) {
             Some(curr_prio) if curr_prio < prio => schedule(),
+            // The thread was put ahead of the current thread in the runqueue.
+            #[cfg(all(feature = "edf", feature = "single-core"))]
+            Some(curr_prio)
+                if curr_prio == prio && self.runqueue.peek_head(prio) == Some(_thread_id) =>
+            {
+                schedule();
+            }
             _ => {}
         }
         #[cfg(feature = "multi-core")]
         match self.lowest_running_prio(_thread_id) {
             (core, Some(lowest_prio)) if lowest_prio < prio => schedule_on_core(core),
+            #[cfg(feature = "edf")]
+            (_, Some(lowest_prio)) if lowest_prio == prio => {
+                if let Some(core) = self.latest_deadline_core(_thread_id, prio) {
+                    schedule_on_core(core);
+                }
+            }
             _ => {}
         }
     }
@@ -429,7 +562,7 @@ impl Scheduler {
         else {
             return;
         };
-        self.runqueue.add(tid, prio);
+        self.add_to_runqueue(tid, prio);
     }
 
     /// Returns the next thread from the runqueue.
@@ -443,30 +576,44 @@ impl Scheduler {
     #[allow(dead_code, reason = "used in scheduler implementation")]
     #[cfg(any(feature = "single-core", feature = "multi-core"))]
     fn get_next_tid(&mut self) -> Option<ThreadId> {
-        // On single-core, only read the head of the runqueue.
-        #[cfg(feature = "single-core")]
-        {
-            self.runqueue.get_next()
-        }
+        // Rotate the runqueue first if the time slice of the running thread has elapsed.
+        #[cfg(feature = "time-slicing")]
+        self.end_time_slice();
 
-        // On multi-core, the head is popped of the runqueue.
-        #[cfg(all(feature = "multi-core", not(feature = "core-affinity")))]
-        {
-            self.runqueue.pop_next()
-        }
+        #[cfg(feature = "edf")]
+        self.check_deadlines();
 
-        // On multi-core with core-affinities, get next thread with matching affinity.
-        #[cfg(all(feature = "multi-core", feature = "core-affinity"))]
-        {
-            // TODO: this would benefit from a `del_one_with_filter` to avoid
-            // iterating twice.
-            let next = self
-                .runqueue
-                .get_next_filter(|&t| self.is_affine_to_curr_core(t))?;
-            // Delete thread from runqueue to match the `pop_next`.
-            self.runqueue.del(next);
-            Some(next)
-        }
+        let next = {
+            // On single-core, only read the head of the runqueue.
+            #[cfg(feature = "single-core")]
+            {
+                self.runqueue.get_next()
+            }
+
+            // On multi-core, the head is popped of the runqueue.
+            #[cfg(all(feature = "multi-core", not(feature = "core-affinity")))]
+            {
+                self.runqueue.pop_next()
+            }
+
+            // On multi-core with core-affinities, get next thread with matching affinity.
+            #[cfg(all(feature = "multi-core", feature = "core-affinity"))]
+            {
+                // TODO: this would benefit from a `del_one_with_filter` to avoid
+                // iterating twice.
+                let next = self
+                    .runqueue
+                    .get_next_filter(|&t| self.is_affine_to_curr_core(t))?;
+                // Delete thread from runqueue to match the `pop_next`.
+                self.runqueue.del(next);
+                Some(next)
+            }
+        };
+
+        #[cfg(feature = "time-slicing")]
+        self.update_time_slice();
+
+        next
     }
 
     /// Searches for the lowest priority thread among the currently running threads.
@@ -499,6 +646,33 @@ impl Scheduler {
             .unwrap()
     }
 
+    /// Searches for the core running the thread of priority `prio` with the latest deadline, if
+    /// it is later than the deadline of thread `tid`.
+    ///
+    /// Threads without a deadline are considered to have the latest one.
+    #[cfg(all(feature = "edf", feature = "multi-core"))]
+    fn latest_deadline_core(&self, tid: ThreadId, prio: RunqueueId) -> Option<CoreId> {
+        let deadline = self.get_unchecked(tid).sched_deadline?;
+        #[cfg(feature = "core-affinity")]
+        let affinity = self.get_unchecked(tid).core_affinity;
+        self.current_threads
+            .iter()
+            .enumerate()
+            .filter_map(|(core, current)| {
+                let core = CoreId(core as u8);
+                // Skip cores that don't match the core-affinity.
+                #[cfg(feature = "core-affinity")]
+                if !affinity.contains(core) {
+                    return None;
+                }
+                let current = self.get_unchecked((*current)?);
+                (current.prio == prio).then_some((core, current.sched_deadline.unwrap_or(u64::MAX)))
+            })
+            .max_by_key(|(_, current_deadline)| *current_deadline)
+            .filter(|(_, current_deadline)| *current_deadline > deadline)
+            .map(|(core, _)| core)
+    }
+
     /// Checks if a thread can be scheduled on the current core.
     #[allow(dead_code, reason = "used in scheduler implementation")]
     #[cfg(feature = "core-affinity")]
@@ -642,6 +816,33 @@ pub fn create<T>(
     prio: u8,
     core_affinity: Option<CoreAffinity>,
 ) -> ThreadId
+where
+    T: Arguable + Send,
+{
+    create_with_join(
+        func,
+        arg,
+        stack,
+        prio,
+        core_affinity,
+        #[cfg(not(feature = "multi-core"))]
+        None,
+    )
+}
+
+/// Creates a thread, whose termination is reported to `join`.
+///
+/// # Panics
+///
+/// Panics if more than [`THREAD_COUNT`] concurrent threads have been created.
+fn create_with_join<T>(
+    func: fn(T),
+    arg: T,
+    stack: &'static mut [u8],
+    prio: u8,
+    core_affinity: Option<CoreAffinity>,
+    #[cfg(not(feature = "multi-core"))] join: Option<&'static JoinState>,
+) -> ThreadId
 where
     T: Arguable + Send,
 {
@@ -656,7 +857,17 @@ where
         unsafe { core::mem::transmute::<*const (), fn()>(func) }
     };
 
-    unsafe { create_raw(func, arg, stack, prio, core_affinity) }
+    unsafe {
+        create_raw_with_join(
+            func,
+            arg,
+            stack,
+            prio,
+            core_affinity,
+            #[cfg(not(feature = "multi-core"))]
+            join,
+        )
+    }
 }
 
 /// Low-level function to create a thread without argument.
@@ -685,11 +896,40 @@ pub unsafe fn create_raw(
     stack: &'static mut [u8],
     prio: u8,
     core_affinity: Option<CoreAffinity>,
+) -> ThreadId {
+    unsafe {
+        create_raw_with_join(
+            func,
+            arg,
+            stack,
+            prio,
+            core_affinity,
+            #[cfg(not(feature = "multi-core"))]
+            None,
+        )
+    }
+}
+
+/// Creates a thread, low-level, whose termination is reported to `join`.
+///
+/// # Safety
+///
+/// See [`create_raw()`].
+unsafe fn create_raw_with_join(
+    func: fn(),
+    arg: Option<usize>,
+    stack: &'static mut [u8],
+    prio: u8,
+    core_affinity: Option<CoreAffinity>,
+    #[cfg(not(feature = "multi-core"))] join: Option<&'static JoinState>,
 ) -> ThreadId {
     SCHEDULER.with_mut(|mut scheduler| {
         let thread_id = scheduler
             .create(func, arg, stack, RunqueueId::new(prio), core_affinity)
             .expect("Max `THREAD_COUNT` concurrent threads should be created.");
+        // Registered before the thread runs, so that its termination cannot be missed.
+        #[cfg(not(feature = "multi-core"))]
+        scheduler.thread_joins[usize::from(thread_id)] = join;
         scheduler.set_state(thread_id, ThreadState::Running);
         thread_id
     })
@@ -731,6 +971,9 @@ pub fn is_valid_tid(thread_id: ThreadId) -> bool {
 /// Panics if this is called outside of a thread context.
 #[allow(unused)]
 fn cleanup() -> ! {
+    #[cfg(not(feature = "multi-core"))]
+    terminate(current_tid().unwrap(), false);
+    #[cfg(feature = "multi-core")]
     SCHEDULER.with_mut(|mut scheduler| {
         let thread_id = scheduler.current_tid().unwrap();
         scheduler.set_state(thread_id, ThreadState::Invalid);
@@ -739,6 +982,33 @@ fn cleanup() -> ! {
     unreachable!();
 }
 
+/// Terminates a thread, waking up the thread or task joining it, if any.
+///
+/// `panicked` indicates whether the thread function panicked instead of returning.
+#[cfg(not(feature = "multi-core"))]
+fn terminate(thread_id: ThreadId, panicked: bool) {
+    critical_section::with(|cs| {
+        let waker = SCHEDULER.with_mut_cs(cs, |mut scheduler| {
+            let join = scheduler.thread_joins[usize::from(thread_id)].take();
+            let waker = join.and_then(|join| join.complete(cs, &mut scheduler, panicked));
+            scheduler.set_state(thread_id, ThreadState::Invalid);
+            waker
+        });
+        // Woken up within the critical section, so that the stack of the thread is not reused
+        // before it stopped running, but with the scheduler released, as the waker may use it.
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    });
+}
+
+/// Checks if a thread can be joined, i.e., was created using [`spawn()`].
+#[cfg(not(feature = "multi-core"))]
+#[allow(dead_code, reason = "only used on native")]
+fn is_joinable(thread_id: ThreadId) -> bool {
+    SCHEDULER.with(|scheduler| scheduler.thread_joins[usize::from(thread_id)].is_some())
+}
+
 /// "Yields" to another thread with the same priority.
 pub fn yield_same() {
     // Nothing to do for infini-core, because all threads run in *parallel* from the point of view
@@ -759,7 +1029,7 @@ pub fn yield_same() {
         };
 
         #[cfg(feature = "single-core")]
-        if scheduler.runqueue.advance(prio) {
+        if scheduler.rotate_runqueue(prio) {
             schedule();
         }
 
@@ -808,6 +1078,13 @@ pub fn unpark(thread_id: ThreadId) -> bool {
     })
 }
 
+/// Returns the state of a thread.
+///
+/// Returns `None` if this is not a valid thread.
+pub fn get_state(thread_id: ThreadId) -> Option<ThreadState> {
+    SCHEDULER.with(|scheduler| scheduler.get_state(thread_id))
+}
+
 /// Returns the priority of a thread.
 ///
 /// Returns `None` if this is not a valid thread.
@@ -830,3 +1107,15 @@ pub fn current_stack_limits() -> Option<(usize, usize)> {
             .map(|thread| (thread.stack_lowest, thread.stack_highest))
     })
 }
+
+/// Returns a thread's stack limits (lowest, highest).
+///
+/// Returns `None` if this is not a valid thread.
+pub fn stack_limits(thread_id: ThreadId) -> Option<(usize, usize)> {
+    SCHEDULER.with(|scheduler| {
+        scheduler.is_valid_tid(thread_id).then(|| {
+            let thread = scheduler.get_unchecked(thread_id);
+            (thread.stack_lowest, thread.stack_highest)
+        })
+    })
+}
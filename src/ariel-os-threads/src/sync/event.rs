@@ -9,7 +9,10 @@
 
 use core::cell::UnsafeCell;
 
-use crate::{ThreadState, threadlist::ThreadList};
+use critical_section::CriticalSection;
+use embassy_time::{Duration, Instant};
+
+use crate::{ThreadState, TimeoutError, threadlist::ThreadList};
 
 /// An [`Event`], allowing to notify multiple threads that some event has happened.
 ///
@@ -69,15 +72,65 @@ impl Event {
     ///
     /// Panics if this is called outside of a thread context.
     pub fn wait(&self) {
-        critical_section::with(|cs| {
-            let state = unsafe { &mut *self.state.get() };
-            match state {
-                LockState::Unlocked => {}
-                LockState::Locked(waiters) => {
-                    waiters.put_current(cs, ThreadState::LockBlocked);
-                }
+        critical_section::with(|cs| self.wait_cs(cs));
+    }
+
+    fn wait_cs(&self, cs: CriticalSection<'_>) {
+        let state = unsafe { &mut *self.state.get() };
+        match state {
+            LockState::Unlocked => {}
+            LockState::Locked(waiters) => {
+                waiters.put_current(cs, ThreadState::LockBlocked);
             }
-        });
+        }
+    }
+
+    /// Waits for this [`Event`] to be set, at most for `timeout` (blocking).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TimeoutError`] if the event was not set before the timeout.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    pub fn wait_timeout(&self, timeout: Duration) -> Result<(), TimeoutError> {
+        self.wait_until(Instant::now().saturating_add(timeout))
+    }
+
+    /// Waits for this [`Event`] to be set, at most until `deadline` (blocking).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TimeoutError`] if the event was not set before the deadline.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    pub fn wait_until(&self, deadline: Instant) -> Result<(), TimeoutError> {
+        if self.is_set() {
+            return Ok(());
+        }
+        // Safety: `on_timeout` takes care of removing the thread from the waiters.
+        let is_set = unsafe {
+            crate::timeout::with_deadline(
+                deadline,
+                |cs| self.wait_cs(cs),
+                |cs| self.cancel_wait(cs),
+            )
+        };
+        if is_set { Ok(()) } else { Err(TimeoutError) }
+    }
+
+    /// Removes the current thread from the waiters after a timeout.
+    ///
+    /// Returns true if the event has been set in the meantime.
+    fn cancel_wait(&self, cs: CriticalSection<'_>) -> bool {
+        let state = unsafe { &mut *self.state.get() };
+        match state {
+            LockState::Unlocked => true,
+            LockState::Locked(waiters) => !waiters.remove_current(cs),
+        }
     }
 
     /// Clears the event (non-blocking).
@@ -3,10 +3,12 @@ mod channel;
 mod event;
 mod lock;
 mod mutex;
+mod queue;
 mod wait_queue;
 
 pub use channel::Channel;
 pub use event::Event;
 pub use lock::Lock;
 pub use mutex::{Mutex, MutexGuard};
+pub use queue::Queue;
 pub use wait_queue::WaitQueue;
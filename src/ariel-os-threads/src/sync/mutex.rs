@@ -13,8 +13,9 @@ use core::{
 
 use ariel_os_runqueue::{RunqueueId, ThreadId};
 use critical_section::CriticalSection;
+use embassy_time::{Duration, Instant};
 
-use crate::{SCHEDULER, thread::ThreadState, threadlist::ThreadList};
+use crate::{SCHEDULER, TimeoutError, thread::ThreadState, threadlist::ThreadList};
 
 /// A basic mutex with priority inheritance.
 pub struct Mutex<T> {
@@ -91,34 +92,7 @@ impl<T> Mutex<T> {
     ///
     /// Panics if called outside of a thread context.
     pub fn lock(&self) -> MutexGuard<'_, T> {
-        critical_section::with(|cs| {
-            // SAFETY: access to the state only happens in critical sections, so it's always unique.
-            let state = unsafe { &mut *self.state.get() };
-            match state {
-                LockState::Unlocked => {
-                    *state = LockState::locked_with_current(cs);
-                }
-                LockState::Locked {
-                    waiters,
-                    owner_id,
-                    owner_prio,
-                } => {
-                    // Insert thread in waitlist, which also triggers the scheduler.
-                    match waiters.put_current(cs, ThreadState::LockBlocked) {
-                        // `Some` when the inserted thread is the highest priority
-                        // thread in the waitlist.
-                        Some(waiter_prio) if waiter_prio > *owner_prio => {
-                            // Current mutex owner inherits the priority.
-                            SCHEDULER.with_mut_cs(cs, |mut scheduler| {
-                                scheduler.set_priority(*owner_id, waiter_prio);
-                            });
-                        }
-                        _ => {}
-                    }
-                    // Context switch happens here as soon as we leave the critical section.
-                }
-            }
-        });
+        critical_section::with(|cs| self.lock_cs(cs));
         // Mutex was either directly acquired because it was unlocked, or the current thread was entered
         // to the waitlist. In the latter case, it only continues running here after it was popped again
         // from the waitlist and the thread acquired the mutex.
@@ -126,6 +100,114 @@ impl<T> Mutex<T> {
         MutexGuard::new(self)
     }
 
+    fn lock_cs(&self, cs: CriticalSection<'_>) {
+        // SAFETY: access to the state only happens in critical sections, so it's always unique.
+        let state = unsafe { &mut *self.state.get() };
+        match state {
+            LockState::Unlocked => {
+                *state = LockState::locked_with_current(cs);
+            }
+            LockState::Locked {
+                waiters,
+                owner_id,
+                owner_prio,
+            } => {
+                // Insert thread in waitlist, which also triggers the scheduler.
+                match waiters.put_current(cs, ThreadState::LockBlocked) {
+                    // `Some` when the inserted thread is the highest priority
+                    // thread in the waitlist.
+                    Some(waiter_prio) if waiter_prio > *owner_prio => {
+                        // Current mutex owner inherits the priority.
+                        SCHEDULER.with_mut_cs(cs, |mut scheduler| {
+                            scheduler.set_priority(*owner_id, waiter_prio);
+                        });
+                    }
+                    _ => {}
+                }
+                // Context switch happens here as soon as we leave the critical section.
+            }
+        }
+    }
+
+    /// Acquires a mutex, blocking the current thread at most for `timeout`.
+    ///
+    /// See [`Mutex::lock()`] for priority inheritance.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TimeoutError`] if the mutex could not be acquired before the timeout.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a thread context.
+    pub fn lock_timeout(&self, timeout: Duration) -> Result<MutexGuard<'_, T>, TimeoutError> {
+        self.lock_until(Instant::now().saturating_add(timeout))
+    }
+
+    /// Acquires a mutex, blocking the current thread at most until `deadline`.
+    ///
+    /// See [`Mutex::lock()`] for priority inheritance.
+    /// If the mutex could not be acquired, the priority the owner inherited from the current
+    /// thread is dropped again.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TimeoutError`] if the mutex could not be acquired before the deadline.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a thread context.
+    pub fn lock_until(&self, deadline: Instant) -> Result<MutexGuard<'_, T>, TimeoutError> {
+        if let Some(guard) = self.try_lock() {
+            return Ok(guard);
+        }
+        // SAFETY: `on_timeout` takes care of removing the thread from the waitlist.
+        let acquired = unsafe {
+            crate::timeout::with_deadline(
+                deadline,
+                |cs| self.lock_cs(cs),
+                |cs| self.cancel_lock(cs),
+            )
+        };
+        if acquired {
+            Ok(MutexGuard::new(self))
+        } else {
+            Err(TimeoutError)
+        }
+    }
+
+    /// Removes the current thread from the waitlist after a timeout, and adjusts the inherited
+    /// priority of the owner.
+    ///
+    /// Returns true if the mutex has been handed over to the thread in the meantime.
+    fn cancel_lock(&self, cs: CriticalSection<'_>) -> bool {
+        // SAFETY: access to the state only happens in critical sections, so it's always unique.
+        let state = unsafe { &mut *self.state.get() };
+        let LockState::Locked {
+            waiters,
+            owner_id,
+            owner_prio,
+        } = state
+        else {
+            return false;
+        };
+        if !waiters.remove_current(cs) {
+            return true;
+        }
+        SCHEDULER.with_mut_cs(cs, |mut scheduler| {
+            // The owner only keeps the priority inherited from the remaining waiters.
+            let prio = waiters
+                .peek(cs)
+                .map(|head| scheduler.get_unchecked(head).prio)
+                .filter(|waiter_prio| *waiter_prio > *owner_prio)
+                .unwrap_or(*owner_prio);
+            if scheduler.get_unchecked(*owner_id).prio != prio {
+                scheduler.set_priority(*owner_id, prio);
+            }
+        });
+        false
+    }
+
     /// Attempts to acquire this lock, in a non-blocking fashion.
     ///
     /// If the mutex was unlocked, it will be locked and a [`MutexGuard`] is returned.
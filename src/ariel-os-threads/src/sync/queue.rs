@@ -0,0 +1,349 @@
+//! This module provides a bounded message queue for sending data between threads.
+
+#![expect(unsafe_code)]
+#![deny(missing_docs)]
+
+use core::cell::{Cell, UnsafeCell};
+use core::future::poll_fn;
+use core::task::Poll;
+
+use critical_section::CriticalSection;
+use embassy_sync::waitqueue::WakerRegistration;
+use embassy_time::{Duration, Instant};
+use ringbuffer::ArrayRingBuffer;
+
+use crate::{ThreadState, TimeoutError, threadlist::ThreadList};
+
+/// A bounded, statically allocated message queue, with multiple senders and receivers.
+///
+/// The queue buffers up to `N` elements, which must be a power of two between 2 and 128.
+/// Elements are received in the order they were sent.
+///
+/// [`Self::send()`] blocks the current thread while the queue is full, and [`Self::recv()`]
+/// while it is empty; both have variants that give up after a timeout.
+/// The non-blocking [`Self::try_send()`] and [`Self::try_recv()`] can also be used from
+/// interrupt handlers, and [`Self::send_async()`] and [`Self::recv_async()`] from async tasks.
+pub struct Queue<T, const N: usize> {
+    state: UnsafeCell<QueueState<T, N>>,
+}
+
+// Safety: the state is only accessed within critical sections, and elements are moved from the
+// sender to the receiver.
+unsafe impl<T: Send, const N: usize> Sync for Queue<T, N> {}
+
+struct QueueState<T, const N: usize> {
+    buffer: ArrayRingBuffer<T, N>,
+    /// Threads waiting for an element.
+    receivers: ThreadList,
+    /// Threads waiting for a free slot.
+    senders: ThreadList,
+    /// Task waiting for an element.
+    rx_waker: WakerRegistration,
+    /// Task waiting for a free slot.
+    tx_waker: WakerRegistration,
+}
+
+impl<T, const N: usize> QueueState<T, N> {
+    /// Appends an element, and wakes up a thread and a task waiting to receive.
+    ///
+    /// # Errors
+    ///
+    /// Returns the element back if the buffer is full.
+    fn put(&mut self, cs: CriticalSection<'_>, element: T) -> Result<(), T> {
+        self.buffer.put(element)?;
+        self.receivers.pop(cs);
+        self.rx_waker.wake();
+        Ok(())
+    }
+
+    /// Removes the oldest element, and wakes up a thread and a task waiting to send.
+    fn get(&mut self, cs: CriticalSection<'_>) -> Option<T> {
+        let element = self.buffer.get()?;
+        self.senders.pop(cs);
+        self.tx_waker.wake();
+        Some(element)
+    }
+}
+
+impl<T: Send, const N: usize> Queue<T, N> {
+    /// Creates a new, empty [`Queue`].
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            state: UnsafeCell::new(QueueState {
+                buffer: ArrayRingBuffer::new(),
+                receivers: ThreadList::new(),
+                senders: ThreadList::new(),
+                rx_waker: WakerRegistration::new(),
+                tx_waker: WakerRegistration::new(),
+            }),
+        }
+    }
+
+    fn with_state<R>(
+        &self,
+        _cs: CriticalSection<'_>,
+        f: impl FnOnce(&mut QueueState<T, N>) -> R,
+    ) -> R {
+        // Safety: the critical section is used to uphold aliasing rules, and `f` does not access
+        // the queue again.
+        f(unsafe { &mut *self.state.get() })
+    }
+
+    /// Sends an element on the queue (blocking).
+    ///
+    /// If the queue is full, the current thread is suspended until an element has been received.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    pub fn send(&self, mut element: T) {
+        loop {
+            let result = critical_section::with(|cs| {
+                self.with_state(cs, |state| {
+                    state.put(cs, element).inspect_err(|_| {
+                        state.senders.put_current(cs, ThreadState::QueueTxBlocked);
+                    })
+                })
+            });
+            // Once woken up, another sender may have taken the free slot.
+            match result {
+                Ok(()) => return,
+                Err(returned) => element = returned,
+            }
+        }
+    }
+
+    /// Sends an element on the queue, waiting at most for `timeout` (blocking).
+    ///
+    /// # Errors
+    ///
+    /// Returns the element back if the queue remained full until the timeout.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    pub fn send_timeout(&self, element: T, timeout: Duration) -> Result<(), T> {
+        self.send_until(element, Instant::now().saturating_add(timeout))
+    }
+
+    /// Sends an element on the queue, waiting at most until `deadline` (blocking).
+    ///
+    /// # Errors
+    ///
+    /// Returns the element back if the queue remained full until the deadline.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    pub fn send_until(&self, element: T, deadline: Instant) -> Result<(), T> {
+        let mut element = element;
+        loop {
+            let pending = Cell::new(Some(element));
+            // Safety: `on_timeout` takes care of removing the thread from the waiting senders.
+            unsafe {
+                crate::timeout::with_deadline_check(
+                    deadline,
+                    |cs| {
+                        self.with_state(cs, |state| {
+                            let Some(element) = pending.take() else {
+                                return true;
+                            };
+                            state
+                                .put(cs, element)
+                                .map_err(|element| pending.set(Some(element)))
+                                .is_ok()
+                        })
+                    },
+                    |cs| {
+                        self.with_state(cs, |state| {
+                            state.senders.put_current(cs, ThreadState::QueueTxBlocked);
+                        });
+                    },
+                    |cs| {
+                        self.with_state(cs, |state| {
+                            state.senders.remove_current(cs);
+                        });
+                    },
+                );
+            }
+            match pending.into_inner() {
+                None => return Ok(()),
+                Some(returned) if Instant::now() >= deadline => return Err(returned),
+                Some(returned) => element = returned,
+            }
+        }
+    }
+
+    /// Tries to send an element on the queue (non-blocking).
+    ///
+    /// This can be called from interrupt handlers.
+    ///
+    /// # Errors
+    ///
+    /// Returns the element back if the queue is full.
+    pub fn try_send(&self, element: T) -> Result<(), T> {
+        critical_section::with(|cs| self.with_state(cs, |state| state.put(cs, element)))
+    }
+
+    /// Sends an element on the queue, waiting asynchronously while it is full.
+    pub async fn send_async(&self, element: T) {
+        let mut pending = Some(element);
+        poll_fn(|cx| {
+            critical_section::with(|cs| {
+                self.with_state(cs, |state| {
+                    let Some(element) = pending.take() else {
+                        return Poll::Ready(());
+                    };
+                    match state.put(cs, element) {
+                        Ok(()) => Poll::Ready(()),
+                        Err(element) => {
+                            pending = Some(element);
+                            state.tx_waker.register(cx.waker());
+                            Poll::Pending
+                        }
+                    }
+                })
+            })
+        })
+        .await;
+    }
+
+    /// Receives an element from the queue (blocking).
+    ///
+    /// If the queue is empty, the current thread is suspended until an element has been sent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    pub fn recv(&self) -> T {
+        loop {
+            let result = critical_section::with(|cs| {
+                self.with_state(cs, |state| {
+                    let element = state.get(cs);
+                    if element.is_none() {
+                        state.receivers.put_current(cs, ThreadState::QueueRxBlocked);
+                    }
+                    element
+                })
+            });
+            // Once woken up, another receiver may have taken the element.
+            if let Some(element) = result {
+                return element;
+            }
+        }
+    }
+
+    /// Receives an element from the queue, waiting at most for `timeout` (blocking).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TimeoutError`] if the queue remained empty until the timeout.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, TimeoutError> {
+        self.recv_until(Instant::now().saturating_add(timeout))
+    }
+
+    /// Receives an element from the queue, waiting at most until `deadline` (blocking).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TimeoutError`] if the queue remained empty until the deadline.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called outside of a thread context.
+    pub fn recv_until(&self, deadline: Instant) -> Result<T, TimeoutError> {
+        let received = Cell::new(None);
+        loop {
+            // Safety: `on_timeout` takes care of removing the thread from the waiting receivers.
+            unsafe {
+                crate::timeout::with_deadline_check(
+                    deadline,
+                    |cs| {
+                        self.with_state(cs, |state| {
+                            let element = state.get(cs);
+                            let is_some = element.is_some();
+                            received.set(element);
+                            is_some
+                        })
+                    },
+                    |cs| {
+                        self.with_state(cs, |state| {
+                            state.receivers.put_current(cs, ThreadState::QueueRxBlocked);
+                        });
+                    },
+                    |cs| {
+                        self.with_state(cs, |state| {
+                            state.receivers.remove_current(cs);
+                        });
+                    },
+                );
+            }
+            if let Some(element) = received.take() {
+                return Ok(element);
+            }
+            if Instant::now() >= deadline {
+                return Err(TimeoutError);
+            }
+        }
+    }
+
+    /// Tries to receive an element from the queue (non-blocking).
+    ///
+    /// Returns `None` if the queue is empty.
+    /// This can be called from interrupt handlers.
+    pub fn try_recv(&self) -> Option<T> {
+        critical_section::with(|cs| self.with_state(cs, |state| state.get(cs)))
+    }
+
+    /// Receives an element from the queue, waiting asynchronously while it is empty.
+    pub async fn recv_async(&self) -> T {
+        poll_fn(|cx| {
+            critical_section::with(|cs| {
+                self.with_state(cs, |state| {
+                    if let Some(element) = state.get(cs) {
+                        Poll::Ready(element)
+                    } else {
+                        state.rx_waker.register(cx.waker());
+                        Poll::Pending
+                    }
+                })
+            })
+        })
+        .await
+    }
+
+    /// Returns the number of elements in the queue.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        critical_section::with(|cs| self.with_state(cs, |state| state.buffer.len()))
+    }
+
+    /// Returns `true` if the queue holds no element.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        critical_section::with(|cs| self.with_state(cs, |state| state.buffer.is_empty()))
+    }
+
+    /// Returns `true` if the queue is full.
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        critical_section::with(|cs| self.with_state(cs, |state| state.buffer.is_full()))
+    }
+
+    /// Returns the number of elements the queue can hold.
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+}
+
+impl<T: Send, const N: usize> Default for Queue<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -43,6 +43,8 @@ impl WaitQueue {
 
     /// Waits for this [`WaitQueue`] to be notified, with deadline (blocking).
     ///
+    /// Returns false if the deadline was reached before being notified.
+    ///
     /// # Panics
     ///
     /// Panics if this is called outside of a thread context.
@@ -29,6 +29,12 @@ pub struct Thread {
 
     /// Possibly set deadline.
     pub deadline: Option<u64>,
+    /// Absolute deadline for earliest-deadline-first scheduling, in ticks.
+    #[cfg(feature = "edf")]
+    pub sched_deadline: Option<u64>,
+    /// Whether `sched_deadline` has passed and was reported as missed.
+    #[cfg(feature = "edf")]
+    pub deadline_missed: bool,
 
     /// Lowest stack address.
     pub stack_lowest: usize,
@@ -49,7 +55,7 @@ pub enum ThreadState {
     Running,
     /// Suspended / paused.
     Parked,
-    /// Waiting to acquire a [`super::lock::Lock`].
+    /// Waiting to acquire a [`crate::sync::Lock`].
     LockBlocked,
     /// Waiting for [`ThreadFlags`] to be set.
     FlagBlocked(crate::thread_flags::WaitMode),
@@ -57,8 +63,14 @@ pub enum ThreadState {
     ChannelRxBlocked(usize),
     /// Waiting to send on a [`crate::sync::Channel`], i.e. waiting for the receiver.
     ChannelTxBlocked(usize),
-    /// Waiting for a [`super::wait_queue::WaitQueue`].
+    /// Waiting for a [`crate::sync::WaitQueue`].
     WaitQueueBlocked,
+    /// Waiting to receive on an empty [`crate::sync::Queue`].
+    QueueRxBlocked,
+    /// Waiting to send on a full [`crate::sync::Queue`].
+    QueueTxBlocked,
+    /// Waiting for a thread to terminate, see `JoinHandle::join()`.
+    JoinBlocked,
 }
 
 impl Thread {
@@ -73,6 +85,10 @@ impl Thread {
             #[cfg(feature = "core-affinity")]
             core_affinity: crate::CoreAffinity::no_affinity(),
             deadline: None,
+            #[cfg(feature = "edf")]
+            sched_deadline: None,
+            #[cfg(feature = "edf")]
+            deadline_missed: false,
             stack_highest: 0,
             stack_lowest: 0,
         }
@@ -1,5 +1,9 @@
 //! Thread flags.
-use crate::{SCHEDULER, Scheduler, ThreadId, ThreadState};
+use core::cell::Cell;
+
+use embassy_time::{Duration, Instant};
+
+use crate::{SCHEDULER, Scheduler, ThreadId, ThreadState, TimeoutError};
 
 /// Bitmask that represent the flags that are set for a thread.
 pub type ThreadFlags = u16;
@@ -72,6 +76,136 @@ pub fn wait_one(mask: ThreadFlags) -> ThreadFlags {
     }
 }
 
+/// Waits until all flags in `mask` are set for the current thread, at most for `timeout`.
+///
+/// Returns the set flags for this mask and clears them for the thread.
+///
+/// # Errors
+///
+/// Returns [`TimeoutError`] if the flags were not set before the timeout.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+pub fn wait_all_timeout(mask: ThreadFlags, timeout: Duration) -> Result<ThreadFlags, TimeoutError> {
+    wait_all_until(mask, Instant::now().saturating_add(timeout))
+}
+
+/// Waits until all flags in `mask` are set for the current thread, at most until `deadline`.
+///
+/// Returns the set flags for this mask and clears them for the thread.
+///
+/// # Errors
+///
+/// Returns [`TimeoutError`] if the flags were not set before the deadline.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+pub fn wait_all_until(mask: ThreadFlags, deadline: Instant) -> Result<ThreadFlags, TimeoutError> {
+    wait_until(deadline, WaitMode::All(mask), Scheduler::flag_take_all)
+}
+
+/// Waits until any flag in `mask` is set for the current thread, at most for `timeout`.
+///
+/// Returns all set flags for this mask and clears them for the thread.
+///
+/// # Errors
+///
+/// Returns [`TimeoutError`] if no flag was set before the timeout.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+pub fn wait_any_timeout(mask: ThreadFlags, timeout: Duration) -> Result<ThreadFlags, TimeoutError> {
+    wait_any_until(mask, Instant::now().saturating_add(timeout))
+}
+
+/// Waits until any flag in `mask` is set for the current thread, at most until `deadline`.
+///
+/// Returns all set flags for this mask and clears them for the thread.
+///
+/// # Errors
+///
+/// Returns [`TimeoutError`] if no flag was set before the deadline.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+pub fn wait_any_until(mask: ThreadFlags, deadline: Instant) -> Result<ThreadFlags, TimeoutError> {
+    wait_until(deadline, WaitMode::Any(mask), Scheduler::flag_take_any)
+}
+
+/// Waits until any flag in `mask` is set for the current thread, at most for `timeout`.
+///
+/// Compared to [`wait_any_timeout`], this returns and clears only one flag
+/// from the mask.
+///
+/// # Errors
+///
+/// Returns [`TimeoutError`] if no flag was set before the timeout.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+pub fn wait_one_timeout(mask: ThreadFlags, timeout: Duration) -> Result<ThreadFlags, TimeoutError> {
+    wait_one_until(mask, Instant::now().saturating_add(timeout))
+}
+
+/// Waits until any flag in `mask` is set for the current thread, at most until `deadline`.
+///
+/// Compared to [`wait_any_until`], this returns and clears only one flag
+/// from the mask.
+///
+/// # Errors
+///
+/// Returns [`TimeoutError`] if no flag was set before the deadline.
+///
+/// # Panics
+///
+/// Panics if this is called outside of a thread context.
+pub fn wait_one_until(mask: ThreadFlags, deadline: Instant) -> Result<ThreadFlags, TimeoutError> {
+    wait_until(deadline, WaitMode::Any(mask), Scheduler::flag_take_one)
+}
+
+/// Waits until `take` returns flags of the current thread, at most until `deadline`.
+///
+/// # Errors
+///
+/// Returns [`TimeoutError`] if `take` did not return flags before the deadline.
+fn wait_until(
+    deadline: Instant,
+    mode: WaitMode,
+    take: fn(&mut Scheduler, ThreadFlags) -> Option<ThreadFlags>,
+) -> Result<ThreadFlags, TimeoutError> {
+    let mask = match mode {
+        WaitMode::Any(mask) | WaitMode::All(mask) => mask,
+    };
+    loop {
+        let flags = Cell::new(None);
+        // Safety: a thread waiting for flags is not in any thread list, so that there is nothing
+        // to clean up on timeout.
+        unsafe {
+            crate::timeout::with_deadline_check(
+                deadline,
+                |cs| {
+                    flags
+                        .set(SCHEDULER.with_mut_cs(cs, |mut scheduler| take(&mut scheduler, mask)));
+                    flags.get().is_some()
+                },
+                |cs| SCHEDULER.with_mut_cs(cs, |mut scheduler| scheduler.flag_block(mode)),
+                |_| {},
+            );
+        }
+        if let Some(flags) = flags.get() {
+            return Ok(flags);
+        }
+        if Instant::now() >= deadline {
+            return Err(TimeoutError);
+        }
+    }
+}
+
 /// Clears flags for the current thread.
 ///
 /// # Panics
@@ -113,37 +247,72 @@ impl Scheduler {
     ///
     /// Panics if called outside a thread context.
     fn flag_wait_all(&mut self, mask: ThreadFlags) -> Option<ThreadFlags> {
+        let flags = self.flag_take_all(mask);
+        if flags.is_none() {
+            self.flag_block(WaitMode::All(mask));
+        }
+        flags
+    }
+
+    /// # Panics
+    ///
+    /// Panics if called outside a thread context.
+    fn flag_wait_any(&mut self, mask: ThreadFlags) -> Option<ThreadFlags> {
+        let flags = self.flag_take_any(mask);
+        if flags.is_none() {
+            self.flag_block(WaitMode::Any(mask));
+        }
+        flags
+    }
+
+    /// # Panics
+    ///
+    /// Panics if called outside a thread context.
+    fn flag_wait_one(&mut self, mask: ThreadFlags) -> Option<ThreadFlags> {
+        let flags = self.flag_take_one(mask);
+        if flags.is_none() {
+            self.flag_block(WaitMode::Any(mask));
+        }
+        flags
+    }
+
+    /// Clears and returns the flags in `mask` if all of them are set for the current thread.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside a thread context.
+    fn flag_take_all(&mut self, mask: ThreadFlags) -> Option<ThreadFlags> {
         let thread = self.current().unwrap();
         if thread.flags & mask == mask {
             thread.flags &= !mask;
             Some(mask)
         } else {
-            let thread_id = thread.tid;
-            self.set_state(thread_id, ThreadState::FlagBlocked(WaitMode::All(mask)));
             None
         }
     }
 
+    /// Clears and returns the flags in `mask` that are set for the current thread, if any.
+    ///
     /// # Panics
     ///
     /// Panics if called outside a thread context.
-    fn flag_wait_any(&mut self, mask: ThreadFlags) -> Option<ThreadFlags> {
+    fn flag_take_any(&mut self, mask: ThreadFlags) -> Option<ThreadFlags> {
         let thread = self.current().unwrap();
         if thread.flags & mask != 0 {
             let res = thread.flags & mask;
             thread.flags &= !res;
             Some(res)
         } else {
-            let thread_id = thread.tid;
-            self.set_state(thread_id, ThreadState::FlagBlocked(WaitMode::Any(mask)));
             None
         }
     }
 
+    /// Clears and returns one of the flags in `mask` that are set for the current thread, if any.
+    ///
     /// # Panics
     ///
     /// Panics if called outside a thread context.
-    fn flag_wait_one(&mut self, mask: ThreadFlags) -> Option<ThreadFlags> {
+    fn flag_take_one(&mut self, mask: ThreadFlags) -> Option<ThreadFlags> {
         let thread = self.current().unwrap();
         if thread.flags & mask != 0 {
             let mut res = thread.flags & mask;
@@ -152,9 +321,17 @@ impl Scheduler {
             thread.flags &= !res;
             Some(res)
         } else {
-            let thread_id = thread.tid;
-            self.set_state(thread_id, ThreadState::FlagBlocked(WaitMode::Any(mask)));
             None
         }
     }
+
+    /// Blocks the current thread until flags matching `mode` are set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside a thread context.
+    fn flag_block(&mut self, mode: WaitMode) {
+        let thread_id = self.current_tid().unwrap();
+        self.set_state(thread_id, ThreadState::FlagBlocked(mode));
+    }
 }
@@ -104,6 +104,11 @@ impl ThreadList {
         })
     }
 
+    /// Returns the head of this [`ThreadList`], i.e., the waiter with the highest priority.
+    pub(crate) fn peek(&self, _cs: CriticalSection<'_>) -> Option<ThreadId> {
+        self.head
+    }
+
     /// Determines if this [`ThreadList`] is empty.
     pub fn is_empty(&self, _cs: CriticalSection<'_>) -> bool {
         self.head.is_none()
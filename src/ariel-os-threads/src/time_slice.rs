@@ -0,0 +1,349 @@
+//! Provides optional round-robin time slicing among threads of the same priority.
+//!
+//! When time slicing is enabled for a priority, a running thread of that priority is preempted
+//! once its time slice has elapsed if other threads of the same priority are ready, and moved to
+//! the tail of its runqueue.
+//! The end of time slices is signaled by the `embassy-time` driver, i.e., by the system timer
+//! interrupt, and only while threads compete for the CPU.
+//!
+//! On native, where each thread runs in its own host thread, this is emulated: the threads of a
+//! priority with time slicing take turns, only one of them running at a time, and are switched at
+//! the end of each time slice, also signaled by the `embassy-time` driver.
+//! As host threads cannot be preempted, a thread whose time slice has elapsed is only suspended
+//! once it leaves a critical section, e.g., when calling into this crate.
+
+use core::task::{RawWaker, RawWakerVTable, Waker};
+
+use embassy_time::Duration;
+#[cfg(any(feature = "single-core", feature = "multi-core"))]
+use portable_atomic::{AtomicBool, Ordering};
+
+use crate::{RunqueueId, SCHED_PRIO_LEVELS, SCHEDULER, Scheduler};
+#[cfg(feature = "infini-core")]
+use crate::{
+    THREAD_COUNT, ThreadId, ThreadState,
+    arch::{Arch, Cpu},
+};
+
+/// Default time slice of threads (in microseconds).
+const DEFAULT_TIME_SLICE_US: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_THREAD_TIME_SLICE_US",
+    10_000,
+    "default time slice of threads (in microseconds)"
+);
+
+/// Set when the alarm of the current time slice has fired, until handled by the scheduler.
+#[cfg(any(feature = "single-core", feature = "multi-core"))]
+static EXPIRED: AtomicBool = AtomicBool::new(false);
+
+/// Time slicing state of the scheduler.
+pub(crate) struct TimeSlices {
+    /// Time slice of each priority (in ticks), `None` if time slicing is disabled for it.
+    quanta: [Option<u64>; SCHED_PRIO_LEVELS],
+    /// Priority and end (in ticks) of the current time slice.
+    #[cfg(any(feature = "single-core", feature = "multi-core"))]
+    current: Option<(RunqueueId, u64)>,
+    /// On native, thread running for each priority, and end (in ticks) of its time slice.
+    #[cfg(feature = "infini-core")]
+    holders: [Option<(ThreadId, u64)>; SCHED_PRIO_LEVELS],
+}
+
+impl TimeSlices {
+    pub(crate) const fn new() -> Self {
+        let quantum = Duration::from_micros(DEFAULT_TIME_SLICE_US as u64).as_ticks();
+        #[allow(unused_mut, reason = "only mutated with idle threads")]
+        let mut quanta = [Some(if quantum == 0 { 1 } else { quantum }); SCHED_PRIO_LEVELS];
+        // Idle threads have the lowest priority and must not keep the timer running.
+        #[cfg(feature = "idle-threads")]
+        {
+            quanta[0] = None;
+        }
+        Self {
+            quanta,
+            #[cfg(any(feature = "single-core", feature = "multi-core"))]
+            current: None,
+            #[cfg(feature = "infini-core")]
+            holders: [None; SCHED_PRIO_LEVELS],
+        }
+    }
+}
+
+#[cfg(any(feature = "single-core", feature = "multi-core"))]
+impl Scheduler {
+    /// Ends the current time slice if it has elapsed, moving the running thread to the tail of
+    /// its runqueue.
+    ///
+    /// Must be called by the scheduler before picking the next thread.
+    pub(crate) fn end_time_slice(&mut self) {
+        if !EXPIRED.swap(false, Ordering::Relaxed) {
+            return;
+        }
+        #[allow(unused_variables, reason = "prio only used on single-core")]
+        let Some((prio, end)) = self.time_slices.current else {
+            return;
+        };
+        if embassy_time_driver::now() < end {
+            // The alarm was set for an earlier time slice.
+            set_alarm(end);
+            return;
+        }
+        self.time_slices.current = None;
+
+        // On multi-core, the running thread is re-added at the tail of its runqueue by the
+        // scheduler anyway.
+        #[cfg(feature = "single-core")]
+        self.rotate_runqueue(prio);
+    }
+
+    /// Starts a time slice if ready threads of the same priority compete for the CPU, and stops
+    /// it otherwise.
+    ///
+    /// Must be called whenever the runqueue changes.
+    pub(crate) fn update_time_slice(&mut self) {
+        let Some((prio, quantum)) = self.contended_prio() else {
+            self.time_slices.current = None;
+            return;
+        };
+        if self
+            .time_slices
+            .current
+            .is_some_and(|(current, _)| current == prio)
+        {
+            return;
+        }
+        let end = embassy_time_driver::now().saturating_add(quantum);
+        self.time_slices.current = Some((prio, end));
+        set_alarm(end);
+    }
+
+    /// Returns the priority whose threads compete for the CPU and its time slice, if time slicing
+    /// is enabled for it.
+    fn contended_prio(&mut self) -> Option<(RunqueueId, u64)> {
+        // On single-core, the head of the highest-priority runqueue is running, and the other
+        // threads of that runqueue are waiting.
+        #[cfg(feature = "single-core")]
+        let prio = {
+            let (head, prio) = self.runqueue.get_next_with_rq()?;
+            self.runqueue
+                .iter_from(head, prio)
+                .next()
+                .filter(|next| self.get_unchecked(*next).prio == prio)?;
+            prio
+        };
+
+        // On multi-core, running threads are not in the runqueue, so that any thread in it is
+        // waiting for a core.
+        #[cfg(feature = "multi-core")]
+        let prio = (0..SCHED_PRIO_LEVELS)
+            .rev()
+            .map(|prio| RunqueueId::new(prio as u8))
+            .find(|prio| {
+                self.time_slices.quanta[usize::from(*prio)].is_some()
+                    && !self.runqueue.is_empty(*prio)
+            })?;
+
+        self.time_slices.quanta[usize::from(prio)].map(|quantum| (prio, quantum))
+    }
+}
+
+#[cfg(feature = "infini-core")]
+impl Scheduler {
+    /// Returns whether a thread may run, i.e., has the time slice of its priority, or time
+    /// slicing is disabled for it.
+    pub(crate) fn has_time_slice(&self, thread_id: ThreadId) -> bool {
+        let prio = usize::from(self.get_unchecked(thread_id).prio);
+        self.time_slices.quanta[prio].is_none()
+            || self.time_slices.holders[prio].is_none_or(|(holder, _)| holder == thread_id)
+    }
+
+    /// Gives the time slice of priority `prio` to a thread that starts running, unless another
+    /// thread has it.
+    ///
+    /// Returns whether the thread may run.
+    pub(crate) fn acquire_time_slice(&mut self, thread_id: ThreadId, prio: RunqueueId) -> bool {
+        let Some(quantum) = self.time_slices.quanta[usize::from(prio)] else {
+            return true;
+        };
+        match self.time_slices.holders[usize::from(prio)] {
+            Some((holder, _)) => holder == thread_id,
+            None => {
+                self.start_time_slice(thread_id, prio, quantum);
+                true
+            }
+        }
+    }
+
+    /// Passes the time slice of a thread that stopped running to the next thread of the same
+    /// priority.
+    pub(crate) fn release_time_slice(&mut self, thread_id: ThreadId, prio: RunqueueId) {
+        if self.time_slices.holders[usize::from(prio)]
+            .is_some_and(|(holder, _)| holder == thread_id)
+        {
+            self.time_slices.holders[usize::from(prio)] = None;
+            self.pass_time_slice(thread_id, prio);
+        }
+    }
+
+    /// Ends the time slices that have elapsed, suspending their threads and resuming the next
+    /// threads of the same priorities.
+    fn end_time_slices(&mut self) {
+        let now = embassy_time_driver::now();
+        for prio in 0..SCHED_PRIO_LEVELS {
+            let Some((holder, end)) = self.time_slices.holders[prio] else {
+                continue;
+            };
+            if now < end {
+                // The alarm was set for another time slice.
+                set_alarm(end);
+                continue;
+            }
+            self.time_slices.holders[prio] = None;
+            self.pass_time_slice(holder, RunqueueId::new(prio as u8));
+        }
+    }
+
+    /// Gives the time slice of priority `prio` to the running thread following `thread_id`,
+    /// which may be `thread_id` itself, and suspends `thread_id` otherwise.
+    fn pass_time_slice(&mut self, thread_id: ThreadId, prio: RunqueueId) {
+        let Some(quantum) = self.time_slices.quanta[usize::from(prio)] else {
+            return;
+        };
+        let Some(next) = (1..=THREAD_COUNT)
+            .map(|offset| ThreadId::new(((usize::from(thread_id) + offset) % THREAD_COUNT) as u8))
+            .find(|next| {
+                let thread = self.get_unchecked(*next);
+                thread.state == ThreadState::Running && thread.prio == prio
+            })
+        else {
+            return;
+        };
+        self.start_time_slice(next, prio, quantum);
+        if next != thread_id {
+            Cpu::set_stopped(thread_id);
+            Cpu::set_running(next);
+        }
+    }
+
+    /// Starts a time slice of priority `prio` for a thread.
+    fn start_time_slice(&mut self, thread_id: ThreadId, prio: RunqueueId, quantum: u64) {
+        let end = embassy_time_driver::now().saturating_add(quantum);
+        self.time_slices.holders[usize::from(prio)] = Some((thread_id, end));
+        set_alarm(end);
+    }
+
+    /// Restarts time slicing of the running threads of priority `prio`, after its time slice
+    /// changed.
+    fn restart_time_slice(&mut self, prio: RunqueueId) {
+        self.time_slices.holders[usize::from(prio)] = None;
+        for thread_id in (0..THREAD_COUNT).map(|i| ThreadId::new(i as u8)) {
+            let thread = self.get_unchecked(thread_id);
+            if thread.state != ThreadState::Running || thread.prio != prio {
+                continue;
+            }
+            if self.acquire_time_slice(thread_id, prio) {
+                Cpu::set_running(thread_id);
+            } else {
+                Cpu::set_stopped(thread_id);
+            }
+        }
+    }
+}
+
+/// Signals the end of a time slice, called by the timer driver.
+///
+/// The driver may call this while the scheduler is borrowed, so this must not access it.
+#[cfg(any(feature = "single-core", feature = "multi-core"))]
+fn wake(_ptr: *const ()) {
+    EXPIRED.store(true, Ordering::Relaxed);
+
+    #[cfg(not(feature = "multi-core"))]
+    crate::schedule();
+
+    #[cfg(feature = "multi-core")]
+    for core in 0..crate::CORE_COUNT {
+        crate::schedule_on_core(crate::CoreId(core as u8));
+    }
+}
+
+/// Host thread ending the time slices on native, see [`wake()`].
+#[cfg(feature = "infini-core")]
+static TICKER: std::sync::OnceLock<std::thread::Thread> = std::sync::OnceLock::new();
+
+/// Signals the end of a time slice, called by the timer driver.
+///
+/// On native, the driver calls this while holding its lock, so that neither the timer nor the
+/// scheduler can be accessed here; the time slices are ended by a dedicated host thread instead.
+#[cfg(feature = "infini-core")]
+fn wake(_ptr: *const ()) {
+    if let Some(ticker) = TICKER.get() {
+        ticker.unpark();
+    }
+}
+
+/// Ends the time slices each time it is woken up by [`wake()`].
+#[cfg(feature = "infini-core")]
+fn ticker() -> ! {
+    loop {
+        std::thread::park();
+        SCHEDULER.with_mut(|mut scheduler| scheduler.end_time_slices());
+    }
+}
+
+static VTABLE: RawWakerVTable = RawWakerVTable::new(
+    // clone
+    |ptr| RawWaker::new(ptr, &VTABLE),
+    wake,
+    wake,
+    |_ptr| {},
+);
+
+/// Sets the timer alarm for the end of the current time slice.
+fn set_alarm(end: u64) {
+    #[cfg(feature = "infini-core")]
+    TICKER.get_or_init(|| std::thread::spawn(ticker).thread().clone());
+
+    let raw_waker = RawWaker::new(core::ptr::null(), &VTABLE);
+    // SAFETY: the vtable functions do not use the data pointer.
+    let waker = unsafe { Waker::from_raw(raw_waker) };
+    embassy_time_driver::schedule_wake(end, &waker);
+}
+
+/// Sets the time slice of threads with priority `prio`.
+///
+/// With `None`, time slicing is disabled for these threads, which are then scheduled
+/// cooperatively (see [`yield_same()`](crate::yield_same)).
+/// By default, all priorities use the time slice set by the `CONFIG_THREAD_TIME_SLICE_US`
+/// environment variable (10 ms if unset).
+///
+/// On native, threads of priority `prio` take turns only while time slicing is enabled for them,
+/// and run in parallel otherwise.
+///
+/// # Panics
+///
+/// Panics if `prio` is not lower than [`SCHED_PRIO_LEVELS`].
+pub fn set_time_slice(prio: RunqueueId, time_slice: Option<Duration>) {
+    SCHEDULER.with_mut(|mut scheduler| {
+        scheduler.time_slices.quanta[usize::from(prio)] =
+            time_slice.map(|time_slice| time_slice.as_ticks().max(1));
+        // Restart the current time slice with the new settings.
+        #[cfg(any(feature = "single-core", feature = "multi-core"))]
+        {
+            scheduler.time_slices.current = None;
+            scheduler.update_time_slice();
+        }
+        #[cfg(feature = "infini-core")]
+        scheduler.restart_time_slice(prio);
+    });
+}
+
+/// Returns the time slice of threads with priority `prio`, or `None` if time slicing is disabled
+/// for them.
+///
+/// # Panics
+///
+/// Panics if `prio` is not lower than [`SCHED_PRIO_LEVELS`].
+#[must_use]
+pub fn time_slice(prio: RunqueueId) -> Option<Duration> {
+    SCHEDULER
+        .with(|scheduler| scheduler.time_slices.quanta[usize::from(prio)].map(Duration::from_ticks))
+}
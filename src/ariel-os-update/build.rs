@@ -0,0 +1,73 @@
+use std::{env, path::PathBuf};
+
+use ariel_os_buildutils::context;
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=CONFIG_UPDATE_PRIMARY_SLOT_OFFSET");
+    println!("cargo:rerun-if-env-changed=CONFIG_UPDATE_SECONDARY_SLOT_OFFSET");
+    println!("cargo:rerun-if-env-changed=CONFIG_UPDATE_SLOT_SIZE");
+    println!("cargo:rerun-if-env-changed=CONFIG_UPDATE_PUBLIC_KEY");
+
+    let (primary, secondary, size, public_key) = if context("ariel-os") {
+        (
+            config("CONFIG_UPDATE_PRIMARY_SLOT_OFFSET"),
+            config("CONFIG_UPDATE_SECONDARY_SLOT_OFFSET"),
+            config("CONFIG_UPDATE_SLOT_SIZE"),
+            public_key(),
+        )
+    } else {
+        // Dummy values for platform-independent tooling.
+        (0x1_0000, 0x8_0000, 0x7_0000, "00".repeat(32))
+    };
+
+    let slots = format!(
+        "const PRIMARY_SLOT: core::ops::Range<u32> = {primary:#x}..{:#x};\n\
+         const SECONDARY_SLOT: core::ops::Range<u32> = {secondary:#x}..{:#x};\n\
+         const PUBLIC_KEY: &str = \"{public_key}\";\n",
+        primary + size,
+        secondary + size,
+    );
+
+    let out = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    std::fs::write(out.join("slots.rs"), slots).unwrap();
+}
+
+/// Reads a flash offset or size, in decimal or prefixed with `0x` in hexadecimal.
+fn config(name: &str) -> u32 {
+    let Ok(value) = env::var(name) else {
+        panic!("`{name}` must be set according to the MCUboot flash layout");
+    };
+
+    let parsed = match value.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(&hex.replace('_', ""), 16),
+        None => value.replace('_', "").parse(),
+    };
+
+    parsed.unwrap_or_else(|_| panic!("could not parse `{name}={value}`"))
+}
+
+/// Reads the public key, which is required as images could not be verified otherwise.
+fn public_key() -> String {
+    let Ok(key) = env::var("CONFIG_UPDATE_PUBLIC_KEY") else {
+        panic!("`CONFIG_UPDATE_PUBLIC_KEY` must be set to the public key images are signed with");
+    };
+
+    let feature = match key.len() {
+        64 => "ED25519",
+        130 => "ECDSA_P256",
+        _ => panic!(
+            "`CONFIG_UPDATE_PUBLIC_KEY` must be a 32-byte Ed25519 or 65-byte ECDSA P-256 key in hexadecimal"
+        ),
+    };
+    assert!(
+        key.bytes().all(|b| b.is_ascii_hexdigit()),
+        "`CONFIG_UPDATE_PUBLIC_KEY` must be in hexadecimal"
+    );
+    assert!(
+        env::var_os(format!("CARGO_FEATURE_{feature}")).is_some(),
+        "the `{}` feature is required for the type of `CONFIG_UPDATE_PUBLIC_KEY`",
+        feature.to_lowercase().replace('_', "-")
+    );
+
+    key
+}
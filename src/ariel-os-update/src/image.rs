@@ -0,0 +1,277 @@
+//! Parsing and verification of MCUboot images.
+//!
+//! An image consists of a header, the firmware itself, then an area of protected TLVs (covered by
+//! the hash) and an area of unprotected TLVs (holding the hash and the signature).
+
+#![expect(
+    clippy::cast_possible_truncation,
+    reason = "flash offsets and lengths fit into a u32"
+)]
+#![expect(clippy::missing_errors_doc)]
+
+use embedded_storage_async::nor_flash::ReadNorFlash;
+use sha2::{Digest as _, Sha256};
+
+use crate::{Error, PublicKey};
+
+const IMAGE_MAGIC: u32 = 0x96f3_b83d;
+pub(crate) const HEADER_LEN: usize = 32;
+
+const TLV_INFO_MAGIC: u16 = 0x6907;
+const TLV_INFO_LEN: u32 = 4;
+const TLV_HEADER_LEN: u32 = 4;
+
+const TLV_SHA256: u16 = 0x10;
+#[cfg(feature = "ecdsa-p256")]
+const TLV_ECDSA_SIG: u16 = 0x22;
+#[cfg(feature = "ed25519")]
+const TLV_ED25519: u16 = 0x24;
+
+const HASH_LEN: usize = 32;
+// Large enough for any supported signature (a DER-encoded ECDSA P-256 signature is at most 72
+// bytes long).
+const MAX_SIGNATURE_LEN: usize = 72;
+
+const CHUNK_LEN: u32 = 256;
+
+/// Version of an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ImageVersion {
+    /// Major version.
+    pub major: u8,
+    /// Minor version.
+    pub minor: u8,
+    /// Revision.
+    pub revision: u16,
+    /// Build number.
+    pub build: u32,
+}
+
+impl core::fmt::Display for ImageVersion {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}+{}",
+            self.major, self.minor, self.revision, self.build
+        )
+    }
+}
+
+/// Information about a verified image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub struct ImageInfo {
+    /// Version of the image.
+    pub version: ImageVersion,
+    /// Size of the image including its header and TLVs, in bytes.
+    pub size: u32,
+}
+
+/// Header of an MCUboot image.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ImageHeader {
+    header_len: u16,
+    protected_tlvs_len: u16,
+    firmware_len: u32,
+    version: ImageVersion,
+}
+
+impl ImageHeader {
+    pub(crate) fn parse(header: &[u8; HEADER_LEN]) -> Option<Self> {
+        let u16_at = |offset: usize| {
+            Some(u16::from_le_bytes(
+                header.get(offset..offset + 2)?.try_into().ok()?,
+            ))
+        };
+        let u32_at = |offset: usize| {
+            Some(u32::from_le_bytes(
+                header.get(offset..offset + 4)?.try_into().ok()?,
+            ))
+        };
+
+        if u32_at(0)? != IMAGE_MAGIC {
+            return None;
+        }
+
+        let header = Self {
+            header_len: u16_at(8)?,
+            protected_tlvs_len: u16_at(10)?,
+            firmware_len: u32_at(12)?,
+            version: ImageVersion {
+                major: *header.get(20)?,
+                minor: *header.get(21)?,
+                revision: u16_at(22)?,
+                build: u32_at(24)?,
+            },
+        };
+
+        if usize::from(header.header_len) < HEADER_LEN {
+            return None;
+        }
+
+        Some(header)
+    }
+
+    /// Returns the length of the data covered by the hash, i.e., up to the unprotected TLVs.
+    fn hashed_len(&self) -> Option<u32> {
+        u32::from(self.header_len)
+            .checked_add(self.firmware_len)?
+            .checked_add(u32::from(self.protected_tlvs_len))
+    }
+}
+
+// Verifies the image starting at `offset` in `flash`, which must be at most `max_len` long.
+//
+// The image is accepted if its hash matches, and it is signed by any of `keys`.
+pub(crate) async fn verify<F: ReadNorFlash>(
+    flash: &mut F,
+    offset: u32,
+    max_len: u32,
+    keys: &[PublicKey],
+) -> Result<ImageInfo, Error<F::Error>> {
+    let mut header = [0; HEADER_LEN];
+    read(flash, offset, &mut header).await?;
+    let header = ImageHeader::parse(&header).ok_or(Error::InvalidImage)?;
+
+    let hashed_len = header
+        .hashed_len()
+        .filter(|len| *len <= max_len)
+        .ok_or(Error::InvalidImage)?;
+    let hash = hash(flash, offset, hashed_len).await?;
+
+    // Unprotected TLVs.
+    let tlvs_offset = offset + hashed_len;
+    let mut info = [0; TLV_INFO_LEN as usize];
+    read(flash, tlvs_offset, &mut info).await?;
+    let [magic_0, magic_1, len_0, len_1] = info;
+    if u16::from_le_bytes([magic_0, magic_1]) != TLV_INFO_MAGIC {
+        return Err(Error::InvalidImage);
+    }
+    let tlvs_len = u32::from(u16::from_le_bytes([len_0, len_1]));
+
+    let size = hashed_len
+        .checked_add(tlvs_len)
+        .filter(|size| *size <= max_len)
+        .ok_or(Error::InvalidImage)?;
+
+    let mut hash_matches = false;
+    let mut signature_verified = false;
+
+    let mut tlv_offset = tlvs_offset + TLV_INFO_LEN;
+    while tlv_offset < tlvs_offset + tlvs_len {
+        let mut tlv_header = [0; TLV_HEADER_LEN as usize];
+        read(flash, tlv_offset, &mut tlv_header).await?;
+        let [type_0, type_1, len_0, len_1] = tlv_header;
+        let tlv_type = u16::from_le_bytes([type_0, type_1]);
+        let value_len = u16::from_le_bytes([len_0, len_1]);
+
+        let value_offset = tlv_offset + TLV_HEADER_LEN;
+        tlv_offset = value_offset + u32::from(value_len);
+        if tlv_offset > tlvs_offset + tlvs_len {
+            return Err(Error::InvalidImage);
+        }
+
+        let mut value = [0; MAX_SIGNATURE_LEN];
+        let Some(value) = value.get_mut(..usize::from(value_len)) else {
+            // No TLV that needs to be checked is this long.
+            continue;
+        };
+
+        match tlv_type {
+            TLV_SHA256 => {
+                read(flash, value_offset, value).await?;
+                if value != hash.as_slice() {
+                    return Err(Error::HashMismatch);
+                }
+                hash_matches = true;
+            }
+            #[cfg(feature = "ed25519")]
+            TLV_ED25519 => {
+                read(flash, value_offset, value).await?;
+                signature_verified |= keys.iter().any(|key| key.verify_ed25519(&hash, value));
+            }
+            #[cfg(feature = "ecdsa-p256")]
+            TLV_ECDSA_SIG => {
+                read(flash, value_offset, value).await?;
+                signature_verified |= keys.iter().any(|key| key.verify_ecdsa_p256(&hash, value));
+            }
+            _ => {}
+        }
+    }
+
+    if !hash_matches {
+        return Err(Error::HashMismatch);
+    }
+    if !signature_verified {
+        return Err(Error::InvalidSignature);
+    }
+
+    Ok(ImageInfo {
+        version: header.version,
+        size,
+    })
+}
+
+async fn hash<F: ReadNorFlash>(
+    flash: &mut F,
+    offset: u32,
+    len: u32,
+) -> Result<[u8; HASH_LEN], Error<F::Error>> {
+    let mut hasher = Sha256::new();
+    let mut chunk = [0; CHUNK_LEN as usize];
+
+    let mut done = 0;
+    while done < len {
+        let chunk_len = (len - done).min(CHUNK_LEN);
+        let chunk = chunk
+            .get_mut(..chunk_len as usize)
+            .ok_or(Error::InvalidImage)?;
+        read(flash, offset + done, chunk).await?;
+        hasher.update(&*chunk);
+        done += chunk_len;
+    }
+
+    Ok(hasher.finalize().into())
+}
+
+// Reads `buf.len()` bytes at `offset`, honoring the read alignment of the flash.
+pub(crate) async fn read<F: ReadNorFlash>(
+    flash: &mut F,
+    offset: u32,
+    buf: &mut [u8],
+) -> Result<(), Error<F::Error>> {
+    if F::READ_SIZE == 1 {
+        return flash.read(offset, buf).await.map_err(Error::Flash);
+    }
+
+    // Read through an aligned bounce buffer.
+    let mut bounce = [0; CHUNK_LEN as usize];
+    let mut done = 0;
+    while done < buf.len() {
+        let position = offset + done as u32;
+        let skip = position as usize % F::READ_SIZE;
+        let len = (skip + buf.len() - done)
+            .next_multiple_of(F::READ_SIZE)
+            .min(bounce.len() - bounce.len() % F::READ_SIZE);
+
+        let bounce = bounce.get_mut(..len).ok_or(Error::InvalidImage)?;
+        flash
+            .read(position - skip as u32, bounce)
+            .await
+            .map_err(Error::Flash)?;
+
+        let copied = (len - skip).min(buf.len() - done);
+        let (Some(dst), Some(src)) = (
+            buf.get_mut(done..done + copied),
+            bounce.get(skip..skip + copied),
+        ) else {
+            return Err(Error::InvalidImage);
+        };
+        dst.copy_from_slice(src);
+        done += copied;
+    }
+
+    Ok(())
+}
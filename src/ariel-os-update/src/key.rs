@@ -0,0 +1,95 @@
+//! Public keys verifying the signature of images.
+
+/// Public key an image may be signed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PublicKey {
+    /// Ed25519 public key.
+    #[cfg(feature = "ed25519")]
+    Ed25519([u8; 32]),
+    /// ECDSA P-256 public key, as an uncompressed SEC1 point.
+    #[cfg(feature = "ecdsa-p256")]
+    EcdsaP256([u8; 65]),
+}
+
+impl PublicKey {
+    /// Decodes a raw public key from its hexadecimal representation.
+    ///
+    /// The type of the key is inferred from its length: 32 bytes for Ed25519, and 65 bytes for an
+    /// uncompressed ECDSA P-256 point.
+    ///
+    /// Returns `None` if `hex` is not the representation of a supported key.
+    #[must_use]
+    pub fn from_hex(hex: &str) -> Option<Self> {
+        if !hex.len().is_multiple_of(2) {
+            return None;
+        }
+        let len = hex.len() / 2;
+
+        let mut bytes = [0; 65];
+        for (byte, digits) in bytes.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+            let [high, low] = digits else {
+                return None;
+            };
+            *byte = (hex_digit(*high)? << 4) | hex_digit(*low)?;
+        }
+
+        match len {
+            #[cfg(feature = "ed25519")]
+            32 => Some(Self::Ed25519(bytes.first_chunk().copied()?)),
+            #[cfg(feature = "ecdsa-p256")]
+            65 => Some(Self::EcdsaP256(bytes)),
+            _ => None,
+        }
+    }
+
+    #[cfg(feature = "ed25519")]
+    pub(crate) fn verify_ed25519(&self, hash: &[u8; 32], signature: &[u8]) -> bool {
+        #[cfg_attr(
+            not(feature = "ecdsa-p256"),
+            expect(irrefutable_let_patterns, reason = "single key type")
+        )]
+        let Self::Ed25519(key) = self else {
+            return false;
+        };
+        let Ok(key) = ed25519_dalek::VerifyingKey::from_bytes(key) else {
+            return false;
+        };
+        let Ok(signature) = ed25519_dalek::Signature::from_slice(signature) else {
+            return false;
+        };
+
+        // MCUboot signs the hash of the image rather than the image itself.
+        key.verify_strict(hash, &signature).is_ok()
+    }
+
+    #[cfg(feature = "ecdsa-p256")]
+    pub(crate) fn verify_ecdsa_p256(&self, hash: &[u8; 32], signature: &[u8]) -> bool {
+        use p256::ecdsa::signature::hazmat::PrehashVerifier as _;
+
+        #[cfg_attr(
+            not(feature = "ed25519"),
+            expect(irrefutable_let_patterns, reason = "single key type")
+        )]
+        let Self::EcdsaP256(key) = self else {
+            return false;
+        };
+        let Ok(key) = p256::ecdsa::VerifyingKey::from_sec1_bytes(key) else {
+            return false;
+        };
+        let Ok(signature) = p256::ecdsa::Signature::from_der(signature) else {
+            return false;
+        };
+
+        key.verify_prehash(hash, &signature).is_ok()
+    }
+}
+
+fn hex_digit(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        _ => None,
+    }
+}
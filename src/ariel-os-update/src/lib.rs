@@ -0,0 +1,259 @@
+//! Provides firmware updates through the [MCUboot] bootloader.
+//!
+//! A new image is written to the secondary slot of MCUboot, is verified, and then marked to be
+//! booted for testing after the next reset.
+//! Once running, the new image needs to be [confirmed](confirm()), e.g., after a successful
+//! self-test; otherwise MCUboot reverts to the previous image after the following reset, which can
+//! also be triggered right away with [`rollback()`].
+//!
+//! This is agnostic of the transport of the image, which is passed in chunks, e.g., as received
+//! from a CoAP block-wise transfer, USB, or UART.
+//!
+//! The functions of this module use the flash of the system storage and the slots configured at
+//! build time; [`Slots`] can be used directly for other flash layouts or other flash drivers.
+//!
+//! # Images
+//!
+//! Images must be signed with MCUboot's `imgtool`, using an Ed25519 or ECDSA P-256 key and a
+//! SHA-256 hash, e.g.:
+//!
+//! ```text
+//! imgtool sign --key key.pem --version 1.2.3 --header-size 0x200 --pad-header \
+//!     --align 8 --slot-size 0x70000 app.bin app-signed.bin
+//! ```
+//!
+//! The last erase sector of the secondary slot is reserved for the trailer of MCUboot, and cannot
+//! be used by the image.
+//!
+//! # Configuration
+//!
+//! The following environment variables are read at build time:
+//!
+//! | Variable                              | Description                                            |
+//! | --                                    | --                                                     |
+//! | `CONFIG_UPDATE_PRIMARY_SLOT_OFFSET`   | Offset of the primary slot in flash                    |
+//! | `CONFIG_UPDATE_SECONDARY_SLOT_OFFSET` | Offset of the secondary slot in flash                  |
+//! | `CONFIG_UPDATE_SLOT_SIZE`             | Size of each slot                                      |
+//! | `CONFIG_UPDATE_PUBLIC_KEY`            | Hexadecimal raw public key images must be signed with  |
+//!
+//! Offsets and sizes must match the flash layout of MCUboot, and can be given in decimal or in
+//! hexadecimal prefixed with `0x`.
+//! The public key is either a 32-byte Ed25519 key or a 65-byte uncompressed ECDSA P-256 point.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! use ariel_os::update::{self, BootState};
+//!
+//! if update::boot_state().await? == BootState::Testing {
+//!     if self_test().await {
+//!         update::confirm().await?;
+//!     } else {
+//!         update::rollback().await?;
+//!     }
+//! }
+//! ```
+//!
+//! [MCUboot]: https://docs.mcuboot.com/
+
+#![cfg_attr(not(test), no_std)]
+#![deny(missing_docs)]
+
+#[cfg(not(any(feature = "ed25519", feature = "ecdsa-p256")))]
+compile_error!("at least one of the `ed25519` and `ecdsa-p256` features must be enabled");
+
+mod image;
+mod key;
+mod slots;
+
+use core::convert::Infallible;
+
+use ariel_os_hal::hal::storage::FlashError;
+use ariel_os_log::info;
+use ariel_os_storage::{SharedFlash, Storage};
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, mutex::MutexGuard};
+
+pub use image::{ImageInfo, ImageVersion};
+pub use key::PublicKey;
+pub use slots::{BootState, SlotWriter, Slots};
+
+// Defines `PRIMARY_SLOT`, `SECONDARY_SLOT`, and `PUBLIC_KEY`, checked at build time.
+include!(concat!(env!("OUT_DIR"), "/slots.rs"));
+
+const SLOTS: Slots = Slots {
+    primary: PRIMARY_SLOT,
+    secondary: SECONDARY_SLOT,
+};
+
+/// Error returned by updates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error<E> {
+    /// Accessing the flash failed.
+    Flash(E),
+    /// The image does not fit into the secondary slot.
+    ImageTooLarge,
+    /// The image is not a valid MCUboot image, or is incomplete.
+    InvalidImage,
+    /// The hash of the image does not match the one it contains.
+    HashMismatch,
+    /// The image is not signed by any of the accepted keys.
+    InvalidSignature,
+    /// The configured public key is invalid.
+    InvalidKey,
+    /// The running image is not being tested.
+    NotTesting,
+    /// The configured slots overlap a storage partition, queue or raw partition.
+    SlotsOverlapStorage,
+}
+
+impl<E: core::fmt::Debug> core::fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Flash(err) => write!(f, "flash error: {err:?}"),
+            Self::ImageTooLarge => write!(f, "image too large"),
+            Self::InvalidImage => write!(f, "invalid image"),
+            Self::HashMismatch => write!(f, "hash mismatch"),
+            Self::InvalidSignature => write!(f, "invalid signature"),
+            Self::InvalidKey => write!(f, "invalid public key"),
+            Self::NotTesting => write!(f, "running image not being tested"),
+            Self::SlotsOverlapStorage => write!(f, "slots overlap the storage"),
+        }
+    }
+}
+
+impl<E: core::fmt::Debug> core::error::Error for Error<E> {}
+
+/// Update in progress, obtained from [`begin()`].
+#[derive(Debug)]
+pub struct Update {
+    writer: SlotWriter,
+}
+
+/// Starts an update, writing a new image to the secondary slot.
+///
+/// Any update started before is discarded.
+///
+/// # Errors
+///
+/// Returns [`Error::Flash`] if accessing the flash fails.
+/// All functions of this module return [`Error::SlotsOverlapStorage`] if the configured slots
+/// overlap a storage partition, queue or raw partition.
+pub async fn begin() -> Result<Update, Error<FlashError>> {
+    let mut storage = lock_storage().await?;
+    let writer = SLOTS.begin(storage.flash_mut()).await?;
+
+    info!("update: writing image to {:?}", SLOTS.secondary);
+
+    Ok(Update { writer })
+}
+
+impl Update {
+    /// Returns the number of bytes of the image written so far.
+    #[must_use]
+    pub fn offset(&self) -> u32 {
+        self.writer.offset()
+    }
+
+    /// Appends `data` to the image.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ImageTooLarge`] if the image does not fit into the secondary slot, and
+    /// [`Error::Flash`] if writing fails.
+    pub async fn write(&mut self, data: &[u8]) -> Result<(), Error<FlashError>> {
+        let mut storage = lock_storage().await?;
+        self.writer.write(storage.flash_mut(), data).await
+    }
+
+    /// Completes the image, verifies it, and marks it to be booted for testing after the next
+    /// reset.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidKey`] if the configured public key is invalid,
+    /// [`Error::InvalidImage`], [`Error::HashMismatch`] or [`Error::InvalidSignature`] if the
+    /// image fails verification, and [`Error::Flash`] if accessing the flash fails.
+    pub async fn finish(self) -> Result<ImageInfo, Error<FlashError>> {
+        let key = PublicKey::from_hex(PUBLIC_KEY).ok_or(Error::InvalidKey)?;
+
+        let mut storage = lock_storage().await?;
+        let info = self
+            .writer
+            .finish(storage.flash_mut(), &[key], false)
+            .await?;
+
+        info!(
+            "update: image {} of {} bytes ready for testing",
+            info.version, info.size
+        );
+
+        Ok(info)
+    }
+}
+
+/// Returns the state of the running image.
+///
+/// # Errors
+///
+/// Returns [`Error::Flash`] if reading fails.
+pub async fn boot_state() -> Result<BootState, Error<FlashError>> {
+    let mut storage = lock_storage().await?;
+    SLOTS.boot_state(storage.flash_mut()).await
+}
+
+/// Confirms the running image if it is [being tested](BootState::Testing), so that it is booted
+/// again after the next reset.
+///
+/// # Errors
+///
+/// Returns [`Error::Flash`] if accessing the flash fails.
+pub async fn confirm() -> Result<(), Error<FlashError>> {
+    let mut storage = lock_storage().await?;
+    SLOTS.confirm(storage.flash_mut()).await
+}
+
+/// Reverts to the previous image by rebooting without confirming the running one.
+///
+/// # Errors
+///
+/// Returns [`Error::NotTesting`] if the running image has already been confirmed, and
+/// [`Error::Flash`] if reading fails.
+pub async fn rollback() -> Result<Infallible, Error<FlashError>> {
+    if boot_state().await? != BootState::Testing {
+        return Err(Error::NotTesting);
+    }
+
+    info!("update: rolling back");
+    ariel_os_power::reboot()
+}
+
+/// Locks the storage to access its flash, after checking that the slots do not overlap any
+/// storage partition, queue or raw partition, which would otherwise be corrupted.
+///
+/// # Errors
+///
+/// Returns [`Error::SlotsOverlapStorage`] if a slot overlaps a partition, queue or raw partition.
+async fn lock_storage()
+-> Result<MutexGuard<'static, CriticalSectionRawMutex, Storage<SharedFlash>>, Error<FlashError>> {
+    let storage_ranges = ariel_os_storage::partitions()
+        .iter()
+        .map(|partition| partition.range())
+        .chain(ariel_os_storage::queues().iter().map(|queue| queue.range()))
+        .chain(
+            ariel_os_storage::raw_partitions()
+                .iter()
+                .map(|raw_partition| raw_partition.range()),
+        );
+
+    for range in storage_ranges {
+        let overlaps =
+            |slot: &core::ops::Range<u32>| slot.start < range.end && range.start < slot.end;
+        if overlaps(&SLOTS.primary) || overlaps(&SLOTS.secondary) {
+            return Err(Error::SlotsOverlapStorage);
+        }
+    }
+
+    Ok(ariel_os_storage::lock().await)
+}
@@ -0,0 +1,528 @@
+//! Writing images to MCUboot slots, and managing their trailers.
+//!
+//! The trailer of a slot is located at its end, and is laid out as follows for a `BOOT_MAX_ALIGN`
+//! of 8, MCUboot's default, with each field padded to 8 bytes:
+//!
+//! | Field       | Offset from the end of the slot |
+//! | --          | --                              |
+//! | `swap_info` | 40                              |
+//! | `copy_done` | 32                              |
+//! | `image_ok`  | 24                              |
+//! | Magic       | 16                              |
+
+#![expect(
+    clippy::cast_possible_truncation,
+    reason = "flash offsets and lengths fit into a u32"
+)]
+#![expect(clippy::missing_errors_doc)]
+
+use core::ops::Range;
+
+use embedded_storage_async::nor_flash::{NorFlash, ReadNorFlash};
+
+use crate::{
+    Error, PublicKey,
+    image::{self, ImageInfo},
+};
+
+/// `BOOT_MAX_ALIGN` of MCUboot.
+const MAX_ALIGN: u32 = 8;
+const MAGIC: [u8; 16] = [
+    0x77, 0xc2, 0x95, 0xf3, 0x60, 0xd2, 0xef, 0x7f, 0x35, 0x52, 0x50, 0x0f, 0x2c, 0xb6, 0x79, 0x80,
+];
+const MAGIC_LEN: u32 = MAGIC.len() as u32;
+
+const FLAG_SET: u8 = 0x01;
+const SWAP_TYPE_TEST: u8 = 2;
+const SWAP_TYPE_PERM: u8 = 3;
+
+/// State of the running image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum BootState {
+    /// The image has been confirmed, and is booted again after the next reset.
+    Confirmed,
+    /// The image is booted for testing, and is reverted to the previous one after the next reset
+    /// unless confirmed.
+    Testing,
+}
+
+/// Flash layout of the MCUboot slots of an image.
+///
+/// The ranges are offsets as seen by the flash driver, and must be aligned to its erase size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Slots {
+    /// Slot the running image is booted from.
+    pub primary: Range<u32>,
+    /// Slot new images are written to.
+    pub secondary: Range<u32>,
+}
+
+impl Slots {
+    /// Starts writing a new image to the secondary slot.
+    ///
+    /// The secondary slot is erased as the image is written, except for its last erase sector,
+    /// which is erased right away and is reserved for the trailer.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Flash`] if erasing fails.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the secondary slot is not aligned to the erase size of `flash`, and at compile
+    /// time if the write size of `flash` does not divide 8.
+    pub async fn begin<F: NorFlash>(&self, flash: &mut F) -> Result<SlotWriter, Error<F::Error>> {
+        const { assert!((MAX_ALIGN as usize).is_multiple_of(F::WRITE_SIZE)) };
+
+        let erase_size = F::ERASE_SIZE as u32;
+        let slot = self.secondary.clone();
+        assert!(
+            slot.start.is_multiple_of(erase_size)
+                && slot.end.is_multiple_of(erase_size)
+                && !slot.is_empty(),
+            "secondary slot not aligned to the erase size"
+        );
+
+        // Clear any pending update.
+        flash
+            .erase(slot.end - erase_size, slot.end)
+            .await
+            .map_err(Error::Flash)?;
+
+        Ok(SlotWriter {
+            erased: slot.start,
+            limit: slot.end - erase_size,
+            written: slot.start,
+            slot,
+            pending: [0xff; MAX_ALIGN as usize],
+            pending_len: 0,
+        })
+    }
+
+    /// Returns the state of the running image.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Flash`] if reading fails.
+    pub async fn boot_state<F: ReadNorFlash>(
+        &self,
+        flash: &mut F,
+    ) -> Result<BootState, Error<F::Error>> {
+        let trailer = Trailer::new(&self.primary);
+
+        let mut magic = [0; MAGIC.len()];
+        image::read(flash, trailer.magic, &mut magic).await?;
+        let mut copy_done = [0];
+        image::read(flash, trailer.copy_done, &mut copy_done).await?;
+        let mut image_ok = [0];
+        image::read(flash, trailer.image_ok, &mut image_ok).await?;
+
+        // This is the state in which MCUboot reverts the swap (`BOOT_SWAP_TYPE_REVERT`).
+        if magic == MAGIC && copy_done == [FLAG_SET] && image_ok != [FLAG_SET] {
+            Ok(BootState::Testing)
+        } else {
+            Ok(BootState::Confirmed)
+        }
+    }
+
+    /// Confirms the running image if it is [being tested](BootState::Testing), so that it is
+    /// booted again after the next reset.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Flash`] if accessing the flash fails.
+    pub async fn confirm<F: NorFlash>(&self, flash: &mut F) -> Result<(), Error<F::Error>> {
+        if self.boot_state(flash).await? == BootState::Testing {
+            write_flag(flash, Trailer::new(&self.primary).image_ok, FLAG_SET).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writer of an image to the secondary slot, obtained from [`Slots::begin()`].
+///
+/// The image is written sequentially; the flash is passed to each method so that it does not
+/// need to be held while waiting for the next chunk of the image.
+#[derive(Debug)]
+pub struct SlotWriter {
+    slot: Range<u32>,
+    /// End of the erased part of the slot.
+    erased: u32,
+    /// End of the part of the slot available to the image.
+    limit: u32,
+    /// End of the part of the slot written to.
+    written: u32,
+    /// Data not written yet, waiting to fill a flash write unit.
+    pending: [u8; MAX_ALIGN as usize],
+    pending_len: usize,
+}
+
+impl SlotWriter {
+    /// Returns the number of bytes of the image written so far.
+    #[must_use]
+    pub fn offset(&self) -> u32 {
+        self.written - self.slot.start + self.pending_len as u32
+    }
+
+    /// Appends `data` to the image.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ImageTooLarge`] if the image does not fit into the slot, and
+    /// [`Error::Flash`] if writing fails.
+    pub async fn write<F: NorFlash>(
+        &mut self,
+        flash: &mut F,
+        mut data: &[u8],
+    ) -> Result<(), Error<F::Error>> {
+        let available = self.limit - self.written - self.pending_len as u32;
+        if data.len() > available as usize {
+            return Err(Error::ImageTooLarge);
+        }
+
+        // Complete the pending write unit first.
+        if self.pending_len > 0 {
+            let (head, tail) = data.split_at(data.len().min(F::WRITE_SIZE - self.pending_len));
+            self.pending
+                .get_mut(self.pending_len..self.pending_len + head.len())
+                .ok_or(Error::ImageTooLarge)?
+                .copy_from_slice(head);
+            self.pending_len += head.len();
+            data = tail;
+
+            if self.pending_len < F::WRITE_SIZE {
+                return Ok(());
+            }
+            let pending = self.pending;
+            self.write_aligned(flash, pending.get(..F::WRITE_SIZE).unwrap_or_default())
+                .await?;
+            self.pending_len = 0;
+        }
+
+        let (aligned, rest) = data.split_at(data.len() - data.len() % F::WRITE_SIZE);
+        self.write_aligned(flash, aligned).await?;
+
+        self.pending
+            .get_mut(..rest.len())
+            .ok_or(Error::ImageTooLarge)?
+            .copy_from_slice(rest);
+        self.pending_len = rest.len();
+
+        Ok(())
+    }
+
+    /// Completes the image, verifies it, and marks it to be booted after the next reset.
+    ///
+    /// The image must be signed by any of `keys`.
+    /// When `permanent` is `false`, the image is booted for testing, and must be
+    /// [confirmed](Slots::confirm()) once running, otherwise it is reverted at the following
+    /// reset.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidImage`], [`Error::HashMismatch`] or [`Error::InvalidSignature`] if
+    /// the image fails verification, and [`Error::Flash`] if accessing the flash fails.
+    pub async fn finish<F: NorFlash>(
+        mut self,
+        flash: &mut F,
+        keys: &[PublicKey],
+        permanent: bool,
+    ) -> Result<ImageInfo, Error<F::Error>> {
+        if self.pending_len > 0 {
+            let mut pending = self.pending;
+            pending
+                .get_mut(self.pending_len..)
+                .unwrap_or_default()
+                .fill(0xff);
+            self.write_aligned(flash, pending.get(..F::WRITE_SIZE).unwrap_or_default())
+                .await?;
+        }
+
+        let info =
+            image::verify(flash, self.slot.start, self.written - self.slot.start, keys).await?;
+
+        // Same sequence as `boot_set_pending()` of MCUboot.
+        let trailer = Trailer::new(&self.slot);
+        flash
+            .write(trailer.magic, &MAGIC)
+            .await
+            .map_err(Error::Flash)?;
+        let swap_type = if permanent {
+            write_flag(flash, trailer.image_ok, FLAG_SET).await?;
+            SWAP_TYPE_PERM
+        } else {
+            SWAP_TYPE_TEST
+        };
+        write_flag(flash, trailer.swap_info, swap_type).await?;
+
+        Ok(info)
+    }
+
+    async fn write_aligned<F: NorFlash>(
+        &mut self,
+        flash: &mut F,
+        data: &[u8],
+    ) -> Result<(), Error<F::Error>> {
+        let end = self.written + data.len() as u32;
+
+        if end > self.erased {
+            let erase_end = end.next_multiple_of(F::ERASE_SIZE as u32);
+            flash
+                .erase(self.erased, erase_end)
+                .await
+                .map_err(Error::Flash)?;
+            self.erased = erase_end;
+        }
+
+        flash
+            .write(self.written, data)
+            .await
+            .map_err(Error::Flash)?;
+        self.written = end;
+
+        Ok(())
+    }
+}
+
+/// Offsets of the fields of the trailer of a slot.
+struct Trailer {
+    magic: u32,
+    image_ok: u32,
+    copy_done: u32,
+    swap_info: u32,
+}
+
+impl Trailer {
+    fn new(slot: &Range<u32>) -> Self {
+        let magic = slot.end - MAGIC_LEN;
+        Self {
+            magic,
+            image_ok: magic - MAX_ALIGN,
+            copy_done: magic - 2 * MAX_ALIGN,
+            swap_info: magic - 3 * MAX_ALIGN,
+        }
+    }
+}
+
+async fn write_flag<F: NorFlash>(
+    flash: &mut F,
+    offset: u32,
+    value: u8,
+) -> Result<(), Error<F::Error>> {
+    let mut buf = [0xff; MAX_ALIGN as usize];
+    buf[0] = value;
+
+    flash
+        .write(offset, buf.get(..F::WRITE_SIZE).unwrap_or_default())
+        .await
+        .map_err(Error::Flash)
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::block_on;
+    use embedded_storage_async::nor_flash::{ErrorType, NorFlashErrorKind};
+    use sha2::{Digest as _, Sha256};
+
+    use super::*;
+    use crate::ImageVersion;
+
+    const ERASE_SIZE: usize = 1024;
+
+    struct RamFlash([u8; 8 * ERASE_SIZE]);
+
+    impl ErrorType for RamFlash {
+        type Error = NorFlashErrorKind;
+    }
+
+    impl ReadNorFlash for RamFlash {
+        const READ_SIZE: usize = 1;
+
+        async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+            let offset = offset as usize;
+            bytes.copy_from_slice(&self.0[offset..offset + bytes.len()]);
+            Ok(())
+        }
+
+        fn capacity(&self) -> usize {
+            self.0.len()
+        }
+    }
+
+    impl NorFlash for RamFlash {
+        const WRITE_SIZE: usize = 4;
+        const ERASE_SIZE: usize = ERASE_SIZE;
+
+        async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+            assert_eq!(from as usize % ERASE_SIZE, 0);
+            assert_eq!(to as usize % ERASE_SIZE, 0);
+            self.0[from as usize..to as usize].fill(0xff);
+            Ok(())
+        }
+
+        async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+            let offset = offset as usize;
+            assert_eq!(offset % Self::WRITE_SIZE, 0);
+            assert_eq!(bytes.len() % Self::WRITE_SIZE, 0);
+            let target = &mut self.0[offset..offset + bytes.len()];
+            assert!(target.iter().all(|byte| *byte == 0xff), "not erased");
+            target.copy_from_slice(bytes);
+            Ok(())
+        }
+    }
+
+    const SLOTS: Slots = Slots {
+        primary: 0..(4 * ERASE_SIZE as u32),
+        secondary: (4 * ERASE_SIZE as u32)..(8 * ERASE_SIZE as u32),
+    };
+
+    const SECRET_KEY: [u8; 32] = [42; 32];
+
+    fn public_key() -> PublicKey {
+        PublicKey::Ed25519(
+            ed25519_dalek::SigningKey::from_bytes(&SECRET_KEY)
+                .verifying_key()
+                .to_bytes(),
+        )
+    }
+
+    // Returns an image as produced by `imgtool sign`, with `firmware_len` bytes of firmware.
+    fn image(firmware_len: u16) -> ([u8; 2 * ERASE_SIZE], usize) {
+        use ed25519_dalek::Signer as _;
+
+        const HEADER_LEN: usize = 64;
+
+        let mut image = [0xff; 2 * ERASE_SIZE];
+        image[..HEADER_LEN].fill(0);
+        image[..4].copy_from_slice(&0x96f3_b83d_u32.to_le_bytes());
+        image[8..10].copy_from_slice(&(HEADER_LEN as u16).to_le_bytes());
+        image[12..16].copy_from_slice(&u32::from(firmware_len).to_le_bytes());
+        // Version 1.2.3+4.
+        image[20..28].copy_from_slice(&[1, 2, 3, 0, 4, 0, 0, 0]);
+
+        let hashed_len = HEADER_LEN + usize::from(firmware_len);
+        for (i, byte) in image[HEADER_LEN..hashed_len].iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+
+        let hash: [u8; 32] = Sha256::digest(&image[..hashed_len]).into();
+        let signature = ed25519_dalek::SigningKey::from_bytes(&SECRET_KEY)
+            .sign(&hash)
+            .to_bytes();
+
+        let mut tlvs = [0; 4 + 4 + 32 + 4 + 64];
+        let tlvs_len = tlvs.len() as u8;
+        tlvs[..4].copy_from_slice(&[0x07, 0x69, tlvs_len, 0]);
+        tlvs[4..8].copy_from_slice(&[0x10, 0, 32, 0]);
+        tlvs[8..40].copy_from_slice(&hash);
+        tlvs[40..44].copy_from_slice(&[0x24, 0, 64, 0]);
+        tlvs[44..].copy_from_slice(&signature);
+        image[hashed_len..hashed_len + tlvs.len()].copy_from_slice(&tlvs);
+
+        (image, hashed_len + tlvs.len())
+    }
+
+    fn write_image(flash: &mut RamFlash, image: &[u8]) -> SlotWriter {
+        let mut writer = block_on(SLOTS.begin(flash)).unwrap();
+        // Chunks that are not aligned to the write size.
+        for chunk in image.chunks(7) {
+            block_on(writer.write(flash, chunk)).unwrap();
+        }
+        assert_eq!(writer.offset() as usize, image.len());
+        writer
+    }
+
+    #[test]
+    fn test_update() {
+        let mut flash = RamFlash([0; 8 * ERASE_SIZE]);
+        let (image, len) = image(1000);
+
+        let writer = write_image(&mut flash, &image[..len]);
+        let info = block_on(writer.finish(&mut flash, &[public_key()], false)).unwrap();
+        assert_eq!(
+            info,
+            ImageInfo {
+                version: ImageVersion {
+                    major: 1,
+                    minor: 2,
+                    revision: 3,
+                    build: 4,
+                },
+                size: len as u32,
+            }
+        );
+
+        let secondary = &flash.0[SLOTS.secondary.start as usize..];
+        assert_eq!(&secondary[..len], &image[..len]);
+        let trailer = &secondary[secondary.len() - 40..];
+        assert_eq!(trailer[0], SWAP_TYPE_TEST);
+        assert_eq!(trailer[16], 0xff);
+        assert_eq!(&trailer[24..], &MAGIC);
+    }
+
+    #[test]
+    fn test_invalid_images() {
+        let mut flash = RamFlash([0; 8 * ERASE_SIZE]);
+        let (mut image, len) = image(1000);
+
+        let writer = write_image(&mut flash, &image[..len]);
+        let other_key = PublicKey::Ed25519(
+            ed25519_dalek::SigningKey::from_bytes(&[1; 32])
+                .verifying_key()
+                .to_bytes(),
+        );
+        assert_eq!(
+            block_on(writer.finish(&mut flash, &[other_key], false)),
+            Err(Error::InvalidSignature)
+        );
+
+        image[100] ^= 1;
+        let writer = write_image(&mut flash, &image[..len]);
+        assert_eq!(
+            block_on(writer.finish(&mut flash, &[public_key()], false)),
+            Err(Error::HashMismatch)
+        );
+
+        image[100] ^= 1;
+        let writer = write_image(&mut flash, &image[..len - 8]);
+        assert_eq!(
+            block_on(writer.finish(&mut flash, &[public_key()], false)),
+            Err(Error::InvalidImage)
+        );
+
+        // The last sector of the slot is reserved for the trailer.
+        let mut writer = block_on(SLOTS.begin(&mut flash)).unwrap();
+        let data = [0; 3 * ERASE_SIZE + 1];
+        assert_eq!(
+            block_on(writer.write(&mut flash, &data)),
+            Err(Error::ImageTooLarge)
+        );
+    }
+
+    #[test]
+    fn test_confirm() {
+        let mut flash = RamFlash([0xff; 8 * ERASE_SIZE]);
+        assert_eq!(
+            block_on(SLOTS.boot_state(&mut flash)),
+            Ok(BootState::Confirmed)
+        );
+
+        // Primary trailer as left by MCUboot after swapping in an image for testing.
+        let trailer = Trailer::new(&SLOTS.primary);
+        flash.0[trailer.magic as usize..][..16].copy_from_slice(&MAGIC);
+        flash.0[trailer.copy_done as usize] = FLAG_SET;
+        assert_eq!(
+            block_on(SLOTS.boot_state(&mut flash)),
+            Ok(BootState::Testing)
+        );
+
+        block_on(SLOTS.confirm(&mut flash)).unwrap();
+        assert_eq!(flash.0[trailer.image_ok as usize], FLAG_SET);
+        assert_eq!(
+            block_on(SLOTS.boot_state(&mut flash)),
+            Ok(BootState::Confirmed)
+        );
+    }
+}
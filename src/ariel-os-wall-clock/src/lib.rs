@@ -0,0 +1,192 @@
+//! Provides the system wall clock, keeping the current UTC time.
+//!
+//! The wall clock is not running until it has been [set](set()) from a time source, e.g., a GNSS
+//! fix, SNTP, or the cellular network; until then, [`now()`] returns `None`.
+//! It then keeps UTC as an offset from the monotonic clock of [`embassy_time`], and therefore
+//! drifts along with it; setting it again from a time source corrects that drift.
+//!
+//! When a hardware [`Rtc`] is [registered](use_rtc()), the wall clock is persisted to it every time
+//! it is set, and is restored from it at registration, so that the time is kept across resets.
+//!
+//! # Examples
+//!
+//! ```ignore
+//! use ariel_os::wall_clock::{self, TimeSource, UtcDateTime};
+//!
+//! wall_clock::set(UtcDateTime::from_unix_timestamp(1_750_000_000).unwrap(), TimeSource::Manual);
+//!
+//! if let Some(now) = wall_clock::now() {
+//!     info!("It is {}:{}", now.hour(), now.minute());
+//! }
+//! ```
+
+#![no_std]
+#![deny(missing_docs)]
+
+use core::cell::{Cell, RefCell};
+
+use ariel_os_log::debug;
+use embassy_sync::blocking_mutex::CriticalSectionMutex;
+use embassy_time::Instant;
+
+pub use time::UtcDateTime;
+
+/// Source the wall clock was set from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum TimeSource {
+    /// The application, e.g., from user input.
+    Manual,
+    /// The hardware [`Rtc`].
+    Rtc,
+    /// A GNSS fix.
+    Gnss,
+    /// An SNTP server.
+    Sntp,
+    /// A CoAP peer, e.g., an authorization server vouching for a time to have passed.
+    Coap,
+    /// The cellular network (e.g., NITZ on LTE).
+    CellularNetwork,
+}
+
+/// Error returned when setting the wall clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum Error {
+    /// The time could not be read from GNSS samples.
+    #[cfg(feature = "gnss")]
+    Gnss(ariel_os_sensors_gnss_time_ext::GnssTimeExtError),
+    /// The time is outside of the supported range.
+    OutOfRange,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            #[cfg(feature = "gnss")]
+            Self::Gnss(err) => write!(f, "GNSS time error: {err}"),
+            Self::OutOfRange => write!(f, "time out of range"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// Hardware real-time clock, keeping the time while the wall clock is not running, e.g., across
+/// resets.
+///
+/// This is implemented by the application for the RTC of its MCU or board.
+/// As the methods may be called from within a critical section, they must not block for long.
+pub trait Rtc: Send {
+    /// Returns the time of the RTC, or `None` if it has not been set or has lost the time.
+    fn read(&mut self) -> Option<UtcDateTime>;
+
+    /// Sets the time of the RTC.
+    fn write(&mut self, time: UtcDateTime);
+}
+
+#[derive(Clone, Copy)]
+struct ClockState {
+    /// UTC time at boot, in nanoseconds since the UNIX epoch.
+    offset_nanos: i128,
+    source: TimeSource,
+}
+
+impl ClockState {
+    fn time_at(&self, instant: Instant) -> Option<UtcDateTime> {
+        UtcDateTime::from_unix_timestamp_nanos(self.offset_nanos + nanos_since_boot(instant)).ok()
+    }
+}
+
+static CLOCK: CriticalSectionMutex<Cell<Option<ClockState>>> =
+    CriticalSectionMutex::new(Cell::new(None));
+
+static RTC: CriticalSectionMutex<RefCell<Option<&'static mut dyn Rtc>>> =
+    CriticalSectionMutex::new(RefCell::new(None));
+
+/// Returns the current UTC time, or `None` if the wall clock has not been set yet.
+#[must_use]
+pub fn now() -> Option<UtcDateTime> {
+    CLOCK.lock(Cell::get)?.time_at(Instant::now())
+}
+
+/// Returns the source the wall clock was last set from, or `None` if it has not been set yet.
+#[must_use]
+pub fn source() -> Option<TimeSource> {
+    CLOCK.lock(Cell::get).map(|state| state.source)
+}
+
+/// Sets the wall clock to `time`, obtained from `source`.
+///
+/// The time is also persisted to the hardware [`Rtc`], if any, unless it was read from it.
+pub fn set(time: UtcDateTime, source: TimeSource) {
+    set_at(time, Instant::now(), source);
+}
+
+/// Sets the wall clock, given that it was `time` at `instant`.
+///
+/// This allows accounting for the latency of the time source, e.g., when `time` is the timestamp
+/// of a message received at `instant`.
+///
+/// The time is also persisted to the hardware [`Rtc`], if any, unless it was read from it.
+pub fn set_at(time: UtcDateTime, instant: Instant, source: TimeSource) {
+    let state = ClockState {
+        offset_nanos: time.unix_timestamp_nanos() - nanos_since_boot(instant),
+        source,
+    };
+    CLOCK.lock(|cell| cell.set(Some(state)));
+
+    debug!("wall clock: set from {:?}", source);
+
+    if source != TimeSource::Rtc
+        && let Some(now) = state.time_at(Instant::now())
+    {
+        persist(now);
+    }
+}
+
+/// Sets the wall clock from the time of fix of GNSS `samples`.
+///
+/// # Errors
+///
+/// Returns [`Error::Gnss`] if the samples do not contain a time of fix, and
+/// [`Error::OutOfRange`] if that time cannot be represented.
+#[cfg(feature = "gnss")]
+pub fn set_from_gnss(samples: &ariel_os_sensors::sensor::Samples) -> Result<(), Error> {
+    use ariel_os_sensors_gnss_time_ext::GnssTimeExt as _;
+
+    let nanos = samples.time_of_fix_timestamp_nanos().map_err(Error::Gnss)?;
+    let time = UtcDateTime::from_unix_timestamp_nanos(nanos).map_err(|_| Error::OutOfRange)?;
+    set(time, TimeSource::Gnss);
+
+    Ok(())
+}
+
+/// Registers the hardware `rtc`, replacing the previously registered one.
+///
+/// If the wall clock has not been set yet, it is set from the RTC; otherwise the RTC is set from
+/// the wall clock.
+pub fn use_rtc(rtc: &'static mut dyn Rtc) {
+    let rtc_time = rtc.read();
+    RTC.lock(|cell| *cell.borrow_mut() = Some(rtc));
+
+    if let Some(now) = now() {
+        persist(now);
+    } else if let Some(rtc_time) = rtc_time {
+        set(rtc_time, TimeSource::Rtc);
+    }
+}
+
+fn persist(time: UtcDateTime) {
+    RTC.lock(|cell| {
+        if let Some(rtc) = cell.borrow_mut().as_mut() {
+            rtc.write(time);
+        }
+    });
+}
+
+fn nanos_since_boot(instant: Instant) -> i128 {
+    i128::from(instant.as_micros()) * 1000
+}
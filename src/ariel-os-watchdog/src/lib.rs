@@ -0,0 +1,192 @@
+//! Provides supervision of threads and tasks by the hardware watchdog.
+//!
+//! The MCU watchdog is started at boot, with a timeout of `CONFIG_WATCHDOG_TIMEOUT_MS`
+//! milliseconds (capped to what the hardware supports).
+//! It is then fed periodically, but only as long as every registered [`Client`] has checked in
+//! within its own deadline: a thread or task that hangs therefore results in a reset of the MCU.
+//!
+//! The name of the client that missed its deadline is retained across the reset, and can be
+//! obtained after reboot with [`ariel_os_power::last_watchdog_starvation()`].
+//!
+//! # Examples
+//!
+//! ```ignore
+//! let client = ariel_os::watchdog::register("sensor-loop", Duration::from_secs(5)).unwrap();
+//!
+//! loop {
+//!     // ... do some work ...
+//!     client.check_in();
+//! }
+//! ```
+
+#![no_std]
+#![deny(missing_docs)]
+
+use core::cell::RefCell;
+
+use ariel_os_hal::hal;
+use ariel_os_log::{debug, error};
+use embassy_sync::blocking_mutex::CriticalSectionMutex;
+use embassy_time::{Duration, Instant, Timer};
+
+/// Timeout of the hardware watchdog.
+const TIMEOUT_MS: u32 = {
+    let timeout_ms = ariel_os_utils::u32_from_env_or!(
+        "CONFIG_WATCHDOG_TIMEOUT_MS",
+        4000,
+        "timeout of the hardware watchdog, in milliseconds"
+    );
+    if timeout_ms < hal::watchdog::MAX_TIMEOUT_MS {
+        timeout_ms
+    } else {
+        hal::watchdog::MAX_TIMEOUT_MS
+    }
+};
+
+/// Maximum number of clients that can be registered at the same time.
+pub const MAX_CLIENTS: usize = ariel_os_utils::usize_from_env_or!(
+    "CONFIG_WATCHDOG_MAX_CLIENTS",
+    8,
+    "maximum number of clients supervised by the watchdog"
+);
+
+const _: () = assert!(TIMEOUT_MS >= 4, "the watchdog timeout is too short");
+
+struct Registration {
+    name: &'static str,
+    thread_id: Option<usize>,
+    deadline: Duration,
+    last_check_in: Instant,
+}
+
+impl Registration {
+    fn is_starving(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.last_check_in) > self.deadline
+    }
+}
+
+static CLIENTS: CriticalSectionMutex<RefCell<[Option<Registration>; MAX_CLIENTS]>> =
+    CriticalSectionMutex::new(RefCell::new([const { None }; MAX_CLIENTS]));
+
+/// Errors returned when registering a client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// [`MAX_CLIENTS`] clients are already registered.
+    TooManyClients,
+}
+
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::TooManyClients => write!(f, "too many watchdog clients"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// Registers a new client named `name`, which has to [check in](Client::check_in) at least once
+/// every `deadline`.
+///
+/// The deadline starts running immediately.
+/// When called from a thread, the id of that thread is retained along with the name in case the
+/// client starves the watchdog.
+/// The client is unregistered when the returned [`Client`] is dropped.
+///
+/// # Errors
+///
+/// Returns [`Error::TooManyClients`] if [`MAX_CLIENTS`] clients are already registered.
+pub fn register(name: &'static str, deadline: Duration) -> Result<Client, Error> {
+    #[cfg(feature = "threading")]
+    let thread_id = ariel_os_threads::current_tid().map(usize::from);
+    #[cfg(not(feature = "threading"))]
+    let thread_id = None;
+
+    CLIENTS.lock(|clients| {
+        let mut clients = clients.borrow_mut();
+        let (index, slot) = clients
+            .iter_mut()
+            .enumerate()
+            .find(|(_, slot)| slot.is_none())
+            .ok_or(Error::TooManyClients)?;
+
+        *slot = Some(Registration {
+            name,
+            thread_id,
+            deadline,
+            last_check_in: Instant::now(),
+        });
+
+        debug!("watchdog: registered client {}", name);
+
+        Ok(Client { index })
+    })
+}
+
+/// A thread or task supervised by the watchdog, obtained with [`register()`].
+#[derive(Debug)]
+pub struct Client {
+    index: usize,
+}
+
+impl Client {
+    /// Signals that the client is alive, restarting its deadline.
+    pub fn check_in(&self) {
+        let now = Instant::now();
+        CLIENTS.lock(|clients| {
+            if let Some(Some(registration)) = clients.borrow_mut().get_mut(self.index) {
+                registration.last_check_in = now;
+            }
+        });
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        CLIENTS.lock(|clients| {
+            if let Some(slot) = clients.borrow_mut().get_mut(self.index) {
+                *slot = None;
+            }
+        });
+    }
+}
+
+/// Returns the name and the thread id of the first client that missed its deadline, if any.
+fn find_starving_client() -> Option<(&'static str, Option<usize>)> {
+    let now = Instant::now();
+    CLIENTS.lock(|clients| {
+        clients
+            .borrow()
+            .iter()
+            .flatten()
+            .find(|registration| registration.is_starving(now))
+            .map(|registration| (registration.name, registration.thread_id))
+    })
+}
+
+/// Starts the hardware watchdog.
+#[doc(hidden)]
+pub fn init(peripherals: &mut hal::OptionalPeripherals) -> hal::watchdog::Watchdog {
+    debug!("watchdog: starting with a timeout of {} ms", TIMEOUT_MS);
+    hal::watchdog::init(peripherals, TIMEOUT_MS)
+}
+
+/// Feeds `watchdog` as long as no client misses its deadline.
+#[doc(hidden)]
+pub async fn supervise(mut watchdog: hal::watchdog::Watchdog) -> ! {
+    // Leave some margin so that the watchdog does not bite because of scheduling latencies.
+    let period = Duration::from_millis(u64::from(TIMEOUT_MS / 4));
+
+    loop {
+        if let Some((name, thread_id)) = find_starving_client() {
+            error!("watchdog: client {} missed its deadline", name);
+            ariel_os_power::record_watchdog_starvation(name, thread_id);
+
+            // Stop feeding the watchdog, so that it resets the MCU.
+            core::future::pending::<()>().await;
+        }
+
+        watchdog.feed();
+        Timer::after(period).await;
+    }
+}
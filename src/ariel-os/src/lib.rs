@@ -33,28 +33,46 @@ pub use ariel_os_buildinfo as buildinfo;
 pub use ariel_os_coap as coap;
 #[doc(inline)]
 pub use ariel_os_debug as debug;
+#[cfg(feature = "fs")]
+#[doc(inline)]
+pub use ariel_os_fs as fs;
 #[doc(inline)]
 pub use ariel_os_hal::api::*;
 #[doc(inline)]
 pub use ariel_os_identity as identity;
 #[doc(inline)]
 pub use ariel_os_log as log;
-#[doc(inline)]
-pub use ariel_os_power as power;
 #[cfg(feature = "random")]
 #[doc(inline)]
 pub use ariel_os_random as random;
 #[doc(inline)]
 pub use ariel_os_rt as rt;
+#[cfg(feature = "shell")]
+#[doc(inline)]
+pub use ariel_os_shell as shell;
+#[cfg(feature = "sntp")]
+#[doc(inline)]
+pub use ariel_os_sntp as sntp;
 #[cfg(feature = "storage")]
 #[doc(inline)]
 pub use ariel_os_storage as storage;
 #[cfg(feature = "threading")]
 #[doc(inline)]
 pub use ariel_os_threads as thread;
+#[cfg(feature = "update")]
+#[doc(inline)]
+pub use ariel_os_update as update;
+#[cfg(feature = "wall-clock")]
+#[doc(inline)]
+pub use ariel_os_wall_clock as wall_clock;
+#[cfg(feature = "watchdog")]
+#[doc(inline)]
+pub use ariel_os_watchdog as watchdog;
 
 // Attribute macros
 pub use ariel_os_macros::config;
+#[cfg(any(feature = "shell", doc))]
+pub use ariel_os_macros::shell_command;
 pub use ariel_os_macros::spawner;
 pub use ariel_os_macros::task;
 #[cfg(any(feature = "threading", doc))]
@@ -62,6 +80,17 @@ pub use ariel_os_macros::thread;
 
 pub use ariel_os_embassy::api::*;
 
+pub mod power {
+    //! Provides power management functionality.
+
+    #[doc(inline)]
+    pub use ariel_os_power::*;
+
+    #[cfg(feature = "system-off")]
+    #[doc(inline)]
+    pub use ariel_os_hal::system_off::*;
+}
+
 pub mod config {
     //! Provides configuration to the system and the application.
 
@@ -100,6 +100,97 @@ where
     }
 }
 
+/// Typed FIFO ringbuffer with inline backing storage for `N` elements.
+///
+/// Unlike [`RingBuffer`], elements are moved in and out, so that they need not be `Copy`.
+/// `N` must be a power of two, between 2 and 128.
+#[derive(Debug)]
+pub struct ArrayRingBuffer<T, const N: usize> {
+    index: RingBufferIndex,
+    array: [MaybeUninit<T>; N],
+}
+
+impl<T, const N: usize> ArrayRingBuffer<T, N> {
+    const VALID_SIZE: () = assert!(
+        N.is_power_of_two() && N >= 2 && N <= 128,
+        "N must be a power of two between 2 and 128"
+    );
+
+    /// Creates a new, empty [`ArrayRingBuffer`].
+    #[must_use]
+    pub const fn new() -> Self {
+        let () = Self::VALID_SIZE;
+        Self {
+            #[expect(clippy::cast_possible_truncation, reason = "N is at most 128")]
+            index: RingBufferIndex::new(N as u8),
+            array: [const { MaybeUninit::uninit() }; N],
+        }
+    }
+
+    /// Appends an element, or returns it back if the ringbuffer is full.
+    ///
+    /// # Errors
+    ///
+    /// Returns the element if the ringbuffer is full.
+    pub fn put(&mut self, element: T) -> Result<(), T> {
+        match self
+            .index
+            .put()
+            .and_then(|pos| self.array.get_mut(usize::from(pos)))
+        {
+            Some(slot) => {
+                slot.write(element);
+                Ok(())
+            }
+            None => Err(element),
+        }
+    }
+
+    /// Removes and returns the oldest element, if any.
+    pub fn get(&mut self) -> Option<T> {
+        let slot = self.array.get(usize::from(self.index.get()?))?;
+        // SAFETY: this only returns elements that have been stored with `put()`, and marks them
+        // unused so that they are not read twice.
+        Some(unsafe { slot.assume_init_read() })
+    }
+
+    /// Returns the number of elements in the ringbuffer.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        usize::from(self.index.available())
+    }
+
+    /// Returns the number of elements the ringbuffer can hold.
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns `true` if the ringbuffer holds no element.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    /// Returns `true` if no element can be appended.
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        self.index.is_full()
+    }
+}
+
+impl<T, const N: usize> Default for ArrayRingBuffer<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Drop for ArrayRingBuffer<T, N> {
+    fn drop(&mut self) {
+        while self.get().is_some() {}
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::RingBuffer;
@@ -112,4 +203,29 @@ mod tests {
         assert_eq!(rb.peek(), Some('0'));
         assert_eq!(rb.get(), Some('0'));
     }
+
+    #[test]
+    fn array() {
+        use super::ArrayRingBuffer;
+        use std::rc::Rc;
+
+        let element = Rc::new(0);
+        let mut rb: ArrayRingBuffer<Rc<i32>, 4> = ArrayRingBuffer::new();
+        assert!(rb.is_empty());
+        assert_eq!(rb.capacity(), 4);
+        for _ in 0..4 {
+            assert!(rb.put(Rc::clone(&element)).is_ok());
+        }
+        assert!(rb.is_full());
+        assert!(rb.put(Rc::clone(&element)).is_err());
+        assert_eq!(Rc::strong_count(&element), 5);
+
+        assert!(rb.get().is_some());
+        assert_eq!(rb.len(), 3);
+        assert_eq!(Rc::strong_count(&element), 4);
+
+        // Remaining elements are dropped with the ringbuffer.
+        drop(rb);
+        assert_eq!(Rc::strong_count(&element), 1);
+    }
 }
@@ -0,0 +1,318 @@
+//! Driver exposing an ADC channel as a voltage sensor.
+//!
+//! Compatible with [`ariel_os_sensors::Sensor`].
+//!
+//! The driver is generic over [`AnalogInput`], which is implemented by
+//! `ariel_os::adc::AdcInput`.
+
+#![cfg_attr(not(test), no_std)]
+#![deny(missing_docs)]
+
+use ariel_os_embassy_common::adc::AnalogInput;
+use ariel_os_sensors::{
+    Category, Label, MeasurementUnit, Sensor,
+    sensor::{
+        Mode as SensorMode, ReadingChannel, ReadingChannels, ReadingError, ReadingResult,
+        ReadingWaiter, Sample, SampleMetadata, Samples, SetModeError, State,
+        TriggerMeasurementError,
+    },
+    signal::Signal as ReadingSignal,
+};
+use ariel_os_sensors_utils::AtomicState;
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex, mutex::Mutex, once_lock::OnceLock, signal::Signal,
+};
+use portable_atomic::{AtomicU32, Ordering};
+
+/// Configuration of the sensor driver.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Config {
+    /// Numerator of the ratio applied to the measured voltage.
+    ///
+    /// This allows to compensate for a voltage divider in front of the ADC input, e.g., when
+    /// monitoring a battery.
+    pub ratio_numerator: u32,
+    /// Denominator of the ratio applied to the measured voltage.
+    pub ratio_denominator: u32,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            ratio_numerator: 1,
+            ratio_denominator: 1,
+        }
+    }
+}
+
+ariel_os_hal::define_peripherals!(
+    /// Peripherals required by the sensor driver.
+    Peripherals {}
+);
+
+/// Driver exposing an ADC channel as a voltage sensor.
+pub struct AdcSensor<A> {
+    state: AtomicState,
+    label: Option<&'static str>,
+    input: OnceLock<Mutex<CriticalSectionRawMutex, A>>,
+    ratio_numerator: AtomicU32,
+    ratio_denominator: AtomicU32,
+    signaling: Signal<CriticalSectionRawMutex, ()>,
+    reading: ReadingSignal<ReadingResult<Samples>>,
+}
+
+impl<A: AnalogInput + Send> AdcSensor<A> {
+    /// Creates an uninitialized driver.
+    #[must_use]
+    pub const fn new(label: Option<&'static str>) -> Self {
+        Self {
+            state: AtomicState::new(State::Uninitialized),
+            label,
+            input: OnceLock::new(),
+            ratio_numerator: AtomicU32::new(1),
+            ratio_denominator: AtomicU32::new(1),
+            signaling: Signal::new(),
+            reading: ReadingSignal::new(),
+        }
+    }
+
+    /// Initializes the driver.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`Config::ratio_denominator`] is zero.
+    pub fn init(&'static self, _peripherals: Peripherals, input: A, config: Config) {
+        assert!(config.ratio_denominator != 0, "ratio denominator is zero");
+
+        if !self.input.is_set() {
+            self.ratio_numerator
+                .store(config.ratio_numerator, Ordering::Release);
+            self.ratio_denominator
+                .store(config.ratio_denominator, Ordering::Release);
+
+            let _ = self.input.init(Mutex::new(input));
+
+            self.state.set(State::Enabled);
+        }
+    }
+
+    /// Listens for measurement requests generated by [`AdcSensor::trigger_measurement()`], and
+    /// responds to them.
+    /// This should be called before [`AdcSensor::wait_for_reading()`], as that method will
+    /// otherwise not be able to respond to measurement requests from
+    /// [`AdcSensor::trigger_measurement()`].
+    ///
+    /// # Note
+    ///
+    /// [`AdcSensor::init()`] needs to be called before calling this method.
+    pub async fn run(&'static self) -> ! {
+        loop {
+            self.signaling.wait().await;
+
+            self.reading.signal(self.measure().await);
+        }
+    }
+
+    /// Samples the ADC channel and returns the voltage.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ReadingError::SensorAccess` if the conversion failed.
+    async fn measure(&'static self) -> ReadingResult<Samples> {
+        let mut input = self.input.get().await.lock().await;
+
+        let sample = input
+            .sample()
+            .await
+            .map_err(|_| ReadingError::SensorAccess)?;
+
+        let numerator = self.ratio_numerator.load(Ordering::Acquire);
+        let denominator = self.ratio_denominator.load(Ordering::Acquire);
+
+        let millivolts =
+            i64::from(sample.millivolts()) * i64::from(numerator) / i64::from(denominator);
+        let millivolts = i32::try_from(millivolts).map_err(|_| ReadingError::SensorAccess)?;
+
+        let sample = Sample::new(millivolts, SampleMetadata::UnknownAccuracy);
+
+        Ok(Samples::from_1(self, [sample]))
+    }
+}
+
+impl<A: Send> Sensor for AdcSensor<A> {
+    fn trigger_measurement(&self) -> Result<(), TriggerMeasurementError> {
+        self.reading.clear();
+
+        match self.state.get() {
+            State::Measuring => {}
+            State::Enabled => {
+                self.state.set(State::Measuring);
+            }
+            State::Uninitialized | State::Disabled | State::Sleeping => {
+                return Err(TriggerMeasurementError::NonEnabled);
+            }
+        }
+
+        self.signaling.signal(());
+
+        Ok(())
+    }
+
+    fn wait_for_reading(&'static self) -> ReadingWaiter {
+        match self.state.get() {
+            State::Measuring => {
+                self.state.set(State::Enabled);
+
+                ReadingWaiter::new(self.reading.wait())
+            }
+            State::Enabled => ReadingWaiter::new_err(ReadingError::NotMeasuring),
+            State::Uninitialized | State::Disabled | State::Sleeping => {
+                ReadingWaiter::new_err(ReadingError::NonEnabled)
+            }
+        }
+    }
+
+    fn set_mode(&self, mode: SensorMode) -> Result<State, SetModeError> {
+        self.state.set_mode(mode)
+    }
+
+    fn state(&self) -> State {
+        self.state.get()
+    }
+
+    fn categories(&self) -> &'static [Category] {
+        &[Category::Voltage]
+    }
+
+    fn reading_channels(&self) -> ReadingChannels {
+        ReadingChannels::from([ReadingChannel::new(
+            Label::Voltage,
+            -3,
+            MeasurementUnit::Volt,
+        )])
+    }
+
+    fn label(&self) -> Option<&'static str> {
+        self.label
+    }
+
+    fn display_name(&self) -> Option<&'static str> {
+        Some("voltage sensor")
+    }
+
+    fn part_number(&self) -> Option<&'static str> {
+        None
+    }
+
+    fn version(&self) -> u8 {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ariel_os_embassy_common::adc::{Error, Sample as AdcSample};
+
+    use super::*;
+
+    #[derive(Default)]
+    struct AnalogInputMock {
+        reading_count: usize,
+    }
+
+    impl AnalogInput for AnalogInputMock {
+        async fn sample(&mut self) -> Result<AdcSample, Error> {
+            // Provide different samples for consecutive readings.
+            let raw = match self.reading_count {
+                0 => 2048,
+                1 => 1024,
+                _ => return Err(Error::Conversion),
+            };
+            self.reading_count += 1;
+            Ok(AdcSample::new(raw, 4095, 3300))
+        }
+    }
+
+    #[test]
+    fn fetch_voltage_reading() {
+        use ariel_os_sensors::Reading;
+
+        static SENSOR: AdcSensor<AnalogInputMock> =
+            AdcSensor::<AnalogInputMock>::new(Some("label"));
+
+        SENSOR.init(Peripherals {}, AnalogInputMock::default(), Config::default());
+
+        embassy_futures::block_on(async {
+            embassy_futures::select::select(SENSOR.run(), async {
+                SENSOR.trigger_measurement().unwrap();
+
+                let reading = SENSOR.wait_for_reading().await.unwrap();
+                let (channel, sample) = reading.sample();
+
+                assert_eq!(channel.label(), Label::Voltage);
+                assert_eq!(channel.unit(), MeasurementUnit::Volt);
+                assert_eq!(sample.value(), Ok(1650));
+
+                SENSOR.trigger_measurement().unwrap();
+
+                let reading = SENSOR.wait_for_reading().await.unwrap();
+                let (_channel, sample) = reading.sample();
+
+                assert_eq!(sample.value(), Ok(825));
+
+                SENSOR.trigger_measurement().unwrap();
+
+                assert!(matches!(
+                    SENSOR.wait_for_reading().await,
+                    Err(ReadingError::SensorAccess)
+                ));
+            })
+            .await;
+        });
+    }
+
+    #[test]
+    fn voltage_divider() {
+        use ariel_os_sensors::Reading;
+
+        static SENSOR: AdcSensor<AnalogInputMock> =
+            AdcSensor::<AnalogInputMock>::new(Some("label"));
+
+        let config = Config {
+            ratio_numerator: 2,
+            ratio_denominator: 1,
+        };
+        SENSOR.init(Peripherals {}, AnalogInputMock::default(), config);
+
+        embassy_futures::block_on(async {
+            embassy_futures::select::select(SENSOR.run(), async {
+                SENSOR.trigger_measurement().unwrap();
+
+                let reading = SENSOR.wait_for_reading().await.unwrap();
+                let (_channel, sample) = reading.sample();
+
+                assert_eq!(sample.value(), Ok(3300));
+            })
+            .await;
+        });
+    }
+
+    #[test]
+    fn awaited_before_triggered() {
+        static SENSOR: AdcSensor<AnalogInputMock> =
+            AdcSensor::<AnalogInputMock>::new(Some("label"));
+
+        SENSOR.init(Peripherals {}, AnalogInputMock::default(), Config::default());
+
+        embassy_futures::block_on(async {
+            embassy_futures::select::select(SENSOR.run(), async {
+                assert!(matches!(
+                    SENSOR.wait_for_reading().await,
+                    Err(ReadingError::NotMeasuring)
+                ));
+            })
+            .await
+        });
+    }
+}
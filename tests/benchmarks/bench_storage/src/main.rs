@@ -0,0 +1,37 @@
+#![no_main]
+#![no_std]
+
+use core::fmt::Write;
+
+use ariel_os::{log::println, storage, thread::block_on};
+
+/// Numbers of items stored before benchmarking lookups.
+const ITEM_COUNTS: [u32; 4] = [1, 8, 16, 32];
+
+// Accessing the storage requires buffers on the stack.
+#[ariel_os::thread(autostart, stacksize = 8192)]
+fn main() {
+    for item_count in ITEM_COUNTS {
+        block_on(storage::erase_all()).unwrap();
+
+        let mut s = block_on(storage::lock());
+        for i in 0..item_count {
+            let mut key = heapless::String::<16>::new();
+            write!(key, "key{i}").unwrap();
+            block_on(s.insert(&key, i)).unwrap();
+        }
+
+        // Look up the oldest item, which is the farthest from the end of the log.
+        match ariel_os::bench::benchmark(100, || {
+            let value: Option<u32> = block_on(s.get("key0")).unwrap();
+            assert_eq!(value, Some(0));
+        }) {
+            Ok(ticks) => {
+                println!("{} items: took {} ticks per lookup", item_count, ticks);
+            }
+            Err(_) => {
+                println!("benchmark returned error");
+            }
+        }
+    }
+}
@@ -0,0 +1,27 @@
+#![no_main]
+#![no_std]
+
+use ariel_os::{
+    log::*,
+    update::{self, BootState},
+};
+
+#[ariel_os::task(autostart)]
+async fn coap_run() {
+    use coap_handler_implementations::{HandlerBuilder, SimpleRendered, new_dispatcher};
+
+    let handler = new_dispatcher()
+        .at(&["hello"], SimpleRendered("Hello from Ariel OS"))
+        .at(&["fwup"], ariel_os::coap::update::handler());
+
+    ariel_os::coap::coap_run(handler).await;
+}
+
+#[ariel_os::task(autostart)]
+async fn confirm() {
+    if update::boot_state().await.unwrap() == BootState::Testing {
+        // Being able to run is all this application needs to consider itself healthy.
+        update::confirm().await.unwrap();
+        info!("Confirmed the updated image");
+    }
+}
@@ -0,0 +1,72 @@
+#![no_main]
+#![no_std]
+
+use ariel_os::{
+    debug::{ExitCode, exit},
+    fs,
+    log::info,
+    storage,
+};
+
+#[ariel_os::task(autostart)]
+async fn main() {
+    // Start from a clean state, the filesystem persists across runs.
+    if fs::exists("/test").await.unwrap() {
+        fs::remove("/test/dir/data.bin").await.unwrap();
+        fs::remove("/test/dir").await.unwrap();
+        fs::remove("/test").await.unwrap();
+    }
+
+    storage::insert("answer", 42u32).await.unwrap();
+
+    fs::create_dir_all("/test/dir").await.unwrap();
+    assert!(fs::metadata("/test/dir").await.unwrap().is_dir());
+
+    fs::write("/test/dir/data.bin", b"hello").await.unwrap();
+    fs::append("/test/dir/data.bin", b", world").await.unwrap();
+
+    let mut buf = [0; 32];
+    let len = fs::read("/test/dir/data.bin", &mut buf).await.unwrap();
+    assert_eq!(buf.get(..len), Some(&b"hello, world"[..]));
+
+    let len = fs::read_at("/test/dir/data.bin", 7, &mut buf)
+        .await
+        .unwrap();
+    assert_eq!(buf.get(..len), Some(&b"world"[..]));
+
+    let metadata = fs::metadata("/test/dir/data.bin").await.unwrap();
+    assert!(metadata.is_file());
+    assert_eq!(metadata.len(), 12);
+
+    fs::rename("/test/dir/data.bin", "/test/dir/renamed.bin")
+        .await
+        .unwrap();
+    assert_eq!(
+        fs::read("/test/dir/data.bin", &mut buf).await,
+        Err(fs::Error::NotFound)
+    );
+
+    let mut entries = 0;
+    fs::read_dir("/test/dir", |name, _| {
+        assert_eq!(name, "renamed.bin");
+        entries += 1;
+    })
+    .await
+    .unwrap();
+    assert_eq!(entries, 1);
+
+    assert_eq!(
+        fs::remove("/test/dir").await,
+        Err(fs::Error::DirectoryNotEmpty)
+    );
+    fs::rename("/test/dir/renamed.bin", "/test/dir/data.bin")
+        .await
+        .unwrap();
+
+    // The storage is not affected by the filesystem.
+    assert_eq!(storage::get::<u32>("answer").await.unwrap(), Some(42));
+
+    info!("{} bytes available", fs::available_space().await.unwrap());
+    info!("Test passed!");
+    exit(ExitCode::SUCCESS);
+}
@@ -0,0 +1,105 @@
+use ariel_os::{
+    hal::pwm,
+    pwm::{DutyCycle, Error, Hertz},
+};
+
+#[cfg(context = "nrf")]
+pub type TestPwm = pwm::PWM0;
+#[cfg(all(context = "nrf", not(context = "nrf5340-app")))]
+ariel_os::hal::define_peripherals!(Peripherals {
+    pin_0: P0_02,
+    pin_1: P0_03,
+});
+#[cfg(context = "nrf5340-app")]
+ariel_os::hal::define_peripherals!(Peripherals {
+    pin_0: P0_04,
+    pin_1: P0_05,
+});
+
+#[cfg(context = "rp")]
+pub type TestPwm = pwm::PWM_SLICE0;
+#[cfg(context = "rp")]
+ariel_os::hal::define_peripherals!(Peripherals {
+    pin_0: PIN_0,
+    pin_1: PIN_1,
+});
+
+#[cfg(context = "esp")]
+pub type TestPwm = pwm::LEDC;
+#[cfg(context = "esp32")]
+ariel_os::hal::define_peripherals!(Peripherals {
+    pin_0: GPIO16,
+    pin_1: GPIO17,
+});
+#[cfg(all(context = "esp", not(context = "esp32")))]
+ariel_os::hal::define_peripherals!(Peripherals {
+    pin_0: GPIO2,
+    pin_1: GPIO3,
+});
+
+#[cfg(any(
+    context = "stm32c031c6",
+    context = "stm32f042k6",
+    context = "stm32wb55rg",
+    context = "stm32wle5jc",
+))]
+pub type TestPwm = pwm::TIM1;
+#[cfg(any(
+    context = "stm32c031c6",
+    context = "stm32f042k6",
+    context = "stm32wb55rg",
+    context = "stm32wle5jc",
+))]
+ariel_os::hal::define_peripherals!(Peripherals {
+    pin_0: PA8,
+    pin_1: PA9,
+});
+
+#[cfg(any(
+    context = "stm32f303re",
+    context = "stm32f401re",
+    context = "stm32f411re",
+    context = "stm32f767zi",
+    context = "stm32g431rb",
+    context = "stm32h755zi",
+    context = "stm32l475vg",
+    context = "stm32u083mc",
+    context = "stm32u585ai",
+))]
+pub type TestPwm = pwm::TIM2;
+#[cfg(any(
+    context = "stm32f303re",
+    context = "stm32f401re",
+    context = "stm32f411re",
+    context = "stm32f767zi",
+    context = "stm32g431rb",
+    context = "stm32h755zi",
+    context = "stm32l475vg",
+    context = "stm32u083mc",
+    context = "stm32u585ai",
+))]
+ariel_os::hal::define_peripherals!(Peripherals {
+    pin_0: PA0,
+    pin_1: PA1,
+});
+
+/// Returns a PWM driver with two channels, `0` on `pin_0` and `1` on `pin_1`.
+pub fn build_pwm(
+    peripherals: Peripherals,
+    frequency: Hertz,
+    initial_duty_cycle: DutyCycle,
+) -> Result<pwm::Pwm, Error> {
+    let builder = TestPwm::builder(frequency).initial_duty_cycle(initial_duty_cycle);
+
+    cfg_select! {
+        any(context = "nrf", context = "esp") => {
+            builder.channel(peripherals.pin_0).channel(peripherals.pin_1).build()
+        }
+        context = "rp" => {
+            builder.channel_a(peripherals.pin_0).channel_b(peripherals.pin_1).build()
+        }
+        context = "stm32" => {
+            builder.ch1(peripherals.pin_0).ch2(peripherals.pin_1).build()
+        }
+    }
+}
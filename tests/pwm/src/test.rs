@@ -0,0 +1,34 @@
+#![no_main]
+#![no_std]
+
+mod pins;
+
+use ariel_os::{
+    debug::{ExitCode, exit, log::info},
+    pwm::{DutyCycle, Error, Hertz, PwmChannel},
+};
+use embedded_hal::pwm::SetDutyCycle as _;
+
+#[ariel_os::task(autostart, peripherals)]
+async fn main(peripherals: pins::Peripherals) {
+    let mut pwm =
+        pins::build_pwm(peripherals, Hertz::kHz(1), DutyCycle::from_percent(50)).unwrap();
+
+    pwm.set_duty_cycle(0, DutyCycle::from_percent(10)).unwrap();
+    pwm.set_duty_cycle(1, DutyCycle::FULLY_ON).unwrap();
+    assert_eq!(
+        pwm.set_duty_cycle(pwm.channel_count(), DutyCycle::FULLY_OFF),
+        Err(Error::InvalidChannel)
+    );
+
+    // Servo-like frequency.
+    pwm.set_frequency(Hertz::Hz(50)).unwrap();
+    assert_eq!(pwm.set_frequency(Hertz::Hz(0)), Err(Error::FrequencyNotSupported));
+
+    let mut channel = PwmChannel::new(&mut pwm, 0).unwrap();
+    channel.set_duty_cycle_percent(25).unwrap();
+    channel.set_duty_cycle_fully_off().unwrap();
+
+    info!("Test passed!");
+    exit(ExitCode::Success);
+}
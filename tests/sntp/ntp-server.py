@@ -0,0 +1,73 @@
+#!/usr/bin/env python3
+"""
+Minimal stand-in for an NTP server, answering SNTP requests with the time of
+the host.
+
+It listens on an unprivileged port by default, so that it does not need to run
+as root nor to compete with a time daemon running on the host.
+"""
+
+import argparse
+import socket
+import struct
+import time
+
+# Seconds between the NTP epoch (1900-01-01) and the UNIX epoch (1970-01-01).
+NTP_TO_UNIX = 2_208_988_800
+
+p = argparse.ArgumentParser(description=__doc__)
+p.add_argument("--address", default="10.42.0.1", help="Address to listen on")
+p.add_argument("--port", type=int, default=12300, help="UDP port to listen on")
+p.add_argument(
+    "--offset",
+    type=float,
+    default=0.0,
+    help="Offset to add to the time of the host, in seconds",
+)
+args = p.parse_args()
+
+
+def ntp_timestamp(unix_time):
+    ntp_time = unix_time + args.offset + NTP_TO_UNIX
+    seconds = int(ntp_time)
+    fraction = int((ntp_time - seconds) * 2**32)
+    return struct.pack("!II", seconds % 2**32, fraction)
+
+
+sock = socket.socket(socket.AF_INET, socket.SOCK_DGRAM)
+sock.bind((args.address, args.port))
+print(f"Listening on {args.address}:{args.port}")
+
+while True:
+    request, client = sock.recvfrom(1024)
+    receive = ntp_timestamp(time.time())
+
+    if len(request) < 48 or request[0] & 0b111 != 3:
+        print(f"Ignoring invalid request from {client}")
+        continue
+
+    version = (request[0] >> 3) & 0b111
+    response = (
+        struct.pack(
+            "!BBbbII4s",
+            # No leap second warning, same version as the request, server mode.
+            (version << 3) | 4,
+            # Stratum 1, as if attached to a reference clock.
+            1,
+            # Poll interval and precision (~1 µs).
+            request[2],
+            -20,
+            # Root delay and dispersion.
+            0,
+            0,
+            b"LOCL",
+        )
+        # Reference timestamp.
+        + receive
+        # Origin timestamp: the transmit timestamp of the request.
+        + request[40:48]
+        + receive
+        + ntp_timestamp(time.time())
+    )
+    sock.sendto(response, client)
+    print(f"Answered request from {client}")
@@ -0,0 +1,51 @@
+#![no_main]
+#![no_std]
+
+use ariel_os::{
+    debug::{ExitCode, exit},
+    log::*,
+    time::{Duration, Instant, Timer},
+    wall_clock::{self, TimeSource},
+};
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[ariel_os::task(autostart)]
+async fn main() {
+    let start = Instant::now();
+
+    while !ariel_os::sntp::is_synchronized() {
+        if start.elapsed() > TIMEOUT {
+            error!(
+                "Not synchronized after {} s: {:?}",
+                TIMEOUT.as_secs(),
+                ariel_os::sntp::status()
+            );
+            exit(ExitCode::FAILURE);
+        }
+        Timer::after_millis(500).await;
+    }
+
+    let status = ariel_os::sntp::status();
+    info!("Synchronized: {:?}", status);
+
+    let now = wall_clock::now().unwrap();
+    info!(
+        "It is {}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        now.year(),
+        u8::from(now.month()),
+        now.day(),
+        now.hour(),
+        now.minute(),
+        now.second()
+    );
+
+    // The stand-in server reports the time of the host, which is well past the epoch.
+    assert_eq!(wall_clock::source(), Some(TimeSource::Sntp));
+    assert!(now.year() >= 2025);
+    // This is the first synchronization, so no offset has been applied yet.
+    assert_eq!(status.last_offset_micros, None);
+
+    info!("Test passed!");
+    exit(ExitCode::SUCCESS);
+}
@@ -0,0 +1,63 @@
+#![no_main]
+#![no_std]
+
+use ariel_os::{
+    debug::{ExitCode, exit},
+    log::info,
+    storage,
+};
+
+/// Upper bound on the number of records pushed while filling the queue.
+const MAX_RECORDS: u32 = 10_000;
+
+#[ariel_os::task(autostart)]
+async fn main() {
+    let log = storage::queue("log").unwrap();
+    log.erase_all().await.unwrap();
+    assert_eq!(log.peek::<u32>().await.unwrap(), None);
+
+    storage::insert("answer", 42u32).await.unwrap();
+
+    for i in 0..5u32 {
+        log.push(&i).await.unwrap();
+    }
+    assert_eq!(log.peek::<u32>().await.unwrap(), Some(0));
+
+    {
+        let mut queue = log.lock().await;
+        let mut records = queue.records().await.unwrap();
+        let mut expected = 0u32;
+        while let Some(record) = records.next::<u32>().await.unwrap() {
+            assert_eq!(record, expected);
+            expected += 1;
+        }
+        assert_eq!(expected, 5);
+    }
+
+    assert_eq!(log.pop::<u32>().await.unwrap(), Some(0));
+    assert_eq!(log.pop::<u32>().await.unwrap(), Some(1));
+    assert_eq!(log.peek::<u32>().await.unwrap(), Some(2));
+    let front = log.peek::<u32>().await.unwrap().unwrap();
+
+    // Fill the queue, then check that the oldest records get dropped.
+    let mut next = 5u32;
+    loop {
+        assert!(next < MAX_RECORDS, "queue never got full");
+        match log.push(&next).await {
+            Ok(()) => next += 1,
+            Err(storage::Error::FullStorage) => break,
+            Err(_) => panic!("pushing to the queue failed"),
+        }
+    }
+    info!("queue full after {} records", next);
+
+    log.push_dropping_oldest(&next).await.unwrap();
+    let new_front = log.peek::<u32>().await.unwrap().unwrap();
+    assert!(new_front > front);
+
+    // The key-value storage is not affected by the queue.
+    assert_eq!(storage::get::<u32>("answer").await.unwrap(), Some(42));
+
+    info!("Test passed!");
+    exit(ExitCode::SUCCESS);
+}
@@ -0,0 +1,104 @@
+#![no_main]
+#![no_std]
+
+use ariel_os::{
+    cell::ConstStaticCell,
+    debug::{ExitCode, exit},
+    log::info,
+    thread::{self, thread_flags},
+    time::{Duration, Instant},
+};
+use portable_atomic::{AtomicUsize, Ordering};
+
+const STACK_SIZE: usize = 2048;
+
+static STACKS: ConstStaticCell<[[u8; STACK_SIZE]; 3]> = ConstStaticCell::new([[0; STACK_SIZE]; 3]);
+
+/// Relative deadlines of the workers, in milliseconds.
+const DEADLINES_MS: [u64; 3] = [3000, 1000, 2000];
+
+static RUN_COUNT: AtomicUsize = AtomicUsize::new(0);
+static RUN_ORDER: [AtomicUsize; 3] = [const { AtomicUsize::new(usize::MAX) }; 3];
+static MISSES: AtomicUsize = AtomicUsize::new(0);
+/// Thread that missed the last deadline.
+static MISSED_BY: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+fn worker(index: usize) {
+    let relative_deadline = Duration::from_millis(*DEADLINES_MS.get(index).unwrap());
+    thread::set_deadline(Some(Instant::now() + relative_deadline));
+    thread_flags::wait_any(0b1);
+
+    let position = RUN_COUNT.fetch_add(1, Ordering::AcqRel);
+    RUN_ORDER
+        .get(position)
+        .unwrap()
+        .store(index, Ordering::Release);
+
+    // The deadline has not passed yet.
+    thread::set_deadline(None);
+}
+
+#[ariel_os::thread(autostart, priority = 2)]
+fn checker() {
+    let mut index = 0;
+    let workers = STACKS.take().each_mut().map(|stack| {
+        let thread_id = thread::create(worker, index, stack, 1, None);
+        index += 1;
+        thread_id
+    });
+
+    // Let the workers declare their deadlines and wait for their flag.
+    thread::sleep(Duration::from_millis(10));
+
+    // Without deadlines, the workers would run in this order.
+    for thread_id in workers {
+        thread_flags::set(thread_id, 0b1);
+    }
+    thread::sleep(Duration::from_millis(10));
+    assert_eq!(RUN_COUNT.load(Ordering::Acquire), 3);
+
+    let order = RUN_ORDER
+        .each_ref()
+        .map(|index| index.load(Ordering::Acquire));
+    info!("run order: {:?}", order);
+    // On native and multi-core, the workers run in parallel.
+    if thread::CORE_COUNT == 1 && !cfg!(context = "native") {
+        assert_eq!(order, [1, 2, 0]);
+    }
+    assert_eq!(MISSES.load(Ordering::Acquire), 0);
+
+    thread::set_deadline_miss_hook(Some(|miss| {
+        assert!(miss.lateness() > Duration::from_ticks(0));
+        MISSED_BY.store(usize::from(miss.thread_id), Ordering::Release);
+        MISSES.fetch_add(1, Ordering::AcqRel);
+    }));
+
+    // Missing a deadline while blocked, which the scheduler reports.
+    thread::set_deadline(Some(Instant::now() + Duration::from_millis(1)));
+    thread::sleep(Duration::from_millis(5));
+    assert_eq!(MISSES.load(Ordering::Acquire), 1);
+    assert_eq!(
+        MISSED_BY.load(Ordering::Acquire),
+        usize::from(thread::current_tid().unwrap())
+    );
+    // The miss is only reported once.
+    thread::set_deadline(None);
+    assert_eq!(MISSES.load(Ordering::Acquire), 1);
+
+    // Periodic releases, with the deadline at the end of each period.
+    let period = Duration::from_millis(10);
+    let mut periodic = thread::Periodic::start(period);
+    let start = thread::deadline().unwrap() - period;
+    periodic.wait();
+    assert_eq!(thread::deadline(), Some(start + period * 2));
+    assert_eq!(MISSES.load(Ordering::Acquire), 1);
+
+    // Overrunning the second period, so that the third one is already underway.
+    thread::sleep(Duration::from_millis(25));
+    periodic.wait();
+    assert_eq!(MISSES.load(Ordering::Acquire), 2);
+    assert_eq!(thread::deadline(), Some(start + period * 4));
+
+    info!("Test passed!");
+    exit(ExitCode::Success);
+}
@@ -0,0 +1,45 @@
+#![no_main]
+#![no_std]
+
+use ariel_os::{
+    cell::ConstStaticCell,
+    debug::{ExitCode, exit},
+    log::info,
+    thread,
+};
+
+const STACK_SIZE: usize = 2048;
+
+static STACK: ConstStaticCell<[u8; STACK_SIZE]> = ConstStaticCell::new([0; STACK_SIZE]);
+
+#[ariel_os::thread(autostart, priority = 1)]
+fn thread0() {
+    let stack: &'static mut [u8] = STACK.take();
+    let stack_ptr = stack.as_ptr();
+
+    // With a higher priority, the thread terminates before `spawn()` returns.
+    let values = [1u32, 2, 3];
+    let handle = thread::spawn(move || values.iter().sum::<u32>(), stack, 2, None);
+    assert!(handle.is_finished());
+    let (sum, stack) = handle.join();
+    assert_eq!(sum, Ok(6));
+    // The whole stack buffer is returned.
+    assert_eq!(stack.as_ptr(), stack_ptr);
+    assert_eq!(stack.len(), STACK_SIZE);
+
+    // With the same priority, joining blocks until the thread has run.
+    let handle = thread::spawn(|| thread::current_tid().unwrap(), stack, 1, None);
+    assert!(!handle.is_finished());
+    let thread_id = handle.thread_id();
+    let (worker_thread_id, stack) = handle.join();
+    assert_eq!(worker_thread_id, Ok(thread_id));
+
+    // Awaiting the handle.
+    let handle = thread::spawn(|| 42u64, stack, 1, None);
+    let (value, stack) = thread::block_on(handle);
+    assert_eq!(value, Ok(42));
+    assert_eq!(stack.as_ptr(), stack_ptr);
+
+    info!("Test passed!");
+    exit(ExitCode::Success);
+}
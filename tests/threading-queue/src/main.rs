@@ -0,0 +1,79 @@
+#![no_main]
+#![no_std]
+
+use ariel_os::{
+    debug::{ExitCode, exit},
+    log::info,
+    thread::{self, sync::Queue},
+    time::{Duration, Instant},
+};
+
+const COUNT: u32 = 32;
+
+/// Elements sent by the checker, received by both a thread and a task.
+static QUEUE: Queue<u32, 4> = Queue::new();
+/// Elements forwarded back to the checker.
+static RECEIVED: Queue<u32, 32> = Queue::new();
+/// Queue without receivers, to check the behavior when full or empty.
+static UNUSED: Queue<u32, 4> = Queue::new();
+
+#[ariel_os::thread(autostart, priority = 1)]
+fn consumer() {
+    loop {
+        let element = QUEUE.recv();
+        RECEIVED.send(element);
+    }
+}
+
+#[ariel_os::task(autostart)]
+async fn consumer_task() {
+    loop {
+        let element = QUEUE.recv_async().await;
+        RECEIVED.send_async(element).await;
+    }
+}
+
+#[ariel_os::thread(autostart, priority = 2)]
+fn checker() {
+    assert_eq!(UNUSED.capacity(), 4);
+    assert_eq!(UNUSED.try_recv(), None);
+
+    let timeout = Duration::from_millis(10);
+    let start = Instant::now();
+    assert_eq!(UNUSED.recv_timeout(timeout), Err(thread::TimeoutError));
+    assert!(Instant::now() - start >= timeout);
+
+    for element in 0..4 {
+        assert_eq!(UNUSED.try_send(element), Ok(()));
+    }
+    assert!(UNUSED.is_full());
+    assert_eq!(UNUSED.try_send(4), Err(4));
+    let start = Instant::now();
+    assert_eq!(UNUSED.send_timeout(4, timeout), Err(4));
+    assert!(Instant::now() - start >= timeout);
+
+    // Elements are received in order.
+    assert_eq!(UNUSED.recv(), 0);
+    assert_eq!(UNUSED.send_timeout(4, timeout), Ok(()));
+    for element in 1..5 {
+        assert_eq!(UNUSED.recv_timeout(timeout), Ok(element));
+    }
+    assert!(UNUSED.is_empty());
+
+    // Blocks whenever the queue is full, until a consumer has received an element.
+    for element in 0..COUNT {
+        QUEUE.send(element);
+    }
+
+    let mut seen = 0u32;
+    for _ in 0..COUNT {
+        let element = RECEIVED.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(seen & (1 << element), 0);
+        seen |= 1 << element;
+    }
+    assert_eq!(seen, u32::MAX);
+    assert!(QUEUE.is_empty());
+
+    info!("Test passed!");
+    exit(ExitCode::Success);
+}
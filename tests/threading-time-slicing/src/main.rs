@@ -0,0 +1,80 @@
+#![no_main]
+#![no_std]
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use ariel_os::{
+    debug::{ExitCode, exit},
+    log::info,
+    thread::{self, RunqueueId},
+    time::Duration,
+};
+
+static COUNTERS: [AtomicU32; 3] = [const { AtomicU32::new(0) }; 3];
+
+/// Counts forever, without ever yielding.
+fn count(counter: &AtomicU32) -> ! {
+    loop {
+        // On native, threads are only suspended when leaving a critical section, which this
+        // does without yielding.
+        #[cfg(context = "native")]
+        let _ = thread::current_tid();
+
+        // Only this thread writes to the counter.
+        counter.store(
+            counter.load(Ordering::Relaxed).wrapping_add(1),
+            Ordering::Relaxed,
+        );
+    }
+}
+
+#[ariel_os::thread(autostart, priority = 1)]
+fn thread0() {
+    count(&COUNTERS[0]);
+}
+
+#[ariel_os::thread(autostart, priority = 1)]
+fn thread1() {
+    count(&COUNTERS[1]);
+}
+
+#[ariel_os::thread(autostart, priority = 1)]
+fn thread2() {
+    count(&COUNTERS[2]);
+}
+
+fn counts() -> [u32; 3] {
+    COUNTERS
+        .each_ref()
+        .map(|counter| counter.load(Ordering::Relaxed))
+}
+
+#[ariel_os::thread(autostart, priority = 2)]
+fn checker() {
+    assert!(thread::time_slice(RunqueueId::new(1)).is_some());
+
+    // Let the counting threads share the CPU for about 20 time slices.
+    thread::sleep(Duration::from_millis(200));
+    let counts = counts();
+    info!("counts with time slicing: {:?}", counts);
+
+    // All threads got CPU time, about the same amount.
+    let min = *counts.iter().min().unwrap();
+    let max = *counts.iter().max().unwrap();
+    assert!(min > 0);
+    assert!(max < min * 2);
+
+    // Without time slicing, the running thread keeps the CPU, except on native, where the
+    // threads then run in parallel.
+    thread::set_time_slice(RunqueueId::new(1), None);
+    assert!(thread::time_slice(RunqueueId::new(1)).is_none());
+    let before = counts();
+    thread::sleep(Duration::from_millis(50));
+    let after = counts();
+    info!("counts without time slicing: {:?} -> {:?}", before, after);
+    let running = before.iter().zip(&after).filter(|(b, a)| b != a).count();
+    assert_eq!(running, if cfg!(context = "native") { 3 } else { 1 });
+
+    info!("Test passed!");
+    exit(ExitCode::Success);
+}
@@ -0,0 +1,92 @@
+#![no_main]
+#![no_std]
+
+use ariel_os::{
+    cell::ConstStaticCell,
+    debug::{ExitCode, exit},
+    log::info,
+    thread::{
+        self, RunqueueId, ThreadId, TimeoutError,
+        sync::{Channel, Event, Lock, Mutex},
+        thread_flags,
+    },
+    time::{Duration, Instant},
+};
+
+static LOCK: Lock = Lock::new();
+static MUTEX: Mutex<u32> = Mutex::new(0);
+static EVENT: Event = Event::new();
+static CHANNEL: Channel<u32> = Channel::new();
+
+const STACK_SIZE: usize = 2048;
+
+static STACK: ConstStaticCell<[u8; STACK_SIZE]> = ConstStaticCell::new([0; STACK_SIZE]);
+
+/// Holds the lock and the mutex until the checker, whose `ThreadId` is passed as argument, has
+/// timed out on everything, and then releases everything.
+fn owner(checker: usize) {
+    let checker = ThreadId::new(u8::try_from(checker).unwrap());
+
+    LOCK.acquire();
+    let mut guard = MUTEX.lock();
+    thread_flags::set(checker, 0b1);
+
+    // Wait for the checker to time out on everything.
+    thread_flags::wait_any(0b1);
+    LOCK.release();
+    *guard = 42;
+    drop(guard);
+    EVENT.set();
+    CHANNEL.send(&42);
+    thread_flags::set(checker, 0b100);
+}
+
+/// Asserts that `f` times out after `timeout`.
+fn assert_times_out<T>(timeout: Duration, f: impl FnOnce(Duration) -> Result<T, TimeoutError>) {
+    let start = Instant::now();
+    assert!(f(timeout).is_err());
+    assert!(Instant::now() - start >= timeout);
+}
+
+#[ariel_os::thread(autostart, priority = 2)]
+fn checker() {
+    let checker = thread::current_tid().unwrap();
+    let owner = thread::create(owner, usize::from(checker), STACK.take(), 1, None);
+
+    // Wait for the owner to hold the lock and the mutex.
+    thread_flags::wait_any(0b1);
+
+    let timeout = Duration::from_millis(10);
+    assert_times_out(timeout, |timeout| LOCK.acquire_timeout(timeout));
+    assert_times_out(timeout, |timeout| MUTEX.lock_timeout(timeout).map(drop));
+    // The owner only inherited the priority of the checker while it was waiting.
+    assert_eq!(thread::get_priority(owner), Some(RunqueueId::new(1)));
+    assert_times_out(timeout, |timeout| EVENT.wait_timeout(timeout));
+    assert_times_out(timeout, |timeout| CHANNEL.send_timeout(&0, timeout));
+    assert_times_out(timeout, |timeout| CHANNEL.recv_timeout(timeout));
+    assert_times_out(timeout, |timeout| {
+        thread_flags::wait_any_timeout(0b100, timeout)
+    });
+    assert_times_out(timeout, |timeout| {
+        thread_flags::wait_all_timeout(0b101, timeout)
+    });
+    // Timeouts are not signaled through thread flags.
+    assert_times_out(timeout, |timeout| {
+        thread_flags::wait_any_timeout(0b10, timeout)
+    });
+    assert_eq!(thread_flags::get(), 0);
+
+    // Let the owner release everything.
+    thread_flags::set(owner, 0b1);
+
+    let timeout = Duration::from_secs(1);
+    assert_eq!(LOCK.acquire_timeout(timeout), Ok(()));
+    LOCK.release();
+    assert_eq!(MUTEX.lock_timeout(timeout).map(|guard| *guard), Ok(42));
+    assert_eq!(EVENT.wait_timeout(timeout), Ok(()));
+    assert_eq!(CHANNEL.recv_timeout(timeout), Ok(42));
+    assert_eq!(thread_flags::wait_one_timeout(0b100, timeout), Ok(0b100));
+
+    info!("Test passed!");
+    exit(ExitCode::Success);
+}